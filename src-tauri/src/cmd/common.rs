@@ -7,8 +7,10 @@ use crate::core::imaging::normalize::robust_asinh_preview;
 use crate::core::imaging::stats::compute_image_stats;
 use crate::core::imaging::stf::{auto_stf, apply_stf, AutoStfConfig};
 use crate::infra::cache::{GLOBAL_IMAGE_CACHE, ImageEntry};
-use crate::infra::fits::dispatcher::resolve_single_image;
+use crate::core::cube::video::VideoSequence;
+use crate::infra::fits::dispatcher::{is_raster_path, is_video_path, resolve_single_image};
 use crate::infra::fits::reader::extract_image_mmap;
+use crate::infra::raster::read_raster;
 use crate::infra::render::grayscale::{render_grayscale, save_stf_png};
 use crate::types::header::HduHeader;
 use crate::types::image::ImageStats;
@@ -72,37 +74,43 @@ fn try_asdf_image(p: &std::path::Path) -> Result<ResolvedImage> {
     }
 }
 
+fn load_video_first_frame(path: &str) -> Result<(Array2<f32>, HduHeader)> {
+    let video = VideoSequence::open(path)?;
+    Ok((video.get_frame(0)?, video.frame_header(0)))
+}
+
+fn read_resolved(path: &std::path::Path) -> Result<(Array2<f32>, HduHeader)> {
+    if crate::infra::asdf::converter::is_asdf_file(path) {
+        let resolved = try_asdf_image(path)?;
+        return Ok((resolved.arr, resolved.header));
+    }
+    if is_video_path(path) {
+        return load_video_first_frame(&path.to_string_lossy());
+    }
+    if is_raster_path(path) {
+        let raster = read_raster(path)?;
+        return Ok((raster.mono(), raster.header));
+    }
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let result = extract_image_mmap(&file)?;
+    Ok((result.image, result.header))
+}
+
 pub(crate) fn extract_image_resolved(path: &str) -> Result<ResolvedImage> {
     let p = std::path::Path::new(path);
     if crate::infra::asdf::converter::is_asdf_file(p) {
         return try_asdf_image(p);
     }
 
-    let (fits_path, tmp) = resolve_single_image(path)?;
-    let file = File::open(&fits_path)
-        .with_context(|| format!("Failed to open {}", fits_path.display()))?;
-    let result = extract_image_mmap(&file)?;
-    Ok(ResolvedImage {
-        arr: result.image,
-        header: result.header,
-        _tmp: tmp,
-    })
+    let (resolved, tmp) = resolve_single_image(path)?;
+    let (arr, header) = read_resolved(&resolved)?;
+    Ok(ResolvedImage { arr, header, _tmp: tmp })
 }
 
 fn load_image_and_stats(path: &str) -> Result<(Array2<f32>, ImageStats)> {
-    let p = std::path::Path::new(path);
-    if crate::infra::asdf::converter::is_asdf_file(p) {
-        bail_if_calib(p)?;
-        let result = crate::infra::asdf_bridge::extract_image_from_asdf(p)?;
-        let stats = compute_image_stats(&result.image);
-        return Ok((result.image, stats));
-    }
-
-    let (fits_path, _tmp) = resolve_single_image(path)?;
-    let file = File::open(&fits_path)?;
-    let result = extract_image_mmap(&file)?;
-    let stats = compute_image_stats(&result.image);
-    Ok((result.image, stats))
+    let (arr, stats, _) = load_image_stats_header(path)?;
+    Ok((arr, stats))
 }
 
 fn load_image_stats_header(path: &str) -> Result<(Array2<f32>, ImageStats, HduHeader)> {
@@ -114,11 +122,10 @@ fn load_image_stats_header(path: &str) -> Result<(Array2<f32>, ImageStats, HduHe
         return Ok((result.image, stats, result.header));
     }
 
-    let (fits_path, _tmp) = resolve_single_image(path)?;
-    let file = File::open(&fits_path)?;
-    let result = extract_image_mmap(&file)?;
-    let stats = compute_image_stats(&result.image);
-    Ok((result.image, stats, result.header))
+    let (resolved, _tmp) = resolve_single_image(path)?;
+    let (arr, header) = read_resolved(&resolved)?;
+    let stats = compute_image_stats(&arr);
+    Ok((arr, stats, header))
 }
 
 pub(crate) fn load_cached(path: &str) -> Result<ImageEntry> {
@@ -321,14 +328,28 @@ pub(crate) struct ResolvedRgbImage {
 }
 
 pub(crate) fn try_extract_rgb_resolved(path: &str) -> Result<Option<ResolvedRgbImage>> {
-    let p = std::path::Path::new(path);
+    let (resolved, tmp) = resolve_single_image(path)?;
+    let p = resolved.as_path();
     if crate::infra::asdf::converter::is_asdf_file(p) {
         return Ok(None);
     }
+    if is_video_path(p) {
+        let video = VideoSequence::open(&p.to_string_lossy())?;
+        let mut planes = video.get_planes(0)?;
+        if planes.len() != 3 {
+            return Ok(None);
+        }
+        let (b, g, r) = (planes.pop().unwrap(), planes.pop().unwrap(), planes.pop().unwrap());
+        return Ok(Some(ResolvedRgbImage { r, g, b, header: video.frame_header(0), _tmp: tmp }));
+    }
+    if is_raster_path(p) {
+        return Ok(read_raster(p)?
+            .into_rgb()
+            .map(|([r, g, b], header)| ResolvedRgbImage { r, g, b, header, _tmp: tmp }));
+    }
 
-    let (fits_path, tmp) = resolve_single_image(path)?;
-    let file = File::open(&fits_path)
-        .with_context(|| format!("Failed to open {}", fits_path.display()))?;
+    let file = File::open(p)
+        .with_context(|| format!("Failed to open {}", p.display()))?;
 
     match crate::infra::fits::reader::try_extract_rgb_mmap(&file)? {
         Some(result) => Ok(Some(ResolvedRgbImage {
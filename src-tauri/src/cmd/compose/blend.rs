@@ -13,7 +13,7 @@ use crate::core::imaging::resample::resample_image;
 use crate::core::imaging::stats::compute_image_stats;
 use crate::infra::fits::writer::{write_fits_mono, filter_header};
 use crate::infra::cache::GLOBAL_IMAGE_CACHE;
-use crate::types::constants::{MAX_DIMENSION_RATIO, RES_DIMENSIONS, RES_ELAPSED_MS, RES_MAX, RES_MEAN, RES_MEDIAN, RES_MIN, RES_PNG_PATH, RES_STATS_B, RES_STATS_G, RES_STATS_R, ALIGN_METHOD, DIMENSIONS, CHANNELS, RES_CHANNEL, RES_PATH, RES_FILE_SIZE_BYTES, RES_OFFSET, RES_BLEND_PRESET, RES_CHANNEL_COUNT};
+use crate::types::constants::{MAX_DIMENSION_RATIO, RES_DIMENSIONS, RES_ELAPSED_MS, RES_MAX, RES_MEAN, RES_MEDIAN, RES_MIN, RES_PNG_PATH, RES_STATS_B, RES_STATS_G, RES_STATS_R, ALIGN_METHOD, DIMENSIONS, CHANNELS, RES_CHANNEL, RES_PATH, RES_FILE_SIZE_BYTES, RES_OFFSET, RES_BLEND_PRESET, RES_CHANNEL_COUNT, RES_IS_FLIPPED};
 
 use super::rgb::{composite_png_path, load_entry};
 
@@ -325,6 +325,7 @@ pub async fn align_channels_cmd(
                 "matched_stars": result.matched_stars,
                 "inliers": result.inliers,
                 "residual_px": result.residual_px,
+                RES_IS_FLIPPED: result.is_flipped,
                 "cache_key": cache_key,
             });
 
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use serde_json::json;
+
+use crate::cmd::common::{blocking_cmd, resolve_output_dir, MAX_PREVIEW_DIM};
+use crate::cmd::helpers;
+use crate::core::compose::color_adjust::adjust_color;
+use crate::core::compose::colorspace::{component_names, rgb_to_planes};
+use crate::core::imaging::stats::compute_image_stats;
+use crate::core::imaging::stf::{make_stf_u8_fn, AutoStfConfig};
+use crate::infra::cache::GLOBAL_IMAGE_CACHE;
+use crate::infra::fits::writer::write_fits_mono;
+use crate::types::constants::{
+    RES_AUTO_STF, RES_COLOR_SPACE, RES_COMPONENTS, RES_DIMENSIONS, RES_ELAPSED_MS, RES_FITS_PATH,
+    RES_KEY, RES_PNG_PATH, RES_STATS,
+};
+use crate::types::image::{ColorAdjustParams, ColorSpace};
+
+use super::rgb::composite_png_path;
+
+#[tauri::command]
+pub async fn adjust_color_composite_cmd(
+    output_dir: String,
+    params: ColorAdjustParams,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        let t0 = Instant::now();
+        resolve_output_dir(&output_dir)?;
+
+        let (er, eg, eb) = helpers::load_composite_rgb()?;
+        let (r, g, b) = adjust_color(er.arr(), eg.arr(), eb.arr(), &params)?;
+        let (rows, cols) = r.dim();
+
+        let (stats_r, (stats_g, stats_b)) = rayon::join(
+            || compute_image_stats(&r),
+            || rayon::join(|| compute_image_stats(&g), || compute_image_stats(&b)),
+        );
+
+        let png_path = composite_png_path(&output_dir);
+        let linked_stf = helpers::compute_linked_stf(&stats_r, &stats_g, &stats_b, &AutoStfConfig::default());
+        let fn_r = make_stf_u8_fn(&linked_stf, &stats_r);
+        let fn_g = make_stf_u8_fn(&linked_stf, &stats_g);
+        let fn_b = make_stf_u8_fn(&linked_stf, &stats_b);
+        helpers::render_rgb_preview_with_stf(&r, &g, &b, fn_r, fn_g, fn_b, &png_path, MAX_PREVIEW_DIM)?;
+        helpers::insert_composite_rgb(r, g, b, stats_r, stats_g, stats_b);
+
+        Ok(json!({
+            RES_PNG_PATH: png_path,
+            RES_COLOR_SPACE: params.space,
+            RES_AUTO_STF: helpers::stf_json(&linked_stf),
+            RES_DIMENSIONS: [cols, rows],
+            RES_ELAPSED_MS: t0.elapsed().as_millis() as u64,
+        }))
+    })
+}
+
+#[tauri::command]
+pub async fn extract_color_components_cmd(
+    output_dir: String,
+    space: Option<ColorSpace>,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        let t0 = Instant::now();
+        resolve_output_dir(&output_dir)?;
+
+        let space = space.unwrap_or_default();
+        let (er, eg, eb) = helpers::load_composite_rgb()?;
+        let planes = rgb_to_planes(er.arr(), eg.arr(), eb.arr(), space)?;
+        let (rows, cols) = planes[0].dim();
+
+        let mut components = Vec::with_capacity(3);
+        for (name, plane) in component_names(space).iter().zip(planes) {
+            let fits_path = format!("{}/composite_{}.fits", output_dir, name);
+            write_fits_mono(&fits_path, &plane, None)?;
+            let stats = compute_image_stats(&plane);
+            let stats_json = helpers::stats_json(&stats);
+            GLOBAL_IMAGE_CACHE.insert_synthetic(&fits_path, Arc::new(plane), stats);
+            components.push(json!({
+                RES_KEY: name,
+                RES_FITS_PATH: fits_path,
+                RES_STATS: stats_json,
+            }));
+        }
+
+        Ok(json!({
+            RES_COLOR_SPACE: space,
+            RES_COMPONENTS: components,
+            RES_DIMENSIONS: [cols, rows],
+            RES_ELAPSED_MS: t0.elapsed().as_millis() as u64,
+        }))
+    })
+}
@@ -0,0 +1,66 @@
+use std::time::Instant;
+
+use serde_json::json;
+
+use crate::cmd::common::{blocking_cmd, resolve_output_dir, MAX_PREVIEW_DIM};
+use crate::cmd::helpers;
+use crate::core::compose::colorspace::convert_linear_rgb;
+use crate::core::imaging::stats::compute_image_stats;
+use crate::core::imaging::stf::{make_stf_u8_fn, AutoStfConfig};
+use crate::infra::render::icc::profile_description;
+use crate::types::constants::{RES_COLOR_SPACE, RES_CONVERTED, RES_ELAPSED_MS, RES_PNG_PATH, RES_PROFILE};
+use crate::types::image::RgbColorSpace;
+
+use super::rgb::composite_png_path;
+
+#[tauri::command]
+pub async fn get_working_color_space_cmd() -> Result<serde_json::Value, String> {
+    let space = helpers::composite_color_space();
+    Ok(json!({
+        RES_COLOR_SPACE: space,
+        RES_PROFILE: profile_description(space),
+    }))
+}
+
+#[tauri::command]
+pub async fn set_working_color_space_cmd(
+    space: RgbColorSpace,
+    output_dir: String,
+    convert: Option<bool>,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        let t0 = Instant::now();
+        let previous = helpers::composite_color_space();
+        let convert = convert.unwrap_or(false) && previous != space;
+
+        let png_path = if convert {
+            resolve_output_dir(&output_dir)?;
+            let (er, eg, eb) = helpers::load_composite_rgb()?;
+            let [r, g, b] = convert_linear_rgb(er.arr(), eg.arr(), eb.arr(), previous, space)?;
+            let (stats_r, (stats_g, stats_b)) = rayon::join(
+                || compute_image_stats(&r),
+                || rayon::join(|| compute_image_stats(&g), || compute_image_stats(&b)),
+            );
+
+            let png_path = composite_png_path(&output_dir);
+            let linked_stf = helpers::compute_linked_stf(&stats_r, &stats_g, &stats_b, &AutoStfConfig::default());
+            let fn_r = make_stf_u8_fn(&linked_stf, &stats_r);
+            let fn_g = make_stf_u8_fn(&linked_stf, &stats_g);
+            let fn_b = make_stf_u8_fn(&linked_stf, &stats_b);
+            helpers::render_rgb_preview_with_stf(&r, &g, &b, fn_r, fn_g, fn_b, &png_path, MAX_PREVIEW_DIM)?;
+            helpers::insert_composite_rgb(r, g, b, stats_r, stats_g, stats_b);
+            Some(png_path)
+        } else {
+            None
+        };
+        helpers::set_composite_color_space(space);
+
+        Ok(json!({
+            RES_COLOR_SPACE: space,
+            RES_PROFILE: profile_description(space),
+            RES_CONVERTED: convert,
+            RES_PNG_PATH: png_path,
+            RES_ELAPSED_MS: t0.elapsed().as_millis() as u64,
+        }))
+    })
+}
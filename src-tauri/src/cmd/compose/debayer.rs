@@ -0,0 +1,130 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use serde_json::json;
+
+use crate::cmd::common::{blocking_cmd, load_cached_full, resolve_output_dir, MAX_PREVIEW_DIM};
+use crate::cmd::helpers;
+use crate::core::compose::rgb::process_rgb;
+use crate::core::imaging::debayer::{debayer, CfaLayout};
+use crate::core::imaging::stats::compute_image_stats;
+use crate::core::stacking::calibration::calibrate_from_paths;
+use crate::core::stacking::dark_scaling::DarkScaling;
+use crate::infra::cache::GLOBAL_IMAGE_CACHE;
+use crate::infra::fits::writer::write_fits_mono;
+use crate::types::compose::RgbComposeConfig;
+use crate::types::constants::{
+    RES_BAYER_PATTERN, RES_CALIBRATED, RES_CHANNEL_PATHS, RES_DEBAYER_METHOD, RES_DIMENSIONS,
+    RES_ELAPSED_MS, RES_PNG_PATH, STF_B, STF_G, STF_R,
+};
+
+use super::rgb::composite_png_path;
+
+#[tauri::command]
+pub async fn debayer_cmd(
+    path: String,
+    output_dir: String,
+    method: Option<String>,
+    pattern: Option<String>,
+    x_offset: Option<usize>,
+    y_offset: Option<usize>,
+    bias_paths: Option<Vec<String>>,
+    dark_paths: Option<Vec<String>>,
+    flat_paths: Option<Vec<String>>,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        let t0 = Instant::now();
+        resolve_output_dir(&output_dir)?;
+
+        let entry = load_cached_full(&path)?;
+        let layout = match pattern.as_deref() {
+            Some(p) => {
+                let (header_x, header_y) = entry.header().map_or((0, 0), CfaLayout::header_offsets);
+                CfaLayout::parse(p, x_offset.unwrap_or(header_x), y_offset.unwrap_or(header_y))
+                    .ok_or_else(|| anyhow::anyhow!("Unrecognised CFA pattern: {}", p))?
+            }
+            None => entry
+                .header()
+                .and_then(CfaLayout::from_header)
+                .ok_or_else(|| anyhow::anyhow!("No BAYERPAT in header; specify the CFA pattern"))?,
+        };
+
+        let has_calibration = [&bias_paths, &dark_paths, &flat_paths]
+            .iter()
+            .any(|p| p.as_ref().is_some_and(|v| !v.is_empty()));
+
+        let calibrated = if has_calibration {
+            let (frame, _) = calibrate_from_paths(
+                &path,
+                bias_paths.as_deref(),
+                dark_paths.as_deref(),
+                flat_paths.as_deref(),
+                1.0,
+                DarkScaling::default(),
+                None,
+            )?;
+            Some(frame.image)
+        } else {
+            None
+        };
+        let cfa = calibrated.as_ref().unwrap_or_else(|| entry.arr());
+
+        let rgb = debayer(cfa, &layout, helpers::parse_debayer_method(method.as_deref()))?;
+        let (rows, cols) = rgb.r.dim();
+
+        let stem = std::path::Path::new(&path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("debayer");
+        let mut channel_paths = Vec::with_capacity(3);
+        for (suffix, plane) in [("r", &rgb.r), ("g", &rgb.g), ("b", &rgb.b)] {
+            let fits_path = format!("{}/{}_debayer_{}.fits", output_dir, stem, suffix);
+            write_fits_mono(&fits_path, plane, None)?;
+            GLOBAL_IMAGE_CACHE.insert_synthetic(
+                &fits_path,
+                Arc::new(plane.clone()),
+                compute_image_stats(plane),
+            );
+            channel_paths.push(fits_path);
+        }
+
+        let config = RgbComposeConfig {
+            align: false,
+            ..RgbComposeConfig::default()
+        };
+        let mut processed = process_rgb(Some(&rgb.r), Some(&rgb.g), Some(&rgb.b), &config)?;
+
+        if let (Some(pre_r), Some(pre_g), Some(pre_b)) = (
+            processed.pre_stretch_r.take(),
+            processed.pre_stretch_g.take(),
+            processed.pre_stretch_b.take(),
+        ) {
+            let stats_r = processed.stats_wb_r.clone().unwrap_or_else(|| compute_image_stats(&pre_r));
+            let stats_g = processed.stats_wb_g.clone().unwrap_or_else(|| compute_image_stats(&pre_g));
+            let stats_b = processed.stats_wb_b.clone().unwrap_or_else(|| compute_image_stats(&pre_b));
+            helpers::insert_composite_and_orig(pre_r, pre_g, pre_b, stats_r, stats_g, stats_b);
+        }
+
+        let png_path = composite_png_path(&output_dir);
+        helpers::render_rgb_preview(
+            &processed.r,
+            &processed.g,
+            &processed.b,
+            &png_path,
+            MAX_PREVIEW_DIM,
+        )?;
+
+        Ok(json!({
+            RES_PNG_PATH: png_path,
+            RES_DIMENSIONS: [cols, rows],
+            RES_BAYER_PATTERN: layout.pattern_string(),
+            RES_DEBAYER_METHOD: rgb.method.to_string(),
+            RES_CALIBRATED: has_calibration,
+            RES_CHANNEL_PATHS: channel_paths,
+            STF_R: helpers::stf_json(&processed.stf_r),
+            STF_G: helpers::stf_json(&processed.stf_g),
+            STF_B: helpers::stf_json(&processed.stf_b),
+            RES_ELAPSED_MS: t0.elapsed().as_millis() as u64,
+        }))
+    })
+}
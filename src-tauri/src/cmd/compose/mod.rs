@@ -1,9 +1,15 @@
 mod blend;
 mod color;
+mod color_adjust;
+mod color_space;
 mod crop;
+mod debayer;
 mod rgb;
 
 pub use blend::*;
 pub use color::*;
+pub use color_adjust::*;
+pub use color_space::*;
 pub use crop::*;
+pub use debayer::*;
 pub use rgb::*;
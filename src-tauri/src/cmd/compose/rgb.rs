@@ -3,7 +3,7 @@ use std::time::Instant;
 
 use serde_json::json;
 
-use crate::cmd::common::{blocking_cmd, load_cached, load_from_cache_or_disk, resolve_output_dir, MAX_PREVIEW_DIM};
+use crate::cmd::common::{blocking_cmd, load_cached_full, load_from_cache_or_disk, resolve_output_dir, MAX_PREVIEW_DIM};
 use crate::cmd::helpers;
 use crate::core::compose::rgb::process_rgb;
 use crate::core::compose::lrgb::apply_lrgb;
@@ -13,6 +13,7 @@ use crate::core::imaging::stf::{StfParams, apply_stf_f32};
 use crate::core::imaging::scnr::apply_scnr_inplace;
 use crate::infra::cache::{ImageEntry, GLOBAL_IMAGE_CACHE};
 use crate::types::compose::{RgbComposeConfig, RgbComposeResult};
+use crate::types::image::RgbColorSpace;
 use crate::types::constants::{RES_DIMENSIONS, RES_DIMENSION_INFO, RES_ELAPSED_MS, RES_MAX, RES_MEAN, RES_MEDIAN, RES_MIN, RES_OFFSET_B, RES_OFFSET_G, RES_PNG_PATH, RES_SCNR_APPLIED, RES_STATS_B, RES_STATS_G, RES_STATS_R, RES_SHADOW, RES_MIDTONE, RES_HIGHLIGHT, LRGB_APPLIED, RESAMPLED, STF_G, STF_R, STF_B, COMPOSITE_KEY_R, COMPOSITE_KEY_G, COMPOSITE_KEY_B, COMPOSITE_ORIG_R, COMPOSITE_ORIG_G, COMPOSITE_ORIG_B};
 
 pub(super) fn composite_png_path(output_dir: &str) -> String {
@@ -34,7 +35,7 @@ pub(super) fn composite_png_path(output_dir: &str) -> String {
 
 pub(super) fn load_entry(path: &Option<String>) -> anyhow::Result<Option<ImageEntry>> {
     match path {
-        Some(p) => Ok(Some(load_cached(p)?)),
+        Some(p) => Ok(Some(load_cached_full(p)?)),
         None => Ok(None),
     }
 }
@@ -60,6 +61,7 @@ pub async fn compose_rgb_cmd(
     dimension_tolerance: Option<usize>,
     lrgb_lightness: Option<f64>,
     lrgb_chrominance: Option<f64>,
+    registration_path: Option<String>,
 ) -> Result<serde_json::Value, String> {
     blocking_cmd!({
         let t0 = Instant::now();
@@ -70,9 +72,15 @@ pub async fn compose_rgb_cmd(
         let g_entry = load_entry(&g_path)?;
         let b_entry = load_entry(&b_path)?;
 
-        let r_ref = r_entry.as_ref().map(|e| e.arr());
-        let g_ref = g_entry.as_ref().map(|e| e.arr());
-        let b_ref = b_entry.as_ref().map(|e| e.arr());
+        let registration = helpers::load_registration(registration_path.as_deref())?;
+        let reg = registration.as_ref();
+        let r_reg = helpers::registered_channel(reg, r_path.as_deref(), r_entry.as_ref().map(|e| e.arr()))?;
+        let g_reg = helpers::registered_channel(reg, g_path.as_deref(), g_entry.as_ref().map(|e| e.arr()))?;
+        let b_reg = helpers::registered_channel(reg, b_path.as_deref(), b_entry.as_ref().map(|e| e.arr()))?;
+
+        let r_ref = r_reg.as_ref().map(|(img, _)| img).or(r_entry.as_ref().map(|e| e.arr()));
+        let g_ref = g_reg.as_ref().map(|(img, _)| img).or(g_entry.as_ref().map(|e| e.arr()));
+        let b_ref = b_reg.as_ref().map(|(img, _)| img).or(b_entry.as_ref().map(|e| e.arr()));
 
         let wb = helpers::parse_wb(wb_mode.as_deref(), wb_r, wb_g, wb_b);
 
@@ -89,7 +97,7 @@ pub async fn compose_rgb_cmd(
             white_balance: wb,
             auto_stretch: auto_stretch.unwrap_or(true),
             linked_stf: linked_stf.unwrap_or(false),
-            align: align.unwrap_or(true),
+            align: align.unwrap_or(true) && registration.is_none(),
             align_method: align_m,
             scnr: scnr_cfg,
             dimension_tolerance: dimension_tolerance.unwrap_or(100),
@@ -113,6 +121,11 @@ pub async fn compose_rgb_cmd(
             let stats_b = processed.stats_wb_b.clone().unwrap_or_else(|| compute_image_stats(&pre_b));
 
             helpers::insert_composite_and_orig(pre_r, pre_g, pre_b, stats_r, stats_g, stats_b);
+            helpers::attach_composite_headers([
+                r_entry.as_ref().and_then(|e| e.header()),
+                g_entry.as_ref().and_then(|e| e.header()),
+                b_entry.as_ref().and_then(|e| e.header()),
+            ]);
         }
 
         let lrgb_applied = if let Some(l_entry_ref) = l_entry.as_ref() {
@@ -170,8 +183,8 @@ pub async fn compose_rgb_cmd(
             stats_r: processed.stats_r.clone(),
             stats_g: processed.stats_g.clone(),
             stats_b: processed.stats_b.clone(),
-            offset_g: processed.offset_g,
-            offset_b: processed.offset_b,
+            offset_g: g_reg.as_ref().map_or(processed.offset_g, |(_, off)| *off),
+            offset_b: b_reg.as_ref().map_or(processed.offset_b, |(_, off)| *off),
             width: processed.cols,
             height: processed.rows,
             scnr_applied: processed.scnr_applied,
@@ -248,6 +261,7 @@ pub async fn clear_composite_cache_cmd() -> Result<(), String> {
     GLOBAL_IMAGE_CACHE.remove(COMPOSITE_ORIG_R);
     GLOBAL_IMAGE_CACHE.remove(COMPOSITE_ORIG_G);
     GLOBAL_IMAGE_CACHE.remove(COMPOSITE_ORIG_B);
+    helpers::set_composite_color_space(RgbColorSpace::default());
     Ok(())
 }
 
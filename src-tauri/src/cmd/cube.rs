@@ -4,11 +4,13 @@ use crate::cmd::common::blocking_cmd;
 use crate::core::cube::eager::classify_spectral_cube;
 use crate::core::cube::eager::{process_cube, build_wavelength_axis};
 use crate::core::cube::lazy::{process_cube_lazy, LazyCube};
+use crate::core::cube::video::VideoSequence;
 use crate::types::constants::{
     RES_BITPIX, RES_FITS_PATH, RES_FRAME_INDEX, RES_FRAMES, RES_HEIGHT,
     RES_OUTPUT_PATH, RES_SPECTRUM, RES_WIDTH,
     RES_SPECTRAL_CLASSIFICATION, RES_IS_SPECTRAL, RES_SPECTRAL_REASON,
     RES_AXIS_TYPE, RES_AXIS_UNIT, RES_CHANNEL_COUNT, RES_WAVELENGTHS,
+    RES_BAYER_PATTERN, RES_TIMESTAMP,
 };
 
 #[tauri::command]
@@ -102,3 +104,39 @@ pub async fn get_cube_spectrum(
         }))
     })
 }
+
+#[tauri::command]
+pub async fn get_video_info(path: String) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        let video = VideoSequence::open(&path)?;
+        Ok(serde_json::to_value(video.info())?)
+    })
+}
+
+#[tauri::command]
+pub async fn get_video_frame(
+    path: String,
+    frame_index: usize,
+    output_path: String,
+    output_fits: Option<String>,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        let video = VideoSequence::open(&path)?;
+        let frame = video.get_frame(frame_index)?;
+        crate::infra::render::grayscale::render_grayscale(&frame, &output_path)?;
+        let fits_path = if let Some(fp) = &output_fits {
+            let header = video.frame_header(frame_index);
+            crate::infra::fits::writer::write_fits_mono(fp, &frame, Some(&header))?;
+            Some(fp.clone())
+        } else {
+            None
+        };
+        Ok(json!({
+            RES_FRAME_INDEX: frame_index,
+            RES_OUTPUT_PATH: output_path,
+            RES_FITS_PATH: fits_path,
+            RES_TIMESTAMP: video.timestamp(frame_index),
+            RES_BAYER_PATTERN: video.cfa_pattern(),
+        }))
+    })
+}
@@ -4,13 +4,60 @@ use serde_json::json;
 
 use crate::cmd::common::{blocking_cmd, extract_image_resolved, load_cached, load_from_cache_or_disk, try_extract_rgb_resolved};
 use crate::cmd::helpers;
+use crate::core::compose::colorspace::{convert_display_rgb, convert_linear_rgb};
 use crate::core::imaging::stats::compute_image_stats;
 use crate::core::imaging::stf::{apply_stf_f32, AutoStfConfig, StfParams};
 use crate::infra::cache::GLOBAL_IMAGE_CACHE;
 use crate::infra::fits::writer::{filter_header, write_fits_mono_bitpix, write_fits_rgb_bitpix};
 use crate::infra::render::grayscale::{render_grayscale, render_grayscale_16bit, render_stretched_8bit, render_stretched_16bit};
-use crate::infra::render::rgb::{render_rgb, render_rgb_16bit};
-use crate::types::constants::{COPY_WCS, COMPOSITE_KEY_R, COMPOSITE_KEY_G, COMPOSITE_KEY_B, RES_APPLY_STF, RES_BIT_DEPTH, RES_BITPIX, RES_COPY_METADATA, RES_DIMENSIONS, RES_ELAPSED_MS, RES_FILE_SIZE_BYTES, RES_OUTPUT_PATH};
+use crate::infra::render::icc::icc_profile;
+use crate::infra::render::rgb::write_rgb_image;
+use crate::infra::tiff::write_tiff;
+use crate::infra::xisf::writer::write_xisf;
+use crate::infra::xisf::XisfMetadata;
+use crate::types::header::HduHeader;
+use crate::types::image::{RgbColorSpace, SampleFormat, TiffCompression};
+use crate::types::constants::{CHANNELS, COPY_WCS, COMPOSITE_KEY_R, COMPOSITE_KEY_G, COMPOSITE_KEY_B, RES_APPLY_STF, RES_BIT_DEPTH, RES_BITPIX, RES_COLOR_SPACE, RES_COMPRESSION, RES_COPY_METADATA, RES_DIMENSIONS, RES_ELAPSED_MS, RES_FILE_SIZE_BYTES, RES_ICC_EMBEDDED, RES_KEYWORD_COUNT, RES_OUTPUT_PATH, RES_PROPERTY_COUNT, RES_SAMPLE_FORMAT};
+
+fn write_color_managed(
+    r: &ndarray::Array2<f32>,
+    g: &ndarray::Array2<f32>,
+    b: &ndarray::Array2<f32>,
+    path: &str,
+    depth: u8,
+    source: RgbColorSpace,
+    target: RgbColorSpace,
+) -> anyhow::Result<u8> {
+    let icc = Some(icc_profile(target));
+    if source == target {
+        return write_rgb_image(r, g, b, path, depth, icc);
+    }
+    let [r2, g2, b2] = convert_display_rgb(r, g, b, source, target)?;
+    write_rgb_image(&r2, &g2, &b2, path, depth, icc)
+}
+
+struct ExportPlanes {
+    planes: Vec<ndarray::Array2<f32>>,
+    header: Option<HduHeader>,
+    color_space: RgbColorSpace,
+}
+
+fn load_export_planes(path: Option<&str>) -> anyhow::Result<ExportPlanes> {
+    let Some(path) = path else {
+        let (r, g, b) = helpers::load_composite_rgb()?;
+        return Ok(ExportPlanes {
+            header: r.header().cloned(),
+            planes: vec![r.arr().to_owned(), g.arr().to_owned(), b.arr().to_owned()],
+            color_space: helpers::composite_color_space(),
+        });
+    };
+    if let Some(rgb) = try_extract_rgb_resolved(path)? {
+        return Ok(ExportPlanes { planes: vec![rgb.r, rgb.g, rgb.b], header: Some(rgb.header), color_space: RgbColorSpace::LinearSrgb });
+    }
+    let resolved = extract_image_resolved(path)?;
+    let arr = load_from_cache_or_disk(path).map(|e| e.arr().to_owned()).unwrap_or(resolved.arr);
+    Ok(ExportPlanes { planes: vec![arr], header: Some(resolved.header), color_space: RgbColorSpace::LinearSrgb })
+}
 
 #[tauri::command]
 pub async fn export_fits(
@@ -168,11 +215,13 @@ pub async fn export_png(
     shadow: Option<f64>,
     midtone: Option<f64>,
     highlight: Option<f64>,
+    color_space: Option<RgbColorSpace>,
 ) -> Result<serde_json::Value, String> {
     blocking_cmd!({
         let t0 = Instant::now();
         let depth = bit_depth.unwrap_or(16);
         let do_stf = apply_stf_stretch.unwrap_or(false);
+        let target_space = color_space.unwrap_or_default();
 
         if let Some(rgb) = try_extract_rgb_resolved(&path)? {
             let sr = compute_image_stats(&rgb.r);
@@ -200,18 +249,15 @@ pub async fn export_png(
                 )
             };
 
-            if depth == 16 {
-                render_rgb_16bit(&r_out, &g_out, &b_out, &output_path)?;
-            } else {
-                render_rgb(&r_out, &g_out, &b_out, &output_path)?;
-            }
+            let written = write_color_managed(&r_out, &g_out, &b_out, &output_path, depth, RgbColorSpace::Srgb, target_space)?;
 
             let file_size = std::fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
             let (rows, cols) = rgb.r.dim();
 
             return Ok(json!({
                 RES_OUTPUT_PATH: output_path,
-                RES_BIT_DEPTH: depth,
+                RES_BIT_DEPTH: written,
+                RES_COLOR_SPACE: target_space,
                 RES_APPLY_STF: true,
                 RES_FILE_SIZE_BYTES: file_size,
                 RES_DIMENSIONS: [cols, rows],
@@ -274,11 +320,13 @@ pub async fn export_rgb_png(
     shadow_b: Option<f64>,
     midtone_b: Option<f64>,
     highlight_b: Option<f64>,
+    color_space: Option<RgbColorSpace>,
 ) -> Result<serde_json::Value, String> {
     blocking_cmd!({
         let t0 = Instant::now();
         let depth = bit_depth.unwrap_or(16);
         let do_stf = apply_stf_stretch.unwrap_or(false);
+        let target_space = color_space.unwrap_or_default();
 
         let cache_r = GLOBAL_IMAGE_CACHE.get(COMPOSITE_KEY_R);
         let cache_g = GLOBAL_IMAGE_CACHE.get(COMPOSITE_KEY_G);
@@ -321,16 +369,14 @@ pub async fn export_rgb_png(
             let r_out = apply_stf_f32(cr.arr(), &stf_r, cr.stats());
             let g_out = apply_stf_f32(cg.arr(), &stf_g, cg.stats());
             let b_out = apply_stf_f32(cb.arr(), &stf_b, cb.stats());
-            if depth == 16 {
-                render_rgb_16bit(&r_out, &g_out, &b_out, &output_path)?;
-            } else {
-                render_rgb(&r_out, &g_out, &b_out, &output_path)?;
-            }
+            let source_space = helpers::composite_color_space();
+            let written = write_color_managed(&r_out, &g_out, &b_out, &output_path, depth, source_space, target_space)?;
             let (rows, cols) = cr.arr().dim();
             let file_size = std::fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
             return Ok(json!({
                 RES_OUTPUT_PATH: output_path,
-                RES_BIT_DEPTH: depth,
+                RES_BIT_DEPTH: written,
+                RES_COLOR_SPACE: target_space,
                 RES_APPLY_STF: do_stf,
                 RES_FILE_SIZE_BYTES: file_size,
                 RES_DIMENSIONS: [cols, rows],
@@ -378,14 +424,14 @@ pub async fn export_rgb_png(
                 )
             };
 
-            if depth == 16 { render_rgb_16bit(&r_out, &g_out, &b_out, &output_path)?; }
-            else { render_rgb(&r_out, &g_out, &b_out, &output_path)?; }
+            let written = write_color_managed(&r_out, &g_out, &b_out, &output_path, depth, RgbColorSpace::Srgb, target_space)?;
 
             let (rows, cols) = r.dim();
             let file_size = std::fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
             Ok(json!({
                 RES_OUTPUT_PATH: output_path,
-                RES_BIT_DEPTH: depth,
+                RES_BIT_DEPTH: written,
+                RES_COLOR_SPACE: target_space,
                 RES_APPLY_STF: true,
                 RES_FILE_SIZE_BYTES: file_size,
                 RES_DIMENSIONS: [cols, rows],
@@ -409,3 +455,89 @@ pub async fn export_rgb_png(
         stretch_and_render(ra, ga, ba)
     })
 }
+
+#[tauri::command]
+pub async fn export_tiff(
+    path: Option<String>,
+    output_path: String,
+    sample_format: Option<SampleFormat>,
+    compression: Option<TiffCompression>,
+    embed_icc: Option<bool>,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        let t0 = Instant::now();
+        let format = sample_format.unwrap_or_default();
+        let compression = compression.unwrap_or_default();
+
+        let source = load_export_planes(path.as_deref())?;
+        let embed = embed_icc.unwrap_or(true) && source.planes.len() == 3;
+        let planes = match (embed, source.planes.as_slice()) {
+            (true, [r, g, b]) if source.color_space != RgbColorSpace::LinearSrgb => {
+                convert_linear_rgb(r, g, b, source.color_space, RgbColorSpace::LinearSrgb)?.to_vec()
+            }
+            _ => source.planes,
+        };
+        let refs: Vec<&ndarray::Array2<f32>> = planes.iter().collect();
+        let icc = embed.then(|| icc_profile(RgbColorSpace::LinearSrgb));
+
+        write_tiff(&refs, &output_path, format, compression, icc.as_deref())?;
+
+        let file_size = std::fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
+        let (rows, cols) = refs[0].dim();
+
+        Ok(json!({
+            RES_OUTPUT_PATH: output_path,
+            RES_SAMPLE_FORMAT: format,
+            RES_COMPRESSION: compression,
+            CHANNELS: refs.len(),
+            RES_ICC_EMBEDDED: icc.is_some(),
+            RES_COLOR_SPACE: icc.as_ref().map(|_| RgbColorSpace::LinearSrgb),
+            RES_FILE_SIZE_BYTES: file_size,
+            RES_DIMENSIONS: [cols, rows],
+            RES_ELAPSED_MS: t0.elapsed().as_millis() as u64,
+        }))
+    })
+}
+
+#[tauri::command]
+pub async fn export_xisf(
+    path: Option<String>,
+    output_path: String,
+    sample_format: Option<SampleFormat>,
+    copy_wcs: Option<bool>,
+    copy_metadata: Option<bool>,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        let t0 = Instant::now();
+        let format = sample_format.unwrap_or_default();
+        let do_wcs = copy_wcs.unwrap_or(true);
+        let do_meta = copy_metadata.unwrap_or(true);
+
+        let source = load_export_planes(path.as_deref())?;
+        let refs: Vec<&ndarray::Array2<f32>> = source.planes.iter().collect();
+        let metadata = source
+            .header
+            .as_ref()
+            .and_then(|h| filter_header(h, do_wcs, do_meta))
+            .map(|h| XisfMetadata::from_header(&h))
+            .unwrap_or_default();
+
+        write_xisf(&refs, &output_path, format, &metadata)?;
+
+        let file_size = std::fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
+        let (rows, cols) = refs[0].dim();
+
+        Ok(json!({
+            RES_OUTPUT_PATH: output_path,
+            RES_SAMPLE_FORMAT: format,
+            CHANNELS: refs.len(),
+            COPY_WCS: do_wcs,
+            RES_COPY_METADATA: do_meta,
+            RES_KEYWORD_COUNT: metadata.keywords.len(),
+            RES_PROPERTY_COUNT: metadata.properties.len(),
+            RES_FILE_SIZE_BYTES: file_size,
+            RES_DIMENSIONS: [cols, rows],
+            RES_ELAPSED_MS: t0.elapsed().as_millis() as u64,
+        }))
+    })
+}
@@ -1,23 +1,31 @@
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use anyhow::Context;
 use ndarray::Array2;
 use serde_json::json;
 
+use crate::core::alignment::registration::RegistrationSet;
+use crate::core::imaging::debayer::DebayerMethod;
 use crate::core::imaging::stf::{auto_stf, AutoStfConfig};
+use crate::core::stacking::calibration::{prepare_calibration, CalibrationConfig};
+use crate::core::stacking::cosmetic::{CosmeticConfig, DefectMap};
+use crate::core::stacking::dark_scaling::{DarkScaleMode, DarkScaling};
 use crate::infra::cache::{ImageEntry, GLOBAL_IMAGE_CACHE};
 use crate::types::compose::{AlignMethod, WhiteBalance};
 use crate::types::constants::{
+    DARK_SCALE_EXPOSURE, DARK_SCALE_OPTIMIZE, DEBAYER_AHD, DEBAYER_AHD4, DEBAYER_VNG,
     DEFAULT_SCNR_AMOUNT, DEFAULT_WB_VALUE,
-    KERNEL_GAUSSIAN, KERNEL_LANCZOS, KERNEL_LANCZOS3,
+    GEOMETRY_AFFINE, GEOMETRY_WCS,
+    KERNEL_GAUSSIAN, KERNEL_LANCZOS, KERNEL_LANCZOS3, KERNEL_POINT, KERNEL_TURBO,
     SCNR_METHOD_MAXIMUM, WB_MODE_MANUAL, WB_MODE_NONE,
     COMPOSITE_KEY_R, COMPOSITE_KEY_G, COMPOSITE_KEY_B,
     COMPOSITE_ORIG_R, COMPOSITE_ORIG_G, COMPOSITE_ORIG_B,
     RES_MIN, RES_MAX, RES_MEAN, RES_SIGMA, RES_MEDIAN, RES_MAD,
     RES_SHADOW, RES_MIDTONE, RES_HIGHLIGHT,
 };
-use crate::types::image::{ImageStats, ScnrConfig, ScnrMethod, StfParams};
-use crate::types::stacking::DrizzleKernel;
+use crate::types::header::HduHeader;
+use crate::types::image::{ImageStats, RgbColorSpace, ScnrConfig, ScnrMethod, StfParams};
+use crate::types::stacking::{DrizzleGeometry, DrizzleKernel, DrizzleWeighting, QualityMetric, ReferenceSelection};
 
 pub(crate) fn parse_scnr_config(
     enabled: Option<bool>,
@@ -59,6 +67,9 @@ pub(crate) fn parse_wb(
 pub(crate) fn parse_align_method(method: Option<&str>) -> AlignMethod {
     match method {
         Some("affine") => AlignMethod::Affine,
+        Some("polynomial") | Some("polynomial2") => AlignMethod::Polynomial2,
+        Some("polynomial3") => AlignMethod::Polynomial3,
+        Some("tps") | Some("thin_plate") => AlignMethod::ThinPlate,
         _ => AlignMethod::PhaseCorrelation,
     }
 }
@@ -67,16 +78,198 @@ pub(crate) fn align_method_str(method: AlignMethod) -> &'static str {
     match method {
         AlignMethod::Affine => "affine",
         AlignMethod::PhaseCorrelation => "phase_correlation",
+        AlignMethod::Polynomial2 => "polynomial2",
+        AlignMethod::Polynomial3 => "polynomial3",
+        AlignMethod::ThinPlate => "thin_plate",
     }
 }
 pub(crate) fn parse_drizzle_kernel(kernel: Option<&str>) -> DrizzleKernel {
     match kernel {
         Some(KERNEL_GAUSSIAN) => DrizzleKernel::Gaussian,
         Some(KERNEL_LANCZOS3) | Some(KERNEL_LANCZOS) => DrizzleKernel::Lanczos3,
+        Some(KERNEL_TURBO) => DrizzleKernel::Turbo,
+        Some(KERNEL_POINT) => DrizzleKernel::Point,
         _ => DrizzleKernel::Square,
     }
 }
 
+pub(crate) fn parse_drizzle_weighting(
+    weight_paths: Option<Vec<String>>,
+    mask_paths: Option<Vec<String>>,
+    use_err: Option<bool>,
+    use_dq: Option<bool>,
+    dq_bad_bits: Option<u32>,
+    saturation: Option<f32>,
+    exposure_weight: Option<bool>,
+    trail_sigma: Option<f32>,
+) -> Option<DrizzleWeighting> {
+    let weighting = DrizzleWeighting {
+        weight_paths,
+        mask_paths,
+        use_err: use_err.unwrap_or(false),
+        use_dq: use_dq.unwrap_or(false),
+        dq_bad_bits,
+        saturation,
+        exposure_weight: exposure_weight.unwrap_or(false),
+        trail_sigma,
+    };
+    let active = weighting.weight_paths.is_some()
+        || weighting.mask_paths.is_some()
+        || weighting.use_err
+        || weighting.use_dq
+        || weighting.saturation.is_some()
+        || weighting.exposure_weight
+        || weighting.trail_sigma.is_some();
+    active.then_some(weighting)
+}
+
+pub(crate) fn parse_drizzle_geometry(geometry: Option<&str>) -> DrizzleGeometry {
+    match geometry {
+        Some(GEOMETRY_AFFINE) => DrizzleGeometry::Affine,
+        Some(GEOMETRY_WCS) => DrizzleGeometry::Wcs,
+        _ => DrizzleGeometry::Translation,
+    }
+}
+
+pub(crate) fn parse_quality_metric(metric: Option<&str>) -> QualityMetric {
+    match metric {
+        Some("gradient") => QualityMetric::Gradient,
+        _ => QualityMetric::Laplacian,
+    }
+}
+
+pub(crate) fn parse_reference_selection(reference_index: Option<usize>) -> ReferenceSelection {
+    reference_index.map_or(ReferenceSelection::Auto, ReferenceSelection::Index)
+}
+
+pub(crate) fn load_registration(path: Option<&str>) -> anyhow::Result<Option<RegistrationSet>> {
+    path.map(RegistrationSet::load_json).transpose()
+}
+
+type RegisteredChannel = (Array2<f32>, (f64, f64));
+
+pub(crate) fn registered_channel(
+    set: Option<&RegistrationSet>,
+    path: Option<&str>,
+    image: Option<&Array2<f32>>,
+) -> anyhow::Result<Option<RegisteredChannel>> {
+    let (Some(set), Some(path), Some(image)) = (set, path, image) else {
+        return Ok(None);
+    };
+    let frame = set
+        .frame(path)
+        .with_context(|| format!("{} is not in the registration file", path))?;
+    let (dx, dy) = frame.offset_at(set.cols as f64 * 0.5, set.rows as f64 * 0.5);
+    Ok(Some((frame.warp(image, set.rows, set.cols), (dy, dx))))
+}
+
+pub(crate) fn parse_dark_scale_mode(mode: Option<&str>) -> DarkScaleMode {
+    match mode {
+        Some(DARK_SCALE_EXPOSURE) => DarkScaleMode::Exposure,
+        Some(DARK_SCALE_OPTIMIZE) => DarkScaleMode::Optimize,
+        _ => DarkScaleMode::Fixed,
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct CalibrationOptions {
+    pub bias_paths: Option<Vec<String>>,
+    pub dark_paths: Option<Vec<String>>,
+    pub flat_paths: Option<Vec<String>>,
+    pub dark_exposure_ratio: Option<f32>,
+    pub dark_scale_mode: Option<String>,
+    pub dark_temp_doubling: Option<f64>,
+    pub cosmetic: Option<bool>,
+    pub hot_sigma: Option<f32>,
+    pub cold_sigma: Option<f32>,
+    pub light_hot_sigma: Option<f32>,
+    pub bad_columns: Option<Vec<usize>>,
+    pub bad_rows: Option<Vec<usize>>,
+    pub defect_map_path: Option<String>,
+}
+
+pub(crate) fn parse_cosmetic_config(
+    cosmetic: Option<bool>,
+    hot_sigma: Option<f32>,
+    cold_sigma: Option<f32>,
+    light_hot_sigma: Option<f32>,
+    bad_columns: Option<Vec<usize>>,
+    bad_rows: Option<Vec<usize>>,
+    defect_map_path: Option<&str>,
+) -> anyhow::Result<Option<CosmeticConfig>> {
+    let existing_map = match defect_map_path {
+        Some(path) => Some(DefectMap::load_fits(path)?),
+        None => None,
+    };
+    if !cosmetic.unwrap_or(false) && existing_map.is_none() {
+        return Ok(None);
+    }
+
+    let defaults = CosmeticConfig::default();
+    Ok(Some(CosmeticConfig {
+        hot_sigma: hot_sigma.unwrap_or(defaults.hot_sigma),
+        cold_sigma: cold_sigma.unwrap_or(defaults.cold_sigma),
+        detect_in_lights: light_hot_sigma.is_some(),
+        light_hot_sigma: light_hot_sigma.unwrap_or(defaults.light_hot_sigma),
+        bad_columns: bad_columns.unwrap_or_default(),
+        bad_rows: bad_rows.unwrap_or_default(),
+        defect_map: existing_map,
+    }))
+}
+
+pub(crate) fn prepare_stack_calibration(
+    options: Option<&CalibrationOptions>,
+    paths: &[String],
+) -> anyhow::Result<Option<CalibrationConfig>> {
+    let (Some(options), Some(first)) = (options, paths.first()) else {
+        return Ok(None);
+    };
+    let dark_scaling = DarkScaling {
+        mode: parse_dark_scale_mode(options.dark_scale_mode.as_deref()),
+        temperature_doubling: options.dark_temp_doubling,
+    };
+    let cosmetic = parse_cosmetic_config(
+        options.cosmetic,
+        options.hot_sigma,
+        options.cold_sigma,
+        options.light_hot_sigma,
+        options.bad_columns.clone(),
+        options.bad_rows.clone(),
+        options.defect_map_path.as_deref(),
+    )?;
+    prepare_calibration(
+        options.bias_paths.as_deref(),
+        options.dark_paths.as_deref(),
+        options.flat_paths.as_deref(),
+        options.dark_exposure_ratio.unwrap_or(1.0),
+        dark_scaling,
+        cosmetic.as_ref(),
+        first,
+    )
+    .map(Some)
+}
+
+pub(crate) fn parse_debayer_method(method: Option<&str>) -> DebayerMethod {
+    match method {
+        Some(DEBAYER_VNG) => DebayerMethod::Vng,
+        Some(DEBAYER_AHD) => DebayerMethod::Ahd,
+        Some(DEBAYER_AHD4) => DebayerMethod::Ahd4,
+        _ => DebayerMethod::Bilinear,
+    }
+}
+
+
+static COMPOSITE_COLOR_SPACE: RwLock<RgbColorSpace> = RwLock::new(RgbColorSpace::Srgb);
+
+pub(crate) fn composite_color_space() -> RgbColorSpace {
+    COMPOSITE_COLOR_SPACE.read().map(|s| *s).unwrap_or_default()
+}
+
+pub(crate) fn set_composite_color_space(space: RgbColorSpace) {
+    if let Ok(mut s) = COMPOSITE_COLOR_SPACE.write() {
+        *s = space;
+    }
+}
 
 pub(crate) fn load_composite_channel(key: &str) -> anyhow::Result<ImageEntry> {
     GLOBAL_IMAGE_CACHE
@@ -119,9 +312,32 @@ pub(crate) fn insert_composite_rgb(
     stats_g: ImageStats,
     stats_b: ImageStats,
 ) {
-    GLOBAL_IMAGE_CACHE.insert_synthetic(COMPOSITE_KEY_R, Arc::new(r), stats_r);
-    GLOBAL_IMAGE_CACHE.insert_synthetic(COMPOSITE_KEY_G, Arc::new(g), stats_g);
-    GLOBAL_IMAGE_CACHE.insert_synthetic(COMPOSITE_KEY_B, Arc::new(b), stats_b);
+    let header = |key: &str| GLOBAL_IMAGE_CACHE.get(key).and_then(|e| e.header().cloned());
+    let (hr, hg, hb) = (header(COMPOSITE_KEY_R), header(COMPOSITE_KEY_G), header(COMPOSITE_KEY_B));
+    GLOBAL_IMAGE_CACHE.insert_synthetic_with_header(COMPOSITE_KEY_R, Arc::new(r), stats_r, hr);
+    GLOBAL_IMAGE_CACHE.insert_synthetic_with_header(COMPOSITE_KEY_G, Arc::new(g), stats_g, hg);
+    GLOBAL_IMAGE_CACHE.insert_synthetic_with_header(COMPOSITE_KEY_B, Arc::new(b), stats_b, hb);
+}
+
+pub(crate) fn attach_composite_headers(headers: [Option<&HduHeader>; 3]) {
+    let keys = [
+        [COMPOSITE_KEY_R, COMPOSITE_ORIG_R],
+        [COMPOSITE_KEY_G, COMPOSITE_ORIG_G],
+        [COMPOSITE_KEY_B, COMPOSITE_ORIG_B],
+    ];
+    for (channel_keys, header) in keys.iter().zip(headers) {
+        let Some(header) = header else { continue };
+        for key in channel_keys {
+            if let Some(entry) = GLOBAL_IMAGE_CACHE.get(key) {
+                GLOBAL_IMAGE_CACHE.insert_synthetic_with_header(
+                    key,
+                    entry.data_arc(),
+                    entry.stats().clone(),
+                    Some(header.clone()),
+                );
+            }
+        }
+    }
 }
 
 pub(crate) fn insert_composite_and_orig(
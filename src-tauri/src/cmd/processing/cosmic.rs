@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use serde_json::json;
+
+use crate::cmd::common::{blocking_cmd, load_cached_full, render_and_save, resolve_output_dir};
+use crate::core::imaging::cosmic_ray::{la_cosmic, LaCosmicConfig};
+use crate::core::imaging::stats::compute_image_stats;
+use crate::infra::cache::GLOBAL_IMAGE_CACHE;
+use crate::infra::progress::ProgressHandle;
+use crate::types::constants::{
+    EVENT_COSMIC_PROGRESS,
+    RES_CR_FRACTION, RES_CR_MASK_PATH, RES_CR_PIXELS, RES_DIMENSIONS, RES_ELAPSED_MS,
+    RES_FITS_PATH, RES_GAIN, RES_ITERATIONS_RUN, RES_PNG_PATH, RES_READ_NOISE,
+};
+
+#[tauri::command]
+pub async fn remove_cosmic_rays_cmd(
+    app: tauri::AppHandle,
+    path: String,
+    output_dir: String,
+    sigclip: Option<f32>,
+    sigfrac: Option<f32>,
+    objlim: Option<f32>,
+    gain: Option<f32>,
+    read_noise: Option<f32>,
+    max_iterations: Option<usize>,
+) -> Result<serde_json::Value, String> {
+    let defaults = LaCosmicConfig::default();
+    let n_iter = max_iterations.unwrap_or(defaults.max_iterations).clamp(1, 10);
+    let progress = ProgressHandle::new(&app, EVENT_COSMIC_PROGRESS, n_iter as u64);
+    let progress_clone = progress.clone();
+
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let entry = load_cached_full(&path)?;
+        let (header_gain, header_rn) = entry
+            .header()
+            .map(LaCosmicConfig::detector_from_header)
+            .unwrap_or((None, None));
+
+        let config = LaCosmicConfig {
+            sigclip: sigclip.unwrap_or(defaults.sigclip),
+            sigfrac: sigfrac.unwrap_or(defaults.sigfrac),
+            objlim: objlim.unwrap_or(defaults.objlim),
+            gain: gain.or(header_gain).unwrap_or(defaults.gain),
+            read_noise: read_noise.or(header_rn).unwrap_or(defaults.read_noise),
+            max_iterations: n_iter,
+        };
+
+        let result = la_cosmic(entry.arr(), &config, Some(&progress_clone))?;
+
+        let ro = render_and_save(&result.cleaned, &path, &output_dir, "crclean", true)?;
+        let (rows, cols) = ro.dims;
+
+        let stem = std::path::Path::new(&path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("output");
+        let mask_path = format!("{}/{}_crmask.fits", output_dir, stem);
+        crate::infra::fits::writer::write_fits_mono(
+            &mask_path,
+            &result.mask.mapv(|v| v as f32),
+            None,
+        )?;
+
+        if let Some(fits_path) = &ro.fits_path {
+            let stats = compute_image_stats(&result.cleaned);
+            GLOBAL_IMAGE_CACHE.insert_synthetic(fits_path, Arc::new(result.cleaned), stats);
+        }
+
+        progress_clone.emit_complete();
+
+        Ok(json!({
+            RES_PNG_PATH: ro.png_path,
+            RES_FITS_PATH: ro.fits_path,
+            RES_CR_MASK_PATH: mask_path,
+            RES_CR_PIXELS: result.cr_pixels,
+            RES_CR_FRACTION: result.cr_pixels as f64 / (rows * cols).max(1) as f64,
+            RES_ITERATIONS_RUN: result.iterations,
+            RES_GAIN: config.gain,
+            RES_READ_NOISE: config.read_noise,
+            RES_ELAPSED_MS: result.elapsed_ms,
+            RES_DIMENSIONS: [cols, rows],
+        }))
+    })
+}
@@ -1,14 +1,18 @@
 use serde_json::json;
 
-use crate::cmd::common::{blocking_cmd, load_from_cache_or_disk, render_and_save, resolve_output_dir};
+use crate::cmd::common::{blocking_cmd, load_cached_full, load_from_cache_or_disk, render_and_save, resolve_output_dir};
 use crate::core::analysis::deconvolution::{generate_gaussian_psf, richardson_lucy};
+use crate::core::analysis::psf_model::{image_pixel_scale, PsfModel};
+use crate::core::analysis::spatial_deconv::{measure_psf_grid, richardson_lucy_variant, PsfGrid};
 use crate::core::imaging::psf_estimation::{estimate_psf, psf_to_kernel, PsfEstimationConfig};
 use crate::infra::progress::ProgressHandle;
 use crate::types::constants::{
     EVENT_DECONV_PROGRESS, SUFFIX_DECONV,
-    RES_CONVERGENCE, RES_DIMENSIONS, RES_ELAPSED_MS, RES_FITS_PATH,
-    RES_ITERATIONS_RUN, RES_PNG_PATH,
+    RES_CONVERGENCE, RES_DIMENSIONS, RES_ELAPSED_MS, RES_FITS_PATH, RES_FWHM,
+    RES_ITERATIONS_RUN, RES_MASK_COVERAGE, RES_MEASURED, RES_PNG_PATH, RES_PSF_TILES,
+    RES_TILES_MEASURED, RES_X, RES_Y,
 };
+use crate::types::image::{PsfSource, SpatialDeconvParams};
 use crate::types::stacking::RLConfig;
 
 #[tauri::command]
@@ -75,3 +79,75 @@ pub async fn deconvolve_rl_cmd(
         }))
     })
 }
+
+#[tauri::command]
+pub async fn deconvolve_variant_cmd(
+    app: tauri::AppHandle,
+    path: String,
+    output_dir: String,
+    params: SpatialDeconvParams,
+) -> Result<serde_json::Value, String> {
+    let progress_clone = ProgressHandle::new(&app, EVENT_DECONV_PROGRESS, params.iterations as u64).clone();
+
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let entry = load_cached_full(&path)?;
+        let image = entry.arr();
+
+        let gaussian = generate_gaussian_psf(params.psf_size, params.psf_sigma as f32);
+        let grid = match params.psf_source {
+            PsfSource::Gaussian => PsfGrid::uniform(gaussian),
+            PsfSource::External => {
+                let psf_path = params
+                    .psf_path
+                    .as_deref()
+                    .ok_or_else(|| anyhow::anyhow!("External PSF source needs a PSF FITS path"))?;
+                let scale = params.pixel_scale.or_else(|| entry.header().and_then(image_pixel_scale));
+                PsfGrid::uniform(PsfModel::load(psf_path)?.resampled(scale)?)
+            }
+            PsfSource::Measured => {
+                let psf_config = PsfEstimationConfig {
+                    num_stars: params.psf_num_stars,
+                    cutout_radius: params.psf_cutout_radius,
+                    ..PsfEstimationConfig::default()
+                };
+                let fallback = estimate_psf(image, &psf_config)
+                    .map(|r| psf_to_kernel(&r))
+                    .unwrap_or(gaussian);
+                measure_psf_grid(image, params.tile_grid, &psf_config, &fallback)
+            }
+        };
+
+        let result = richardson_lucy_variant(image, &grid, &params, Some(&progress_clone))?;
+
+        let ro = render_and_save(&result.image, &path, &output_dir, SUFFIX_DECONV, true)?;
+        let (rows, cols) = ro.dims;
+
+        progress_clone.emit_complete();
+
+        let tiles: Vec<serde_json::Value> = grid
+            .tiles
+            .iter()
+            .enumerate()
+            .map(|(k, t)| json!({
+                RES_X: k % grid.cols,
+                RES_Y: k / grid.cols,
+                RES_FWHM: t.fwhm,
+                RES_MEASURED: t.measured,
+            }))
+            .collect();
+
+        Ok(json!({
+            RES_PNG_PATH: ro.png_path,
+            RES_FITS_PATH: ro.fits_path,
+            RES_ITERATIONS_RUN: result.iterations_run,
+            RES_CONVERGENCE: result.convergence,
+            RES_PSF_TILES: tiles,
+            RES_TILES_MEASURED: grid.measured_count(),
+            RES_MASK_COVERAGE: result.star_mask_coverage,
+            RES_ELAPSED_MS: result.elapsed_ms,
+            RES_DIMENSIONS: [cols, rows],
+        }))
+    })
+}
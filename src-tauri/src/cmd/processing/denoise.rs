@@ -0,0 +1,136 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::bail;
+use ndarray::Array2;
+use serde_json::json;
+
+use crate::cmd::common::{blocking_cmd, load_cached_full, render_and_save, resolve_output_dir, MAX_PREVIEW_DIM};
+use crate::cmd::helpers;
+use crate::core::imaging::cosmic_ray::LaCosmicConfig;
+use crate::core::imaging::denoise::{denoise, denoise_rgb, NoiseModel};
+use crate::core::imaging::stats::compute_image_stats;
+use crate::core::imaging::stf::{make_stf_u8_fn, AutoStfConfig};
+use crate::infra::cache::GLOBAL_IMAGE_CACHE;
+use crate::infra::fits::dispatcher::is_fits_path;
+use crate::infra::fits::reader::load_fits_extension;
+use crate::types::constants::{
+    EXT_ERR,
+    RES_AUTO_STF, RES_DIMENSIONS, RES_ELAPSED_MS, RES_FITS_PATH, RES_METHOD, RES_NOISE_ESTIMATE,
+    RES_NOISE_MODEL, RES_PNG_PATH, SUFFIX_DENOISE,
+};
+use crate::types::header::HduHeader;
+use crate::types::image::{DenoiseParams, NoiseModelKind};
+
+fn err_plane(path: &str) -> anyhow::Result<Option<Array2<f32>>> {
+    if !is_fits_path(Path::new(path)) {
+        return Ok(None);
+    }
+    load_fits_extension(path, EXT_ERR)
+}
+
+fn resolve_noise_model(
+    path: Option<&str>,
+    header: Option<&HduHeader>,
+    image: &Array2<f32>,
+    params: &DenoiseParams,
+) -> anyhow::Result<NoiseModel> {
+    let (header_gain, header_rn) = header.map(LaCosmicConfig::detector_from_header).unwrap_or((None, None));
+    let gain = params.gain.map(|g| g as f32).or(header_gain);
+    let read_noise = params.read_noise.map(|r| r as f32).or(header_rn).unwrap_or(0.0);
+
+    Ok(match params.noise_model {
+        NoiseModelKind::Global => NoiseModel::estimate(image),
+        NoiseModelKind::Detector => match gain {
+            Some(gain) => NoiseModel::Detector { gain, read_noise },
+            None => bail!("Detector noise model needs a gain, either as a parameter or from the GAIN keyword"),
+        },
+        NoiseModelKind::ErrPlane => match path.map(err_plane).transpose()?.flatten() {
+            Some(err) => NoiseModel::ErrPlane(err),
+            None => bail!("No {} extension available for this image", EXT_ERR),
+        },
+        NoiseModelKind::Auto => match (path.and_then(|p| err_plane(p).ok().flatten()), gain) {
+            (Some(err), _) if err.dim() == image.dim() => NoiseModel::ErrPlane(err),
+            (_, Some(gain)) => NoiseModel::Detector { gain, read_noise },
+            _ => NoiseModel::estimate(image),
+        },
+    })
+}
+
+#[tauri::command]
+pub async fn denoise_cmd(
+    path: String,
+    output_dir: String,
+    params: DenoiseParams,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let entry = load_cached_full(&path)?;
+        let model = resolve_noise_model(Some(&path), entry.header(), entry.arr(), &params)?;
+        let result = denoise(entry.arr(), &params, &model)?;
+
+        let ro = render_and_save(&result.image, &path, &output_dir, SUFFIX_DENOISE, true)?;
+        if let Some(fp) = &ro.fits_path {
+            let stats = compute_image_stats(&result.image);
+            GLOBAL_IMAGE_CACHE.insert_synthetic(fp, Arc::new(result.image), stats);
+        }
+        let (rows, cols) = ro.dims;
+
+        Ok(json!({
+            RES_PNG_PATH: ro.png_path,
+            RES_FITS_PATH: ro.fits_path,
+            RES_METHOD: params.method,
+            RES_NOISE_MODEL: model.name(),
+            RES_NOISE_ESTIMATE: result.noise_sigma,
+            RES_ELAPSED_MS: result.elapsed_ms,
+            RES_DIMENSIONS: [cols, rows],
+        }))
+    })
+}
+
+#[tauri::command]
+pub async fn denoise_composite_cmd(
+    output_dir: String,
+    params: DenoiseParams,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let (er, eg, eb) = helpers::load_composite_rgb()?;
+        let models = [
+            resolve_noise_model(None, er.header(), er.arr(), &params)?,
+            resolve_noise_model(None, eg.header(), eg.arr(), &params)?,
+            resolve_noise_model(None, eb.header(), eb.arr(), &params)?,
+        ];
+        let result = denoise_rgb([er.arr(), eg.arr(), eb.arr()], &params, [&models[0], &models[1], &models[2]])?;
+        let [r, g, b] = result.channels;
+        let (rows, cols) = r.dim();
+
+        let (stats_r, (stats_g, stats_b)) = rayon::join(
+            || compute_image_stats(&r),
+            || rayon::join(|| compute_image_stats(&g), || compute_image_stats(&b)),
+        );
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let png_path = format!("{}/composite_{}_{}.png", output_dir, SUFFIX_DENOISE, ts);
+        let linked_stf = helpers::compute_linked_stf(&stats_r, &stats_g, &stats_b, &AutoStfConfig::default());
+        let fn_r = make_stf_u8_fn(&linked_stf, &stats_r);
+        let fn_g = make_stf_u8_fn(&linked_stf, &stats_g);
+        let fn_b = make_stf_u8_fn(&linked_stf, &stats_b);
+        helpers::render_rgb_preview_with_stf(&r, &g, &b, fn_r, fn_g, fn_b, &png_path, MAX_PREVIEW_DIM)?;
+        helpers::insert_composite_rgb(r, g, b, stats_r, stats_g, stats_b);
+
+        Ok(json!({
+            RES_PNG_PATH: png_path,
+            RES_METHOD: params.method,
+            RES_NOISE_MODEL: models.iter().map(NoiseModel::name).collect::<Vec<_>>(),
+            RES_NOISE_ESTIMATE: result.noise_sigmas,
+            RES_AUTO_STF: helpers::stf_json(&linked_stf),
+            RES_ELAPSED_MS: result.elapsed_ms,
+            RES_DIMENSIONS: [cols, rows],
+        }))
+    })
+}
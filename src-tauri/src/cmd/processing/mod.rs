@@ -1,13 +1,23 @@
 mod background;
+mod cosmic;
 mod curves;
 mod deconvolution;
+mod denoise;
+mod pixelmath;
 mod resample;
+mod star_removal;
 mod stretch;
+mod trails;
 mod wavelet;
 
 pub use background::*;
+pub use cosmic::*;
 pub use curves::*;
 pub use deconvolution::*;
+pub use denoise::*;
+pub use pixelmath::*;
 pub use resample::*;
+pub use star_removal::*;
 pub use stretch::*;
+pub use trails::*;
 pub use wavelet::*;
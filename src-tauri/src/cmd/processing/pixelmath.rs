@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::{bail, Context};
+use ndarray::Array2;
+use serde_json::json;
+
+use crate::cmd::common::{blocking_cmd, load_from_cache_or_disk, render_asinh_and_save, resolve_output_dir, MAX_PREVIEW_DIM};
+use crate::cmd::helpers;
+use crate::core::imaging::stats::compute_image_stats;
+use crate::core::imaging::stf::{make_stf_u8_fn, AutoStfConfig};
+use crate::core::pixelmath::eval::{rescale_to_unit, Program};
+use crate::core::pixelmath::parser::{parse, ImageRef};
+use crate::infra::cache::GLOBAL_IMAGE_CACHE;
+use crate::types::constants::{
+    COMPOSITE_KEY_B, COMPOSITE_KEY_G, COMPOSITE_KEY_R, FILE_PIXELMATH_STEM,
+    RES_DIMENSIONS, RES_ELAPSED_MS, RES_FITS_PATH, RES_KEY, RES_PNG_PATH,
+    RES_STATS, RES_STATS_B, RES_STATS_G, RES_STATS_R,
+};
+
+fn evaluate_channel(
+    source: &str,
+    target: Option<&str>,
+    aliases: &HashMap<String, String>,
+    rescale: bool,
+) -> anyhow::Result<Array2<f32>> {
+    let expr = parse(source).with_context(|| format!("Invalid PixelMath expression '{}'", source))?;
+    let resolve = |r: &ImageRef| -> anyhow::Result<Arc<Array2<f32>>> {
+        let key = match r {
+            ImageRef::Target => target.context("Expression uses $T but no target image was given")?,
+            ImageRef::Alias(name) => aliases
+                .get(name)
+                .map(String::as_str)
+                .with_context(|| format!("Unknown image alias ${}", name))?,
+            ImageRef::Key(key) => key.as_str(),
+        };
+        Ok(load_from_cache_or_disk(key)?.data_arc())
+    };
+    let dims = match target {
+        Some(t) => Some(load_from_cache_or_disk(t)?.arr().dim()),
+        None => None,
+    };
+    let mut image = Program::compile(&expr, resolve, dims)?.evaluate();
+    if rescale {
+        rescale_to_unit(&mut image);
+    }
+    Ok(image)
+}
+
+#[tauri::command]
+pub async fn pixel_math_cmd(
+    expressions: Vec<String>,
+    output_dir: String,
+    targets: Option<Vec<String>>,
+    images: Option<HashMap<String, String>>,
+    output_key: Option<String>,
+    rescale: Option<bool>,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        let t0 = Instant::now();
+        resolve_output_dir(&output_dir)?;
+
+        let aliases = images.unwrap_or_default();
+        let rescale = rescale.unwrap_or(false);
+        let targets = targets.unwrap_or_default();
+
+        match expressions.len() {
+            1 => {
+                if targets.len() > 1 {
+                    bail!("A single expression takes at most one target, got {}", targets.len());
+                }
+                let image = evaluate_channel(&expressions[0], targets.first().map(String::as_str), &aliases, rescale)?;
+                let stats = compute_image_stats(&image);
+
+                let stem = output_key.as_deref().unwrap_or(FILE_PIXELMATH_STEM);
+                let (png_path, fits_path) = render_asinh_and_save(&image, &output_dir, stem, true)?;
+                let key = fits_path.clone().context("PixelMath output FITS was not written")?;
+                let (rows, cols) = image.dim();
+                GLOBAL_IMAGE_CACHE.insert_synthetic(&key, Arc::new(image), stats.clone());
+
+                Ok(json!({
+                    RES_KEY: key,
+                    RES_PNG_PATH: png_path,
+                    RES_FITS_PATH: fits_path,
+                    RES_DIMENSIONS: [cols, rows],
+                    RES_STATS: helpers::stats_json_full(&stats),
+                    RES_ELAPSED_MS: t0.elapsed().as_millis() as u64,
+                }))
+            }
+            3 => {
+                let channel_targets: Vec<Option<String>> = match targets.len() {
+                    0 => [COMPOSITE_KEY_R, COMPOSITE_KEY_G, COMPOSITE_KEY_B]
+                        .iter()
+                        .map(|k| GLOBAL_IMAGE_CACHE.get(k).map(|_| k.to_string()))
+                        .collect(),
+                    1 => vec![Some(targets[0].clone()); 3],
+                    3 => targets.into_iter().map(Some).collect(),
+                    n => bail!("RGB expressions take 0, 1 or 3 targets, got {}", n),
+                };
+
+                let mut channels = Vec::with_capacity(3);
+                for (source, target) in expressions.iter().zip(&channel_targets) {
+                    channels.push(evaluate_channel(source, target.as_deref(), &aliases, rescale)?);
+                }
+                let dims = channels[0].dim();
+                if channels.iter().any(|c| c.dim() != dims) {
+                    bail!("RGB expressions produced channels of different sizes");
+                }
+                let b = channels.pop().unwrap();
+                let g = channels.pop().unwrap();
+                let r = channels.pop().unwrap();
+
+                let (stats_r, (stats_g, stats_b)) = rayon::join(
+                    || compute_image_stats(&r),
+                    || rayon::join(|| compute_image_stats(&g), || compute_image_stats(&b)),
+                );
+                helpers::insert_composite_and_orig(r, g, b, stats_r.clone(), stats_g.clone(), stats_b.clone());
+
+                let ts = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_millis())
+                    .unwrap_or(0);
+                let png_path = format!("{}/composite_pixelmath_{}.png", output_dir, ts);
+                let (er, eg, eb) = helpers::load_composite_rgb()?;
+                let linked_stf = helpers::compute_linked_stf(er.stats(), eg.stats(), eb.stats(), &AutoStfConfig::default());
+                helpers::render_rgb_preview_with_stf(
+                    er.arr(),
+                    eg.arr(),
+                    eb.arr(),
+                    make_stf_u8_fn(&linked_stf, er.stats()),
+                    make_stf_u8_fn(&linked_stf, eg.stats()),
+                    make_stf_u8_fn(&linked_stf, eb.stats()),
+                    &png_path,
+                    MAX_PREVIEW_DIM,
+                )?;
+
+                Ok(json!({
+                    RES_KEY: [COMPOSITE_KEY_R, COMPOSITE_KEY_G, COMPOSITE_KEY_B],
+                    RES_PNG_PATH: png_path,
+                    RES_DIMENSIONS: [dims.1, dims.0],
+                    RES_STATS_R: helpers::stats_json_full(&stats_r),
+                    RES_STATS_G: helpers::stats_json_full(&stats_g),
+                    RES_STATS_B: helpers::stats_json_full(&stats_b),
+                    RES_ELAPSED_MS: t0.elapsed().as_millis() as u64,
+                }))
+            }
+            n => bail!("PixelMath takes 1 expression or 3 RGB expressions, got {}", n),
+        }
+    })
+}
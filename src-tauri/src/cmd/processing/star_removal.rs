@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use ndarray::Array2;
+use serde_json::json;
+
+use crate::cmd::common::{blocking_cmd, load_from_cache_or_disk, render_and_save, resolve_output_dir, MAX_PREVIEW_DIM};
+use crate::cmd::helpers;
+use crate::core::imaging::star_removal::{remove_stars, screen_blend, StarRemovalConfig};
+use crate::core::imaging::stats::compute_image_stats;
+use crate::infra::cache::GLOBAL_IMAGE_CACHE;
+use crate::infra::fits::writer::write_fits_mono;
+use crate::types::constants::{
+    RES_DIMENSIONS, RES_ELAPSED_MS, RES_FILL_SCALES, RES_FITS_PATH, RES_MASK_COVERAGE,
+    RES_PNG_PATH, RES_STARLESS, RES_STARS_MASKED, RES_STARS_ONLY,
+    SUFFIX_SCREEN, SUFFIX_STARLESS, SUFFIX_STARS,
+};
+
+fn build_config(
+    detection_sigma: Option<f64>,
+    min_fwhm: Option<f64>,
+    max_fwhm: Option<f64>,
+    growth_factor: Option<f64>,
+    softness: Option<f64>,
+) -> StarRemovalConfig {
+    let defaults = StarRemovalConfig::default();
+    StarRemovalConfig {
+        detection_sigma: detection_sigma.unwrap_or(defaults.detection_sigma),
+        min_fwhm: min_fwhm.unwrap_or(defaults.min_fwhm),
+        max_fwhm: max_fwhm.unwrap_or(defaults.max_fwhm),
+        growth_factor: growth_factor.unwrap_or(defaults.growth_factor),
+        softness: softness.unwrap_or(defaults.softness),
+    }
+}
+
+fn save_cached(arr: &Array2<f32>, path: &str, output_dir: &str, suffix: &str) -> anyhow::Result<(String, Option<String>)> {
+    let ro = render_and_save(arr, path, output_dir, suffix, true)?;
+    if let Some(fp) = &ro.fits_path {
+        GLOBAL_IMAGE_CACHE.insert_synthetic(fp, Arc::new(arr.clone()), compute_image_stats(arr));
+    }
+    Ok((ro.png_path, ro.fits_path))
+}
+
+fn composite_preview_path(output_dir: &str, kind: &str) -> String {
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    format!("{}/composite_{}_{}.png", output_dir, kind, ts)
+}
+
+#[tauri::command]
+pub async fn remove_stars_cmd(
+    path: String,
+    output_dir: String,
+    detection_sigma: Option<f64>,
+    min_fwhm: Option<f64>,
+    max_fwhm: Option<f64>,
+    growth_factor: Option<f64>,
+    softness: Option<f64>,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let entry = load_from_cache_or_disk(&path)?;
+        let config = build_config(detection_sigma, min_fwhm, max_fwhm, growth_factor, softness);
+
+        let t0 = std::time::Instant::now();
+        let result = remove_stars(&[entry.arr()], &config)?;
+        let elapsed_ms = t0.elapsed().as_millis() as u64;
+
+        let (starless_png, starless_fits) = save_cached(&result.starless[0], &path, &output_dir, SUFFIX_STARLESS)?;
+        let (stars_png, stars_fits) = save_cached(&result.stars[0], &path, &output_dir, SUFFIX_STARS)?;
+        let (rows, cols) = result.mask.dim();
+
+        Ok(json!({
+            RES_STARLESS: { RES_PNG_PATH: starless_png, RES_FITS_PATH: starless_fits },
+            RES_STARS_ONLY: { RES_PNG_PATH: stars_png, RES_FITS_PATH: stars_fits },
+            RES_STARS_MASKED: result.stars_removed,
+            RES_MASK_COVERAGE: result.coverage_fraction,
+            RES_FILL_SCALES: result.fill_scales,
+            RES_ELAPSED_MS: elapsed_ms,
+            RES_DIMENSIONS: [cols, rows],
+        }))
+    })
+}
+
+#[tauri::command]
+pub async fn remove_stars_composite_cmd(
+    output_dir: String,
+    detection_sigma: Option<f64>,
+    min_fwhm: Option<f64>,
+    max_fwhm: Option<f64>,
+    growth_factor: Option<f64>,
+    softness: Option<f64>,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let (er, eg, eb) = helpers::load_composite_rgb()?;
+        let config = build_config(detection_sigma, min_fwhm, max_fwhm, growth_factor, softness);
+
+        let t0 = std::time::Instant::now();
+        let result = remove_stars(&[er.arr(), eg.arr(), eb.arr()], &config)?;
+        let elapsed_ms = t0.elapsed().as_millis() as u64;
+
+        let mut starless_paths = Vec::with_capacity(3);
+        let mut stars_paths = Vec::with_capacity(3);
+        for (label, (starless, stars)) in ["r", "g", "b"].iter().zip(result.starless.iter().zip(&result.stars)) {
+            for (suffix, arr, out) in [
+                (SUFFIX_STARLESS, starless, &mut starless_paths),
+                (SUFFIX_STARS, stars, &mut stars_paths),
+            ] {
+                let fp = format!("{}/composite_{}_{}.fits", output_dir, suffix, label);
+                write_fits_mono(&fp, arr, None)?;
+                GLOBAL_IMAGE_CACHE.insert_synthetic(&fp, Arc::new(arr.clone()), compute_image_stats(arr));
+                out.push(fp);
+            }
+        }
+
+        let [r, g, b]: [Array2<f32>; 3] = result.starless.try_into().map_err(|_| anyhow::anyhow!("Expected 3 channels"))?;
+        let (stats_r, (stats_g, stats_b)) = rayon::join(
+            || compute_image_stats(&r),
+            || rayon::join(|| compute_image_stats(&g), || compute_image_stats(&b)),
+        );
+        let png_path = composite_preview_path(&output_dir, SUFFIX_STARLESS);
+        helpers::render_rgb_preview(&r, &g, &b, &png_path, MAX_PREVIEW_DIM)?;
+        let (rows, cols) = r.dim();
+        helpers::insert_composite_rgb(r, g, b, stats_r, stats_g, stats_b);
+
+        Ok(json!({
+            RES_PNG_PATH: png_path,
+            RES_STARLESS: starless_paths,
+            RES_STARS_ONLY: stars_paths,
+            RES_STARS_MASKED: result.stars_removed,
+            RES_MASK_COVERAGE: result.coverage_fraction,
+            RES_FILL_SCALES: result.fill_scales,
+            RES_ELAPSED_MS: elapsed_ms,
+            RES_DIMENSIONS: [cols, rows],
+        }))
+    })
+}
+
+#[tauri::command]
+pub async fn screen_blend_cmd(
+    base_paths: Vec<String>,
+    star_paths: Vec<String>,
+    output_dir: String,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        if base_paths.len() != star_paths.len() || !matches!(base_paths.len(), 1 | 3) {
+            anyhow::bail!(
+                "Screen blend needs 1 or 3 matching channels, got {} base and {} star",
+                base_paths.len(), star_paths.len()
+            );
+        }
+
+        let mut blended = Vec::with_capacity(base_paths.len());
+        for (base_path, star_path) in base_paths.iter().zip(&star_paths) {
+            let base = load_from_cache_or_disk(base_path)?;
+            let stars = load_from_cache_or_disk(star_path)?;
+            if base.arr().dim() != stars.arr().dim() {
+                anyhow::bail!("Starless and stars images differ in size: {} vs {}", base_path, star_path);
+            }
+            blended.push(screen_blend(base.arr(), stars.arr()));
+        }
+        let (rows, cols) = blended[0].dim();
+
+        if blended.len() == 1 {
+            let (png_path, fits_path) = save_cached(&blended[0], &base_paths[0], &output_dir, SUFFIX_SCREEN)?;
+            return Ok(json!({
+                RES_PNG_PATH: png_path,
+                RES_FITS_PATH: fits_path,
+                RES_DIMENSIONS: [cols, rows],
+            }));
+        }
+
+        let b = blended.pop().unwrap();
+        let g = blended.pop().unwrap();
+        let r = blended.pop().unwrap();
+        let png_path = composite_preview_path(&output_dir, SUFFIX_SCREEN);
+        helpers::render_rgb_preview(&r, &g, &b, &png_path, MAX_PREVIEW_DIM)?;
+        let (stats_r, (stats_g, stats_b)) = rayon::join(
+            || compute_image_stats(&r),
+            || rayon::join(|| compute_image_stats(&g), || compute_image_stats(&b)),
+        );
+        helpers::insert_composite_rgb(r, g, b, stats_r, stats_g, stats_b);
+
+        Ok(json!({
+            RES_PNG_PATH: png_path,
+            RES_DIMENSIONS: [cols, rows],
+        }))
+    })
+}
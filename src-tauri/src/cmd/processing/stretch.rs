@@ -2,14 +2,17 @@ use serde_json::json;
 
 use crate::cmd::common::{blocking_cmd, load_from_cache_or_disk, render_and_save, resolve_output_dir, MAX_PREVIEW_DIM};
 use crate::cmd::helpers;
+use crate::core::imaging::ghs::{ghs_curve, ghs_histogram_preview, ghs_stretch, ghs_stretch_rgb};
 use crate::core::imaging::stretch::{arcsinh_stretch, arcsinh_stretch_rgb};
 use crate::core::imaging::masked_stretch::{masked_stretch, masked_stretch_rgb_shared, MaskedStretchConfig};
 use crate::types::constants::{
     RES_DIMENSIONS, RES_ELAPSED_MS, RES_FITS_PATH, RES_PNG_PATH,
     RES_STRETCH_FACTOR, RES_ITERATIONS_RUN, RES_STARS_MASKED,
     RES_MASK_COVERAGE, RES_FINAL_BACKGROUND, RES_CONVERGED,
-    SUFFIX_MASKED_STRETCH,
+    SUFFIX_MASKED_STRETCH, SUFFIX_GHS, RES_GHS_CURVE, RES_LINKED, RES_BINS, RES_BIN_EDGES,
+    GHS_CURVE_SAMPLES, HISTOGRAM_BINS_DISPLAY,
 };
+use crate::types::image::GhsParams;
 
 #[tauri::command]
 pub async fn apply_arcsinh_stretch_cmd(
@@ -219,3 +222,86 @@ pub async fn masked_stretch_composite_cmd(
         }))
     })
 }
+
+#[tauri::command]
+pub async fn ghs_stretch_cmd(
+    path: String,
+    output_dir: String,
+    params: GhsParams,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let entry = load_from_cache_or_disk(&path)?;
+
+        let t0 = std::time::Instant::now();
+        let stretched = ghs_stretch(entry.arr(), &params)?;
+        let elapsed_ms = t0.elapsed().as_millis() as u64;
+
+        let ro = render_and_save(&stretched, &path, &output_dir, SUFFIX_GHS, true)?;
+        let (rows, cols) = ro.dims;
+
+        Ok(json!({
+            RES_PNG_PATH: ro.png_path,
+            RES_FITS_PATH: ro.fits_path,
+            RES_ELAPSED_MS: elapsed_ms,
+            RES_DIMENSIONS: [cols, rows],
+        }))
+    })
+}
+
+#[tauri::command]
+pub async fn ghs_stretch_composite_cmd(
+    output_dir: String,
+    params: GhsParams,
+    linked: Option<bool>,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let (er, eg, eb) = helpers::load_composite_rgb()?;
+        let linked = linked.unwrap_or(true);
+
+        let t0 = std::time::Instant::now();
+        let (r, g, b) = ghs_stretch_rgb(er.arr(), eg.arr(), eb.arr(), &params, linked)?;
+        let elapsed_ms = t0.elapsed().as_millis() as u64;
+
+        let (rows, cols) = r.dim();
+
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let png_path = format!("{}/composite_ghs_{}.png", output_dir, ts);
+        helpers::render_rgb_preview(&r, &g, &b, &png_path, MAX_PREVIEW_DIM)?;
+
+        Ok(json!({
+            RES_PNG_PATH: png_path,
+            RES_LINKED: linked,
+            RES_ELAPSED_MS: elapsed_ms,
+            RES_DIMENSIONS: [cols, rows],
+        }))
+    })
+}
+
+#[tauri::command]
+pub async fn ghs_preview_cmd(
+    path: String,
+    params: GhsParams,
+    bins: Option<usize>,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        let entry = load_from_cache_or_disk(&path)?;
+
+        let t0 = std::time::Instant::now();
+        let curve = ghs_curve(&params, GHS_CURVE_SAMPLES)?;
+        let hist = ghs_histogram_preview(entry.arr(), &params, bins.unwrap_or(HISTOGRAM_BINS_DISPLAY))?;
+
+        Ok(json!({
+            RES_GHS_CURVE: curve,
+            RES_BINS: hist.bins,
+            RES_BIN_EDGES: hist.bin_edges,
+            RES_ELAPSED_MS: t0.elapsed().as_millis() as u64,
+        }))
+    })
+}
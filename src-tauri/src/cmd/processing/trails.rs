@@ -0,0 +1,54 @@
+use serde_json::json;
+
+use crate::cmd::common::{blocking_cmd, load_cached_full, resolve_output_dir};
+use crate::core::imaging::trails::{detect_trails, TrailConfig};
+use crate::types::constants::{
+    RES_DIMENSIONS, RES_ELAPSED_MS, RES_MASKED_PIXELS, RES_TRAIL_COUNT, RES_TRAIL_MASK_PATH, RES_TRAILS,
+};
+
+#[tauri::command]
+pub async fn detect_trails_cmd(
+    path: String,
+    output_dir: String,
+    sigma: Option<f32>,
+    min_length: Option<f64>,
+    widen_px: Option<f64>,
+    max_trails: Option<usize>,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        let t0 = std::time::Instant::now();
+        resolve_output_dir(&output_dir)?;
+
+        let entry = load_cached_full(&path)?;
+        let defaults = TrailConfig::default();
+        let config = TrailConfig {
+            sigma: sigma.unwrap_or(defaults.sigma),
+            min_length: min_length.unwrap_or(defaults.min_length),
+            widen_px: widen_px.unwrap_or(defaults.widen_px),
+            max_trails: max_trails.unwrap_or(defaults.max_trails),
+        };
+
+        let result = detect_trails(entry.arr(), &config);
+        let (rows, cols) = result.mask.dim();
+
+        let stem = std::path::Path::new(&path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("output");
+        let mask_path = format!("{}/{}_trailmask.fits", output_dir, stem);
+        crate::infra::fits::writer::write_fits_mono(
+            &mask_path,
+            &result.mask.mapv(|v| v as f32),
+            None,
+        )?;
+
+        Ok(json!({
+            RES_TRAIL_MASK_PATH: mask_path,
+            RES_TRAIL_COUNT: result.trails.len(),
+            RES_TRAILS: result.trails,
+            RES_MASKED_PIXELS: result.masked_pixels,
+            RES_DIMENSIONS: [cols, rows],
+            RES_ELAPSED_MS: t0.elapsed().as_millis() as u64,
+        }))
+    })
+}
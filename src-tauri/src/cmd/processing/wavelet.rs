@@ -1,13 +1,31 @@
+use std::sync::Arc;
+
+use ndarray::Array2;
 use serde_json::json;
 
-use crate::cmd::common::{blocking_cmd, load_from_cache_or_disk, render_and_save, resolve_output_dir};
-use crate::core::imaging::wavelet::{wavelet_denoise, WaveletConfig};
+use crate::cmd::common::{blocking_cmd, load_from_cache_or_disk, render_and_save, resolve_output_dir, MAX_PREVIEW_DIM};
+use crate::cmd::helpers;
+use crate::core::imaging::multiscale::{multiscale_process, multiscale_process_with_mask, rgb_luminance_mask};
+use crate::core::imaging::stats::compute_image_stats;
+use crate::core::imaging::stf::{make_stf_u8_fn, AutoStfConfig};
+use crate::core::imaging::wavelet::{atrous_decompose, atrous_noise_scaling, layer_noise_sigma, wavelet_denoise, WaveletConfig, MAX_SCALES};
+use crate::infra::cache::GLOBAL_IMAGE_CACHE;
 use crate::infra::progress::ProgressHandle;
 use crate::types::constants::{
     EVENT_WAVELET_PROGRESS,
-    RES_DIMENSIONS, RES_ELAPSED_MS, RES_FITS_PATH, RES_NOISE_ESTIMATE,
-    RES_PNG_PATH, RES_SCALES_PROCESSED,
+    RES_AUTO_STF, RES_DIMENSIONS, RES_ELAPSED_MS, RES_FITS_PATH, RES_LAYERS, RES_LAYER_SIGMAS,
+    RES_NOISE_ESTIMATE, RES_PNG_PATH, RES_RESIDUAL, RES_SCALE, RES_SCALES_PROCESSED, RES_SIGMA,
+    SUFFIX_MULTISCALE, SUFFIX_WAVELET_RESIDUAL,
 };
+use crate::types::image::MultiscaleParams;
+
+fn save_cached(arr: &Array2<f32>, path: &str, output_dir: &str, suffix: &str) -> anyhow::Result<(String, Option<String>)> {
+    let ro = render_and_save(arr, path, output_dir, suffix, true)?;
+    if let Some(fp) = &ro.fits_path {
+        GLOBAL_IMAGE_CACHE.insert_synthetic(fp, Arc::new(arr.clone()), compute_image_stats(arr));
+    }
+    Ok((ro.png_path, ro.fits_path))
+}
 
 #[tauri::command]
 pub async fn wavelet_denoise_cmd(
@@ -48,3 +66,127 @@ pub async fn wavelet_denoise_cmd(
         }))
     })
 }
+
+#[tauri::command]
+pub async fn wavelet_layers_cmd(
+    path: String,
+    output_dir: String,
+    num_scales: Option<usize>,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let entry = load_from_cache_or_disk(&path)?;
+        let scales = num_scales.unwrap_or(MultiscaleParams::default().num_scales).clamp(1, MAX_SCALES);
+
+        let t0 = std::time::Instant::now();
+        let layers = atrous_decompose(entry.arr(), scales);
+        let base_sigma = layer_noise_sigma(layers.details[0].as_slice().expect("contiguous"));
+
+        let mut saved = Vec::with_capacity(scales);
+        for (j, layer) in layers.details.iter().enumerate() {
+            let (png_path, fits_path) = save_cached(layer, &path, &output_dir, &format!("wavelet_layer{}", j + 1))?;
+            saved.push(json!({
+                RES_SCALE: 1usize << j,
+                RES_SIGMA: base_sigma * atrous_noise_scaling(j),
+                RES_PNG_PATH: png_path,
+                RES_FITS_PATH: fits_path,
+            }));
+        }
+        let (residual_png, residual_fits) = save_cached(&layers.residual, &path, &output_dir, SUFFIX_WAVELET_RESIDUAL)?;
+        let (rows, cols) = layers.residual.dim();
+
+        Ok(json!({
+            RES_LAYERS: saved,
+            RES_RESIDUAL: { RES_PNG_PATH: residual_png, RES_FITS_PATH: residual_fits },
+            RES_NOISE_ESTIMATE: base_sigma,
+            RES_SCALES_PROCESSED: scales,
+            RES_ELAPSED_MS: t0.elapsed().as_millis() as u64,
+            RES_DIMENSIONS: [cols, rows],
+        }))
+    })
+}
+
+#[tauri::command]
+pub async fn multiscale_process_cmd(
+    path: String,
+    output_dir: String,
+    params: MultiscaleParams,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let entry = load_from_cache_or_disk(&path)?;
+
+        let t0 = std::time::Instant::now();
+        let result = multiscale_process(entry.arr(), &params)?;
+        let elapsed_ms = t0.elapsed().as_millis() as u64;
+
+        let (png_path, fits_path) = save_cached(&result.image, &path, &output_dir, SUFFIX_MULTISCALE)?;
+        let (rows, cols) = result.image.dim();
+
+        Ok(json!({
+            RES_PNG_PATH: png_path,
+            RES_FITS_PATH: fits_path,
+            RES_LAYER_SIGMAS: result.layer_sigmas,
+            RES_SCALES_PROCESSED: result.scales,
+            RES_ELAPSED_MS: elapsed_ms,
+            RES_DIMENSIONS: [cols, rows],
+        }))
+    })
+}
+
+#[tauri::command]
+pub async fn multiscale_process_composite_cmd(
+    output_dir: String,
+    params: MultiscaleParams,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let (er, eg, eb) = helpers::load_composite_rgb()?;
+
+        let t0 = std::time::Instant::now();
+        let mask = rgb_luminance_mask([er.arr(), eg.arr(), eb.arr()], &params);
+        let mask = mask.as_ref();
+        let (rr, (rg, rb)) = rayon::join(
+            || multiscale_process_with_mask(er.arr(), &params, mask),
+            || {
+                rayon::join(
+                    || multiscale_process_with_mask(eg.arr(), &params, mask),
+                    || multiscale_process_with_mask(eb.arr(), &params, mask),
+                )
+            },
+        );
+        let (rr, rg, rb) = (rr?, rg?, rb?);
+        let elapsed_ms = t0.elapsed().as_millis() as u64;
+        let sigmas = [rr.layer_sigmas, rg.layer_sigmas, rb.layer_sigmas];
+        let (r, g, b) = (rr.image, rg.image, rb.image);
+        let (rows, cols) = r.dim();
+
+        let (stats_r, (stats_g, stats_b)) = rayon::join(
+            || compute_image_stats(&r),
+            || rayon::join(|| compute_image_stats(&g), || compute_image_stats(&b)),
+        );
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let png_path = format!("{}/composite_{}_{}.png", output_dir, SUFFIX_MULTISCALE, ts);
+        let linked_stf = helpers::compute_linked_stf(&stats_r, &stats_g, &stats_b, &AutoStfConfig::default());
+        let fn_r = make_stf_u8_fn(&linked_stf, &stats_r);
+        let fn_g = make_stf_u8_fn(&linked_stf, &stats_g);
+        let fn_b = make_stf_u8_fn(&linked_stf, &stats_b);
+        helpers::render_rgb_preview_with_stf(&r, &g, &b, fn_r, fn_g, fn_b, &png_path, MAX_PREVIEW_DIM)?;
+        helpers::insert_composite_rgb(r, g, b, stats_r, stats_g, stats_b);
+
+        Ok(json!({
+            RES_PNG_PATH: png_path,
+            RES_LAYER_SIGMAS: sigmas,
+            RES_SCALES_PROCESSED: rr.scales,
+            RES_AUTO_STF: helpers::stf_json(&linked_stf),
+            RES_ELAPSED_MS: elapsed_ms,
+            RES_DIMENSIONS: [cols, rows],
+        }))
+    })
+}
@@ -1,14 +1,26 @@
+use std::sync::Arc;
+
+use anyhow::bail;
+use ndarray::Array2;
 use serde_json::json;
 
-use crate::cmd::common::{blocking_cmd, load_from_cache_or_disk};
+use crate::cmd::common::{blocking_cmd, load_cached_full, load_from_cache_or_disk, render_and_save, resolve_output_dir};
+use crate::core::analysis::psf_model::{convolve_normalized, image_pixel_scale, psf_matching_kernel, PsfModel};
+use crate::core::analysis::spatial_deconv::kernel_fwhm;
 use crate::core::imaging::psf_estimation::{
-    estimate_psf, PsfEstimationConfig,
+    estimate_psf, psf_to_kernel, PsfEstimationConfig,
 };
+use crate::core::imaging::stats::compute_image_stats;
+use crate::infra::cache::GLOBAL_IMAGE_CACHE;
 use crate::types::constants::{
     RES_X, RES_Y, RES_PEAK, RES_FLUX, RES_FWHM, RES_ELLIPTICITY, RES_SNR,
     RES_KERNEL_SIZE, RES_AVERAGE_FWHM, RES_AVERAGE_ELLIPTICITY,
     RES_SPREAD_PIXELS, RES_STARS_USED, RES_STARS_REJECTED, RES_KERNEL,
+    RES_DIMENSIONS, RES_ELAPSED_MS, RES_FITS_PATH, RES_OVERSAMPLING, RES_PIXEL_SCALE_ARCSEC,
+    RES_PNG_PATH, RES_PSF_PIXEL_SCALE, RES_SCALE_FACTOR, RES_SOURCE_FWHM, RES_TARGET_FWHM,
+    SUFFIX_PSF_MATCHED,
 };
+use crate::types::image::PsfMatchParams;
 
 #[tauri::command]
 pub async fn estimate_psf_cmd(
@@ -57,3 +69,91 @@ pub async fn estimate_psf_cmd(
         }))
     })
 }
+
+fn kernel_rows(kernel: &Array2<f32>) -> Vec<Vec<f32>> {
+    kernel.rows().into_iter().map(|row| row.to_vec()).collect()
+}
+
+fn measured_psf(image: &Array2<f32>, config: &PsfEstimationConfig, label: &str) -> anyhow::Result<Array2<f32>> {
+    estimate_psf(image, config)
+        .map(|r| psf_to_kernel(&r))
+        .map_err(|e| anyhow::anyhow!("{} PSF estimation failed: {}", label, e))
+}
+
+#[tauri::command]
+pub async fn load_psf_model_cmd(
+    psf_path: String,
+    image_path: Option<String>,
+    pixel_scale: Option<f64>,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        let model = PsfModel::load(&psf_path)?;
+        let scale = match (pixel_scale, &image_path) {
+            (Some(s), _) => Some(s),
+            (None, Some(p)) => load_cached_full(p)?.header().and_then(image_pixel_scale),
+            (None, None) => None,
+        };
+        let kernel = model.resampled(scale)?;
+
+        Ok(json!({
+            RES_KERNEL_SIZE: kernel.nrows(),
+            RES_FWHM: kernel_fwhm(&kernel),
+            RES_OVERSAMPLING: model.oversampling,
+            RES_PSF_PIXEL_SCALE: model.pixel_scale,
+            RES_PIXEL_SCALE_ARCSEC: scale,
+            RES_SCALE_FACTOR: model.scale_factor(scale),
+            RES_KERNEL: kernel_rows(&kernel),
+        }))
+    })
+}
+
+#[tauri::command]
+pub async fn psf_match_cmd(
+    path: String,
+    output_dir: String,
+    params: PsfMatchParams,
+) -> Result<serde_json::Value, String> {
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let entry = load_cached_full(&path)?;
+        let scale = params.pixel_scale.or_else(|| entry.header().and_then(image_pixel_scale));
+        let psf_config = PsfEstimationConfig {
+            num_stars: params.psf_num_stars,
+            cutout_radius: params.psf_cutout_radius,
+            ..PsfEstimationConfig::default()
+        };
+
+        let source = match &params.source_psf {
+            Some(p) => PsfModel::load(p)?.resampled(scale)?,
+            None => measured_psf(entry.arr(), &psf_config, "Source")?,
+        };
+        let target = match (&params.target_psf, &params.target_image) {
+            (Some(p), _) => PsfModel::load(p)?.resampled(scale)?,
+            (None, Some(img)) => measured_psf(load_from_cache_or_disk(img)?.arr(), &psf_config, "Target")?,
+            (None, None) => bail!("PSF matching needs a target PSF file or a target image"),
+        };
+
+        let t0 = std::time::Instant::now();
+        let kernel = psf_matching_kernel(&source, &target, params.regularization)?;
+        let matched = convolve_normalized(entry.arr(), &kernel);
+        let elapsed_ms = t0.elapsed().as_millis() as u64;
+
+        let ro = render_and_save(&matched, &path, &output_dir, SUFFIX_PSF_MATCHED, true)?;
+        let (rows, cols) = ro.dims;
+        if let Some(fp) = &ro.fits_path {
+            let stats = compute_image_stats(&matched);
+            GLOBAL_IMAGE_CACHE.insert_synthetic(fp, Arc::new(matched), stats);
+        }
+
+        Ok(json!({
+            RES_PNG_PATH: ro.png_path,
+            RES_FITS_PATH: ro.fits_path,
+            RES_SOURCE_FWHM: kernel_fwhm(&source),
+            RES_TARGET_FWHM: kernel_fwhm(&target),
+            RES_KERNEL_SIZE: kernel.nrows(),
+            RES_ELAPSED_MS: elapsed_ms,
+            RES_DIMENSIONS: [cols, rows],
+        }))
+    })
+}
@@ -1,15 +1,20 @@
 use serde_json::json;
 
 use crate::cmd::common::{blocking_cmd, render_asinh_and_save, resolve_output_dir};
+use crate::cmd::helpers::{self, parse_cosmetic_config, parse_dark_scale_mode};
 use crate::core::imaging::stats::compute_image_stats;
+use crate::core::imaging::trails::TrailConfig;
 use crate::core::stacking::calibration::calibrate_from_paths;
 use crate::core::stacking::calibration::stack_from_paths;
+use crate::core::stacking::dark_scaling::DarkScaling;
 use crate::infra::progress::ProgressHandle;
 use crate::types::constants::{
     EVENT_CALIBRATE_PROGRESS, EVENT_STACK_PROGRESS, STAGE_RENDER, STAGE_SAVE,
     RES_DIMENSIONS, RES_DX, RES_DY, RES_FITS_PATH, RES_FRAME_COUNT,
     RES_HAS_BIAS, RES_HAS_DARK, RES_HAS_FLAT, RES_MAX, RES_MEAN, RES_MIN,
-    RES_OFFSETS, RES_PNG_PATH, RES_REJECTED_PIXELS, RES_SIGMA, RES_STATS,
+    RES_DARK_SCALE, RES_DARK_SCALE_MODE, RES_HOT_PIXELS, RES_COLD_PIXELS,
+    RES_BAD_LINE_PIXELS, RES_COSMETIC_CORRECTED, RES_DEFECT_MAP_PATH,
+    RES_OFFSETS, RES_PNG_PATH, RES_REFERENCE, RES_REJECTED_PIXELS, RES_SIGMA, RES_STATS, RES_TRAILS,
 };
 use crate::types::stacking::StackConfig;
 
@@ -22,6 +27,16 @@ pub async fn calibrate(
     dark_paths: Option<Vec<String>>,
     flat_paths: Option<Vec<String>>,
     dark_exposure_ratio: Option<f32>,
+    dark_scale_mode: Option<String>,
+    dark_temp_doubling: Option<f64>,
+    cosmetic: Option<bool>,
+    hot_sigma: Option<f32>,
+    cold_sigma: Option<f32>,
+    light_hot_sigma: Option<f32>,
+    bad_columns: Option<Vec<usize>>,
+    bad_rows: Option<Vec<usize>>,
+    defect_map_path: Option<String>,
+    save_defect_map: Option<bool>,
 ) -> Result<serde_json::Value, String> {
     let progress = ProgressHandle::new(&app, EVENT_CALIBRATE_PROGRESS, 4);
     let progress_clone = progress.clone();
@@ -29,13 +44,40 @@ pub async fn calibrate(
     blocking_cmd!({
         resolve_output_dir(&output_dir)?;
 
-        let calibrated = calibrate_from_paths(
+        let dark_scaling = DarkScaling {
+            mode: parse_dark_scale_mode(dark_scale_mode.as_deref()),
+            temperature_doubling: dark_temp_doubling,
+        };
+
+        let cosmetic_config = parse_cosmetic_config(
+            cosmetic,
+            hot_sigma,
+            cold_sigma,
+            light_hot_sigma,
+            bad_columns,
+            bad_rows,
+            defect_map_path.as_deref(),
+        )?;
+
+        let (frame, defect_map) = calibrate_from_paths(
             &science_path,
             bias_paths.as_deref(),
             dark_paths.as_deref(),
             flat_paths.as_deref(),
             dark_exposure_ratio.unwrap_or(1.0),
+            dark_scaling,
+            cosmetic_config.as_ref(),
         )?;
+        let calibrated = frame.image;
+
+        let saved_map_path = match (&defect_map, save_defect_map.unwrap_or(false)) {
+            (Some(map), true) => {
+                let path = format!("{}/defect_map.fits", output_dir);
+                map.save_fits(&path)?;
+                Some(path)
+            }
+            _ => None,
+        };
 
         progress_clone.tick_with_stage(STAGE_RENDER);
 
@@ -63,6 +105,13 @@ pub async fn calibrate(
             RES_HAS_BIAS: bias_paths.is_some(),
             RES_HAS_DARK: dark_paths.is_some(),
             RES_HAS_FLAT: flat_paths.is_some(),
+            RES_DARK_SCALE: frame.dark_scale,
+            RES_DARK_SCALE_MODE: dark_scaling.mode.to_string(),
+            RES_HOT_PIXELS: defect_map.as_ref().map_or(0, |m| m.hot_count),
+            RES_COLD_PIXELS: defect_map.as_ref().map_or(0, |m| m.cold_count),
+            RES_BAD_LINE_PIXELS: defect_map.as_ref().map_or(0, |m| m.line_count),
+            RES_COSMETIC_CORRECTED: frame.cosmetic_corrected,
+            RES_DEFECT_MAP_PATH: saved_map_path,
             RES_STATS: {
                 RES_MIN: stats.min,
                 RES_MAX: stats.max,
@@ -83,6 +132,10 @@ pub async fn stack(
     max_iterations: Option<usize>,
     align: Option<bool>,
     name: Option<String>,
+    registration_path: Option<String>,
+    reference_index: Option<usize>,
+    trail_sigma: Option<f32>,
+    calibration: Option<helpers::CalibrationOptions>,
 ) -> Result<serde_json::Value, String> {
     let frame_count = paths.len() as u64;
     let progress = ProgressHandle::new(&app, EVENT_STACK_PROGRESS, frame_count + 2);
@@ -96,9 +149,19 @@ pub async fn stack(
             sigma_high: sigma_high.unwrap_or(3.0),
             max_iterations: max_iterations.unwrap_or(5),
             align: align.unwrap_or(true),
+            reference: helpers::parse_reference_selection(reference_index),
         };
 
-        let result = stack_from_paths(&paths, &config, None)?;
+        let registration = helpers::load_registration(registration_path.as_deref())?;
+        let trail_config = trail_sigma.map(|sigma| TrailConfig { sigma, ..TrailConfig::default() });
+        let calibration = helpers::prepare_stack_calibration(calibration.as_ref(), &paths)?;
+        let result = stack_from_paths(
+            &paths,
+            &config,
+            calibration.as_ref(),
+            registration.as_ref(),
+            trail_config.as_ref(),
+        )?;
 
         progress_clone.tick_with_stage(STAGE_RENDER);
 
@@ -124,6 +187,10 @@ pub async fn stack(
             RES_FRAME_COUNT: result.frame_count,
             RES_REJECTED_PIXELS: result.rejected_pixels,
             RES_OFFSETS: result.offsets.iter().map(|(dy, dx)| json!({RES_DY: dy, RES_DX: dx})).collect::<Vec<_>>(),
+            RES_REFERENCE: result.reference,
+            RES_TRAILS: result.trails,
+            RES_DARK_SCALE: result.calibration.dark_scales,
+            RES_COSMETIC_CORRECTED: result.calibration.cosmetic_corrected,
             RES_STATS: {
                 RES_MIN: stats.min,
                 RES_MAX: stats.max,
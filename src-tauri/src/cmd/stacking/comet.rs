@@ -0,0 +1,92 @@
+use serde_json::json;
+
+use crate::cmd::common::{blocking_cmd, render_asinh_and_save, resolve_output_dir};
+use crate::cmd::helpers;
+use crate::core::stacking::comet::comet_stack_from_paths;
+use crate::infra::progress::ProgressHandle;
+use crate::types::constants::{
+    EVENT_COMET_PROGRESS, FILE_COMET_STEM, STAGE_RENDER, STAGE_SAVE,
+    RES_COMBINED, RES_COMET_END, RES_COMET_START, RES_COMET_STACK, RES_DARK_SCALE, RES_COSMETIC_CORRECTED, RES_DETECTED, RES_DIMENSIONS,
+    RES_DX, RES_DY, RES_FITS_PATH, RES_FRAME_COUNT, RES_MASKED_STARS, RES_MOTION_PX_PER_HOUR,
+    RES_OFFSETS, RES_OBJECT_OFFSETS, RES_PNG_PATH, RES_REFERENCE, RES_REJECTED_PIXELS,
+    RES_STAR_STACK, RES_TIMESTAMPS_USED,
+};
+use crate::types::stacking::{CometConfig, StackConfig};
+
+#[tauri::command]
+pub async fn comet_stack_cmd(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+    output_dir: String,
+    first_position: Option<[f64; 2]>,
+    last_position: Option<[f64; 2]>,
+    sigma_low: Option<f32>,
+    sigma_high: Option<f32>,
+    reject_stars: Option<bool>,
+    reference_index: Option<usize>,
+    name: Option<String>,
+    calibration: Option<helpers::CalibrationOptions>,
+) -> Result<serde_json::Value, String> {
+    let progress = ProgressHandle::new(&app, EVENT_COMET_PROGRESS, 3);
+    let progress_clone = progress.clone();
+
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let config = CometConfig {
+            stack: StackConfig {
+                sigma_low: sigma_low.unwrap_or(3.0),
+                sigma_high: sigma_high.unwrap_or(3.0),
+                reference: helpers::parse_reference_selection(reference_index),
+                ..StackConfig::default()
+            },
+            first_position: first_position.map(|[x, y]| (x, y)),
+            last_position: last_position.map(|[x, y]| (x, y)),
+            reject_stars: reject_stars.unwrap_or(true),
+        };
+
+        let calibration = helpers::prepare_stack_calibration(calibration.as_ref(), &paths)?;
+        let result = comet_stack_from_paths(&paths, &config, calibration.as_ref())?;
+        progress_clone.tick_with_stage(STAGE_RENDER);
+
+        let stem = name.as_deref().unwrap_or(FILE_COMET_STEM);
+        let render = |image, suffix: &str| -> anyhow::Result<serde_json::Value> {
+            let (png_path, fits_path) = render_asinh_and_save(image, &output_dir, &format!("{}_{}", stem, suffix), true)?;
+            Ok(json!({ RES_PNG_PATH: png_path, RES_FITS_PATH: fits_path }))
+        };
+        let star_output = render(&result.star_stack, "stars")?;
+        let comet_output = render(&result.comet_stack, "comet")?;
+        let combined_output = match &result.combined {
+            Some(image) => Some(render(image, "combined")?),
+            None => None,
+        };
+
+        progress_clone.tick_with_stage(STAGE_SAVE);
+        progress_clone.emit_complete();
+
+        let (rows, cols) = result.star_stack.dim();
+        let offsets = |list: &[(f64, f64)]| {
+            list.iter().map(|(dy, dx)| json!({RES_DY: dy, RES_DX: dx})).collect::<Vec<_>>()
+        };
+
+        Ok(json!({
+            RES_STAR_STACK: star_output,
+            RES_COMET_STACK: comet_output,
+            RES_COMBINED: combined_output,
+            RES_DIMENSIONS: [cols, rows],
+            RES_FRAME_COUNT: result.frame_count,
+            RES_REJECTED_PIXELS: result.rejected_pixels,
+            RES_OFFSETS: offsets(&result.star_offsets),
+            RES_OBJECT_OFFSETS: offsets(&result.comet_offsets),
+            RES_COMET_START: [result.comet_start.0, result.comet_start.1],
+            RES_COMET_END: [result.comet_end.0, result.comet_end.1],
+            RES_MOTION_PX_PER_HOUR: result.motion_px_per_hour,
+            RES_DETECTED: result.detected,
+            RES_TIMESTAMPS_USED: result.timestamps_used,
+            RES_MASKED_STARS: result.masked_stars,
+            RES_REFERENCE: result.reference,
+            RES_DARK_SCALE: result.calibration.dark_scales,
+            RES_COSMETIC_CORRECTED: result.calibration.cosmetic_corrected,
+        }))
+    })
+}
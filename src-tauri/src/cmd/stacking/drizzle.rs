@@ -2,21 +2,28 @@ use std::time::Instant;
 
 use serde_json::json;
 
-use crate::cmd::common::{blocking_cmd, resolve_output_dir};
+use crate::cmd::common::{blocking_cmd, render_asinh_and_save, resolve_output_dir, MAX_PREVIEW_DIM};
 use crate::cmd::helpers;
 use crate::core::compose::drizzle_rgb::drizzle_rgb;
+use crate::core::compose::rgb::process_rgb;
+use crate::core::imaging::stats::compute_image_stats;
+use crate::core::stacking::calibration::{drizzle_cfa_from_paths, drizzle_from_paths};
+use crate::infra::fits::writer::{write_fits_context, write_fits_mono};
 use crate::infra::progress::ProgressHandle;
-use crate::types::compose::DrizzleRgbConfig;
+use crate::types::compose::{DrizzleRgbConfig, RgbComposeConfig};
 use crate::types::constants::{
     DEFAULT_DRIZZLE_PIXFRAC, DEFAULT_DRIZZLE_SCALE, DEFAULT_DRIZZLE_SIGMA,
     DEFAULT_DRIZZLE_SIGMA_ITERS,
-    EVENT_DRIZZLE_RGB_PROGRESS,
+    EVENT_DRIZZLE_RGB_PROGRESS, EVENT_STACK_PROGRESS,
+    FILE_DRIZZLE_CFA_CONTEXT_FITS, FILE_DRIZZLE_CFA_PNG, FILE_DRIZZLE_CFA_STEM,
+    FILE_DRIZZLE_CONTEXT_FITS, FILE_DRIZZLE_STEM, FILE_DRIZZLE_WEIGHT_FITS,
     FILE_DRIZZLE_RGB_FITS, FILE_DRIZZLE_RGB_PNG,
-    STAGE_SAVE,
-    RES_DIMENSIONS, RES_ELAPSED_MS, RES_FITS_PATH,
+    STAGE_RENDER, STAGE_SAVE,
+    RES_BAYER_PATTERN, RES_CHANNEL_PATHS, RES_CONTEXT_PATH, RES_CONTEXT_PLANES, RES_DARK_SCALE, RES_COSMETIC_CORRECTED, RES_DX, RES_DY,
+    RES_DIMENSIONS, RES_ELAPSED_MS, RES_FITS_PATH, RES_FRAME_COUNT,
     RES_FRAME_COUNT_B, RES_FRAME_COUNT_G, RES_FRAME_COUNT_R,
-    RES_INPUT_DIMS, RES_OUTPUT_DIMS,
-    RES_PNG_PATH, RES_REJECTED_PIXELS, RES_SCALE,
+    RES_INPUT_DIMS, RES_OFFSETS, RES_OUTPUT_DIMS,
+    RES_PNG_PATH, RES_REFERENCE, RES_REJECTED_PIXELS, RES_SCALE, RES_TRAILS, RES_WEIGHT_PATH, RES_WEIGHT_PATHS,
 };
 use crate::types::stacking::{AlignmentMethod, DrizzleConfig};
 
@@ -34,6 +41,7 @@ pub async fn drizzle_rgb_cmd(
     sigma_high: Option<f32>,
     align: Option<bool>,
     alignment_method: Option<String>,
+    geometry: Option<String>,
     wb_mode: Option<String>,
     wb_r: Option<f64>,
     wb_g: Option<f64>,
@@ -42,6 +50,7 @@ pub async fn drizzle_rgb_cmd(
     scnr_method: Option<String>,
     scnr_amount: Option<f64>,
     save_fits: Option<bool>,
+    reference_index: Option<usize>,
 ) -> Result<serde_json::Value, String> {
     let total_frames = r_paths.as_ref().map_or(0, |v| v.len())
         + g_paths.as_ref().map_or(0, |v| v.len())
@@ -70,6 +79,8 @@ pub async fn drizzle_rgb_cmd(
             sigma_iterations: DEFAULT_DRIZZLE_SIGMA_ITERS,
             align: align.unwrap_or(true),
             alignment_method: am,
+            geometry: helpers::parse_drizzle_geometry(geometry.as_deref()),
+            reference: helpers::parse_reference_selection(reference_index),
         };
 
         let wb = helpers::parse_wb(wb_mode.as_deref(), wb_r, wb_g, wb_b);
@@ -123,8 +134,218 @@ pub async fn drizzle_rgb_cmd(
             RES_FRAME_COUNT_G: result.frame_count_g,
             RES_FRAME_COUNT_B: result.frame_count_b,
             RES_REJECTED_PIXELS: result.rejected_pixels,
+            RES_REFERENCE: result.references,
             RES_ELAPSED_MS: elapsed,
             RES_SCALE: scale_val,
         }))
     })
 }
+
+#[tauri::command]
+pub async fn drizzle_cfa_cmd(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+    output_dir: String,
+    pattern: Option<String>,
+    scale: Option<f64>,
+    pixfrac: Option<f64>,
+    kernel: Option<String>,
+    sigma_low: Option<f32>,
+    sigma_high: Option<f32>,
+    align: Option<bool>,
+    geometry: Option<String>,
+    weight_paths: Option<Vec<String>>,
+    mask_paths: Option<Vec<String>>,
+    use_err: Option<bool>,
+    use_dq: Option<bool>,
+    dq_bad_bits: Option<u32>,
+    saturation: Option<f32>,
+    exposure_weight: Option<bool>,
+    registration_path: Option<String>,
+    reference_index: Option<usize>,
+    trail_sigma: Option<f32>,
+    calibration: Option<helpers::CalibrationOptions>,
+) -> Result<serde_json::Value, String> {
+    let progress = ProgressHandle::new(&app, EVENT_STACK_PROGRESS, 3);
+    let progress_clone = progress.clone();
+    let scale_val = scale.unwrap_or(DEFAULT_DRIZZLE_SCALE);
+
+    blocking_cmd!({
+        let t0 = Instant::now();
+        resolve_output_dir(&output_dir)?;
+
+        let config = DrizzleConfig {
+            scale: scale_val,
+            pixfrac: pixfrac.unwrap_or(DEFAULT_DRIZZLE_PIXFRAC),
+            kernel: helpers::parse_drizzle_kernel(kernel.as_deref()),
+            sigma_low: sigma_low.unwrap_or(DEFAULT_DRIZZLE_SIGMA),
+            sigma_high: sigma_high.unwrap_or(DEFAULT_DRIZZLE_SIGMA),
+            sigma_iterations: DEFAULT_DRIZZLE_SIGMA_ITERS,
+            align: align.unwrap_or(true),
+            alignment_method: AlignmentMethod::PhaseCorrelation,
+            geometry: helpers::parse_drizzle_geometry(geometry.as_deref()),
+            reference: helpers::parse_reference_selection(reference_index),
+        };
+
+        let weighting = helpers::parse_drizzle_weighting(
+            weight_paths, mask_paths, use_err, use_dq, dq_bad_bits, saturation, exposure_weight, trail_sigma,
+        );
+        let registration = helpers::load_registration(registration_path.as_deref())?;
+        let calibration = helpers::prepare_stack_calibration(calibration.as_ref(), &paths)?;
+        let (result, layout) = drizzle_cfa_from_paths(
+            &paths, pattern.as_deref(), &config, calibration.as_ref(), weighting.as_ref(), registration.as_ref(),
+        )?;
+        progress_clone.tick_with_stage(STAGE_SAVE);
+
+        let mut channel_paths = Vec::with_capacity(3);
+        let mut weight_outputs = Vec::with_capacity(3);
+        for (suffix, plane, weights) in [
+            ("r", &result.r, &result.weight_r),
+            ("g", &result.g, &result.weight_g),
+            ("b", &result.b, &result.weight_b),
+        ] {
+            let fits_path = format!("{}/{}_{}.fits", output_dir, FILE_DRIZZLE_CFA_STEM, suffix);
+            let weight_path = format!("{}/{}_{}_weight.fits", output_dir, FILE_DRIZZLE_CFA_STEM, suffix);
+            write_fits_mono(&fits_path, plane, None)?;
+            write_fits_mono(&weight_path, weights, None)?;
+            channel_paths.push(fits_path);
+            weight_outputs.push(weight_path);
+        }
+        let context_path = format!("{}/{}", output_dir, FILE_DRIZZLE_CFA_CONTEXT_FITS);
+        write_fits_context(&context_path, &result.context)?;
+
+        progress_clone.tick_with_stage(STAGE_RENDER);
+
+        let compose_config = RgbComposeConfig {
+            align: false,
+            ..RgbComposeConfig::default()
+        };
+        let mut processed = process_rgb(Some(&result.r), Some(&result.g), Some(&result.b), &compose_config)?;
+
+        if let (Some(pre_r), Some(pre_g), Some(pre_b)) = (
+            processed.pre_stretch_r.take(),
+            processed.pre_stretch_g.take(),
+            processed.pre_stretch_b.take(),
+        ) {
+            let stats_r = processed.stats_wb_r.clone().unwrap_or_else(|| compute_image_stats(&pre_r));
+            let stats_g = processed.stats_wb_g.clone().unwrap_or_else(|| compute_image_stats(&pre_g));
+            let stats_b = processed.stats_wb_b.clone().unwrap_or_else(|| compute_image_stats(&pre_b));
+            helpers::insert_composite_and_orig(pre_r, pre_g, pre_b, stats_r, stats_g, stats_b);
+        }
+
+        let png_path = format!("{}/{}", output_dir, FILE_DRIZZLE_CFA_PNG);
+        helpers::render_rgb_preview(&processed.r, &processed.g, &processed.b, &png_path, MAX_PREVIEW_DIM)?;
+
+        progress_clone.emit_complete();
+
+        let (out_h, out_w) = result.output_dims;
+        let (in_h, in_w) = result.input_dims;
+
+        Ok(json!({
+            RES_PNG_PATH: png_path,
+            RES_CHANNEL_PATHS: channel_paths,
+            RES_WEIGHT_PATHS: weight_outputs,
+            RES_CONTEXT_PATH: context_path,
+            RES_BAYER_PATTERN: layout.pattern_string(),
+            RES_DIMENSIONS: [out_w, out_h],
+            RES_OUTPUT_DIMS: [out_w, out_h],
+            RES_INPUT_DIMS: [in_w, in_h],
+            RES_FRAME_COUNT: result.frame_count,
+            RES_REJECTED_PIXELS: result.rejected_pixels,
+            RES_OFFSETS: result.offsets.iter().map(|(dx, dy)| json!({RES_DX: dx, RES_DY: dy})).collect::<Vec<_>>(),
+            RES_REFERENCE: result.reference,
+            RES_TRAILS: result.trails,
+            RES_DARK_SCALE: result.calibration.dark_scales,
+            RES_COSMETIC_CORRECTED: result.calibration.cosmetic_corrected,
+            RES_SCALE: result.output_scale,
+            RES_ELAPSED_MS: t0.elapsed().as_millis() as u64,
+        }))
+    })
+}
+
+#[tauri::command]
+pub async fn drizzle_stack_cmd(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+    output_dir: String,
+    scale: Option<f64>,
+    pixfrac: Option<f64>,
+    kernel: Option<String>,
+    sigma_low: Option<f32>,
+    sigma_high: Option<f32>,
+    align: Option<bool>,
+    geometry: Option<String>,
+    weight_paths: Option<Vec<String>>,
+    mask_paths: Option<Vec<String>>,
+    use_err: Option<bool>,
+    use_dq: Option<bool>,
+    dq_bad_bits: Option<u32>,
+    saturation: Option<f32>,
+    exposure_weight: Option<bool>,
+    registration_path: Option<String>,
+    reference_index: Option<usize>,
+    trail_sigma: Option<f32>,
+    calibration: Option<helpers::CalibrationOptions>,
+) -> Result<serde_json::Value, String> {
+    let progress = ProgressHandle::new(&app, EVENT_STACK_PROGRESS, 3);
+    let progress_clone = progress.clone();
+
+    blocking_cmd!({
+        let t0 = Instant::now();
+        resolve_output_dir(&output_dir)?;
+
+        let config = DrizzleConfig {
+            scale: scale.unwrap_or(DEFAULT_DRIZZLE_SCALE),
+            pixfrac: pixfrac.unwrap_or(DEFAULT_DRIZZLE_PIXFRAC),
+            kernel: helpers::parse_drizzle_kernel(kernel.as_deref()),
+            sigma_low: sigma_low.unwrap_or(DEFAULT_DRIZZLE_SIGMA),
+            sigma_high: sigma_high.unwrap_or(DEFAULT_DRIZZLE_SIGMA),
+            sigma_iterations: DEFAULT_DRIZZLE_SIGMA_ITERS,
+            align: align.unwrap_or(true),
+            alignment_method: AlignmentMethod::PhaseCorrelation,
+            geometry: helpers::parse_drizzle_geometry(geometry.as_deref()),
+            reference: helpers::parse_reference_selection(reference_index),
+        };
+        let weighting = helpers::parse_drizzle_weighting(
+            weight_paths, mask_paths, use_err, use_dq, dq_bad_bits, saturation, exposure_weight, trail_sigma,
+        );
+
+        let registration = helpers::load_registration(registration_path.as_deref())?;
+        let calibration = helpers::prepare_stack_calibration(calibration.as_ref(), &paths)?;
+        let result = drizzle_from_paths(&paths, &config, calibration.as_ref(), weighting.as_ref(), registration.as_ref())?;
+        progress_clone.tick_with_stage(STAGE_SAVE);
+
+        let weight_path = format!("{}/{}", output_dir, FILE_DRIZZLE_WEIGHT_FITS);
+        write_fits_mono(&weight_path, &result.weight_map, None)?;
+        let context_path = format!("{}/{}", output_dir, FILE_DRIZZLE_CONTEXT_FITS);
+        write_fits_context(&context_path, &result.context)?;
+
+        progress_clone.tick_with_stage(STAGE_RENDER);
+        let (png_path, fits_path) = render_asinh_and_save(&result.image, &output_dir, FILE_DRIZZLE_STEM, true)?;
+
+        progress_clone.emit_complete();
+
+        let (out_h, out_w) = result.output_dims;
+        let (in_h, in_w) = result.input_dims;
+
+        Ok(json!({
+            RES_PNG_PATH: png_path,
+            RES_FITS_PATH: fits_path,
+            RES_WEIGHT_PATH: weight_path,
+            RES_CONTEXT_PATH: context_path,
+            RES_CONTEXT_PLANES: result.context.len(),
+            RES_DIMENSIONS: [out_w, out_h],
+            RES_OUTPUT_DIMS: [out_w, out_h],
+            RES_INPUT_DIMS: [in_w, in_h],
+            RES_FRAME_COUNT: result.frame_count,
+            RES_REJECTED_PIXELS: result.rejected_pixels,
+            RES_OFFSETS: result.offsets.iter().map(|(dx, dy)| json!({RES_DX: dx, RES_DY: dy})).collect::<Vec<_>>(),
+            RES_REFERENCE: result.reference,
+            RES_TRAILS: result.trails,
+            RES_DARK_SCALE: result.calibration.dark_scales,
+            RES_COSMETIC_CORRECTED: result.calibration.cosmetic_corrected,
+            RES_SCALE: result.output_scale,
+            RES_ELAPSED_MS: t0.elapsed().as_millis() as u64,
+        }))
+    })
+}
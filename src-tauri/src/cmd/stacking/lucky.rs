@@ -0,0 +1,95 @@
+use serde_json::json;
+
+use crate::cmd::common::{blocking_cmd, render_asinh_and_save, resolve_output_dir};
+use crate::cmd::helpers;
+use crate::core::cube::video::VideoSequence;
+use crate::core::stacking::lucky::{lucky_stack_from_paths, lucky_stack_from_video, selected_count};
+use crate::infra::fits::dispatcher::is_video_path;
+use crate::infra::progress::ProgressHandle;
+use crate::types::constants::{
+    EVENT_LUCKY_PROGRESS, FILE_LUCKY_STEM, STAGE_SAVE,
+    RES_BEST_INDEX, RES_DARK_SCALE, RES_COSMETIC_CORRECTED, RES_DIMENSIONS, RES_DX, RES_DY, RES_FITS_PATH, RES_FRAME_COUNT,
+    RES_MAX_LOCAL_SHIFT_PX, RES_MEAN_VALID_PATCHES, RES_OFFSETS, RES_PATCH_COUNT, RES_PNG_PATH,
+    RES_QUALITY, RES_SELECTED_COUNT,
+};
+use crate::types::stacking::LuckyConfig;
+
+#[tauri::command]
+pub async fn lucky_stack_cmd(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+    output_dir: String,
+    keep_percent: Option<f32>,
+    metric: Option<String>,
+    reference_frames: Option<usize>,
+    local_align: Option<bool>,
+    patch_size: Option<usize>,
+    grid_step: Option<usize>,
+    name: Option<String>,
+    calibration: Option<helpers::CalibrationOptions>,
+) -> Result<serde_json::Value, String> {
+    let defaults = LuckyConfig::default();
+    let keep_percent = keep_percent.unwrap_or(defaults.keep_percent);
+    let video_path = match paths.as_slice() {
+        [single] if is_video_path(std::path::Path::new(single)) => Some(single.clone()),
+        _ => None,
+    };
+    let frame_count = match &video_path {
+        Some(p) => VideoSequence::open(p).map_err(|e| format!("{:#}", e))?.frame_count(),
+        None => paths.len(),
+    };
+    let steps = selected_count(frame_count, keep_percent) as u64 + 3;
+    let progress = ProgressHandle::new(&app, EVENT_LUCKY_PROGRESS, steps);
+    let progress_clone = progress.clone();
+
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let patch_size = patch_size.unwrap_or(defaults.patch_size);
+        let config = LuckyConfig {
+            metric: helpers::parse_quality_metric(metric.as_deref()),
+            keep_percent,
+            reference_frames: reference_frames.unwrap_or(defaults.reference_frames),
+            local_align: local_align.unwrap_or(defaults.local_align),
+            patch_size,
+            grid_step: grid_step.unwrap_or(patch_size * 3 / 4),
+            ..defaults
+        };
+
+        let result = match &video_path {
+            Some(p) => lucky_stack_from_video(p, &config, Some(&progress_clone))?,
+            None => {
+                let calibration = helpers::prepare_stack_calibration(calibration.as_ref(), &paths)?;
+                lucky_stack_from_paths(&paths, &config, calibration.as_ref(), Some(&progress_clone))?
+            }
+        };
+
+        progress_clone.tick_with_stage(STAGE_SAVE);
+        let stem = name.as_deref().unwrap_or(FILE_LUCKY_STEM);
+        let (png_path, fits_path) = render_asinh_and_save(&result.image, &output_dir, stem, true)?;
+        progress_clone.emit_complete();
+
+        let (rows, cols) = result.image.dim();
+        let offsets: Vec<serde_json::Value> = result
+            .offsets
+            .iter()
+            .map(|(dy, dx)| json!({RES_DY: dy, RES_DX: dx}))
+            .collect();
+
+        Ok(json!({
+            RES_PNG_PATH: png_path,
+            RES_FITS_PATH: fits_path,
+            RES_DIMENSIONS: [cols, rows],
+            RES_FRAME_COUNT: result.frame_count,
+            RES_SELECTED_COUNT: result.selected_count,
+            RES_BEST_INDEX: result.best_index,
+            RES_QUALITY: result.quality,
+            RES_OFFSETS: offsets,
+            RES_PATCH_COUNT: result.patch_count,
+            RES_MEAN_VALID_PATCHES: result.mean_valid_patches,
+            RES_MAX_LOCAL_SHIFT_PX: result.max_local_shift_px,
+            RES_DARK_SCALE: result.calibration.dark_scales,
+            RES_COSMETIC_CORRECTED: result.calibration.cosmetic_corrected,
+        }))
+    })
+}
@@ -1,6 +1,13 @@
 mod combine;
+mod comet;
 mod drizzle;
+mod lucky;
 mod pipeline;
+mod registration;
 
 pub use combine::*;
+pub use comet::comet_stack_cmd;
+pub use drizzle::{drizzle_cfa_cmd, drizzle_stack_cmd};
+pub use lucky::lucky_stack_cmd;
 pub use pipeline::*;
+pub use registration::register_frames_cmd;
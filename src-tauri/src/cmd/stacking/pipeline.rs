@@ -6,10 +6,12 @@ use crate::core::imaging::calibration_pipeline::{
     CalibrationMasters, ChannelInput,
 };
 use crate::core::stacking::calibration::{
-    create_master_bias, create_master_dark, create_master_flat, load_fits_image,
+    create_master_bias, create_master_dark_with_exposure, create_master_flat, load_fits_image_with_header,
 };
+use crate::cmd::helpers::parse_dark_scale_mode;
+use crate::core::stacking::dark_scaling::{DarkScaleMode, DarkScaling, FrameExposure};
 use crate::types::constants::{
-    RES_LABEL, RES_PIXELS_B64, RES_WIDTH, RES_HEIGHT,
+    RES_DARK_SCALE, RES_LABEL, RES_PIXELS_B64, RES_WIDTH, RES_HEIGHT,
     RES_STATS, RES_CHANNEL_PREVIEWS, RES_RGB_PREVIEW,
 };
 
@@ -25,13 +27,19 @@ pub struct PipelineRequest {
     pub dark_paths: Vec<String>,
     pub flat_paths: Vec<String>,
     pub bias_paths: Vec<String>,
+    pub dark_exposure_ratio: Option<f32>,
+    pub dark_scale_mode: Option<String>,
+    pub dark_temp_doubling: Option<f64>,
     pub sigma_low: Option<f32>,
     pub sigma_high: Option<f32>,
     pub normalize: Option<bool>,
 }
 
-fn load_batch(paths: &[String]) -> Result<Vec<ndarray::Array2<f32>>, anyhow::Error> {
-    paths.iter().map(|p| load_fits_image(p)).collect()
+fn load_batch(paths: &[String]) -> Result<(Vec<ndarray::Array2<f32>>, Vec<FrameExposure>), anyhow::Error> {
+    paths
+        .iter()
+        .map(|p| load_fits_image_with_header(p).map(|(img, header)| (img, FrameExposure::from_header(&header))))
+        .collect()
 }
 
 fn array2_to_b64_u16(arr: &ndarray::Array2<f32>) -> String {
@@ -78,29 +86,40 @@ pub async fn run_pipeline_cmd(
             Some(create_master_bias(&request.bias_paths).map_err(|e| format!("{:#}", e))?)
         };
 
-        let master_dark = if request.dark_paths.is_empty() {
-            None
+        let (master_dark, dark_exposure) = if request.dark_paths.is_empty() {
+            (None, None)
         } else {
-            Some(create_master_dark(&request.dark_paths, master_bias.as_ref()).map_err(|e| format!("{:#}", e))?)
+            let (dark, exposure) = create_master_dark_with_exposure(&request.dark_paths, master_bias.as_ref())
+                .map_err(|e| format!("{:#}", e))?;
+            (Some(dark), Some(exposure))
         };
 
         let master_flat = if request.flat_paths.is_empty() {
             None
         } else {
-            Some(create_master_flat(&request.flat_paths, master_bias.as_ref(), master_dark.as_ref()).map_err(|e| format!("{:#}", e))?)
+            Some(create_master_flat(&request.flat_paths, master_bias.as_ref(), master_dark.as_ref(), dark_exposure.as_ref()).map_err(|e| format!("{:#}", e))?)
         };
 
         let masters = CalibrationMasters {
             dark: master_dark,
             flat: master_flat,
             bias: master_bias,
+            dark_exposure,
+            dark_exposure_ratio: request.dark_exposure_ratio.unwrap_or(1.0),
+            dark_scaling: DarkScaling {
+                mode: match request.dark_scale_mode.as_deref() {
+                    Some(mode) => parse_dark_scale_mode(Some(mode)),
+                    None => DarkScaleMode::Exposure,
+                },
+                temperature_doubling: request.dark_temp_doubling,
+            },
         };
 
         let channels: Vec<ChannelInput> = request
             .channels
             .iter()
             .map(|ch| {
-                let lights = load_batch(&ch.paths).map_err(|e| format!("{:#}", e))?;
+                let (lights, exposures) = load_batch(&ch.paths).map_err(|e| format!("{:#}", e))?;
 
                 if lights.len() > 1 {
                     let ref_dim = lights[0].dim();
@@ -116,6 +135,7 @@ pub async fn run_pipeline_cmd(
 
                 Ok(ChannelInput {
                     lights,
+                    exposures,
                     label: ch.label.clone(),
                 })
             })
@@ -152,6 +172,7 @@ pub async fn run_pipeline_cmd(
             RES_STATS: result.stats,
             RES_CHANNEL_PREVIEWS: channel_previews,
             RES_RGB_PREVIEW: rgb_preview,
+            RES_DARK_SCALE: result.dark_scales,
         }))
     })
         .await
@@ -0,0 +1,80 @@
+use serde_json::json;
+
+use crate::cmd::common::{blocking_cmd, resolve_output_dir};
+use crate::cmd::helpers;
+use crate::core::alignment::registration::register_from_paths;
+use crate::infra::progress::ProgressHandle;
+use crate::types::constants::{
+    EVENT_REGISTER_PROGRESS, FILE_REGISTRATION_STEM, STAGE_SAVE,
+    RES_CONFIDENCE, RES_DIMENSIONS, RES_DX, RES_DY, RES_FRAMES, RES_FRAME_COUNT, RES_INLIERS, RES_IS_FLIPPED,
+    RES_MAPPING, RES_METHOD, RES_PATH, RES_REFERENCE, RES_REFERENCE_INDEX, RES_REFERENCE_PATH,
+    RES_REGISTRATION_PATH, RES_RESIDUAL_PX, RES_STARS_MATCHED,
+};
+
+#[tauri::command]
+pub async fn register_frames_cmd(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+    output_dir: String,
+    reference_index: Option<usize>,
+    align_method: Option<String>,
+    name: Option<String>,
+) -> Result<serde_json::Value, String> {
+    let progress = ProgressHandle::new(&app, EVENT_REGISTER_PROGRESS, paths.len() as u64 + 1);
+    let progress_clone = progress.clone();
+
+    blocking_cmd!({
+        resolve_output_dir(&output_dir)?;
+
+        let method = helpers::parse_align_method(align_method.as_deref());
+        let set = register_from_paths(
+            &paths,
+            helpers::parse_reference_selection(reference_index),
+            method,
+            None,
+            Some(&progress_clone),
+        )?;
+
+        progress_clone.tick_with_stage(STAGE_SAVE);
+        let registration_path = format!(
+            "{}/{}.json",
+            output_dir,
+            name.as_deref().unwrap_or(FILE_REGISTRATION_STEM)
+        );
+        set.save_json(&registration_path)?;
+        progress_clone.emit_complete();
+
+        let cx = set.cols as f64 * 0.5;
+        let cy = set.rows as f64 * 0.5;
+        let frames: Vec<serde_json::Value> = set
+            .frames
+            .iter()
+            .map(|f| {
+                let (dx, dy) = f.offset_at(cx, cy);
+                json!({
+                    RES_PATH: f.path,
+                    RES_METHOD: f.method,
+                    RES_MAPPING: f.forward.kind(),
+                    RES_STARS_MATCHED: f.matched_stars,
+                    RES_INLIERS: f.inliers,
+                    RES_RESIDUAL_PX: f.residual_px,
+                    RES_CONFIDENCE: f.confidence,
+                    RES_IS_FLIPPED: f.is_flipped,
+                    RES_DX: dx,
+                    RES_DY: dy,
+                })
+            })
+            .collect();
+
+        Ok(json!({
+            RES_REGISTRATION_PATH: registration_path,
+            RES_REFERENCE_INDEX: set.reference_index,
+            RES_REFERENCE_PATH: set.reference_path,
+            RES_REFERENCE: set.reference,
+            RES_METHOD: set.method,
+            RES_DIMENSIONS: [set.cols, set.rows],
+            RES_FRAME_COUNT: set.frames.len(),
+            RES_FRAMES: frames,
+        }))
+    })
+}
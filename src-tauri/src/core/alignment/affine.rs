@@ -17,7 +17,6 @@ const MIN_VOTES: u32 = 1;
 const MIN_INLIER_RATIO: f64 = 0.20;
 const MAX_RESIDUAL_PX: f64 = 5.0;
 const MAX_OFFSET_FRACTION: f64 = 0.40;
-const MAX_ROTATION_DEG: f64 = 30.0;
 const MIN_SCALE: f64 = 0.70;
 const MAX_SCALE: f64 = 1.40;
 
@@ -52,7 +51,7 @@ fn normalize_for_detection(image: &Array2<f32>) -> Array2<f32> {
     result
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub struct AffineTransform {
     pub a: f64,
     pub b: f64,
@@ -80,7 +79,19 @@ impl AffineTransform {
     }
 
     pub fn rotation_deg(&self) -> f64 {
-        self.c.atan2(self.a).to_degrees()
+        if self.is_flipped() {
+            (-self.c).atan2(-self.a).to_degrees()
+        } else {
+            self.c.atan2(self.a).to_degrees()
+        }
+    }
+
+    pub fn determinant(&self) -> f64 {
+        self.a * self.d - self.b * self.c
+    }
+
+    pub fn is_flipped(&self) -> bool {
+        self.determinant() < 0.0
     }
 
     pub fn scale_x(&self) -> f64 {
@@ -90,6 +101,26 @@ impl AffineTransform {
     pub fn scale_y(&self) -> f64 {
         (self.b * self.b + self.d * self.d).sqrt()
     }
+
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let a = self.d * inv_det;
+        let b = -self.b * inv_det;
+        let c = -self.c * inv_det;
+        let d = self.a * inv_det;
+        Some(Self {
+            a,
+            b,
+            tx: -(a * self.tx + b * self.ty),
+            c,
+            d,
+            ty: -(c * self.tx + d * self.ty),
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -99,6 +130,7 @@ pub struct AffineAlignResult {
     pub inliers: usize,
     pub residual_px: f64,
     pub method: AffineAlignMethod,
+    pub is_flipped: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -124,12 +156,21 @@ struct TriangleDesc {
     star_indices: [usize; 3],
     ratio_mid: f64,
     ratio_long: f64,
+    clockwise: bool,
 }
 
 pub fn align_channel_affine(
     reference: &Array2<f32>,
     target: &Array2<f32>,
 ) -> AffineAlignResult {
+    let matches = find_star_matches(reference, target);
+    affine_from_matches(reference, target, &matches)
+}
+
+pub(crate) fn find_star_matches(
+    reference: &Array2<f32>,
+    target: &Array2<f32>,
+) -> Vec<(f64, f64, f64, f64)> {
     let (rows, cols) = reference.dim();
 
     let ref_norm = normalize_for_detection(reference);
@@ -148,10 +189,10 @@ pub fn align_channel_affine(
 
     if ref_stars.len() < MIN_MATCHES_RIGID || tgt_stars.len() < MIN_MATCHES_RIGID {
         log::info!(
-            "Affine: insufficient stars (ref={}, tgt={}), fallback to phase correlation",
+            "Affine: insufficient stars (ref={}, tgt={})",
             ref_stars.len(), tgt_stars.len()
         );
-        return fallback_phase_correlation(reference, target, rows, cols);
+        return Vec::new();
     }
 
     let ref_tris = build_triangles(&ref_stars);
@@ -164,13 +205,21 @@ pub fn align_channel_affine(
 
     if ref_tris.is_empty() || tgt_tris.is_empty() {
         log::info!(
-            "Affine: no triangles (ref={}, tgt={}), fallback to phase correlation",
+            "Affine: no triangles (ref={}, tgt={})",
             ref_tris.len(), tgt_tris.len()
         );
-        return fallback_phase_correlation(reference, target, rows, cols);
+        return Vec::new();
     }
 
-    let matches = match_triangles(&ref_stars, &tgt_stars, &ref_tris, &tgt_tris);
+    match_triangles(&ref_stars, &tgt_stars, &ref_tris, &tgt_tris)
+}
+
+pub(crate) fn affine_from_matches(
+    reference: &Array2<f32>,
+    target: &Array2<f32>,
+    matches: &[(f64, f64, f64, f64)],
+) -> AffineAlignResult {
+    let (rows, cols) = reference.dim();
 
     if matches.len() < MIN_MATCHES_RIGID {
         log::info!(
@@ -181,7 +230,7 @@ pub fn align_channel_affine(
     }
 
     if matches.len() >= MIN_MATCHES_AFFINE {
-        if let Some(result) = ransac_affine(&matches, AffineAlignMethod::Affine) {
+        if let Some(result) = ransac_affine(matches, AffineAlignMethod::Affine) {
             match check_transform_sanity(&result, rows, cols) {
                 Ok(()) => return result,
                 Err(reason) => log::warn!(
@@ -194,18 +243,16 @@ pub fn align_channel_affine(
         }
     }
 
-    if matches.len() >= MIN_MATCHES_RIGID {
-        if let Some(result) = ransac_affine(&matches, AffineAlignMethod::Rigid) {
-            match check_transform_sanity(&result, rows, cols) {
-                Ok(()) => return result,
-                Err(reason) => log::warn!(
-                    "Rigid transform rejected: {}. stars={}, inliers={}, residual={:.2}px. Fallback to PC.",
-                    reason, result.matched_stars, result.inliers, result.residual_px
-                ),
-            }
-        } else {
-            log::info!("Rigid RANSAC returned no result with {} matches, fallback to PC", matches.len());
+    if let Some(result) = ransac_affine(matches, AffineAlignMethod::Rigid) {
+        match check_transform_sanity(&result, rows, cols) {
+            Ok(()) => return result,
+            Err(reason) => log::warn!(
+                "Rigid transform rejected: {}. stars={}, inliers={}, residual={:.2}px. Fallback to PC.",
+                reason, result.matched_stars, result.inliers, result.residual_px
+            ),
         }
+    } else {
+        log::info!("Rigid RANSAC returned no result with {} matches, fallback to PC", matches.len());
     }
 
     fallback_phase_correlation(reference, target, rows, cols)
@@ -214,20 +261,19 @@ pub fn align_channel_affine(
 fn check_transform_sanity(result: &AffineAlignResult, rows: usize, cols: usize) -> Result<(), String> {
     let t = &result.transform;
 
+    let cx = cols as f64 * 0.5;
+    let cy = rows as f64 * 0.5;
+    let (mx, my) = t.map(cx, cy);
+    let (shift_x, shift_y) = (mx - cx, my - cy);
     let max_tx = cols as f64 * MAX_OFFSET_FRACTION;
     let max_ty = rows as f64 * MAX_OFFSET_FRACTION;
-    if t.tx.abs() > max_tx || t.ty.abs() > max_ty {
+    if shift_x.abs() > max_tx || shift_y.abs() > max_ty {
         return Err(format!(
-            "translation ({:.1}, {:.1}) exceeds limit ({:.0}, {:.0})",
-            t.tx, t.ty, max_tx, max_ty
+            "center shift ({:.1}, {:.1}) exceeds limit ({:.0}, {:.0})",
+            shift_x, shift_y, max_tx, max_ty
         ));
     }
 
-    let rot = t.rotation_deg().abs();
-    if rot > MAX_ROTATION_DEG {
-        return Err(format!("rotation {:.2} deg exceeds {:.0} deg limit", rot, MAX_ROTATION_DEG));
-    }
-
     let sx = t.scale_x();
     let sy = t.scale_y();
     if sx < MIN_SCALE || sx > MAX_SCALE || sy < MIN_SCALE || sy > MAX_SCALE {
@@ -257,6 +303,7 @@ fn fallback_phase_correlation(
             inliers: 0,
             residual_px: 0.0,
             method: AffineAlignMethod::Identity,
+            is_flipped: false,
         };
     }
 
@@ -266,6 +313,7 @@ fn fallback_phase_correlation(
         inliers: 0,
         residual_px: 0.0,
         method: AffineAlignMethod::PhaseCorrelation,
+        is_flipped: false,
     }
 }
 
@@ -302,11 +350,13 @@ fn build_triangles(stars: &[(f64, f64)]) -> Vec<TriangleDesc> {
 
                     let ratio_mid = sides[1] / sides[0];
                     let ratio_long = sides[2] / sides[0];
+                    let star_indices = sort_triangle_vertices(stars, &[i, j, k]);
 
                     local.push(TriangleDesc {
-                        star_indices: [i, j, k],
+                        star_indices,
                         ratio_mid,
                         ratio_long,
+                        clockwise: is_clockwise(stars, &star_indices),
                     });
                 }
             }
@@ -323,10 +373,13 @@ fn match_triangles(
     ref_tris: &[TriangleDesc],
     tgt_tris: &[TriangleDesc],
 ) -> Vec<(f64, f64, f64, f64)> {
-    let local_votes: Vec<std::collections::HashMap<(usize, usize), u32>> = ref_tris
+    type VoteMap = std::collections::HashMap<(usize, usize), u32>;
+
+    let local_votes: Vec<([VoteMap; 2], [usize; 2])> = ref_tris
         .par_iter()
         .map(|rt| {
-            let mut votes = std::collections::HashMap::new();
+            let mut votes = [VoteMap::new(), VoteMap::new()];
+            let mut counts = [0usize; 2];
             for tt in tgt_tris {
                 let d_mid = (rt.ratio_mid - tt.ratio_mid).abs();
                 let d_long = (rt.ratio_long - tt.ratio_long).abs();
@@ -335,27 +388,36 @@ fn match_triangles(
                     continue;
                 }
 
-                let ref_sorted = sort_triangle_vertices(ref_stars, &rt.star_indices);
-                let tgt_sorted = sort_triangle_vertices(tgt_stars, &tt.star_indices);
-
+                let parity = usize::from(rt.clockwise != tt.clockwise);
+                counts[parity] += 1;
                 for p in 0..3 {
-                    let ri = ref_sorted[p];
-                    let ti = tgt_sorted[p];
-                    *votes.entry((ri, ti)).or_insert(0) += 1;
+                    let key = (rt.star_indices[p], tt.star_indices[p]);
+                    *votes[parity].entry(key).or_insert(0) += 1;
                 }
             }
-            votes
+            (votes, counts)
         })
         .collect();
 
-    let mut vote_map: std::collections::HashMap<(usize, usize), u32> =
-        std::collections::HashMap::new();
-    for local in local_votes {
-        for (k, v) in local {
-            *vote_map.entry(k).or_insert(0) += v;
+    let mut vote_maps = [VoteMap::new(), VoteMap::new()];
+    let mut parity_counts = [0usize; 2];
+    for (local, counts) in local_votes {
+        for (parity, map) in local.into_iter().enumerate() {
+            parity_counts[parity] += counts[parity];
+            for (k, v) in map {
+                *vote_maps[parity].entry(k).or_insert(0) += v;
+            }
         }
     }
 
+    let mirrored = parity_counts[1] > parity_counts[0];
+    log::info!(
+        "Affine: triangle matches same-orientation={}, mirrored={}, using {}",
+        parity_counts[0], parity_counts[1], if mirrored { "mirrored" } else { "direct" }
+    );
+    let [direct_votes, mirrored_votes] = vote_maps;
+    let vote_map = if mirrored { mirrored_votes } else { direct_votes };
+
     let mut pairs: Vec<((usize, usize), u32)> = vote_map.into_iter().collect();
     pairs.sort_by(|a, b| b.1.cmp(&a.1));
 
@@ -397,6 +459,11 @@ fn sort_triangle_vertices(
     [verts[0].0, verts[1].0, verts[2].0]
 }
 
+fn is_clockwise(stars: &[(f64, f64)], indices: &[usize; 3]) -> bool {
+    let (p0, p1, p2) = (stars[indices[0]], stars[indices[1]], stars[indices[2]]);
+    (p1.0 - p0.0) * (p2.1 - p0.1) - (p1.1 - p0.1) * (p2.0 - p0.0) > 0.0
+}
+
 fn ransac_affine(
     matches: &[(f64, f64, f64, f64)],
     method: AffineAlignMethod,
@@ -443,30 +510,28 @@ fn ransac_affine(
                 let sample_matches: Vec<(f64, f64, f64, f64)> =
                     sample.iter().map(|&i| matches[i]).collect();
 
-                let transform = match method {
-                    AffineAlignMethod::Affine => fit_affine(&sample_matches),
-                    _ => fit_rigid(&sample_matches),
-                };
-                let transform = match transform {
-                    Some(t) => t,
-                    None => continue,
+                let candidates = match method {
+                    AffineAlignMethod::Affine => [fit_affine(&sample_matches), None],
+                    _ => [fit_rigid(&sample_matches), fit_rigid_mirrored(&sample_matches)],
                 };
 
-                mask.fill(false);
-                let mut inlier_count = 0;
-                for (i, &(rx, ry, tx, ty)) in matches.iter().enumerate() {
-                    let (px, py) = transform.map(rx, ry);
-                    let err = ((px - tx).powi(2) + (py - ty).powi(2)).sqrt();
-                    if err < RANSAC_INLIER_PX {
-                        inlier_count += 1;
-                        mask[i] = true;
+                for transform in candidates.into_iter().flatten() {
+                    mask.fill(false);
+                    let mut inlier_count = 0;
+                    for (i, &(rx, ry, tx, ty)) in matches.iter().enumerate() {
+                        let (px, py) = transform.map(rx, ry);
+                        let err = ((px - tx).powi(2) + (py - ty).powi(2)).sqrt();
+                        if err < RANSAC_INLIER_PX {
+                            inlier_count += 1;
+                            mask[i] = true;
+                        }
                     }
-                }
 
-                if inlier_count > local_best_inliers {
-                    local_best_inliers = inlier_count;
-                    local_best_transform = transform;
-                    local_best_mask.copy_from_slice(&mask);
+                    if inlier_count > local_best_inliers {
+                        local_best_inliers = inlier_count;
+                        local_best_transform = transform;
+                        local_best_mask.copy_from_slice(&mask);
+                    }
                 }
             }
 
@@ -497,6 +562,7 @@ fn ransac_affine(
 
     let refined = match method {
         AffineAlignMethod::Affine => fit_affine(&inlier_matches),
+        _ if _best_transform.is_flipped() => fit_rigid_mirrored(&inlier_matches),
         _ => fit_rigid(&inlier_matches),
     }
         .unwrap_or(_best_transform);
@@ -513,10 +579,11 @@ fn ransac_affine(
         inliers: best_inliers,
         residual_px: residual,
         method,
+        is_flipped: refined.is_flipped(),
     })
 }
 
-fn fit_affine(matches: &[(f64, f64, f64, f64)]) -> Option<AffineTransform> {
+pub(crate) fn fit_affine(matches: &[(f64, f64, f64, f64)]) -> Option<AffineTransform> {
     let n = matches.len();
     if n < 3 {
         return None;
@@ -641,6 +708,15 @@ fn fit_rigid(matches: &[(f64, f64, f64, f64)]) -> Option<AffineTransform> {
     })
 }
 
+fn fit_rigid_mirrored(matches: &[(f64, f64, f64, f64)]) -> Option<AffineTransform> {
+    let reflected: Vec<(f64, f64, f64, f64)> = matches
+        .iter()
+        .map(|&(rx, ry, tx, ty)| (-rx, ry, tx, ty))
+        .collect();
+    let t = fit_rigid(&reflected)?;
+    Some(AffineTransform { a: -t.a, c: -t.c, ..t })
+}
+
 fn compute_residual(matches: &[(f64, f64, f64, f64)], transform: &AffineTransform) -> f64 {
     if matches.is_empty() {
         return 0.0;
@@ -823,6 +899,62 @@ mod tests {
         assert!(matches.len() >= 4, "got {} matches", matches.len());
     }
 
+    fn star_field() -> Vec<(f64, f64)> {
+        let mut state = 0x1234_5678u64;
+        (0..30)
+            .map(|_| {
+                let mut next = || {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                    (state >> 33) as f64 / (1u64 << 31) as f64
+                };
+                (40.0 + next() * 320.0, 40.0 + next() * 320.0)
+            })
+            .collect()
+    }
+
+    fn match_transformed(truth: &AffineTransform) -> Option<AffineAlignResult> {
+        let ref_stars = star_field();
+        let tgt_stars: Vec<(f64, f64)> = ref_stars.iter().map(|&(x, y)| truth.map(x, y)).collect();
+        let ref_tris = build_triangles(&ref_stars);
+        let tgt_tris = build_triangles(&tgt_stars);
+        let matches = match_triangles(&ref_stars, &tgt_stars, &ref_tris, &tgt_tris);
+        let result = ransac_affine(&matches, AffineAlignMethod::Rigid)?;
+        check_transform_sanity(&result, 400, 400).ok()?;
+        Some(result)
+    }
+
+    #[test]
+    fn test_mirrored_rotated_field_is_matched() {
+        let theta = 135.0f64.to_radians();
+        let (cos_t, sin_t) = (theta.cos(), theta.sin());
+        let truth = AffineTransform {
+            a: -cos_t,
+            b: -sin_t,
+            tx: 200.0 + 200.0 * cos_t + 200.0 * sin_t + 6.0,
+            c: -sin_t,
+            d: cos_t,
+            ty: 200.0 + 200.0 * sin_t - 200.0 * cos_t - 4.0,
+        };
+        let result = match_transformed(&truth).expect("mirrored field should align");
+        assert!(result.is_flipped);
+        assert!(result.transform.is_flipped());
+        assert!((result.transform.rotation_deg() - 135.0).abs() < 0.1, "rotation {}", result.transform.rotation_deg());
+        for &(x, y) in &[(50.0, 60.0), (300.0, 120.0), (200.0, 350.0)] {
+            let (ex, ey) = truth.map(x, y);
+            let (gx, gy) = result.transform.map(x, y);
+            assert!((ex - gx).hypot(ey - gy) < 0.05);
+        }
+    }
+
+    #[test]
+    fn test_meridian_flip_is_rotation_not_mirror() {
+        let truth = AffineTransform { a: -1.0, b: 0.0, tx: 403.0, c: 0.0, d: -1.0, ty: 398.0 };
+        let result = match_transformed(&truth).expect("rotated field should align");
+        assert!(!result.is_flipped);
+        assert!((result.transform.rotation_deg().abs() - 180.0).abs() < 0.1);
+        assert!((result.transform.tx - 403.0).abs() < 0.05);
+    }
+
     #[test]
     fn test_nan_fill_outside_bounds() {
         let img = Array2::from_elem((50, 50), 100.0f32);
@@ -0,0 +1,405 @@
+use ndarray::Array2;
+use rayon::prelude::*;
+
+use crate::core::alignment::affine::{self, AffineAlignMethod, AffineAlignResult};
+use crate::core::alignment::mapping::FrameMapping;
+use crate::core::alignment::polynomial::{term_count, PolynomialTransform};
+use crate::core::alignment::thin_plate::ThinPlateSpline;
+use crate::types::compose::AlignMethod;
+
+const RANSAC_ITERATIONS: usize = 1500;
+const RANSAC_INLIER_PX: f64 = 2.0;
+const MIN_INLIER_RATIO: f64 = 0.30;
+const MIN_EXTRA_MATCHES: usize = 4;
+const MAX_RESIDUAL_PX: f64 = 3.0;
+const MAX_DISTORTION_PX: f64 = 60.0;
+const TPS_REGULARIZATION: f64 = 1e-4;
+const TPS_MAX_CONTROL: usize = 400;
+pub const DISTORTION_GRID_STEP: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistortionModel {
+    Polynomial2,
+    Polynomial3,
+    ThinPlate,
+}
+
+impl DistortionModel {
+    pub fn from_align_method(method: AlignMethod) -> Option<Self> {
+        match method {
+            AlignMethod::Polynomial2 => Some(DistortionModel::Polynomial2),
+            AlignMethod::Polynomial3 => Some(DistortionModel::Polynomial3),
+            AlignMethod::ThinPlate => Some(DistortionModel::ThinPlate),
+            AlignMethod::PhaseCorrelation | AlignMethod::Affine => None,
+        }
+    }
+
+    fn ransac_order(&self) -> usize {
+        match self {
+            DistortionModel::Polynomial2 => 2,
+            DistortionModel::Polynomial3 | DistortionModel::ThinPlate => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for DistortionModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DistortionModel::Polynomial2 => write!(f, "polynomial2"),
+            DistortionModel::Polynomial3 => write!(f, "polynomial3"),
+            DistortionModel::ThinPlate => write!(f, "thin_plate"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct StarResidual {
+    pub x: f64,
+    pub y: f64,
+    pub dx: f64,
+    pub dy: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DistortionAlignResult {
+    pub forward: FrameMapping,
+    pub inverse: FrameMapping,
+    pub affine: AffineAlignResult,
+    pub model: Option<DistortionModel>,
+    pub matched_stars: usize,
+    pub inliers: usize,
+    pub residual_px: f64,
+    pub affine_residual_px: f64,
+    pub max_residual_px: f64,
+    pub residuals: Vec<StarResidual>,
+    pub distortion_map: Array2<f32>,
+}
+
+impl DistortionAlignResult {
+    pub fn method_label(&self) -> String {
+        match self.model {
+            Some(model) => model.to_string(),
+            None => self.affine.method.to_string(),
+        }
+    }
+}
+
+pub fn align_channel_distortion(
+    reference: &Array2<f32>,
+    target: &Array2<f32>,
+    model: DistortionModel,
+) -> DistortionAlignResult {
+    let matches = affine::find_star_matches(reference, target);
+    let affine = affine::affine_from_matches(reference, target, &matches);
+    let dims = reference.dim();
+
+    if matches!(affine.method, AffineAlignMethod::Affine | AffineAlignMethod::Rigid) {
+        if let Some(result) = fit_distortion(&matches, model, &affine, dims) {
+            log::info!(
+                "Distortion: {} fit, stars={}, inliers={}, residual={:.3}px (affine {:.3}px), max={:.3}px",
+                model, result.matched_stars, result.inliers, result.residual_px,
+                result.affine_residual_px, result.max_residual_px
+            );
+            return result;
+        }
+        log::warn!("Distortion: {} fit rejected with {} matches, keeping {} solution", model, matches.len(), affine.method);
+    }
+
+    affine_only(affine, dims)
+}
+
+pub fn fit_distortion(
+    matches: &[(f64, f64, f64, f64)],
+    model: DistortionModel,
+    affine: &AffineAlignResult,
+    dims: (usize, usize),
+) -> Option<DistortionAlignResult> {
+    let order = model.ransac_order();
+    let min_matches = term_count(order) + MIN_EXTRA_MATCHES;
+    if matches.len() < min_matches {
+        log::debug!("Distortion: {} matches < {} needed for order {}", matches.len(), min_matches, order);
+        return None;
+    }
+
+    let inlier_matches = ransac_polynomial(matches, order)?;
+    if inlier_matches.len() < min_matches
+        || (inlier_matches.len() as f64) < matches.len() as f64 * MIN_INLIER_RATIO
+    {
+        log::debug!("Distortion: {} of {} matches are inliers", inlier_matches.len(), matches.len());
+        return None;
+    }
+
+    let swapped: Vec<(f64, f64, f64, f64)> = inlier_matches
+        .iter()
+        .map(|&(sx, sy, tx, ty)| (tx, ty, sx, sy))
+        .collect();
+    let (forward, inverse) = match model {
+        DistortionModel::ThinPlate => {
+            let control = thin_control(&inlier_matches);
+            let swapped_control = thin_control(&swapped);
+            (
+                FrameMapping::ThinPlate(ThinPlateSpline::fit(&control, TPS_REGULARIZATION)?),
+                FrameMapping::ThinPlate(ThinPlateSpline::fit(&swapped_control, TPS_REGULARIZATION)?),
+            )
+        }
+        _ => (
+            FrameMapping::Polynomial(PolynomialTransform::fit(&inlier_matches, order)?),
+            FrameMapping::Polynomial(PolynomialTransform::fit(&swapped, order)?),
+        ),
+    };
+
+    let residuals: Vec<StarResidual> = inlier_matches
+        .iter()
+        .map(|&(sx, sy, tx, ty)| {
+            let (mx, my) = forward.map(sx, sy);
+            StarResidual { x: sx, y: sy, dx: mx - tx, dy: my - ty }
+        })
+        .collect();
+    let residual_px = rms(residuals.iter().map(|r| r.dx * r.dx + r.dy * r.dy));
+    if residual_px > MAX_RESIDUAL_PX {
+        log::debug!("Distortion: residual {:.3}px > {:.1}px", residual_px, MAX_RESIDUAL_PX);
+        return None;
+    }
+    let max_residual_px = residuals
+        .iter()
+        .map(|r| r.dx.hypot(r.dy))
+        .fold(0.0f64, f64::max);
+
+    let linear = affine::fit_affine(&inlier_matches).unwrap_or(affine.transform);
+    let affine_residual_px = rms(inlier_matches.iter().map(|&(sx, sy, tx, ty)| {
+        let (mx, my) = linear.map(sx, sy);
+        (mx - tx).powi(2) + (my - ty).powi(2)
+    }));
+
+    let distortion_map = distortion_grid(dims, |x, y| {
+        let (mx, my) = forward.map(x, y);
+        let (ax, ay) = linear.map(x, y);
+        (mx - ax).hypot(my - ay)
+    });
+    let peak = distortion_map.iter().fold(0.0f32, |a, &b| a.max(b)) as f64;
+    if !peak.is_finite() || peak > MAX_DISTORTION_PX {
+        log::debug!("Distortion: peak deviation from affine {:.1}px > {:.0}px", peak, MAX_DISTORTION_PX);
+        return None;
+    }
+
+    Some(DistortionAlignResult {
+        forward,
+        inverse,
+        affine: affine.clone(),
+        model: Some(model),
+        matched_stars: matches.len(),
+        inliers: inlier_matches.len(),
+        residual_px,
+        affine_residual_px,
+        max_residual_px,
+        residuals,
+        distortion_map,
+    })
+}
+
+fn affine_only(affine: AffineAlignResult, dims: (usize, usize)) -> DistortionAlignResult {
+    let inverse = affine
+        .transform
+        .inverse()
+        .map(FrameMapping::Affine)
+        .unwrap_or_else(FrameMapping::identity);
+    DistortionAlignResult {
+        forward: FrameMapping::Affine(affine.transform),
+        inverse,
+        model: None,
+        matched_stars: affine.matched_stars,
+        inliers: affine.inliers,
+        residual_px: affine.residual_px,
+        affine_residual_px: affine.residual_px,
+        max_residual_px: affine.residual_px,
+        residuals: Vec::new(),
+        distortion_map: distortion_grid(dims, |_, _| 0.0),
+        affine,
+    }
+}
+
+fn ransac_polynomial(matches: &[(f64, f64, f64, f64)], order: usize) -> Option<Vec<(f64, f64, f64, f64)>> {
+    let n = matches.len();
+    let sample_size = term_count(order);
+    let num_threads = rayon::current_num_threads().max(1);
+    let chunk_size = RANSAC_ITERATIONS.div_ceil(num_threads);
+
+    let (best_count, best_mask) = (0..num_threads)
+        .into_par_iter()
+        .map(|thread_id| {
+            let mut rng_state: u64 = 0x5DEE_CE66_D1CE_4E5Bu64.wrapping_add(thread_id as u64 * 0x9E3779B97F4A7C15u64);
+            let next = |state: &mut u64| -> usize {
+                *state ^= *state << 13;
+                *state ^= *state >> 7;
+                *state ^= *state << 17;
+                (*state as usize) % n
+            };
+
+            let mut best_count = 0usize;
+            let mut best_mask = vec![false; n];
+            let mut mask = vec![false; n];
+            let mut sample = Vec::with_capacity(sample_size);
+
+            for _ in 0..chunk_size {
+                sample.clear();
+                let mut attempts = 0;
+                while sample.len() < sample_size && attempts < sample_size * 8 {
+                    let idx = next(&mut rng_state);
+                    if !sample.contains(&idx) {
+                        sample.push(idx);
+                    }
+                    attempts += 1;
+                }
+                if sample.len() < sample_size {
+                    continue;
+                }
+
+                let subset: Vec<(f64, f64, f64, f64)> = sample.iter().map(|&i| matches[i]).collect();
+                let Some(poly) = PolynomialTransform::fit(&subset, order) else {
+                    continue;
+                };
+
+                let count = mark_inliers(&poly, matches, &mut mask);
+                if count > best_count {
+                    best_count = count;
+                    best_mask.copy_from_slice(&mask);
+                }
+            }
+            (best_count, best_mask)
+        })
+        .reduce_with(|a, b| if b.0 > a.0 { b } else { a })?;
+
+    if best_count < sample_size {
+        return None;
+    }
+
+    let mut mask = best_mask;
+    let mut inliers = select(matches, &mask);
+    for _ in 0..2 {
+        let poly = PolynomialTransform::fit(&inliers, order)?;
+        if mark_inliers(&poly, matches, &mut mask) < sample_size {
+            break;
+        }
+        inliers = select(matches, &mask);
+    }
+    Some(inliers)
+}
+
+fn mark_inliers(poly: &PolynomialTransform, matches: &[(f64, f64, f64, f64)], mask: &mut [bool]) -> usize {
+    let mut count = 0;
+    for (m, &(sx, sy, tx, ty)) in mask.iter_mut().zip(matches) {
+        let (px, py) = poly.map(sx, sy);
+        *m = (px - tx).hypot(py - ty) < RANSAC_INLIER_PX;
+        count += *m as usize;
+    }
+    count
+}
+
+fn select(matches: &[(f64, f64, f64, f64)], mask: &[bool]) -> Vec<(f64, f64, f64, f64)> {
+    matches
+        .iter()
+        .zip(mask)
+        .filter(|(_, &keep)| keep)
+        .map(|(&m, _)| m)
+        .collect()
+}
+
+fn thin_control(matches: &[(f64, f64, f64, f64)]) -> Vec<(f64, f64, f64, f64)> {
+    if matches.len() <= TPS_MAX_CONTROL {
+        return matches.to_vec();
+    }
+    let step = matches.len() as f64 / TPS_MAX_CONTROL as f64;
+    (0..TPS_MAX_CONTROL)
+        .map(|i| matches[(i as f64 * step) as usize])
+        .collect()
+}
+
+fn rms(squares: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = squares.fold((0.0f64, 0usize), |(s, c), v| (s + v, c + 1));
+    if count == 0 {
+        return 0.0;
+    }
+    (sum / count as f64).sqrt()
+}
+
+fn distortion_grid(dims: (usize, usize), f: impl Fn(f64, f64) -> f64 + Sync) -> Array2<f32> {
+    let grid_rows = dims.0.div_ceil(DISTORTION_GRID_STEP).max(1);
+    let grid_cols = dims.1.div_ceil(DISTORTION_GRID_STEP).max(1);
+    let half = DISTORTION_GRID_STEP as f64 * 0.5;
+    let mut buf = vec![0.0f32; grid_rows * grid_cols];
+    buf.par_chunks_mut(grid_cols).enumerate().for_each(|(gy, row)| {
+        let y = ((gy * DISTORTION_GRID_STEP) as f64 + half).min(dims.0 as f64 - 1.0).max(0.0);
+        for (gx, v) in row.iter_mut().enumerate() {
+            let x = ((gx * DISTORTION_GRID_STEP) as f64 + half).min(dims.1 as f64 - 1.0).max(0.0);
+            *v = f(x, y) as f32;
+        }
+    });
+    Array2::from_shape_vec((grid_rows, grid_cols), buf).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::alignment::affine::AffineTransform;
+
+    fn barrel(x: f64, y: f64) -> (f64, f64) {
+        let (cx, cy) = (400.0, 300.0);
+        let r2 = ((x - cx).powi(2) + (y - cy).powi(2)) / 400.0f64.powi(2);
+        let k = 1.0 + 0.015 * r2;
+        (cx + (x - cx) * k + 4.0, cy + (y - cy) * k - 2.5)
+    }
+
+    fn synthetic_matches() -> Vec<(f64, f64, f64, f64)> {
+        let mut matches: Vec<(f64, f64, f64, f64)> = (0..80)
+            .map(|i| {
+                let x = ((i * 97) % 800) as f64 + 0.5;
+                let y = ((i * 61) % 600) as f64 + 0.25;
+                let (tx, ty) = barrel(x, y);
+                (x, y, tx, ty)
+            })
+            .collect();
+        for i in 0..12 {
+            let m = &mut matches[i * 6];
+            m.2 += 25.0 + i as f64;
+            m.3 -= 40.0;
+        }
+        matches
+    }
+
+    fn affine_stub(matches: &[(f64, f64, f64, f64)]) -> AffineAlignResult {
+        AffineAlignResult {
+            transform: affine::fit_affine(matches).unwrap_or(AffineTransform::identity()),
+            matched_stars: matches.len(),
+            inliers: matches.len(),
+            residual_px: 0.0,
+            method: AffineAlignMethod::Affine,
+            is_flipped: false,
+        }
+    }
+
+    #[test]
+    fn test_polynomial_ransac_rejects_outliers() {
+        let matches = synthetic_matches();
+        let result = fit_distortion(&matches, DistortionModel::Polynomial3, &affine_stub(&matches), (600, 800)).unwrap();
+        assert_eq!(result.inliers, 68);
+        assert!(result.residual_px < 0.05, "residual {}", result.residual_px);
+        assert!(result.affine_residual_px > result.residual_px * 10.0);
+        let (mx, my) = result.forward.map(20.0, 20.0);
+        let (ex, ey) = barrel(20.0, 20.0);
+        assert!((mx - ex).hypot(my - ey) < 0.1);
+        let (bx, by) = result.inverse.map(ex, ey);
+        assert!((bx - 20.0).hypot(by - 20.0) < 0.25);
+        assert!(result.distortion_map[[0, 0]] > result.distortion_map[[9, 12]]);
+    }
+
+    #[test]
+    fn test_thin_plate_fit_tracks_distortion() {
+        let matches = synthetic_matches();
+        let result = fit_distortion(&matches, DistortionModel::ThinPlate, &affine_stub(&matches), (600, 800)).unwrap();
+        assert_eq!(result.model, Some(DistortionModel::ThinPlate));
+        assert!(result.max_residual_px < 0.5, "max residual {}", result.max_residual_px);
+        let (mx, my) = result.forward.map(410.0, 290.0);
+        let (ex, ey) = barrel(410.0, 290.0);
+        assert!((mx - ex).hypot(my - ey) < 0.2);
+    }
+}
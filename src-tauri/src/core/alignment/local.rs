@@ -0,0 +1,292 @@
+use ndarray::Array2;
+use rayon::prelude::*;
+
+use crate::core::alignment::phase_correlation::PatchCorrelator;
+use crate::core::imaging::sampling::bicubic_sample;
+
+const MIN_PATCH_SIZE: usize = 16;
+const MIN_CONTRAST_FRACTION: f64 = 0.15;
+const OUTLIER_PX: f64 = 1.0;
+
+#[derive(Debug, Clone)]
+pub struct LocalAlignConfig {
+    pub patch_size: usize,
+    pub grid_step: usize,
+    pub min_confidence: f64,
+    pub max_shift_px: f64,
+}
+
+impl Default for LocalAlignConfig {
+    fn default() -> Self {
+        Self {
+            patch_size: 64,
+            grid_step: 48,
+            min_confidence: 4.0,
+            max_shift_px: 12.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShiftField {
+    pub rows: usize,
+    pub cols: usize,
+    pub global: (f64, f64),
+    pub grid_y: Vec<f64>,
+    pub grid_x: Vec<f64>,
+    pub dy: Array2<f64>,
+    pub dx: Array2<f64>,
+    pub valid_patches: usize,
+}
+
+impl ShiftField {
+    pub fn uniform(rows: usize, cols: usize, dy: f64, dx: f64) -> Self {
+        Self {
+            rows,
+            cols,
+            global: (dy, dx),
+            grid_y: vec![0.0],
+            grid_x: vec![0.0],
+            dy: Array2::from_elem((1, 1), dy),
+            dx: Array2::from_elem((1, 1), dx),
+            valid_patches: 0,
+        }
+    }
+
+    pub fn patch_count(&self) -> usize {
+        self.grid_y.len() * self.grid_x.len()
+    }
+
+    pub fn shift_at(&self, y: f64, x: f64) -> (f64, f64) {
+        let (iy, fy) = grid_position(&self.grid_y, y);
+        let (ix, fx) = grid_position(&self.grid_x, x);
+        let iy1 = (iy + 1).min(self.grid_y.len() - 1);
+        let ix1 = (ix + 1).min(self.grid_x.len() - 1);
+        let lerp = |g: &Array2<f64>| {
+            let top = g[[iy, ix]] * (1.0 - fx) + g[[iy, ix1]] * fx;
+            let bottom = g[[iy1, ix]] * (1.0 - fx) + g[[iy1, ix1]] * fx;
+            top * (1.0 - fy) + bottom * fy
+        };
+        (lerp(&self.dy), lerp(&self.dx))
+    }
+
+    pub fn max_local_deviation(&self) -> f64 {
+        self.dy
+            .iter()
+            .zip(self.dx.iter())
+            .map(|(&dy, &dx)| (dy - self.global.0).hypot(dx - self.global.1))
+            .fold(0.0, f64::max)
+    }
+
+    pub fn warp(&self, image: &Array2<f32>) -> Array2<f32> {
+        let (rows, cols) = image.dim();
+        let src = image.as_slice().expect("contiguous");
+        let mut out = vec![f32::NAN; self.rows * self.cols];
+        let max_y = rows as f64 - 1.0;
+        let max_x = cols as f64 - 1.0;
+        out.par_chunks_mut(self.cols).enumerate().for_each(|(y, row)| {
+            for (x, px) in row.iter_mut().enumerate() {
+                let (dy, dx) = self.shift_at(y as f64, x as f64);
+                let sy = y as f64 + dy;
+                let sx = x as f64 + dx;
+                if sy < 0.0 || sx < 0.0 || sy > max_y || sx > max_x {
+                    continue;
+                }
+                *px = bicubic_sample(src, rows, cols, sy, sx);
+            }
+        });
+        Array2::from_shape_vec((self.rows, self.cols), out).unwrap()
+    }
+}
+
+fn grid_position(grid: &[f64], v: f64) -> (usize, f64) {
+    if grid.len() < 2 || v <= grid[0] {
+        return (0, 0.0);
+    }
+    let last = grid.len() - 1;
+    if v >= grid[last] {
+        return (last, 0.0);
+    }
+    let i = grid.partition_point(|&g| g <= v) - 1;
+    (i, (v - grid[i]) / (grid[i + 1] - grid[i]))
+}
+
+fn grid_centers(extent: usize, patch: usize, step: usize) -> Vec<usize> {
+    let half = patch / 2;
+    if extent <= patch {
+        return vec![extent / 2];
+    }
+    let last = extent - half;
+    let mut centers: Vec<usize> = (half..=last).step_by(step.max(1)).collect();
+    if centers.last().is_some_and(|&c| c < last) {
+        centers.push(last);
+    }
+    centers
+}
+
+fn extract_patch(image: &Array2<f32>, cy: isize, cx: isize, size: usize) -> Option<Array2<f32>> {
+    let (rows, cols) = image.dim();
+    let half = (size / 2) as isize;
+    let (y0, x0) = (cy - half, cx - half);
+    if y0 < 0 || x0 < 0 || y0 as usize + size > rows || x0 as usize + size > cols {
+        return None;
+    }
+    let (y0, x0) = (y0 as usize, x0 as usize);
+    let patch = image.slice(ndarray::s![y0..y0 + size, x0..x0 + size]);
+    let finite: Vec<f32> = patch.iter().copied().filter(|v| v.is_finite()).collect();
+    if finite.len() < size * size / 2 {
+        return None;
+    }
+    let mean = finite.iter().map(|&v| v as f64).sum::<f64>() / finite.len() as f64;
+    Some(patch.mapv(|v| if v.is_finite() { (v as f64 - mean) as f32 } else { 0.0 }))
+}
+
+fn patch_contrast(patch: &Array2<f32>) -> f64 {
+    let n = patch.len().max(1) as f64;
+    (patch.iter().map(|&v| (v as f64).powi(2)).sum::<f64>() / n).sqrt()
+}
+
+pub fn estimate_shift_field(
+    reference: &Array2<f32>,
+    target: &Array2<f32>,
+    global: (f64, f64),
+    config: &LocalAlignConfig,
+) -> ShiftField {
+    let (rows, cols) = reference.dim();
+    let size = config.patch_size.max(MIN_PATCH_SIZE).min(rows.min(cols));
+    let ys = grid_centers(rows, size, config.grid_step);
+    let xs = grid_centers(cols, size, config.grid_step);
+    let (gy, gx) = global;
+    let (oy, ox) = (gy.round() as isize, gx.round() as isize);
+    let half = (size / 2) as isize;
+    let (tgt_rows, tgt_cols) = (target.nrows() as isize, target.ncols() as isize);
+
+    let ref_patches: Vec<Option<Array2<f32>>> = ys
+        .iter()
+        .flat_map(|&y| xs.iter().map(move |&x| (y, x)))
+        .map(|(y, x)| extract_patch(reference, y as isize, x as isize, size))
+        .collect();
+    let peak_contrast = ref_patches.iter().flatten().map(patch_contrast).fold(0.0, f64::max);
+    let min_contrast = peak_contrast * MIN_CONTRAST_FRACTION;
+
+    let correlator = PatchCorrelator::new(size, size);
+    let estimates: Vec<Option<(f64, f64)>> = ref_patches
+        .par_iter()
+        .enumerate()
+        .map(|(i, ref_patch)| {
+            let ref_patch = ref_patch.as_ref().filter(|p| patch_contrast(p) >= min_contrast)?;
+            let (y, x) = (ys[i / xs.len()] as isize, xs[i % xs.len()] as isize);
+            let ty = (y + oy).clamp(half, tgt_rows - size as isize + half);
+            let tx = (x + ox).clamp(half, tgt_cols - size as isize + half);
+            let tgt_patch = extract_patch(target, ty, tx, size)?;
+            let pc = correlator.correlate(ref_patch, &tgt_patch);
+            let dy = (ty - y) as f64 + pc.dy;
+            let dx = (tx - x) as f64 + pc.dx;
+            let ok = pc.confidence >= config.min_confidence
+                && (dy - gy).hypot(dx - gx) <= config.max_shift_px;
+            ok.then_some((dy, dx))
+        })
+        .collect();
+
+    let valid_patches = estimates.iter().flatten().count();
+    let (ny, nx) = (ys.len(), xs.len());
+    let raw = Array2::from_shape_vec((ny, nx), estimates).unwrap();
+    let mut dy = Array2::from_elem((ny, nx), gy);
+    let mut dx = Array2::from_elem((ny, nx), gx);
+    for r in 0..ny {
+        for c in 0..nx {
+            let mut sy = Vec::with_capacity(9);
+            let mut sx = Vec::with_capacity(9);
+            for rr in r.saturating_sub(1)..(r + 2).min(ny) {
+                for cc in c.saturating_sub(1)..(c + 2).min(nx) {
+                    if let Some((vy, vx)) = raw[[rr, cc]] {
+                        sy.push(vy);
+                        sx.push(vx);
+                    }
+                }
+            }
+            let (Some(my), Some(mx)) = (median(&mut sy), median(&mut sx)) else {
+                continue;
+            };
+            let (vy, vx) = match raw[[r, c]] {
+                Some((vy, vx)) if (vy - my).hypot(vx - mx) <= OUTLIER_PX => (vy, vx),
+                _ => (my, mx),
+            };
+            dy[[r, c]] = vy;
+            dx[[r, c]] = vx;
+        }
+    }
+
+    ShiftField {
+        rows,
+        cols,
+        global,
+        grid_y: ys.iter().map(|&v| v as f64).collect(),
+        grid_x: xs.iter().map(|&v| v as f64).collect(),
+        dy,
+        dx,
+        valid_patches,
+    }
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) { (values[mid - 1] + values[mid]) * 0.5 } else { values[mid] })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn textured(rows: usize, cols: usize, warp: impl Fn(f64, f64) -> (f64, f64)) -> Array2<f32> {
+        let blobs: Vec<(f64, f64, f64, f64)> = (0..rows * cols / 40)
+            .map(|i| {
+                let h = (i as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+                let f = |shift: u32| ((h >> shift) & 0xFFFF) as f64 / 65535.0;
+                (f(0) * rows as f64, f(16) * cols as f64, 1.0 + f(32) * 2.5, f(48) - 0.5)
+            })
+            .collect();
+        Array2::from_shape_fn((rows, cols), |(y, x)| {
+            let (wy, wx) = warp(y as f64, x as f64);
+            let v: f64 = blobs
+                .iter()
+                .map(|&(by, bx, r, a)| a * (-((wy - by).powi(2) + (wx - bx).powi(2)) / (2.0 * r * r)).exp())
+                .sum();
+            (v * 400.0 + 1000.0) as f32
+        })
+    }
+
+    #[test]
+    fn test_local_shifts_follow_deformation() {
+        let reference = textured(192, 256, |y, x| (y, x));
+        let shift = |x: f64| 1.0 + 2.5 * x / 255.0;
+        let target = textured(192, 256, |y, x| (y - 0.5, x - shift(x)));
+        let field = estimate_shift_field(&reference, &target, (0.5, 2.0), &LocalAlignConfig::default());
+        assert!(field.valid_patches >= field.patch_count() / 2, "valid {}", field.valid_patches);
+        let (dy_left, dx_left) = field.shift_at(96.0, 40.0);
+        let (dy_right, dx_right) = field.shift_at(96.0, 215.0);
+        assert!((dx_left - shift(40.0)).abs() < 0.35, "left dx {}", dx_left);
+        assert!((dx_right - shift(215.0)).abs() < 0.35, "right dx {}", dx_right);
+        assert!((dy_left - 0.5).abs() < 0.3 && (dy_right - 0.5).abs() < 0.3);
+
+        let warped = field.warp(&target);
+        let err: f64 = (40..150)
+            .flat_map(|y| (40..215).map(move |x| (y, x)))
+            .map(|(y, x)| (warped[[y, x]] - reference[[y, x]]).abs() as f64)
+            .sum::<f64>()
+            / (110.0 * 175.0);
+        assert!(err < 40.0, "mean abs error {}", err);
+    }
+
+    #[test]
+    fn test_uniform_field_shifts_image() {
+        let image = Array2::from_shape_fn((20, 20), |(y, x)| (y * 20 + x) as f32);
+        let warped = ShiftField::uniform(20, 20, 1.0, 2.0).warp(&image);
+        assert!((warped[[5, 5]] - image[[6, 7]]).abs() < 1e-3);
+        assert!(warped[[19, 19]].is_nan());
+    }
+}
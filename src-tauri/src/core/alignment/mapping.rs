@@ -0,0 +1,167 @@
+use anyhow::{Context, Result};
+use ndarray::Array2;
+use rayon::prelude::*;
+
+use crate::core::alignment::affine::AffineTransform;
+use crate::core::alignment::polynomial::PolynomialTransform;
+use crate::core::alignment::thin_plate::ThinPlateSpline;
+use crate::core::astrometry::wcs::WcsTransform;
+use crate::core::imaging::sampling::bicubic_sample;
+use crate::types::header::HduHeader;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FrameMapping {
+    Translation { dx: f64, dy: f64 },
+    Affine(AffineTransform),
+    Polynomial(PolynomialTransform),
+    ThinPlate(ThinPlateSpline),
+    Wcs { frame: WcsTransform, reference: WcsTransform },
+}
+
+impl FrameMapping {
+    pub fn identity() -> Self {
+        FrameMapping::Translation { dx: 0.0, dy: 0.0 }
+    }
+
+    pub fn from_offset(dx: f64, dy: f64) -> Self {
+        FrameMapping::Translation { dx: -dx, dy: -dy }
+    }
+
+    pub fn from_wcs_headers(frame: &HduHeader, reference: &HduHeader) -> Result<Self> {
+        Ok(FrameMapping::Wcs {
+            frame: WcsTransform::from_header(frame).context("Frame has no usable WCS")?,
+            reference: WcsTransform::from_header(reference)
+                .context("Reference frame has no usable WCS")?,
+        })
+    }
+
+    #[inline]
+    pub fn map(&self, x: f64, y: f64) -> (f64, f64) {
+        match self {
+            FrameMapping::Translation { dx, dy } => (x + dx, y + dy),
+            FrameMapping::Affine(t) => t.map(x, y),
+            FrameMapping::Polynomial(p) => p.map(x, y),
+            FrameMapping::ThinPlate(t) => t.map(x, y),
+            FrameMapping::Wcs { frame, reference } => {
+                let sky = frame.pixel_to_world(x, y);
+                reference.world_to_pixel(sky.ra, sky.dec)
+            }
+        }
+    }
+
+    pub fn is_translation(&self) -> bool {
+        matches!(self, FrameMapping::Translation { .. })
+    }
+
+    pub fn offset_at(&self, x: f64, y: f64) -> (f64, f64) {
+        let (mx, my) = self.map(x, y);
+        (x - mx, y - my)
+    }
+
+    pub fn rotation_deg(&self, x: f64, y: f64) -> f64 {
+        let (x0, y0) = self.map(x, y);
+        let (x1, y1) = self.map(x + 1.0, y);
+        (y1 - y0).atan2(x1 - x0).to_degrees()
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FrameMapping::Translation { .. } => "translation",
+            FrameMapping::Affine(_) => "affine",
+            FrameMapping::Polynomial(_) => "polynomial",
+            FrameMapping::ThinPlate(_) => "thin_plate",
+            FrameMapping::Wcs { .. } => "wcs",
+        }
+    }
+}
+
+pub fn warp_mapped(
+    image: &Array2<f32>,
+    mapping: &FrameMapping,
+    out_rows: usize,
+    out_cols: usize,
+) -> Array2<f32> {
+    warp_mapped_fill(image, mapping, out_rows, out_cols, 0.0)
+}
+
+pub fn warp_mapped_fill(
+    image: &Array2<f32>,
+    mapping: &FrameMapping,
+    out_rows: usize,
+    out_cols: usize,
+    fill: f32,
+) -> Array2<f32> {
+    let (src_rows, src_cols) = image.dim();
+    let slice = image.as_slice().expect("contiguous");
+    let mut buf = vec![fill; out_rows * out_cols];
+
+    buf.par_chunks_mut(out_cols)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, v) in row.iter_mut().enumerate() {
+                let (sx, sy) = mapping.map(x as f64, y as f64);
+                if sx >= 0.0
+                    && sy >= 0.0
+                    && sx < (src_cols - 1) as f64
+                    && sy < (src_rows - 1) as f64
+                {
+                    *v = bicubic_sample(slice, src_rows, src_cols, sy, sx);
+                }
+            }
+        });
+
+    Array2::from_shape_vec((out_rows, out_cols), buf).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_round_trip() {
+        let m = FrameMapping::from_offset(2.5, -1.25);
+        assert_eq!(m.map(10.0, 10.0), (7.5, 11.25));
+        let (dx, dy) = m.offset_at(10.0, 10.0);
+        assert!((dx - 2.5).abs() < 1e-12 && (dy + 1.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_affine_inverse_composes_to_identity() {
+        let t = AffineTransform { a: 0.9, b: -0.2, tx: 14.0, c: 0.25, d: 1.1, ty: -6.0 };
+        let inv = t.inverse().unwrap();
+        let (x, y) = t.map(31.0, 57.0);
+        let (bx, by) = FrameMapping::Affine(inv).map(x, y);
+        assert!((bx - 31.0).abs() < 1e-9 && (by - 57.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_warp_mapped_undoes_polynomial_distortion() {
+        let reference = Array2::from_shape_fn((64, 64), |(y, x)| {
+            ((y as f32 * 0.21).sin() + (x as f32 * 0.17).cos()) * 100.0 + 500.0
+        });
+        let warp = |x: f64, y: f64| (x + 1.5 + 4e-4 * x * y, y - 0.75 + 3e-4 * x * x);
+        let mut matches = Vec::new();
+        for gy in 0..8 {
+            for gx in 0..8 {
+                let (x, y) = (gx as f64 * 9.0, gy as f64 * 9.0);
+                let (tx, ty) = warp(x, y);
+                matches.push((tx, ty, x, y));
+            }
+        }
+        let inverse = PolynomialTransform::fit(&matches, 2).unwrap();
+        let target = warp_mapped(&reference, &FrameMapping::Polynomial(inverse), 64, 64);
+
+        let forward = PolynomialTransform::fit(
+            &matches.iter().map(|&(tx, ty, x, y)| (x, y, tx, ty)).collect::<Vec<_>>(),
+            2,
+        )
+        .unwrap();
+        let restored = warp_mapped(&target, &FrameMapping::Polynomial(forward), 64, 64);
+        for y in 8..48 {
+            for x in 8..48 {
+                assert!((restored[[y, x]] - reference[[y, x]]).abs() < 5.0, "({}, {})", x, y);
+            }
+        }
+    }
+}
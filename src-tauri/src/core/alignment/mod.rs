@@ -1,4 +1,10 @@
 pub mod affine;
+pub mod distortion;
 pub mod downsample;
+pub mod local;
+pub mod mapping;
 pub mod pair;
 pub mod phase_correlation;
+pub mod polynomial;
+pub mod registration;
+pub mod thin_plate;
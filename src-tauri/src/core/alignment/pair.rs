@@ -3,6 +3,8 @@ use ndarray::Array2;
 use rayon::prelude::*;
 
 use crate::core::alignment::affine;
+use crate::core::alignment::distortion::{self, DistortionModel};
+use crate::core::alignment::mapping::warp_mapped;
 use crate::core::alignment::phase_correlation;
 use crate::core::imaging::sampling::bicubic_sample;
 use crate::types::compose::AlignMethod;
@@ -16,6 +18,7 @@ pub struct AlignPairResult {
     pub matched_stars: usize,
     pub inliers: usize,
     pub residual_px: f64,
+    pub is_flipped: bool,
 }
 
 fn shift_image_subpixel(image: &Array2<f32>, dy: f64, dx: f64) -> Array2<f32> {
@@ -57,6 +60,7 @@ pub fn align_pair(
                 matched_stars: 0,
                 inliers: 0,
                 residual_px: 0.0,
+                is_flipped: false,
             })
         }
         AlignMethod::Affine => {
@@ -70,6 +74,23 @@ pub fn align_pair(
                 matched_stars: result.matched_stars,
                 inliers: result.inliers,
                 residual_px: result.residual_px,
+                is_flipped: result.is_flipped,
+            })
+        }
+        AlignMethod::Polynomial2 | AlignMethod::Polynomial3 | AlignMethod::ThinPlate => {
+            let model = DistortionModel::from_align_method(method).expect("distortion method");
+            let result = distortion::align_channel_distortion(reference, target, model);
+            let warped = warp_mapped(target, &result.forward, rows, cols);
+            let (dx, dy) = result.forward.offset_at(cols as f64 * 0.5, rows as f64 * 0.5);
+            Ok(AlignPairResult {
+                aligned: warped,
+                offset: (-dy, -dx),
+                confidence: if result.inliers > 0 { 1.0 } else { 0.0 },
+                method_used: result.method_label(),
+                matched_stars: result.matched_stars,
+                inliers: result.inliers,
+                residual_px: result.residual_px,
+                is_flipped: result.affine.is_flipped,
             })
         }
     }
@@ -91,11 +112,14 @@ pub fn align_pair_with_label(
                 label, result.offset.0, result.offset.1, result.confidence,
             );
         }
-        AlignMethod::Affine => {
+        AlignMethod::Affine
+        | AlignMethod::Polynomial2
+        | AlignMethod::Polynomial3
+        | AlignMethod::ThinPlate => {
             log::info!(
-                "{} alignment: method={}, stars={}, inliers={}, residual={:.3}px, tx={:.2}, ty={:.2}",
+                "{} alignment: method={}, stars={}, inliers={}, residual={:.3}px, tx={:.2}, ty={:.2}, flipped={}",
                 label, result.method_used, result.matched_stars, result.inliers,
-                result.residual_px, result.offset.1, result.offset.0,
+                result.residual_px, result.offset.1, result.offset.0, result.is_flipped,
             );
         }
     }
@@ -10,6 +10,7 @@ use crate::math::window;
 const COARSE_MAX_DIM: usize = 512;
 const REFINE_CROP_SIZE: usize = 512;
 const CONFIDENCE_THRESHOLD: f64 = 2.0;
+const SPECTRAL_DAMPING: f64 = 3e-4;
 const EPSILON: f64 = 1e-15;
 
 #[derive(Debug, Clone)]
@@ -105,38 +106,58 @@ fn extract_crop(
 
 fn correlate_single(a: &Array2<f32>, b: &Array2<f32>) -> PhaseCorrelationResult {
     let (rows, cols) = a.dim();
-    let fft_rows = fft::next_power_of_two(rows);
-    let fft_cols = fft::next_power_of_two(cols);
+    PatchCorrelator::new(rows, cols).correlate(a, b)
+}
 
-    let engine = FftEngine2D::<f64>::new(fft_rows, fft_cols);
+pub(crate) struct PatchCorrelator {
+    engine: FftEngine2D<f64>,
+    hann_y: Vec<f64>,
+    hann_x: Vec<f64>,
+    fft_rows: usize,
+    fft_cols: usize,
+}
 
-    let hann_y = window::hann_periodic::<f64>(rows);
-    let hann_x = window::hann_periodic::<f64>(cols);
+impl PatchCorrelator {
+    pub(crate) fn new(rows: usize, cols: usize) -> Self {
+        let fft_rows = fft::next_power_of_two(rows);
+        let fft_cols = fft::next_power_of_two(cols);
+        Self {
+            engine: FftEngine2D::<f64>::new(fft_rows, fft_cols),
+            hann_y: window::hann_periodic::<f64>(rows),
+            hann_x: window::hann_periodic::<f64>(cols),
+            fft_rows,
+            fft_cols,
+        }
+    }
 
-    let mut fa = fft::prepare_windowed_buffer(a, &hann_y, &hann_x, fft_rows, fft_cols);
-    let mut fb = fft::prepare_windowed_buffer(b, &hann_y, &hann_x, fft_rows, fft_cols);
+    pub(crate) fn correlate(&self, a: &Array2<f32>, b: &Array2<f32>) -> PhaseCorrelationResult {
+        let (fft_rows, fft_cols) = (self.fft_rows, self.fft_cols);
 
-    engine.forward_2d(&mut fa);
-    engine.forward_2d(&mut fb);
+        let mut fa = fft::prepare_windowed_buffer(a, &self.hann_y, &self.hann_x, fft_rows, fft_cols);
+        let mut fb = fft::prepare_windowed_buffer(b, &self.hann_y, &self.hann_x, fft_rows, fft_cols);
 
-    let mut cross = complex::cross_power_spectrum(&fa, &fb, EPSILON);
+        self.engine.forward_2d(&mut fa);
+        self.engine.forward_2d(&mut fb);
 
-    engine.inverse_2d(&mut cross);
+        let mut cross = complex::damped_cross_power_spectrum(&fb, &fa, SPECTRAL_DAMPING, EPSILON);
 
-    let correlation = fft::extract_real(&cross, fft_rows, fft_cols);
+        self.engine.inverse_2d(&mut cross);
 
-    let (peak_y, peak_x, peak_val) = fft::find_peak(&correlation, fft_cols);
-    let (mean, sigma) = normalization::compute_mean_sigma(&correlation);
-    let confidence = normalization::compute_snr(peak_val, mean, sigma);
+        let correlation = fft::extract_real(&cross, fft_rows, fft_cols);
 
-    let shift = subpixel::unwrap_and_refine(
-        &correlation, fft_rows, fft_cols, peak_y, peak_x,
-    );
+        let (peak_y, peak_x, peak_val) = fft::find_peak(&correlation, fft_cols);
+        let (mean, sigma) = normalization::compute_mean_sigma(&correlation);
+        let confidence = normalization::compute_snr(peak_val, mean, sigma);
 
-    PhaseCorrelationResult {
-        dx: shift.dx,
-        dy: shift.dy,
-        confidence,
+        let shift = subpixel::unwrap_and_refine(
+            &correlation, fft_rows, fft_cols, peak_y, peak_x,
+        );
+
+        PhaseCorrelationResult {
+            dx: shift.dx,
+            dy: shift.dy,
+            confidence,
+        }
     }
 }
 
@@ -219,6 +240,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_subpixel_shift_follows_target_offset() {
+        let shifted_pattern = |dy: f64, dx: f64| {
+            Array2::from_shape_fn((128, 128), |(y, x)| {
+                let (y, x) = (y as f64 - dy, x as f64 - dx);
+                ((y * 0.3).sin() * (x * 0.2).cos() * 1000.0 + 500.0 + ((y * 7.0 + x * 13.0) * 0.01).sin() * 200.0) as f32
+            })
+        };
+        let result = phase_correlate(&shifted_pattern(0.0, 0.0), &shifted_pattern(2.7, -1.4));
+        assert!((result.dy - 2.7).abs() < 0.2, "dy={}, expected 2.7", result.dy);
+        assert!((result.dx - (-1.4)).abs() < 0.2, "dx={}, expected -1.4", result.dx);
+    }
+
     #[test]
     fn test_nan_no_panic() {
         let mut img = make_pattern(64, 64);
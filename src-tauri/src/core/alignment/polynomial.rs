@@ -0,0 +1,168 @@
+use crate::core::alignment::affine::AffineTransform;
+
+pub const MAX_POLY_ORDER: usize = 3;
+const MAX_TERMS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PolynomialTransform {
+    pub order: usize,
+    pub origin: (f64, f64),
+    pub norm: f64,
+    pub coeffs_x: [f64; MAX_TERMS],
+    pub coeffs_y: [f64; MAX_TERMS],
+}
+
+pub fn term_count(order: usize) -> usize {
+    (order + 1) * (order + 2) / 2
+}
+
+fn basis(u: f64, v: f64, order: usize) -> [f64; MAX_TERMS] {
+    let mut out = [0.0f64; MAX_TERMS];
+    let mut k = 0;
+    for degree in 0..=order {
+        for j in 0..=degree {
+            out[k] = u.powi((degree - j) as i32) * v.powi(j as i32);
+            k += 1;
+        }
+    }
+    out
+}
+
+impl PolynomialTransform {
+    pub fn from_affine(t: &AffineTransform) -> Self {
+        let mut coeffs_x = [0.0f64; MAX_TERMS];
+        let mut coeffs_y = [0.0f64; MAX_TERMS];
+        coeffs_x[..3].copy_from_slice(&[t.tx, t.a, t.b]);
+        coeffs_y[..3].copy_from_slice(&[t.ty, t.c, t.d]);
+        Self { order: 1, origin: (0.0, 0.0), norm: 1.0, coeffs_x, coeffs_y }
+    }
+
+    #[inline]
+    pub fn map(&self, x: f64, y: f64) -> (f64, f64) {
+        let u = (x - self.origin.0) / self.norm;
+        let v = (y - self.origin.1) / self.norm;
+        let terms = basis(u, v, self.order);
+        let n = term_count(self.order);
+        let dot = |coeffs: &[f64; MAX_TERMS]| -> f64 {
+            coeffs[..n].iter().zip(&terms[..n]).map(|(c, t)| c * t).sum()
+        };
+        (dot(&self.coeffs_x), dot(&self.coeffs_y))
+    }
+
+    pub fn fit(matches: &[(f64, f64, f64, f64)], order: usize) -> Option<Self> {
+        let order = order.clamp(1, MAX_POLY_ORDER);
+        let n = term_count(order);
+        if matches.len() < n {
+            return None;
+        }
+
+        let count = matches.len() as f64;
+        let ox = matches.iter().map(|m| m.0).sum::<f64>() / count;
+        let oy = matches.iter().map(|m| m.1).sum::<f64>() / count;
+        let norm = matches
+            .iter()
+            .map(|m| (m.0 - ox).abs().max((m.1 - oy).abs()))
+            .fold(0.0f64, f64::max)
+            .max(1.0);
+
+        let mut ata = [[0.0f64; MAX_TERMS]; MAX_TERMS];
+        let mut atx = [0.0f64; MAX_TERMS];
+        let mut aty = [0.0f64; MAX_TERMS];
+        for &(sx, sy, tx, ty) in matches {
+            let terms = basis((sx - ox) / norm, (sy - oy) / norm, order);
+            for i in 0..n {
+                for j in 0..n {
+                    ata[i][j] += terms[i] * terms[j];
+                }
+                atx[i] += terms[i] * tx;
+                aty[i] += terms[i] * ty;
+            }
+        }
+
+        let coeffs_x = solve_dense(ata, atx, n)?;
+        let coeffs_y = solve_dense(ata, aty, n)?;
+        Some(Self { order, origin: (ox, oy), norm, coeffs_x, coeffs_y })
+    }
+
+    pub fn residual_rms(&self, matches: &[(f64, f64, f64, f64)]) -> f64 {
+        if matches.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = matches
+            .iter()
+            .map(|&(sx, sy, tx, ty)| {
+                let (mx, my) = self.map(sx, sy);
+                (mx - tx).powi(2) + (my - ty).powi(2)
+            })
+            .sum();
+        (sum / matches.len() as f64).sqrt()
+    }
+}
+
+fn solve_dense(
+    mut a: [[f64; MAX_TERMS]; MAX_TERMS],
+    mut b: [f64; MAX_TERMS],
+    n: usize,
+) -> Option<[f64; MAX_TERMS]> {
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| {
+            a[i][col].abs().partial_cmp(&a[j][col].abs()).unwrap_or(std::cmp::Ordering::Equal)
+        })?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let pivot_row = a[col];
+        for row in col + 1..n {
+            let factor = a[row][col] / pivot_row[col];
+            for (dst, src) in a[row][col..n].iter_mut().zip(&pivot_row[col..n]) {
+                *dst -= factor * src;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+
+    let mut x = [0.0f64; MAX_TERMS];
+    for row in (0..n).rev() {
+        let mut sum = b[row];
+        for k in row + 1..n {
+            sum -= a[row][k] * x[k];
+        }
+        x[row] = sum / a[row][row];
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_recovers_quadratic_distortion() {
+        let warp = |x: f64, y: f64| (x + 2.0 + 1e-4 * x * x, y - 1.5 + 2e-4 * x * y);
+        let mut matches = Vec::new();
+        for gy in 0..8 {
+            for gx in 0..8 {
+                let (x, y) = (gx as f64 * 60.0, gy as f64 * 45.0);
+                let (tx, ty) = warp(x, y);
+                matches.push((x, y, tx, ty));
+            }
+        }
+        let poly = PolynomialTransform::fit(&matches, 2).unwrap();
+        assert!(poly.residual_rms(&matches) < 1e-6);
+        let (mx, my) = poly.map(123.0, 77.0);
+        let (ex, ey) = warp(123.0, 77.0);
+        assert!((mx - ex).abs() < 1e-6 && (my - ey).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_from_affine_matches_affine() {
+        let t = AffineTransform { a: 0.98, b: -0.17, tx: 5.0, c: 0.17, d: 0.98, ty: -3.0 };
+        let poly = PolynomialTransform::from_affine(&t);
+        let (px, py) = poly.map(40.0, 90.0);
+        let (ax, ay) = t.map(40.0, 90.0);
+        assert!((px - ax).abs() < 1e-12 && (py - ay).abs() < 1e-12);
+    }
+}
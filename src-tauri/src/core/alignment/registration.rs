@@ -0,0 +1,362 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use ndarray::{s, Array2};
+
+use crate::core::alignment::affine::{self, AffineAlignMethod};
+use crate::core::alignment::distortion::{self, DistortionModel, StarResidual};
+use crate::core::alignment::mapping::{warp_mapped_fill, FrameMapping};
+use crate::core::alignment::phase_correlation;
+use crate::core::analysis::subframe::{select_reference, SubframeWeightConfig};
+use crate::core::stacking::calibration::{load_calibrated_frames, CalibrationConfig};
+use crate::infra::progress::ProgressHandle;
+use crate::types::compose::AlignMethod;
+use crate::types::error::AppError;
+use crate::types::stacking::{ReferenceChoice, ReferenceSelection};
+
+pub const REGISTRATION_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FrameRegistration {
+    pub path: String,
+    pub forward: FrameMapping,
+    pub inverse: FrameMapping,
+    pub method: String,
+    pub matched_stars: usize,
+    pub inliers: usize,
+    pub residual_px: f64,
+    pub confidence: f64,
+    #[serde(default)]
+    pub is_flipped: bool,
+    #[serde(default)]
+    pub residuals: Vec<StarResidual>,
+    #[serde(default)]
+    pub distortion_map: Option<Array2<f32>>,
+}
+
+impl FrameRegistration {
+    fn reference(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            forward: FrameMapping::identity(),
+            inverse: FrameMapping::identity(),
+            method: "reference".into(),
+            matched_stars: 0,
+            inliers: 0,
+            residual_px: 0.0,
+            confidence: 1.0,
+            is_flipped: false,
+            residuals: Vec::new(),
+            distortion_map: None,
+        }
+    }
+
+    pub fn offset_at(&self, x: f64, y: f64) -> (f64, f64) {
+        let (mx, my) = self.forward.map(x, y);
+        (mx - x, my - y)
+    }
+
+    pub fn warp(&self, image: &Array2<f32>, rows: usize, cols: usize) -> Array2<f32> {
+        self.warp_fill(image, rows, cols, 0.0)
+    }
+
+    pub fn warp_fill(&self, image: &Array2<f32>, rows: usize, cols: usize, fill: f32) -> Array2<f32> {
+        if matches!(self.forward, FrameMapping::Translation { dx, dy } if dx == 0.0 && dy == 0.0)
+            && image.dim() == (rows, cols)
+        {
+            return image.clone();
+        }
+        warp_mapped_fill(image, &self.forward, rows, cols, fill)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RegistrationSet {
+    pub version: u32,
+    pub method: String,
+    pub reference_index: usize,
+    pub reference_path: String,
+    #[serde(default)]
+    pub reference: ReferenceChoice,
+    pub rows: usize,
+    pub cols: usize,
+    pub frames: Vec<FrameRegistration>,
+}
+
+impl RegistrationSet {
+    pub fn save_json(&self, path: &str) -> Result<()> {
+        let content = serde_json::to_string_pretty(self).context("Failed to serialize registration")?;
+        std::fs::write(path, content).with_context(|| format!("Failed to write {}", path))?;
+        Ok(())
+    }
+
+    pub fn load_json(path: &str) -> Result<Self> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+        let set: Self = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse registration file {}", path))?;
+        if set.version > REGISTRATION_VERSION {
+            bail!("Registration file {} has unsupported version {}", path, set.version);
+        }
+        Ok(set)
+    }
+
+    pub fn frame(&self, path: &str) -> Option<&FrameRegistration> {
+        self.frames.iter().find(|f| f.path == path).or_else(|| {
+            let name = Path::new(path).file_name()?;
+            let mut candidates = self.frames.iter().filter(|f| Path::new(&f.path).file_name() == Some(name));
+            let found = candidates.next()?;
+            candidates.next().is_none().then_some(found)
+        })
+    }
+
+    pub fn frames_for(&self, paths: &[String]) -> Result<Vec<&FrameRegistration>> {
+        paths
+            .iter()
+            .map(|p| self.frame(p).with_context(|| format!("{} is not in the registration file", p)))
+            .collect()
+    }
+
+    pub fn reference_for(&self, paths: &[String]) -> ReferenceChoice {
+        let position = paths
+            .iter()
+            .position(|p| self.frame(p).is_some_and(|f| f.path == self.reference_path));
+        if let Some(index) = position {
+            return ReferenceChoice { index, ..self.reference.clone() };
+        }
+        let (cx, cy) = (self.cols as f64 * 0.5, self.rows as f64 * 0.5);
+        let index = paths
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| self.frame(p).map(|f| (i, f.offset_at(cx, cy))))
+            .min_by(|a, b| a.1 .0.hypot(a.1 .1).total_cmp(&b.1 .0.hypot(b.1 .1)))
+            .map_or(0, |(i, _)| i);
+        ReferenceChoice::fixed(index, "closest frame to the registration reference")
+    }
+
+    pub fn forward_mappings(&self, paths: &[String]) -> Result<Vec<FrameMapping>> {
+        Ok(self.frames_for(paths)?.into_iter().map(|f| f.forward.clone()).collect())
+    }
+
+    pub fn inverse_mappings(&self, paths: &[String]) -> Result<Vec<FrameMapping>> {
+        Ok(self.frames_for(paths)?.into_iter().map(|f| f.inverse.clone()).collect())
+    }
+}
+
+pub fn register_images(
+    images: &[Array2<f32>],
+    paths: &[String],
+    selection: ReferenceSelection,
+    method: AlignMethod,
+    progress: Option<&ProgressHandle>,
+) -> Result<RegistrationSet> {
+    if images.is_empty() {
+        bail!("No frames to register");
+    }
+    if images.len() != paths.len() {
+        bail!("{} frames but {} paths", images.len(), paths.len());
+    }
+
+    let refs: Vec<&Array2<f32>> = images.iter().collect();
+    let choice = select_reference(&refs, selection, &SubframeWeightConfig::default())?;
+    let reference_index = choice.index;
+    log::info!("register reference frame_{}: {}", reference_index, choice.reason);
+
+    let reference = &images[reference_index];
+    let (rows, cols) = reference.dim();
+    let mut frames = Vec::with_capacity(images.len());
+
+    for (i, (image, path)) in images.iter().zip(paths).enumerate() {
+        if let Some(p) = progress {
+            if p.is_cancelled() {
+                return Err(AppError::Cancelled.into());
+            }
+            p.tick_with_stage(&format!("register frame {}/{}", i + 1, images.len()));
+        }
+
+        if i == reference_index {
+            frames.push(FrameRegistration::reference(path));
+            continue;
+        }
+
+        let frame = register_pair(reference, image, method, path);
+        log::info!(
+            "register frame_{}: method={}, stars={}, inliers={}, residual={:.3}px, flipped={}",
+            i, frame.method, frame.matched_stars, frame.inliers, frame.residual_px, frame.is_flipped
+        );
+        frames.push(frame);
+    }
+
+    Ok(RegistrationSet {
+        version: REGISTRATION_VERSION,
+        method: method.to_string(),
+        reference_index,
+        reference_path: paths[reference_index].clone(),
+        reference: choice,
+        rows,
+        cols,
+        frames,
+    })
+}
+
+pub fn register_from_paths(
+    paths: &[String],
+    selection: ReferenceSelection,
+    method: AlignMethod,
+    calibration: Option<&CalibrationConfig>,
+    progress: Option<&ProgressHandle>,
+) -> Result<RegistrationSet> {
+    let (images, _, _) = load_calibrated_frames(paths, calibration)?;
+    register_images(&images, paths, selection, method, progress)
+}
+
+fn register_pair(reference: &Array2<f32>, target: &Array2<f32>, method: AlignMethod, path: &str) -> FrameRegistration {
+    if let Some(model) = DistortionModel::from_align_method(method) {
+        let result = distortion::align_channel_distortion(reference, target, model);
+        return FrameRegistration {
+            path: path.to_string(),
+            method: result.method_label(),
+            confidence: if result.inliers > 0 { 1.0 } else { 0.0 },
+            matched_stars: result.matched_stars,
+            inliers: result.inliers,
+            residual_px: result.residual_px,
+            is_flipped: result.affine.is_flipped,
+            distortion_map: result.model.map(|_| result.distortion_map),
+            residuals: result.residuals,
+            forward: result.forward,
+            inverse: result.inverse,
+        };
+    }
+
+    if method == AlignMethod::Affine {
+        let result = affine::align_channel_affine(reference, target);
+        let inverse = match result.transform.inverse() {
+            Some(inv) if result.method != AffineAlignMethod::Identity => FrameMapping::Affine(inv),
+            _ => FrameMapping::identity(),
+        };
+        return FrameRegistration {
+            path: path.to_string(),
+            forward: FrameMapping::Affine(result.transform),
+            inverse,
+            method: result.method.to_string(),
+            matched_stars: result.matched_stars,
+            inliers: result.inliers,
+            residual_px: result.residual_px,
+            confidence: if result.inliers > 0 { 1.0 } else { 0.0 },
+            is_flipped: result.is_flipped,
+            residuals: Vec::new(),
+            distortion_map: None,
+        };
+    }
+
+    let rows = reference.dim().0.min(target.dim().0);
+    let cols = reference.dim().1.min(target.dim().1);
+    let pc = if reference.dim() == target.dim() {
+        phase_correlation::phase_correlate(reference, target)
+    } else {
+        phase_correlation::phase_correlate(
+            &reference.slice(s![..rows, ..cols]).to_owned(),
+            &target.slice(s![..rows, ..cols]).to_owned(),
+        )
+    };
+    FrameRegistration {
+        path: path.to_string(),
+        forward: FrameMapping::Translation { dx: pc.dx, dy: pc.dy },
+        inverse: FrameMapping::from_offset(pc.dx, pc.dy),
+        method: "phase_correlation".into(),
+        matched_stars: 0,
+        inliers: 0,
+        residual_px: 0.0,
+        confidence: pc.confidence,
+        is_flipped: false,
+        residuals: Vec::new(),
+        distortion_map: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::alignment::affine::AffineTransform;
+
+    fn sample_set() -> RegistrationSet {
+        let t = AffineTransform { a: 0.999, b: -0.02, tx: 3.5, c: 0.02, d: 0.999, ty: -1.25 };
+        RegistrationSet {
+            version: REGISTRATION_VERSION,
+            method: "affine".into(),
+            reference_index: 0,
+            reference_path: "/data/lights/l_001.fits".into(),
+            reference: ReferenceChoice::fixed(0, "selected by user"),
+            rows: 100,
+            cols: 120,
+            frames: vec![
+                FrameRegistration::reference("/data/lights/l_001.fits"),
+                FrameRegistration {
+                    path: "/data/lights/l_002.fits".into(),
+                    forward: FrameMapping::Affine(t),
+                    inverse: FrameMapping::Affine(t.inverse().unwrap()),
+                    method: "affine".into(),
+                    matched_stars: 40,
+                    inliers: 36,
+                    residual_px: 0.31,
+                    confidence: 1.0,
+                    is_flipped: false,
+                    residuals: vec![
+                        StarResidual { x: 12.0, y: 30.5, dx: 0.2, dy: -0.1 },
+                        StarResidual { x: 88.25, y: 64.0, dx: -0.35, dy: 0.4 },
+                    ],
+                    distortion_map: Some(Array2::from_shape_fn((4, 4), |(y, x)| (y * 4 + x) as f32 * 0.1)),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_json_round_trip_preserves_mappings() {
+        let set = sample_set();
+        let path = std::env::temp_dir().join(format!("astroburst_registration_{}.json", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        set.save_json(&path).unwrap();
+        let loaded = RegistrationSet::load_json(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(loaded.frames.len(), 2);
+        assert_eq!(loaded.frames[1].forward.kind(), "affine");
+        let a = set.frames[1].forward.map(17.0, 42.0);
+        let b = loaded.frames[1].forward.map(17.0, 42.0);
+        assert!((a.0 - b.0).abs() < 1e-12 && (a.1 - b.1).abs() < 1e-12);
+
+        let residuals = &loaded.frames[1].residuals;
+        assert_eq!(residuals.len(), 2);
+        assert!((residuals[1].x - 88.25).abs() < 1e-12 && (residuals[1].dy - 0.4).abs() < 1e-12);
+        assert_eq!(loaded.frames[1].distortion_map, set.frames[1].distortion_map);
+        assert!(loaded.frames[0].residuals.is_empty() && loaded.frames[0].distortion_map.is_none());
+    }
+
+    #[test]
+    fn test_frame_lookup_falls_back_to_file_name() {
+        let set = sample_set();
+        let paths = vec!["/moved/l_002.fits".to_string(), "/data/lights/l_001.fits".to_string()];
+        let frames = set.frames_for(&paths).unwrap();
+        assert_eq!(frames[0].inliers, 36);
+        assert_eq!(frames[1].method, "reference");
+        assert!(set.frames_for(&["/data/lights/l_009.fits".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_reference_index_follows_caller_paths() {
+        let mut set = sample_set();
+        let paths = vec!["/moved/l_002.fits".to_string(), "/data/lights/l_001.fits".to_string()];
+        assert_eq!(set.reference_for(&paths).index, 1);
+
+        let far = FrameRegistration {
+            path: "/data/lights/l_003.fits".into(),
+            forward: FrameMapping::from_offset(20.0, 5.0),
+            inverse: FrameMapping::from_offset(-20.0, -5.0),
+            ..FrameRegistration::reference("/data/lights/l_003.fits")
+        };
+        set.frames.push(far);
+        set.reference_index = 0;
+        let without_reference = vec!["/data/lights/l_003.fits".to_string(), "/data/lights/l_002.fits".to_string()];
+        assert_eq!(set.reference_for(&without_reference).index, 1);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::core::imaging::background::solve_linear_system;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ThinPlateSpline {
+    pub origin: (f64, f64),
+    pub norm: f64,
+    pub control: Vec<(f64, f64)>,
+    pub weights_x: Vec<f64>,
+    pub weights_y: Vec<f64>,
+    pub affine_x: [f64; 3],
+    pub affine_y: [f64; 3],
+}
+
+#[inline]
+fn radial(r2: f64) -> f64 {
+    if r2 < 1e-20 {
+        0.0
+    } else {
+        r2 * r2.ln()
+    }
+}
+
+impl ThinPlateSpline {
+    pub fn fit(matches: &[(f64, f64, f64, f64)], regularization: f64) -> Option<Self> {
+        let n = matches.len();
+        if n < 3 {
+            return None;
+        }
+
+        let count = n as f64;
+        let ox = matches.iter().map(|m| m.0).sum::<f64>() / count;
+        let oy = matches.iter().map(|m| m.1).sum::<f64>() / count;
+        let norm = matches
+            .iter()
+            .map(|m| (m.0 - ox).abs().max((m.1 - oy).abs()))
+            .fold(0.0f64, f64::max)
+            .max(1.0);
+        let control: Vec<(f64, f64)> = matches
+            .iter()
+            .map(|m| ((m.0 - ox) / norm, (m.1 - oy) / norm))
+            .collect();
+
+        let size = n + 3;
+        let mut system = vec![0.0f64; size * size];
+        for i in 0..n {
+            for j in 0..n {
+                let d2 = (control[i].0 - control[j].0).powi(2) + (control[i].1 - control[j].1).powi(2);
+                system[i * size + j] = radial(d2);
+            }
+            system[i * size + i] += regularization.max(0.0);
+            let p = [1.0, control[i].0, control[i].1];
+            for (k, &pk) in p.iter().enumerate() {
+                system[i * size + n + k] = pk;
+                system[(n + k) * size + i] = pk;
+            }
+        }
+
+        let mut a = system.clone();
+        let mut bx: Vec<f64> = matches.iter().map(|m| m.2).chain([0.0; 3]).collect();
+        solve_linear_system(&mut a, &mut bx, size).ok()?;
+        let mut by: Vec<f64> = matches.iter().map(|m| m.3).chain([0.0; 3]).collect();
+        solve_linear_system(&mut system, &mut by, size).ok()?;
+
+        Some(Self {
+            origin: (ox, oy),
+            norm,
+            control,
+            affine_x: [bx[n], bx[n + 1], bx[n + 2]],
+            affine_y: [by[n], by[n + 1], by[n + 2]],
+            weights_x: bx[..n].to_vec(),
+            weights_y: by[..n].to_vec(),
+        })
+    }
+
+    pub fn map(&self, x: f64, y: f64) -> (f64, f64) {
+        let u = (x - self.origin.0) / self.norm;
+        let v = (y - self.origin.1) / self.norm;
+        let mut mx = self.affine_x[0] + self.affine_x[1] * u + self.affine_x[2] * v;
+        let mut my = self.affine_y[0] + self.affine_y[1] * u + self.affine_y[2] * v;
+        for ((&(cx, cy), &wx), &wy) in self.control.iter().zip(&self.weights_x).zip(&self.weights_y) {
+            let r = radial((u - cx).powi(2) + (v - cy).powi(2));
+            mx += wx * r;
+            my += wy * r;
+        }
+        (mx, my)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn barrel(x: f64, y: f64) -> (f64, f64) {
+        let (cx, cy) = (250.0, 200.0);
+        let r2 = ((x - cx).powi(2) + (y - cy).powi(2)) / 250.0f64.powi(2);
+        let k = 1.0 + 0.02 * r2;
+        (cx + (x - cx) * k + 3.0, cy + (y - cy) * k - 2.0)
+    }
+
+    #[test]
+    fn test_interpolates_control_points() {
+        let matches: Vec<(f64, f64, f64, f64)> = (0..30)
+            .map(|i| {
+                let x = ((i * 37) % 500) as f64;
+                let y = ((i * 53) % 400) as f64;
+                let (tx, ty) = barrel(x, y);
+                (x, y, tx, ty)
+            })
+            .collect();
+        let tps = ThinPlateSpline::fit(&matches, 0.0).unwrap();
+        for &(x, y, tx, ty) in &matches {
+            let (mx, my) = tps.map(x, y);
+            assert!((mx - tx).abs() < 1e-6 && (my - ty).abs() < 1e-6);
+        }
+        let (mx, my) = tps.map(260.0, 190.0);
+        let (ex, ey) = barrel(260.0, 190.0);
+        assert!((mx - ex).abs() < 0.2 && (my - ey).abs() < 0.2, "({}, {}) vs ({}, {})", mx, my, ex, ey);
+    }
+}
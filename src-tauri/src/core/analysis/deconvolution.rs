@@ -32,7 +32,7 @@ pub fn generate_gaussian_psf(size: usize, sigma: f32) -> Array2<f32> {
     psf
 }
 
-struct FftConvolver {
+pub(crate) struct FftConvolver {
     rows: usize,
     cols: usize,
     engine: FftEngine2D<f32>,
@@ -41,7 +41,7 @@ struct FftConvolver {
 }
 
 impl FftConvolver {
-    fn new(rows: usize, cols: usize, psf: &Array2<f32>) -> Self {
+    pub(crate) fn new(rows: usize, cols: usize, psf: &Array2<f32>) -> Self {
         let engine = FftEngine2D::<f32>::from_padded_dims(
             rows, cols, psf.nrows() - 1, psf.ncols() - 1,
         );
@@ -113,11 +113,11 @@ impl FftConvolver {
         self.inverse_2d(&mut buf)
     }
 
-    fn convolve_psf(&self, image: &Array2<f32>) -> Array2<f32> {
+    pub(crate) fn convolve_psf(&self, image: &Array2<f32>) -> Array2<f32> {
         self.convolve_with_freq(image, &self.psf_freq)
     }
 
-    fn convolve_psf_transpose(&self, image: &Array2<f32>) -> Array2<f32> {
+    pub(crate) fn convolve_psf_transpose(&self, image: &Array2<f32>) -> Array2<f32> {
         self.convolve_with_freq(image, &self.psf_conj_freq)
     }
 }
@@ -220,7 +220,7 @@ pub fn richardson_lucy(
     })
 }
 
-fn apply_deringing(estimate: &mut Array2<f32>, original: &Array2<f32>, threshold: f32) {
+pub(crate) fn apply_deringing(estimate: &mut Array2<f32>, original: &Array2<f32>, threshold: f32) {
     let cols = estimate.ncols();
     let orig_slice = original.as_slice().unwrap();
     estimate
@@ -1,5 +1,7 @@
 pub mod confidence;
 pub mod deconvolution;
 pub mod fft;
+pub mod psf_model;
+pub mod spatial_deconv;
 pub mod star_detection;
 pub mod subframe;
@@ -0,0 +1,273 @@
+use std::fs::File;
+
+use anyhow::{bail, Context, Result};
+use ndarray::{Array2, Zip};
+use rustfft::num_complex::Complex;
+
+use crate::core::analysis::deconvolution::FftConvolver;
+use crate::core::analysis::spatial_deconv::kernel_fwhm;
+use crate::core::astrometry::wcs::WcsTransform;
+use crate::infra::fits::reader::{extract_image_mmap_by_index, list_extensions};
+use crate::math::fft::FftEngine2D;
+use crate::types::header::HduHeader;
+
+const PSF_SCALE_KEYS: &[&str] = &["PIXELSCL", "PIXSCALE", "PIXSCAL"];
+const OVERSAMPLE_KEYS: &[&str] = &["OVERSAMP", "OVERSAMPLE"];
+const MAX_PSF_SIZE: usize = 1025;
+const TAPER_START: f64 = 0.3;
+const TAPER_END: f64 = 0.5;
+
+pub struct PsfModel {
+    pub kernel: Array2<f32>,
+    pub pixel_scale: Option<f64>,
+    pub oversampling: Option<f64>,
+}
+
+fn positive_key(header: &HduHeader, keys: &[&str]) -> Option<f64> {
+    keys.iter()
+        .filter_map(|k| header.get_f64(k))
+        .find(|v| v.is_finite() && *v > 0.0)
+}
+
+pub fn image_pixel_scale(header: &HduHeader) -> Option<f64> {
+    WcsTransform::from_header(header)
+        .ok()
+        .map(|w| w.pixel_scale_arcsec())
+        .filter(|s| s.is_finite() && *s > 0.0)
+        .or_else(|| positive_key(header, &["PIXAR_A2"]).map(f64::sqrt))
+}
+
+fn normalized(mut kernel: Array2<f32>) -> Array2<f32> {
+    kernel.mapv_inplace(|v| if v.is_finite() { v } else { 0.0 });
+    let sum: f32 = kernel.sum();
+    if sum != 0.0 {
+        kernel /= sum;
+    }
+    kernel
+}
+
+fn centroid(psf: &Array2<f32>) -> (f64, f64) {
+    let (mut total, mut sy, mut sx) = (0.0f64, 0.0f64, 0.0f64);
+    for ((y, x), &v) in psf.indexed_iter() {
+        let w = v.max(0.0) as f64;
+        total += w;
+        sy += w * y as f64;
+        sx += w * x as f64;
+    }
+    if total > 0.0 {
+        (sy / total, sx / total)
+    } else {
+        ((psf.nrows() - 1) as f64 / 2.0, (psf.ncols() - 1) as f64 / 2.0)
+    }
+}
+
+fn bilinear(psf: &Array2<f32>, y: f64, x: f64) -> f64 {
+    let (rows, cols) = psf.dim();
+    if y < 0.0 || x < 0.0 || y > (rows - 1) as f64 || x > (cols - 1) as f64 {
+        return 0.0;
+    }
+    let (y0, x0) = (y.floor() as usize, x.floor() as usize);
+    let (y1, x1) = ((y0 + 1).min(rows - 1), (x0 + 1).min(cols - 1));
+    let (fy, fx) = (y - y0 as f64, x - x0 as f64);
+    let top = psf[[y0, x0]] as f64 * (1.0 - fx) + psf[[y0, x1]] as f64 * fx;
+    let bottom = psf[[y1, x0]] as f64 * (1.0 - fx) + psf[[y1, x1]] as f64 * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+pub fn resample_psf(psf: &Array2<f32>, factor: f64) -> Result<Array2<f32>> {
+    if !(factor > 0.0 && factor.is_finite()) {
+        bail!("PSF resampling factor must be positive, got {}", factor);
+    }
+    let (rows, cols) = psf.dim();
+    if rows == 0 || cols == 0 {
+        bail!("PSF is empty");
+    }
+    let radius = ((rows.min(cols) - 1) as f64 / 2.0 * factor).floor() as usize;
+    let size = 2 * radius + 1;
+    if size > MAX_PSF_SIZE {
+        bail!("Resampled PSF would be {}x{} pixels, larger than {}", size, size, MAX_PSF_SIZE);
+    }
+    let (cy, cx) = centroid(psf);
+    let step = 1.0 / factor;
+    let sub = (step.ceil() as usize * 2).max(2);
+
+    let out = Array2::from_shape_fn((size, size), |(oy, ox)| {
+        let py = cy + (oy as f64 - radius as f64) * step;
+        let px = cx + (ox as f64 - radius as f64) * step;
+        let mut sum = 0.0;
+        for i in 0..sub {
+            let y = py + ((i as f64 + 0.5) / sub as f64 - 0.5) * step;
+            for j in 0..sub {
+                let x = px + ((j as f64 + 0.5) / sub as f64 - 0.5) * step;
+                sum += bilinear(psf, y, x);
+            }
+        }
+        sum as f32
+    });
+    Ok(normalized(out))
+}
+
+impl PsfModel {
+    pub fn from_header(kernel: Array2<f32>, header: &HduHeader) -> Self {
+        Self {
+            kernel: normalized(kernel),
+            pixel_scale: positive_key(header, PSF_SCALE_KEYS),
+            oversampling: positive_key(header, OVERSAMPLE_KEYS),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to open {}", path))?;
+        let index = list_extensions(&file)?
+            .iter()
+            .position(|h| h.has_data)
+            .with_context(|| format!("No PSF image data in {}", path))?;
+        let result = extract_image_mmap_by_index(&file, index).with_context(|| format!("Failed to load PSF {}", path))?;
+        Ok(Self::from_header(result.image, &result.header))
+    }
+
+    pub fn scale_factor(&self, image_scale: Option<f64>) -> f64 {
+        match (self.pixel_scale, image_scale, self.oversampling) {
+            (Some(psf), Some(image), _) => psf / image,
+            (_, _, Some(oversampling)) => 1.0 / oversampling,
+            _ => 1.0,
+        }
+    }
+
+    pub fn resampled(&self, image_scale: Option<f64>) -> Result<Array2<f32>> {
+        let factor = self.scale_factor(image_scale);
+        if (factor - 1.0).abs() < 1e-6 && self.kernel.nrows() % 2 == 1 && self.kernel.ncols() % 2 == 1 {
+            return Ok(self.kernel.clone());
+        }
+        resample_psf(&self.kernel, factor)
+    }
+}
+
+fn centered_buffer(kernel: &Array2<f32>, n: usize, engine: &FftEngine2D<f32>) -> Vec<Complex<f32>> {
+    let mut buf = engine.alloc_buffer();
+    let (ky, kx) = (kernel.nrows() / 2, kernel.ncols() / 2);
+    for ((y, x), &v) in kernel.indexed_iter() {
+        let dy = (y as isize - ky as isize).rem_euclid(n as isize) as usize;
+        let dx = (x as isize - kx as isize).rem_euclid(n as isize) as usize;
+        buf[dy * n + dx] = Complex::new(v, 0.0);
+    }
+    engine.forward_2d(&mut buf);
+    buf
+}
+
+fn frequency(i: usize, n: usize) -> f64 {
+    let k = if i <= n / 2 { i as f64 } else { i as f64 - n as f64 };
+    k / n as f64
+}
+
+fn taper(rho: f64) -> f32 {
+    if rho <= TAPER_START {
+        1.0
+    } else if rho >= TAPER_END {
+        0.0
+    } else {
+        let t = (rho - TAPER_START) / (TAPER_END - TAPER_START);
+        (0.5 * (1.0 + (std::f64::consts::PI * t).cos())) as f32
+    }
+}
+
+pub fn psf_matching_kernel(source: &Array2<f32>, target: &Array2<f32>, regularization: f64) -> Result<Array2<f32>> {
+    if !(regularization > 0.0 && regularization.is_finite()) {
+        bail!("Matching regularization must be positive, got {}", regularization);
+    }
+    let (source, target) = (normalized(source.clone()), normalized(target.clone()));
+    let (fs, ft) = (kernel_fwhm(&source), kernel_fwhm(&target));
+    if ft < fs * 0.99 {
+        bail!(
+            "Target PSF (FWHM {:.2} px) is sharper than the source PSF (FWHM {:.2} px); match the sharper image to the broader PSF",
+            ft,
+            fs
+        );
+    }
+
+    let size = source.dim().0.max(source.dim().1).max(target.dim().0).max(target.dim().1) | 1;
+    let engine = FftEngine2D::<f32>::new(size, size);
+    let s_hat = centered_buffer(&source, size, &engine);
+    let t_hat = centered_buffer(&target, size, &engine);
+    let peak = s_hat.iter().map(|c| c.norm_sqr()).fold(0.0f32, f32::max);
+    let eps = regularization as f32 * peak;
+
+    let mut k_hat: Vec<Complex<f32>> = s_hat
+        .iter()
+        .zip(&t_hat)
+        .enumerate()
+        .map(|(i, (s, t))| {
+            let rho = frequency(i / size, size).hypot(frequency(i % size, size));
+            t * s.conj() / (s.norm_sqr() + eps) * taper(rho)
+        })
+        .collect();
+    engine.inverse_2d(&mut k_hat);
+
+    let half = size / 2;
+    let kernel = Array2::from_shape_fn((size, size), |(y, x)| {
+        let sy = (y as isize - half as isize).rem_euclid(size as isize) as usize;
+        let sx = (x as isize - half as isize).rem_euclid(size as isize) as usize;
+        k_hat[sy * size + sx].re
+    });
+    Ok(normalized(kernel))
+}
+
+pub fn convolve_normalized(image: &Array2<f32>, kernel: &Array2<f32>) -> Array2<f32> {
+    let (rows, cols) = image.dim();
+    let valid = image.mapv(|v| if v.is_finite() { 1.0f32 } else { 0.0 });
+    let data = image.mapv(|v| if v.is_finite() { v } else { 0.0 });
+    let convolver = FftConvolver::new(rows, cols, kernel);
+    let (signal, weight) = rayon::join(|| convolver.convolve_psf(&data), || convolver.convolve_psf(&valid));
+    let mut out = signal;
+    Zip::from(&mut out).and(&weight).and(image).par_for_each(|o, &w, &orig| {
+        *o = if orig.is_finite() && w.abs() > 1e-6 { *o / w } else { orig };
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::analysis::deconvolution::generate_gaussian_psf;
+
+    #[test]
+    fn test_load_oversampled_psf_and_resample() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("webbpsf.fits");
+        let oversampled = generate_gaussian_psf(81, 4.0);
+        let mut header = crate::infra::raster::synthetic_header(81, 81, 1, -32);
+        header.set("OVERSAMP", "4".into());
+        header.set("PIXELSCL", "0.0078".into());
+        crate::infra::fits::writer::write_fits_mono(path.to_str().unwrap(), &oversampled, Some(&header)).unwrap();
+
+        let model = PsfModel::load(path.to_str().unwrap()).unwrap();
+        assert_eq!((model.oversampling, model.pixel_scale), (Some(4.0), Some(0.0078)));
+        assert!((model.scale_factor(Some(0.0312)) - 0.25).abs() < 1e-9);
+        assert!((model.scale_factor(None) - 0.25).abs() < 1e-9);
+
+        let detector = model.resampled(Some(0.0312)).unwrap();
+        assert_eq!(detector.dim(), (21, 21));
+        assert!((detector.sum() - 1.0).abs() < 1e-4);
+        let fwhm = kernel_fwhm(&detector);
+        let expected = 2.3548 * (1.0f64 + 1.0 / 12.0).sqrt();
+        assert!((fwhm - expected).abs() < 0.1 * expected, "{} vs {}", fwhm, expected);
+    }
+
+    #[test]
+    fn test_matching_kernel_reproduces_broad_psf() {
+        let sharp = generate_gaussian_psf(31, 1.2);
+        let broad = generate_gaussian_psf(31, 2.5);
+        let kernel = psf_matching_kernel(&sharp, &broad, 1e-6).unwrap();
+        let matched = convolve_normalized(&sharp, &kernel);
+        let err = matched.iter().zip(broad.iter()).map(|(a, b)| (a - b).abs()).fold(0.0f32, f32::max);
+        assert!(err < 0.02 * broad[[15, 15]], "max error {}", err);
+        assert!((kernel_fwhm(&kernel) - 2.3548 * (2.5f64 * 2.5 - 1.2 * 1.2).sqrt()).abs() < 0.3);
+        assert!(psf_matching_kernel(&broad, &sharp, 1e-6).is_err());
+
+        let mut image = Array2::from_elem((16, 16), 2.0f32);
+        image[[3, 3]] = f32::NAN;
+        let flat = convolve_normalized(&image, &kernel);
+        assert!(flat[[3, 3]].is_nan());
+        assert!(flat.iter().filter(|v| v.is_finite()).all(|v| (v - 2.0).abs() < 1e-3));
+    }
+}
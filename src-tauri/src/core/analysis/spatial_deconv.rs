@@ -0,0 +1,461 @@
+use anyhow::{bail, Result};
+use ndarray::{s, Array2, Zip};
+use rayon::prelude::*;
+
+use crate::core::analysis::deconvolution::{apply_deringing, FftConvolver};
+use crate::core::imaging::psf_estimation::{estimate_psf, psf_to_kernel, PsfEstimationConfig};
+use crate::core::imaging::star_mask::{generate_star_mask, StarMaskConfig};
+use crate::core::imaging::wavelet::{atrous_decompose, atrous_noise_scaling, layer_noise_sigma};
+use crate::infra::progress::ProgressHandle;
+use crate::types::error::AppError;
+use crate::types::image::{DeconvRegularization, SpatialDeconvParams};
+
+const MAX_TILES: usize = 8;
+const MAX_ITERATIONS: usize = 500;
+const MAX_TV_WEIGHT: f64 = 0.1;
+const WAVELET_REG_SCALES: usize = 2;
+const EPSILON: f32 = 1e-6;
+const CONVERGENCE_THRESHOLD: f64 = 1e-6;
+const FWHM_PER_SIGMA: f64 = 2.354_820_045;
+
+pub struct PsfTile {
+    pub psf: Array2<f32>,
+    pub measured: bool,
+    pub fwhm: f64,
+}
+
+pub struct PsfGrid {
+    pub cols: usize,
+    pub rows: usize,
+    pub tiles: Vec<PsfTile>,
+}
+
+pub struct SpatialDeconvResult {
+    pub image: Array2<f32>,
+    pub iterations_run: usize,
+    pub convergence: f64,
+    pub star_mask_coverage: f64,
+    pub elapsed_ms: u64,
+}
+
+fn normalize_kernel(mut psf: Array2<f32>) -> Array2<f32> {
+    psf.mapv_inplace(|v| if v.is_finite() { v.max(0.0) } else { 0.0 });
+    let sum: f32 = psf.sum();
+    if sum > 0.0 {
+        psf /= sum;
+    }
+    psf
+}
+
+pub fn kernel_fwhm(psf: &Array2<f32>) -> f64 {
+    let (rows, cols) = psf.dim();
+    let (cy, cx) = ((rows / 2) as f64, (cols / 2) as f64);
+    let (mut total, mut second) = (0.0f64, 0.0f64);
+    for ((y, x), &v) in psf.indexed_iter() {
+        let (dy, dx) = (y as f64 - cy, x as f64 - cx);
+        total += v as f64;
+        second += v as f64 * (dx * dx + dy * dy);
+    }
+    if total > 0.0 { FWHM_PER_SIGMA * (second / (2.0 * total)).sqrt() } else { 0.0 }
+}
+
+impl PsfGrid {
+    pub fn uniform(psf: Array2<f32>) -> Self {
+        let psf = normalize_kernel(psf);
+        let fwhm = kernel_fwhm(&psf);
+        Self { cols: 1, rows: 1, tiles: vec![PsfTile { psf, measured: false, fwhm }] }
+    }
+
+    pub fn measured_count(&self) -> usize {
+        self.tiles.iter().filter(|t| t.measured).count()
+    }
+}
+
+fn tile_bounds(n: usize, tiles: usize, k: usize) -> (usize, usize) {
+    (k * n / tiles, (k + 1) * n / tiles)
+}
+
+pub fn measure_psf_grid(
+    image: &Array2<f32>,
+    [nx, ny]: [usize; 2],
+    config: &PsfEstimationConfig,
+    fallback: &Array2<f32>,
+) -> PsfGrid {
+    let (rows, cols) = image.dim();
+    let (nx, ny) = (nx.clamp(1, MAX_TILES), ny.clamp(1, MAX_TILES));
+    let margin = config.cutout_radius + 1;
+    let tile_config = PsfEstimationConfig {
+        edge_margin: margin,
+        max_center_distance_fraction: 1.0,
+        ..config.clone()
+    };
+    let fallback = normalize_kernel(fallback.clone());
+    let fallback_fwhm = kernel_fwhm(&fallback);
+
+    let tiles = (0..nx * ny)
+        .into_par_iter()
+        .map(|k| {
+            let (y0, y1) = tile_bounds(rows, ny, k / nx);
+            let (x0, x1) = tile_bounds(cols, nx, k % nx);
+            let (cy0, cy1) = (y0.saturating_sub(margin), (y1 + margin).min(rows));
+            let (cx0, cx1) = (x0.saturating_sub(margin), (x1 + margin).min(cols));
+            let large_enough = cy1 - cy0 > 2 * margin + 2 && cx1 - cx0 > 2 * margin + 2;
+            let crop = image.slice(s![cy0..cy1, cx0..cx1]).mapv(|v| if v.is_finite() { v } else { 0.0 });
+            match large_enough.then(|| estimate_psf(&crop, &tile_config)) {
+                Some(Ok(result)) => PsfTile {
+                    psf: normalize_kernel(psf_to_kernel(&result)),
+                    measured: true,
+                    fwhm: result.average_fwhm,
+                },
+                _ => PsfTile { psf: fallback.clone(), measured: false, fwhm: fallback_fwhm },
+            }
+        })
+        .collect();
+
+    PsfGrid { cols: nx, rows: ny, tiles }
+}
+
+fn axis_weights(n: usize, tiles: usize, k: usize) -> Vec<f32> {
+    let center = |i: usize| (i as f64 + 0.5) * n as f64 / tiles as f64 - 0.5;
+    let c = center(k);
+    (0..n)
+        .map(|p| {
+            let p = p as f64;
+            let w = if p <= c {
+                if k == 0 { 1.0 } else { (p - center(k - 1)) / (c - center(k - 1)) }
+            } else if k + 1 == tiles {
+                1.0
+            } else {
+                (center(k + 1) - p) / (center(k + 1) - c)
+            };
+            w.clamp(0.0, 1.0) as f32
+        })
+        .collect()
+}
+
+fn support(weights: &[f32]) -> (usize, usize) {
+    let first = weights.iter().position(|&w| w > 0.0).unwrap_or(0);
+    let last = weights.iter().rposition(|&w| w > 0.0).unwrap_or(0);
+    (first, last + 1)
+}
+
+struct TileOperator {
+    region: [usize; 4],
+    support: [usize; 4],
+    weights: Array2<f32>,
+    convolver: FftConvolver,
+}
+
+pub(crate) struct VariantOperator {
+    rows: usize,
+    cols: usize,
+    tiles: Vec<TileOperator>,
+}
+
+impl VariantOperator {
+    pub(crate) fn new(grid: &PsfGrid, rows: usize, cols: usize) -> Self {
+        let tiles = grid
+            .tiles
+            .par_iter()
+            .enumerate()
+            .map(|(k, tile)| {
+                let wy = axis_weights(rows, grid.rows, k / grid.cols);
+                let wx = axis_weights(cols, grid.cols, k % grid.cols);
+                let ((sy0, sy1), (sx0, sx1)) = (support(&wy), support(&wx));
+                let (ph, pw) = (tile.psf.nrows() / 2, tile.psf.ncols() / 2);
+                let region = [sy0.saturating_sub(ph), (sy1 + ph).min(rows), sx0.saturating_sub(pw), (sx1 + pw).min(cols)];
+                let weights = Array2::from_shape_fn((sy1 - sy0, sx1 - sx0), |(y, x)| wy[sy0 + y] * wx[sx0 + x]);
+                let convolver = FftConvolver::new(region[1] - region[0], region[3] - region[2], &tile.psf);
+                TileOperator { region, support: [sy0, sy1, sx0, sx1], weights, convolver }
+            })
+            .collect();
+        Self { rows, cols, tiles }
+    }
+
+    pub(crate) fn forward(&self, x: &Array2<f32>) -> Array2<f32> {
+        let parts: Vec<Array2<f32>> = self
+            .tiles
+            .par_iter()
+            .map(|t| {
+                let [y0, y1, x0, x1] = t.region;
+                let [sy0, sy1, sx0, sx1] = t.support;
+                let mut buf = Array2::zeros((y1 - y0, x1 - x0));
+                Zip::from(buf.slice_mut(s![sy0 - y0..sy1 - y0, sx0 - x0..sx1 - x0]))
+                    .and(x.slice(s![sy0..sy1, sx0..sx1]))
+                    .and(&t.weights)
+                    .for_each(|b, &v, &w| *b = v * w);
+                t.convolver.convolve_psf(&buf)
+            })
+            .collect();
+        let mut out = Array2::zeros((self.rows, self.cols));
+        for (t, part) in self.tiles.iter().zip(parts) {
+            let [y0, y1, x0, x1] = t.region;
+            out.slice_mut(s![y0..y1, x0..x1]).zip_mut_with(&part, |o, &p| *o += p);
+        }
+        out
+    }
+
+    pub(crate) fn transpose(&self, y: &Array2<f32>) -> Array2<f32> {
+        let parts: Vec<Array2<f32>> = self
+            .tiles
+            .par_iter()
+            .map(|t| {
+                let [y0, y1, x0, x1] = t.region;
+                let [sy0, sy1, sx0, sx1] = t.support;
+                let corr = t.convolver.convolve_psf_transpose(&y.slice(s![y0..y1, x0..x1]).to_owned());
+                &corr.slice(s![sy0 - y0..sy1 - y0, sx0 - x0..sx1 - x0]) * &t.weights
+            })
+            .collect();
+        let mut out = Array2::zeros((self.rows, self.cols));
+        for (t, part) in self.tiles.iter().zip(parts) {
+            let [sy0, sy1, sx0, sx1] = t.support;
+            out.slice_mut(s![sy0..sy1, sx0..sx1]).zip_mut_with(&part, |o, &p| *o += p);
+        }
+        out
+    }
+}
+
+fn validate(params: &SpatialDeconvParams) -> Result<()> {
+    if !(1..=MAX_ITERATIONS).contains(&params.iterations) {
+        bail!("Iterations must be within [1, {}], got {}", MAX_ITERATIONS, params.iterations);
+    }
+    if params.tile_grid.iter().any(|n| !(1..=MAX_TILES).contains(n)) {
+        bail!("Tile grid must be within 1x1 and {}x{}, got {:?}", MAX_TILES, MAX_TILES, params.tile_grid);
+    }
+    if !(0.0..=MAX_TV_WEIGHT).contains(&params.tv_weight) {
+        bail!("TV weight must be within [0, {}], got {}", MAX_TV_WEIGHT, params.tv_weight);
+    }
+    if !(params.wavelet_threshold >= 0.0 && params.wavelet_threshold.is_finite()) {
+        bail!("Wavelet threshold must be non-negative, got {}", params.wavelet_threshold);
+    }
+    if !(params.deringing_threshold > 0.0 && params.deringing_threshold <= 1.0) {
+        bail!("Deringing threshold must be within (0, 1], got {}", params.deringing_threshold);
+    }
+    Ok(())
+}
+
+fn tv_divergence(u: &Array2<f32>, eps: f32) -> Array2<f32> {
+    let (rows, cols) = u.dim();
+    let src = u.as_slice().expect("contiguous");
+    let normal = |y: usize, x: usize| -> (f32, f32) {
+        let i = y * cols + x;
+        let gx = if x + 1 < cols { src[i + 1] - src[i] } else { 0.0 };
+        let gy = if y + 1 < rows { src[i + cols] - src[i] } else { 0.0 };
+        let norm = (gx * gx + gy * gy + eps * eps).sqrt();
+        (gx / norm, gy / norm)
+    };
+    let mut out = Array2::zeros((rows, cols));
+    out.as_slice_mut()
+        .expect("contiguous")
+        .par_chunks_mut(cols)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, v) in row.iter_mut().enumerate() {
+                let (nx, ny) = normal(y, x);
+                let west = if x > 0 { normal(y, x - 1).0 } else { 0.0 };
+                let north = if y > 0 { normal(y - 1, x).1 } else { 0.0 };
+                let east_edge = if x + 1 < cols { nx } else { 0.0 };
+                let south_edge = if y + 1 < rows { ny } else { 0.0 };
+                *v = east_edge - west + south_edge - north;
+            }
+        });
+    out
+}
+
+fn wavelet_regularize(estimate: &mut Array2<f32>, base_sigma: f64, k: f64) {
+    let layers = atrous_decompose(estimate, WAVELET_REG_SCALES);
+    let mut out = layers.residual;
+    for (j, detail) in layers.details.iter().enumerate() {
+        let t = (k * base_sigma * atrous_noise_scaling(j) / atrous_noise_scaling(0)) as f32;
+        Zip::from(&mut out).and(detail).par_for_each(|o, &d| *o += d.signum() * (d.abs() - t).max(0.0));
+    }
+    Zip::from(estimate).and(&out).par_for_each(|e, &v| *e = v.max(0.0));
+}
+
+fn local_deringing(estimate: &mut Array2<f32>, original: &Array2<f32>, mask: &Array2<f32>, threshold: f32) {
+    Zip::from(estimate).and(original).and(mask).par_for_each(|e, &orig, &m| {
+        let lower = (orig * (1.0 - threshold)).max(0.0);
+        if *e < lower {
+            *e += m * (lower - *e);
+        }
+    });
+}
+
+pub fn richardson_lucy_variant(
+    image: &Array2<f32>,
+    grid: &PsfGrid,
+    params: &SpatialDeconvParams,
+    progress: Option<&ProgressHandle>,
+) -> Result<SpatialDeconvResult> {
+    validate(params)?;
+    if grid.tiles.len() != grid.rows * grid.cols || grid.tiles.is_empty() {
+        bail!("PSF grid has {} tiles for a {}x{} layout", grid.tiles.len(), grid.cols, grid.rows);
+    }
+    let start = std::time::Instant::now();
+    let (rows, cols) = image.dim();
+    let data = image.mapv(|v| if v.is_finite() { v.max(0.0) } else { 0.0 });
+    let operator = VariantOperator::new(grid, rows, cols);
+    let norm = operator.transpose(&Array2::ones((rows, cols))).mapv(|v| v.max(EPSILON));
+
+    let star_mask = if params.star_mask {
+        generate_star_mask(&data, &StarMaskConfig::default()).ok().map(|m| m.mask)
+    } else {
+        None
+    };
+    let star_mask_coverage = star_mask
+        .as_ref()
+        .map(|m| m.iter().filter(|&&v| v > 0.0).count() as f64 / m.len().max(1) as f64)
+        .unwrap_or(0.0);
+
+    let peak = data.iter().cloned().fold(0.0f32, f32::max);
+    let tv_eps = (peak * 1e-3).max(EPSILON);
+    let base_sigma = match params.regularization {
+        DeconvRegularization::Wavelet => {
+            let finest = atrous_decompose(&data, 1);
+            layer_noise_sigma(finest.details[0].as_slice().expect("contiguous"))
+        }
+        _ => 0.0,
+    };
+    let threshold = params.deringing_threshold as f32;
+
+    let mut estimate = data.clone();
+    let mut convergence = f64::MAX;
+    let mut iterations_run = 0;
+
+    for iter in 0..params.iterations {
+        if let Some(p) = progress {
+            if p.is_cancelled() {
+                return Err(AppError::Cancelled.into());
+            }
+        }
+
+        let blurred = operator.forward(&estimate);
+        let ratio = Zip::from(&blurred).and(&data).par_map_collect(|&c, &d| d / (c + EPSILON));
+        let correction = operator.transpose(&ratio);
+
+        let mut denom = norm.clone();
+        if params.regularization == DeconvRegularization::Tv && params.tv_weight > 0.0 {
+            let lambda = params.tv_weight as f32;
+            Zip::from(&mut denom).and(&tv_divergence(&estimate, tv_eps)).par_for_each(|d, &div| {
+                *d = (*d - lambda * div).max(0.5 * *d);
+            });
+        }
+
+        let previous = estimate.clone();
+        Zip::from(&mut estimate).and(&correction).and(&denom).par_for_each(|e, &c, &d| {
+            *e = (*e * c / d).max(0.0);
+        });
+
+        if params.regularization == DeconvRegularization::Wavelet && params.wavelet_threshold > 0.0 {
+            wavelet_regularize(&mut estimate, base_sigma, params.wavelet_threshold);
+        }
+        if params.deringing {
+            apply_deringing(&mut estimate, &data, threshold);
+        } else if let Some(mask) = &star_mask {
+            local_deringing(&mut estimate, &data, mask, threshold);
+        }
+
+        let sum_sq: f64 = Zip::from(&estimate)
+            .and(&previous)
+            .par_map_collect(|&a, &b| ((a - b) as f64).powi(2))
+            .sum();
+        iterations_run = iter + 1;
+        convergence = (sum_sq / (rows * cols).max(1) as f64).sqrt();
+
+        if let Some(p) = progress {
+            p.tick_with_stage(&format!(
+                "iteration {}/{} (delta: {:.2e})",
+                iterations_run, params.iterations, convergence
+            ));
+        }
+        if convergence < CONVERGENCE_THRESHOLD && iterations_run >= 3 {
+            break;
+        }
+    }
+
+    Zip::from(&mut estimate).and(image).par_for_each(|e, &orig| {
+        if !orig.is_finite() {
+            *e = orig;
+        }
+    });
+
+    Ok(SpatialDeconvResult {
+        image: estimate,
+        iterations_run,
+        convergence,
+        star_mask_coverage,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::analysis::deconvolution::generate_gaussian_psf;
+
+    fn star_field(rows: usize, cols: usize, sigma_at: impl Fn(usize) -> f32) -> Array2<f32> {
+        let mut image = Array2::from_elem((rows, cols), 0.01f32);
+        for sy in (8..rows - 4).step_by(16) {
+            for sx in (8..cols - 4).step_by(16) {
+                let sigma = sigma_at(sx);
+                for ((y, x), v) in image.indexed_iter_mut() {
+                    let (dy, dx) = (y as f32 - sy as f32, x as f32 - sx as f32);
+                    *v += 0.8 * (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp();
+                }
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn test_tiled_operator_matches_uniform_and_is_adjoint() {
+        let (rows, cols) = (40, 52);
+        let psf = generate_gaussian_psf(7, 1.5);
+        let x = Array2::from_shape_fn((rows, cols), |(y, x)| ((y * 31 + x * 17) % 23) as f32 / 23.0);
+        let y = Array2::from_shape_fn((rows, cols), |(y, x)| ((y * 7 + x * 3) % 13) as f32 / 13.0);
+
+        let single = VariantOperator::new(&PsfGrid::uniform(psf.clone()), rows, cols);
+        let tiles = (0..6).map(|_| PsfTile { psf: psf.clone(), measured: false, fwhm: 0.0 }).collect();
+        let tiled = VariantOperator::new(&PsfGrid { cols: 3, rows: 2, tiles }, rows, cols);
+        let (a, b) = (single.forward(&x), tiled.forward(&x));
+        assert!(a.iter().zip(b.iter()).all(|(p, q)| (p - q).abs() < 1e-4));
+
+        let other = generate_gaussian_psf(9, 2.5);
+        let mixed = PsfGrid {
+            cols: 2,
+            rows: 1,
+            tiles: vec![PsfTile { psf: psf.clone(), measured: false, fwhm: 0.0 }, PsfTile { psf: other, measured: false, fwhm: 0.0 }],
+        };
+        let op = VariantOperator::new(&mixed, rows, cols);
+        let lhs: f32 = (&op.forward(&x) * &y).sum();
+        let rhs: f32 = (&x * &op.transpose(&y)).sum();
+        assert!((lhs - rhs).abs() < 1e-3 * lhs.abs(), "{} vs {}", lhs, rhs);
+        assert!((kernel_fwhm(&generate_gaussian_psf(31, 2.0)) - 2.0 * FWHM_PER_SIGMA).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_variant_deconvolution_sharpens_field_dependent_blur() {
+        let (rows, cols) = (64, 96);
+        let sharp = star_field(rows, cols, |_| 0.6);
+        let grid = PsfGrid {
+            cols: 2,
+            rows: 1,
+            tiles: vec![
+                PsfTile { psf: generate_gaussian_psf(9, 1.0), measured: false, fwhm: 0.0 },
+                PsfTile { psf: generate_gaussian_psf(13, 2.0), measured: false, fwhm: 0.0 },
+            ],
+        };
+        let blurred = VariantOperator::new(&grid, rows, cols).forward(&sharp);
+        let params = SpatialDeconvParams { iterations: 40, regularization: DeconvRegularization::Tv, ..Default::default() };
+        let result = richardson_lucy_variant(&blurred, &grid, &params, None).unwrap();
+        for (sy, sx) in [(24, 8), (24, 88)] {
+            assert!(result.image[[sy, sx]] > blurred[[sy, sx]] * 1.3, "{} vs {}", result.image[[sy, sx]], blurred[[sy, sx]]);
+        }
+        assert!(result.image.iter().all(|v| v.is_finite() && *v >= 0.0));
+
+        let flat = measure_psf_grid(&Array2::zeros((rows, cols)), [2, 2], &PsfEstimationConfig::default(), &generate_gaussian_psf(7, 1.0));
+        assert_eq!((flat.tiles.len(), flat.measured_count()), (4, 0));
+        let bad = SpatialDeconvParams { tile_grid: [0, 2], ..Default::default() };
+        assert!(richardson_lucy_variant(&blurred, &grid, &bad, None).is_err());
+    }
+}
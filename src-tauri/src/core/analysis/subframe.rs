@@ -1,7 +1,10 @@
+use anyhow::{bail, Result};
 use ndarray::Array2;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use super::star_detection::{detect_stars, DetectedStar};
+use crate::types::stacking::{ReferenceChoice, ReferenceSelection};
 
 const DETECTION_SIGMA: f64 = 4.0;
 const MIN_STARS_FOR_METRICS: usize = 5;
@@ -148,6 +151,69 @@ pub fn normalize_weights(metrics: &mut [SubframeMetrics]) {
     }
 }
 
+pub fn select_reference(
+    images: &[&Array2<f32>],
+    selection: ReferenceSelection,
+    config: &SubframeWeightConfig,
+) -> Result<ReferenceChoice> {
+    if images.is_empty() {
+        bail!("No frames to choose a reference from");
+    }
+    if let ReferenceSelection::Index(index) = selection {
+        if index >= images.len() {
+            bail!("Reference frame {} out of range for {} frames", index, images.len());
+        }
+        return Ok(ReferenceChoice::fixed(index, "selected by user"));
+    }
+
+    let metrics: Vec<SubframeMetrics> = images
+        .par_iter()
+        .enumerate()
+        .map(|(i, img)| analyze_subframe(img, &format!("frame_{}", i), config))
+        .collect();
+    Ok(choose_reference(&metrics))
+}
+
+pub fn choose_reference(metrics: &[SubframeMetrics]) -> ReferenceChoice {
+    let best = metrics
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.star_count >= MIN_STARS_FOR_METRICS)
+        .max_by(|(ia, a), (ib, b)| {
+            a.accepted
+                .cmp(&b.accepted)
+                .then(a.weight.partial_cmp(&b.weight).unwrap_or(std::cmp::Ordering::Equal))
+                .then(a.star_count.cmp(&b.star_count))
+                .then(ib.cmp(ia))
+        });
+
+    let Some((index, m)) = best else {
+        log::info!("Reference: no frame has {} stars for metrics, using frame_0", MIN_STARS_FOR_METRICS);
+        return ReferenceChoice {
+            automatic: true,
+            ..ReferenceChoice::fixed(0, "no frame had enough stars to score; using the first frame")
+        };
+    };
+
+    let accepted = metrics.iter().filter(|m| m.accepted).count();
+    let reason = format!(
+        "best score of {} frames ({} accepted): FWHM {:.2}px, eccentricity {:.2}, SNR {:.1}, {} stars, noise {:.4}",
+        metrics.len(), accepted, m.median_fwhm, m.median_eccentricity, m.median_snr, m.star_count, m.noise_ratio
+    );
+    log::info!("Reference: {} -> {}", m.file_name, reason);
+
+    ReferenceChoice {
+        index,
+        automatic: true,
+        score: m.weight,
+        star_count: m.star_count,
+        fwhm: m.median_fwhm,
+        snr: m.median_snr,
+        noise_ratio: m.noise_ratio,
+        reason,
+    }
+}
+
 fn median_of(stars: &[DetectedStar], f: impl Fn(&DetectedStar) -> f64) -> f64 {
     let mut vals: Vec<f64> = stars.iter().map(|s| f(s)).filter(|v| v.is_finite()).collect();
     if vals.is_empty() {
@@ -189,6 +255,38 @@ mod tests {
         assert!(w1 > w2, "w1={} should be > w2={}", w1, w2);
     }
 
+    fn metrics(name: &str, stars: usize, fwhm: f64, snr: f64, accepted: bool) -> SubframeMetrics {
+        let cfg = SubframeWeightConfig::default();
+        SubframeMetrics {
+            file_path: name.into(), file_name: name.into(),
+            star_count: stars, median_fwhm: fwhm, median_eccentricity: 0.2,
+            median_snr: snr, background_median: 0.1, background_sigma: 0.01,
+            noise_ratio: 0.1, weight: compute_weight(fwhm, 0.2, snr, 0.1, &cfg), accepted,
+        }
+    }
+
+    #[test]
+    fn test_choose_reference_prefers_sharp_accepted_frame() {
+        let frames = vec![
+            metrics("blurry", 40, 6.5, 30.0, true),
+            metrics("sharp", 60, 2.1, 28.0, true),
+            metrics("clouded", 3, 1.5, 50.0, false),
+            metrics("trailed", 80, 1.8, 40.0, false),
+        ];
+        let choice = choose_reference(&frames);
+        assert_eq!(choice.index, 1);
+        assert!(choice.automatic);
+        assert!((choice.fwhm - 2.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_choose_reference_falls_back_to_first() {
+        let frames = vec![metrics("a", 0, 0.0, 0.0, false), metrics("b", 2, 0.0, 0.0, false)];
+        let choice = choose_reference(&frames);
+        assert_eq!(choice.index, 0);
+        assert_eq!(choice.star_count, 0);
+    }
+
     #[test]
     fn test_normalize_weights() {
         let mut metrics = vec![
@@ -3,7 +3,7 @@ use rayon::prelude::*;
 
 use crate::types::header::HduHeader;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct WcsTransform {
     crpix1: f64,
     crpix2: f64,
@@ -16,7 +16,7 @@ pub struct WcsTransform {
     ra0_rad: f64,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum Projection {
     Tan,
     Sin,
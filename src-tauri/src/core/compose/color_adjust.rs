@@ -0,0 +1,239 @@
+use anyhow::{bail, Result};
+use ndarray::{Array2, Zip};
+use rayon::prelude::*;
+
+use crate::core::compose::colorspace::{rgb_to_space, space_to_rgb};
+use crate::core::imaging::curves::{fritsch_carlson_tangents, hermite_eval};
+use crate::types::image::{ColorAdjustParams, ColorSpace, HueShiftRange, LuminanceMask};
+
+const CURVE_LUT_SIZE: usize = 1024;
+const MAX_GAIN: f64 = 10.0;
+
+struct GainCurve {
+    lut: Option<Vec<f32>>,
+}
+
+impl GainCurve {
+    fn new(points: &[[f64; 2]], periodic: bool) -> Result<Self> {
+        if points.iter().any(|p| !p[0].is_finite() || !(0.0..=MAX_GAIN).contains(&p[1])) {
+            bail!("Saturation curve gains must be within [0, {}]", MAX_GAIN);
+        }
+        let mut pts: Vec<(f64, f64)> = points
+            .iter()
+            .map(|p| {
+                let x = if periodic { p[0].rem_euclid(360.0) / 360.0 } else { p[0].clamp(0.0, 1.0) };
+                (x, p[1])
+            })
+            .collect();
+        pts.sort_by(|a, b| a.0.total_cmp(&b.0));
+        pts.dedup_by(|a, b| (a.0 - b.0).abs() < 1e-9);
+
+        match pts.len() {
+            0 => return Ok(Self { lut: None }),
+            1 => return Ok(Self { lut: Some(vec![pts[0].1 as f32; CURVE_LUT_SIZE]) }),
+            _ => {}
+        }
+        if periodic {
+            let (first, last) = (pts[0], pts[pts.len() - 1]);
+            pts.insert(0, (last.0 - 1.0, last.1));
+            pts.push((first.0 + 1.0, first.1));
+        }
+
+        let tangents = fritsch_carlson_tangents(&pts);
+        let n = pts.len();
+        let lut = (0..CURVE_LUT_SIZE)
+            .map(|i| {
+                let x = i as f64 / (CURVE_LUT_SIZE - 1) as f64;
+                hermite_eval(&pts, &tangents, n, x).max(0.0) as f32
+            })
+            .collect();
+        Ok(Self { lut: Some(lut) })
+    }
+
+    #[inline]
+    fn eval(&self, x: f32) -> f32 {
+        match &self.lut {
+            Some(lut) => lut[((x.clamp(0.0, 1.0) * (CURVE_LUT_SIZE - 1) as f32).round() as usize).min(CURVE_LUT_SIZE - 1)],
+            None => 1.0,
+        }
+    }
+}
+
+#[inline]
+fn hue_distance(a: f32, b: f32) -> f32 {
+    let d = (a - b).rem_euclid(360.0);
+    d.min(360.0 - d)
+}
+
+#[inline]
+fn hue_shift_at(hue: f32, shifts: &[HueShiftRange]) -> f32 {
+    shifts
+        .iter()
+        .map(|s| {
+            let half = (s.width * 0.5) as f32;
+            let d = hue_distance(hue, s.center as f32);
+            if d >= half {
+                0.0
+            } else {
+                s.shift as f32 * 0.5 * (1.0 + (std::f32::consts::PI * d / half).cos())
+            }
+        })
+        .sum()
+}
+
+#[inline]
+pub(crate) fn mask_weight(lightness: f32, mask: Option<&LuminanceMask>) -> f32 {
+    let Some(m) = mask else {
+        return 1.0;
+    };
+    let (low, high) = (m.low as f32, m.high as f32);
+    let w = if high <= low {
+        if lightness >= low { 1.0 } else { 0.0 }
+    } else {
+        let t = ((lightness - low) / (high - low)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    };
+    if m.invert { 1.0 - w } else { w }
+}
+
+fn finite_max(channels: [&Array2<f32>; 3]) -> f32 {
+    channels
+        .iter()
+        .map(|c| {
+            c.as_slice()
+                .expect("contiguous")
+                .par_iter()
+                .filter(|v| v.is_finite())
+                .cloned()
+                .reduce(|| f32::MIN, f32::max)
+        })
+        .fold(f32::MIN, f32::max)
+}
+
+pub fn adjust_color(
+    r: &Array2<f32>,
+    g: &Array2<f32>,
+    b: &Array2<f32>,
+    params: &ColorAdjustParams,
+) -> Result<(Array2<f32>, Array2<f32>, Array2<f32>)> {
+    let dim = r.dim();
+    if g.dim() != dim || b.dim() != dim {
+        bail!("Channel dimensions differ: R {:?}, G {:?}, B {:?}", dim, g.dim(), b.dim());
+    }
+    if !(0.0..=MAX_GAIN).contains(&params.saturation) {
+        bail!("Saturation must be within [0, {}], got {}", MAX_GAIN, params.saturation);
+    }
+    if params.hue_shifts.iter().any(|s| !(s.width > 0.0 && s.width <= 360.0 && s.shift.is_finite())) {
+        bail!("Hue shift ranges need a width within (0, 360] degrees");
+    }
+    if let Some(m) = &params.luminance_mask {
+        if !(0.0..=1.0).contains(&m.low) || !(0.0..=1.0).contains(&m.high) {
+            bail!("Luminance mask bounds must be within [0, 1]");
+        }
+    }
+
+    let hue_curve = GainCurve::new(&params.hue_saturation, true)?;
+    let lightness_curve = GainCurve::new(&params.lightness_saturation, false)?;
+    let saturation = params.saturation as f32;
+    let shifts = params.hue_shifts.as_slice();
+    let mask = params.luminance_mask.as_ref();
+    let space = if params.space == ColorSpace::Hsv { ColorSpace::Hsv } else { ColorSpace::Lch };
+
+    let scale = finite_max([r, g, b]).max(1.0);
+    let inv_scale = 1.0 / scale;
+
+    let mut ro = r.clone();
+    let mut go = g.clone();
+    let mut bo = b.clone();
+    Zip::from(&mut ro).and(&mut go).and(&mut bo).par_for_each(|rv, gv, bv| {
+        if !(rv.is_finite() && gv.is_finite() && bv.is_finite()) {
+            return;
+        }
+        let mut v = rgb_to_space([*rv * inv_scale, *gv * inv_scale, *bv * inv_scale], space);
+        let (hue, lightness) = match space {
+            ColorSpace::Hsv => (v[0], v[2]),
+            _ => (v[2], v[0] / 100.0),
+        };
+        let m = mask_weight(lightness, mask);
+        if m <= 0.0 {
+            return;
+        }
+        let gain = saturation * hue_curve.eval(hue / 360.0) * lightness_curve.eval(lightness);
+        let gain = 1.0 + m * (gain - 1.0);
+        let shift = m * hue_shift_at(hue, shifts);
+
+        match space {
+            ColorSpace::Hsv => {
+                v[0] = (v[0] + shift).rem_euclid(360.0);
+                v[1] = (v[1] * gain).clamp(0.0, 1.0);
+            }
+            _ => {
+                v[1] *= gain;
+                v[2] = (v[2] + shift).rem_euclid(360.0);
+            }
+        }
+        let [nr, ng, nb] = space_to_rgb(v, space);
+        *rv = nr.max(0.0) * scale;
+        *gv = ng.max(0.0) * scale;
+        *bv = nb.max(0.0) * scale;
+    });
+    Ok((ro, go, bo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::compose::colorspace::{lab_to_lch, rgb_to_lab};
+
+    fn pixels(values: &[[f32; 3]]) -> (Array2<f32>, Array2<f32>, Array2<f32>) {
+        let n = values.len();
+        (
+            Array2::from_shape_fn((1, n), |(_, i)| values[i][0]),
+            Array2::from_shape_fn((1, n), |(_, i)| values[i][1]),
+            Array2::from_shape_fn((1, n), |(_, i)| values[i][2]),
+        )
+    }
+
+    fn lch_at(r: &Array2<f32>, g: &Array2<f32>, b: &Array2<f32>, i: usize) -> [f32; 3] {
+        lab_to_lch(rgb_to_lab([r[[0, i]], g[[0, i]], b[[0, i]]]))
+    }
+
+    #[test]
+    fn test_identity_and_neutral_pixels() {
+        let (r, g, b) = pixels(&[[0.2, 0.4, 0.6], [0.5, 0.5, 0.5], [f32::NAN, 0.1, 0.1]]);
+        for space in [ColorSpace::Lch, ColorSpace::Hsv] {
+            let params = ColorAdjustParams { space, ..ColorAdjustParams::default() };
+            let (ro, go, bo) = adjust_color(&r, &g, &b, &params).unwrap();
+            assert!((ro[[0, 0]] - 0.2).abs() < 1e-4 && (go[[0, 0]] - 0.4).abs() < 1e-4 && (bo[[0, 0]] - 0.6).abs() < 1e-4);
+            assert!(ro[[0, 2]].is_nan());
+
+            let boosted = ColorAdjustParams { space, saturation: 2.0, ..ColorAdjustParams::default() };
+            let (ro, go, bo) = adjust_color(&r, &g, &b, &boosted).unwrap();
+            for v in [ro[[0, 1]], go[[0, 1]], bo[[0, 1]]] {
+                assert!((v - 0.5).abs() < 1e-3, "{:?} gray drifted to {}", space, v);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hue_selective_saturation_and_shift() {
+        let (r, g, b) = pixels(&[[0.6, 0.1, 0.1], [0.1, 0.15, 0.6], [0.02, 0.003, 0.003]]);
+        let params = ColorAdjustParams {
+            hue_saturation: vec![[40.0, 1.5], [130.0, 1.0], [200.0, 1.0], [300.0, 1.0]],
+            hue_shifts: vec![HueShiftRange { center: 40.0, width: 60.0, shift: 20.0 }],
+            luminance_mask: Some(LuminanceMask { low: 0.1, high: 0.2, invert: false }),
+            ..ColorAdjustParams::default()
+        };
+        let (ro, go, bo) = adjust_color(&r, &g, &b, &params).unwrap();
+
+        let (red_in, red_out) = (lch_at(&r, &g, &b, 0), lch_at(&ro, &go, &bo, 0));
+        assert!(red_out[1] > red_in[1] * 1.3, "chroma {} -> {}", red_in[1], red_out[1]);
+        assert!(red_out[2] - red_in[2] > 10.0, "hue {} -> {}", red_in[2], red_out[2]);
+
+        let (blue_in, blue_out) = (lch_at(&r, &g, &b, 1), lch_at(&ro, &go, &bo, 1));
+        assert!((blue_in[1] - blue_out[1]).abs() < 0.05 && (blue_in[2] - blue_out[2]).abs() < 0.05);
+
+        assert!((ro[[0, 2]] - 0.02).abs() < 1e-6, "masked dark pixel changed");
+        assert!(adjust_color(&r, &g, &b, &ColorAdjustParams { saturation: -1.0, ..params }).is_err());
+    }
+}
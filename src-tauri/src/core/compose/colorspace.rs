@@ -0,0 +1,344 @@
+use anyhow::{bail, Result};
+use ndarray::{Array2, Zip};
+
+use crate::types::image::{ColorSpace, RgbColorSpace};
+
+const WHITE_D65: [f32; 3] = [0.950_47, 1.0, 1.088_83];
+const LAB_EPSILON: f32 = 216.0 / 24389.0;
+const LAB_KAPPA: f32 = 24389.0 / 27.0;
+
+const WHITE_D65_XY: [f64; 2] = [0.3127, 0.3290];
+const ADOBE_GAMMA: f32 = 563.0 / 256.0;
+
+const RGB_TO_XYZ: [[f32; 3]; 3] = [
+    [0.412_456_4, 0.357_576_1, 0.180_437_5],
+    [0.212_672_9, 0.715_152_2, 0.072_175_0],
+    [0.019_333_9, 0.119_192, 0.950_304_1],
+];
+
+const XYZ_TO_RGB: [[f32; 3]; 3] = [
+    [3.240_454_2, -1.537_138_5, -0.498_531_4],
+    [-0.969_266, 1.876_010_8, 0.041_556_0],
+    [0.055_643_4, -0.204_025_9, 1.057_225_2],
+];
+
+#[inline]
+fn mat3(m: &[[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+    [
+        m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+        m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+        m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+    ]
+}
+
+#[inline]
+fn lab_f(t: f32) -> f32 {
+    if t > LAB_EPSILON { t.cbrt() } else { (LAB_KAPPA * t + 16.0) / 116.0 }
+}
+
+#[inline]
+fn lab_f_inv(f: f32) -> f32 {
+    let f3 = f * f * f;
+    if f3 > LAB_EPSILON { f3 } else { (116.0 * f - 16.0) / LAB_KAPPA }
+}
+
+#[inline]
+pub fn rgb_to_lab(rgb: [f32; 3]) -> [f32; 3] {
+    let xyz = mat3(&RGB_TO_XYZ, rgb);
+    let fx = lab_f(xyz[0] / WHITE_D65[0]);
+    let fy = lab_f(xyz[1] / WHITE_D65[1]);
+    let fz = lab_f(xyz[2] / WHITE_D65[2]);
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+#[inline]
+pub fn lab_to_rgb(lab: [f32; 3]) -> [f32; 3] {
+    let fy = (lab[0] + 16.0) / 116.0;
+    let fx = fy + lab[1] / 500.0;
+    let fz = fy - lab[2] / 200.0;
+    let xyz = [
+        lab_f_inv(fx) * WHITE_D65[0],
+        lab_f_inv(fy) * WHITE_D65[1],
+        lab_f_inv(fz) * WHITE_D65[2],
+    ];
+    mat3(&XYZ_TO_RGB, xyz)
+}
+
+#[inline]
+pub fn lab_to_lch(lab: [f32; 3]) -> [f32; 3] {
+    let c = lab[1].hypot(lab[2]);
+    let h = lab[2].atan2(lab[1]).to_degrees();
+    [lab[0], c, if h < 0.0 { h + 360.0 } else { h }]
+}
+
+#[inline]
+pub fn lch_to_lab(lch: [f32; 3]) -> [f32; 3] {
+    let (s, c) = lch[2].to_radians().sin_cos();
+    [lch[0], lch[1] * c, lch[1] * s]
+}
+
+#[inline]
+pub fn rgb_to_hsv(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb;
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    if delta <= 0.0 || max <= 0.0 {
+        return [0.0, 0.0, max];
+    }
+    let h = if max == r {
+        60.0 * ((g - b) / delta)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    [if h < 0.0 { h + 360.0 } else { h }, delta / max, max]
+}
+
+#[inline]
+pub fn hsv_to_rgb(hsv: [f32; 3]) -> [f32; 3] {
+    let [h, s, v] = hsv;
+    let c = v * s;
+    let hp = h.rem_euclid(360.0) / 60.0;
+    let x = c * (1.0 - (hp % 2.0 - 1.0).abs());
+    let m = v - c;
+    let (r, g, b) = match hp as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    [r + m, g + m, b + m]
+}
+
+#[inline]
+pub fn rgb_to_space(rgb: [f32; 3], space: ColorSpace) -> [f32; 3] {
+    match space {
+        ColorSpace::Lab => rgb_to_lab(rgb),
+        ColorSpace::Lch => lab_to_lch(rgb_to_lab(rgb)),
+        ColorSpace::Hsv => rgb_to_hsv(rgb),
+    }
+}
+
+#[inline]
+pub fn space_to_rgb(v: [f32; 3], space: ColorSpace) -> [f32; 3] {
+    match space {
+        ColorSpace::Lab => lab_to_rgb(v),
+        ColorSpace::Lch => lab_to_rgb(lch_to_lab(v)),
+        ColorSpace::Hsv => hsv_to_rgb(v),
+    }
+}
+
+pub fn component_names(space: ColorSpace) -> [&'static str; 3] {
+    match space {
+        ColorSpace::Lab => ["L", "a", "b"],
+        ColorSpace::Lch => ["L", "C", "h"],
+        ColorSpace::Hsv => ["H", "S", "V"],
+    }
+}
+
+fn convert_planes(
+    p0: &Array2<f32>,
+    p1: &Array2<f32>,
+    p2: &Array2<f32>,
+    f: impl Fn([f32; 3]) -> [f32; 3] + Sync,
+) -> Result<[Array2<f32>; 3]> {
+    let dim = p0.dim();
+    if p1.dim() != dim || p2.dim() != dim {
+        bail!("Channel dimensions differ: {:?}, {:?}, {:?}", dim, p1.dim(), p2.dim());
+    }
+    let mut o0 = Array2::zeros(dim);
+    let mut o1 = Array2::zeros(dim);
+    let mut o2 = Array2::zeros(dim);
+    Zip::from(&mut o0)
+        .and(&mut o1)
+        .and(&mut o2)
+        .and(p0)
+        .and(p1)
+        .and(p2)
+        .par_for_each(|a, b, c, &x, &y, &z| {
+            if !(x.is_finite() && y.is_finite() && z.is_finite()) {
+                return;
+            }
+            [*a, *b, *c] = f([x, y, z]);
+        });
+    Ok([o0, o1, o2])
+}
+
+pub fn rgb_to_planes(r: &Array2<f32>, g: &Array2<f32>, b: &Array2<f32>, space: ColorSpace) -> Result<[Array2<f32>; 3]> {
+    convert_planes(r, g, b, |rgb| rgb_to_space(rgb, space))
+}
+
+pub fn planes_to_rgb(p0: &Array2<f32>, p1: &Array2<f32>, p2: &Array2<f32>, space: ColorSpace) -> Result<[Array2<f32>; 3]> {
+    convert_planes(p0, p1, p2, |v| space_to_rgb(v, space))
+}
+
+pub fn primaries(space: RgbColorSpace) -> [[f64; 2]; 3] {
+    match space {
+        RgbColorSpace::LinearSrgb | RgbColorSpace::Srgb => [[0.64, 0.33], [0.30, 0.60], [0.15, 0.06]],
+        RgbColorSpace::DisplayP3 => [[0.680, 0.320], [0.265, 0.690], [0.150, 0.060]],
+        RgbColorSpace::AdobeRgb => [[0.64, 0.33], [0.21, 0.71], [0.15, 0.06]],
+        RgbColorSpace::Rec2020 => [[0.708, 0.292], [0.170, 0.797], [0.131, 0.046]],
+    }
+}
+
+fn xy_to_xyz(xy: [f64; 2]) -> [f64; 3] {
+    [xy[0] / xy[1], 1.0, (1.0 - xy[0] - xy[1]) / xy[1]]
+}
+
+pub(crate) fn mul3(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+pub(crate) fn invert3(m: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let c = |r0: usize, c0: usize, r1: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let cof = [
+        [c(1, 1, 2, 2), -c(1, 0, 2, 2), c(1, 0, 2, 1)],
+        [-c(0, 1, 2, 2), c(0, 0, 2, 2), -c(0, 0, 2, 1)],
+        [c(0, 1, 1, 2), -c(0, 0, 1, 2), c(0, 0, 1, 1)],
+    ];
+    let det = m[0][0] * cof[0][0] + m[0][1] * cof[0][1] + m[0][2] * cof[0][2];
+    let mut inv = [[0.0; 3]; 3];
+    for (i, row) in inv.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = cof[j][i] / det;
+        }
+    }
+    inv
+}
+
+pub fn white_point_d65() -> [f64; 3] {
+    xy_to_xyz(WHITE_D65_XY)
+}
+
+pub fn rgb_to_xyz_matrix(space: RgbColorSpace) -> [[f64; 3]; 3] {
+    let p = primaries(space).map(xy_to_xyz);
+    let cols = [[p[0][0], p[1][0], p[2][0]], [p[0][1], p[1][1], p[2][1]], [p[0][2], p[1][2], p[2][2]]];
+    let w = white_point_d65();
+    let inv = invert3(&cols);
+    let s: Vec<f64> = (0..3).map(|i| (0..3).map(|k| inv[i][k] * w[k]).sum()).collect();
+    cols.map(|row| [row[0] * s[0], row[1] * s[1], row[2] * s[2]])
+}
+
+pub fn conversion_matrix(from: RgbColorSpace, to: RgbColorSpace) -> [[f64; 3]; 3] {
+    mul3(&invert3(&rgb_to_xyz_matrix(to)), &rgb_to_xyz_matrix(from))
+}
+
+#[inline]
+pub fn decode_transfer(space: RgbColorSpace, v: f32) -> f32 {
+    match space {
+        RgbColorSpace::LinearSrgb => v,
+        RgbColorSpace::Srgb | RgbColorSpace::DisplayP3 => {
+            if v <= 0.040_45 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+        }
+        RgbColorSpace::AdobeRgb => v.max(0.0).powf(ADOBE_GAMMA),
+        RgbColorSpace::Rec2020 => {
+            if v < 0.081 { v / 4.5 } else { ((v + 0.099) / 1.099).powf(1.0 / 0.45) }
+        }
+    }
+}
+
+#[inline]
+pub fn encode_transfer(space: RgbColorSpace, v: f32) -> f32 {
+    match space {
+        RgbColorSpace::LinearSrgb => v,
+        RgbColorSpace::Srgb | RgbColorSpace::DisplayP3 => {
+            if v <= 0.003_130_8 { v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 }
+        }
+        RgbColorSpace::AdobeRgb => v.max(0.0).powf(1.0 / ADOBE_GAMMA),
+        RgbColorSpace::Rec2020 => {
+            if v < 0.018 { v * 4.5 } else { 1.099 * v.powf(0.45) - 0.099 }
+        }
+    }
+}
+
+fn matrix_f32(m: [[f64; 3]; 3]) -> [[f32; 3]; 3] {
+    m.map(|row| row.map(|v| v as f32))
+}
+
+pub fn convert_linear_rgb(
+    r: &Array2<f32>,
+    g: &Array2<f32>,
+    b: &Array2<f32>,
+    from: RgbColorSpace,
+    to: RgbColorSpace,
+) -> Result<[Array2<f32>; 3]> {
+    let m = matrix_f32(conversion_matrix(from, to));
+    convert_planes(r, g, b, |rgb| mat3(&m, rgb))
+}
+
+pub fn convert_display_rgb(
+    r: &Array2<f32>,
+    g: &Array2<f32>,
+    b: &Array2<f32>,
+    from: RgbColorSpace,
+    to: RgbColorSpace,
+) -> Result<[Array2<f32>; 3]> {
+    let m = matrix_f32(conversion_matrix(from, to));
+    convert_planes(r, g, b, |rgb| {
+        let linear = rgb.map(|v| decode_transfer(from, v.clamp(0.0, 1.0)));
+        mat3(&m, linear).map(|v| encode_transfer(to, v.clamp(0.0, 1.0)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: [f32; 3], b: [f32; 3], tol: f32) -> bool {
+        a.iter().zip(&b).all(|(x, y)| (x - y).abs() < tol)
+    }
+
+    #[test]
+    fn test_reference_values() {
+        assert!(close(rgb_to_lab([1.0, 1.0, 1.0]), [100.0, 0.0, 0.0], 1e-2));
+        assert!(close(rgb_to_lab([0.0, 0.0, 0.0]), [0.0, 0.0, 0.0], 1e-4));
+        assert!(close(rgb_to_lab([1.0, 0.0, 0.0]), [53.24, 80.09, 67.20], 0.05));
+        assert!(close(rgb_to_hsv([0.0, 0.5, 0.5]), [180.0, 1.0, 0.5], 1e-5));
+        assert!(close(rgb_to_hsv([0.3, 0.3, 0.3]), [0.0, 0.0, 0.3], 1e-6));
+    }
+
+    #[test]
+    fn test_round_trips() {
+        for space in [ColorSpace::Lab, ColorSpace::Lch, ColorSpace::Hsv] {
+            for rgb in [[0.2, 0.5, 0.8], [0.9, 0.1, 0.05], [0.001, 0.002, 0.0005], [0.4, 0.4, 0.4]] {
+                let back = space_to_rgb(rgb_to_space(rgb, space), space);
+                assert!(close(back, rgb, 1e-4), "{:?} {:?} -> {:?}", space, rgb, back);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rgb_space_matrices_and_transfers() {
+        let srgb = rgb_to_xyz_matrix(RgbColorSpace::Srgb);
+        for (row, expected) in srgb.iter().zip(RGB_TO_XYZ.iter()) {
+            for (a, b) in row.iter().zip(expected) {
+                assert!((*a as f32 - b).abs() < 1e-3, "{} vs {}", a, b);
+            }
+        }
+        let to_p3 = conversion_matrix(RgbColorSpace::Srgb, RgbColorSpace::DisplayP3);
+        assert!((to_p3[0][0] - 0.8225).abs() < 1e-3 && (to_p3[0][1] - 0.1774).abs() < 1e-3);
+        let identity = mul3(&to_p3, &conversion_matrix(RgbColorSpace::DisplayP3, RgbColorSpace::Srgb));
+        for (i, row) in identity.iter().enumerate() {
+            for (j, v) in row.iter().enumerate() {
+                assert!((v - if i == j { 1.0 } else { 0.0 }).abs() < 1e-9);
+            }
+        }
+
+        for space in [RgbColorSpace::Srgb, RgbColorSpace::AdobeRgb, RgbColorSpace::Rec2020, RgbColorSpace::LinearSrgb] {
+            for v in [0.0, 0.01, 0.2, 0.5, 1.0f32] {
+                assert!((decode_transfer(space, encode_transfer(space, v)) - v).abs() < 1e-5, "{:?} {}", space, v);
+            }
+        }
+        assert!((encode_transfer(RgbColorSpace::Srgb, 0.214_041) - 0.5).abs() < 1e-4);
+    }
+}
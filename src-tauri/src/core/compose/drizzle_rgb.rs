@@ -153,7 +153,7 @@ use crate::infra::fits::writer as fits_writer;
 use crate::types::stacking::{DrizzleConfig, DrizzleResult};
 
 fn drizzle_channel(paths: &[String], config: &DrizzleConfig) -> Result<DrizzleResult> {
-    drizzle_from_paths(paths, config, None)
+    drizzle_from_paths(paths, config, None, None, None)
 }
 
 pub fn drizzle_rgb(
@@ -275,6 +275,7 @@ pub fn drizzle_rgb(
         frame_count_g: channels.frame_count_g,
         frame_count_b: channels.frame_count_b,
         rejected_pixels: channels.rejected_pixels,
+        references: [&r_result, &g_result, &b_result].map(|r| r.as_ref().map(|r| r.reference.clone())),
         stf_r: processed.stf_r,
         stf_g: processed.stf_g,
         stf_b: processed.stf_b,
@@ -1,4 +1,6 @@
 pub mod channel_blend;
+pub mod color_adjust;
+pub mod colorspace;
 pub mod drizzle_rgb;
 pub mod lrgb;
 pub mod rgb;
@@ -0,0 +1,288 @@
+use std::fs::File;
+use std::sync::Mutex;
+
+use anyhow::{bail, Context, Result};
+use memmap2::Mmap;
+use ndarray::Array2;
+
+use crate::core::cube::lazy::LruFrameCache;
+use crate::infra::fits::reader::create_mmap_random;
+
+const DEFAULT_CACHE_SIZE: usize = 64;
+const BI_RGB: u32 = 0;
+const MONO_FOURCCS: &[&[u8; 4]] = &[b"Y800", b"Y8  ", b"GREY"];
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AviHeader {
+    pub width: usize,
+    pub height: usize,
+    pub bit_count: usize,
+    pub compression: String,
+    pub frame_count: usize,
+    pub frame_interval_s: Option<f64>,
+    pub bottom_up: bool,
+}
+
+impl AviHeader {
+    pub fn planes(&self) -> usize {
+        if self.bit_count >= 24 { 3 } else { 1 }
+    }
+
+    fn stride(&self) -> usize {
+        let row = self.width * self.bit_count / 8;
+        if self.compression == "BI_RGB" { row.div_ceil(4) * 4 } else { row }
+    }
+
+    fn frame_bytes(&self) -> usize {
+        self.stride() * self.height
+    }
+}
+
+pub struct AviSequence {
+    _file: File,
+    mmap: Mmap,
+    pub header: AviHeader,
+    frames: Vec<usize>,
+    cache: Mutex<LruFrameCache>,
+}
+
+struct Chunk {
+    id: [u8; 4],
+    data_start: usize,
+    size: usize,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn chunks(data: &[u8], mut offset: usize, end: usize) -> Vec<Chunk> {
+    let mut out = Vec::new();
+    while offset + 8 <= end {
+        let id: [u8; 4] = data[offset..offset + 4].try_into().unwrap();
+        let size = read_u32(data, offset + 4) as usize;
+        let data_start = offset + 8;
+        if data_start + size > end {
+            break;
+        }
+        out.push(Chunk { id, data_start, size });
+        offset = data_start + size + (size & 1);
+    }
+    out
+}
+
+fn list_type(data: &[u8], chunk: &Chunk) -> Option<[u8; 4]> {
+    (matches!(&chunk.id, b"LIST" | b"RIFF") && chunk.size >= 4)
+        .then(|| data[chunk.data_start..chunk.data_start + 4].try_into().unwrap())
+}
+
+fn parse_stream_format(data: &[u8], hdrl: &Chunk) -> Result<(usize, i32, usize, u32, Option<f64>)> {
+    let inner = chunks(data, hdrl.data_start + 4, hdrl.data_start + hdrl.size);
+    let interval = inner
+        .iter()
+        .find(|c| &c.id == b"avih" && c.size >= 4)
+        .map(|c| read_u32(data, c.data_start) as f64 * 1e-6)
+        .filter(|&s| s > 0.0);
+
+    for strl in inner.iter().filter(|c| list_type(data, c) == Some(*b"strl")) {
+        let parts = chunks(data, strl.data_start + 4, strl.data_start + strl.size);
+        let is_video = parts
+            .iter()
+            .any(|c| &c.id == b"strh" && c.size >= 4 && &data[c.data_start..c.data_start + 4] == b"vids");
+        let Some(strf) = parts.iter().find(|c| &c.id == b"strf" && c.size >= 40) else {
+            continue;
+        };
+        if !is_video {
+            continue;
+        }
+        let s = strf.data_start;
+        let width = i32::from_le_bytes(data[s + 4..s + 8].try_into().unwrap());
+        let height = i32::from_le_bytes(data[s + 8..s + 12].try_into().unwrap());
+        let bit_count = u16::from_le_bytes(data[s + 14..s + 16].try_into().unwrap()) as usize;
+        let compression = read_u32(data, s + 16);
+        return Ok((width.unsigned_abs() as usize, height, bit_count, compression, interval));
+    }
+    bail!("AVI has no video stream format")
+}
+
+impl AviSequence {
+    pub fn open(path: &str) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to open AVI file {}", path))?;
+        let mmap = create_mmap_random(&file).context("mmap failed for AVI file")?;
+        if mmap.len() < 12 || &mmap[..4] != b"RIFF" || &mmap[8..12] != b"AVI " {
+            bail!("Not an AVI file");
+        }
+
+        let riffs: Vec<Chunk> = chunks(&mmap, 0, mmap.len())
+            .into_iter()
+            .filter(|c| matches!(list_type(&mmap, c).as_ref(), Some(b"AVI ") | Some(b"AVIX")))
+            .collect();
+        let first = riffs.first().context("AVI has no RIFF list")?;
+        let hdrl = chunks(&mmap, first.data_start + 4, first.data_start + first.size)
+            .into_iter()
+            .find(|c| list_type(&mmap, c) == Some(*b"hdrl"))
+            .context("AVI has no header list")?;
+
+        let (width, raw_height, bit_count, compression, frame_interval_s) = parse_stream_format(&mmap, &hdrl)?;
+        let fourcc = compression.to_le_bytes();
+        let compression = if compression == BI_RGB {
+            "BI_RGB".to_string()
+        } else if MONO_FOURCCS.contains(&&fourcc) {
+            String::from_utf8_lossy(&fourcc).trim().to_string()
+        } else {
+            bail!("Compressed AVI video ({}) is not supported", String::from_utf8_lossy(&fourcc));
+        };
+        if !matches!(bit_count, 8 | 24 | 32) {
+            bail!("Unsupported AVI bit depth {}", bit_count);
+        }
+
+        let mut header = AviHeader {
+            width,
+            height: raw_height.unsigned_abs() as usize,
+            bit_count,
+            bottom_up: compression == "BI_RGB" && raw_height > 0,
+            compression,
+            frame_count: 0,
+            frame_interval_s,
+        };
+
+        let frame_bytes = header.frame_bytes();
+        let mut frames = Vec::new();
+        for riff in &riffs {
+            for list in chunks(&mmap, riff.data_start + 4, riff.data_start + riff.size) {
+                if list_type(&mmap, &list) != Some(*b"movi") {
+                    continue;
+                }
+                frames.extend(
+                    chunks(&mmap, list.data_start + 4, list.data_start + list.size)
+                        .into_iter()
+                        .filter(|c| matches!(&c.id[2..], b"db" | b"dc") && c.size >= frame_bytes)
+                        .map(|c| c.data_start),
+                );
+            }
+        }
+        if frames.is_empty() {
+            bail!("AVI contains no uncompressed video frames");
+        }
+        header.frame_count = frames.len();
+
+        Ok(Self {
+            _file: file,
+            mmap,
+            header,
+            frames,
+            cache: Mutex::new(LruFrameCache::new(DEFAULT_CACHE_SIZE)),
+        })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.header.frame_count
+    }
+
+    pub fn timestamp(&self, index: usize) -> Option<f64> {
+        self.header.frame_interval_s.map(|dt| index as f64 * dt)
+    }
+
+    pub fn get_planes(&self, index: usize) -> Result<Vec<Array2<f32>>> {
+        let h = &self.header;
+        let Some(&start) = self.frames.get(index) else {
+            bail!("Frame index {} out of range (frames={})", index, h.frame_count);
+        };
+        let (stride, bpp, planes) = (h.stride(), h.bit_count / 8, h.planes());
+        let mut out: Vec<Array2<f32>> = (0..planes).map(|_| Array2::zeros((h.height, h.width))).collect();
+        for y in 0..h.height {
+            let src_row = if h.bottom_up { h.height - 1 - y } else { y };
+            let row = &self.mmap[start + src_row * stride..start + src_row * stride + h.width * bpp];
+            for (x, px) in row.chunks_exact(bpp).enumerate() {
+                if planes == 1 {
+                    out[0][[y, x]] = px[0] as f32;
+                } else {
+                    out[0][[y, x]] = px[2] as f32;
+                    out[1][[y, x]] = px[1] as f32;
+                    out[2][[y, x]] = px[0] as f32;
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn get_frame(&self, index: usize) -> Result<Array2<f32>> {
+        if let Some(frame) = self.cache.lock().unwrap().get(index) {
+            return Ok(frame);
+        }
+        let mut planes = self.get_planes(index)?;
+        let frame = if planes.len() == 1 {
+            planes.pop().unwrap()
+        } else {
+            (&planes[0] + &planes[1] + &planes[2]) / 3.0
+        };
+        self.cache.lock().unwrap().insert(index, frame.clone());
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = id.to_vec();
+        out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        out.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            out.push(0);
+        }
+        out
+    }
+
+    fn list(kind: &[u8; 4], id: &[u8; 4], parts: &[Vec<u8>]) -> Vec<u8> {
+        let mut body = id.to_vec();
+        parts.iter().for_each(|p| body.extend_from_slice(p));
+        chunk(kind, &body)
+    }
+
+    #[test]
+    fn test_reads_bottom_up_bgr_frames() {
+        let (w, h) = (3i32, 2i32);
+        let mut avih = vec![0u8; 56];
+        avih[..4].copy_from_slice(&40_000u32.to_le_bytes());
+        let mut strh = vec![0u8; 56];
+        strh[..4].copy_from_slice(b"vids");
+        let mut strf = vec![0u8; 40];
+        strf[4..8].copy_from_slice(&w.to_le_bytes());
+        strf[8..12].copy_from_slice(&h.to_le_bytes());
+        strf[14..16].copy_from_slice(&24u16.to_le_bytes());
+
+        let frames: Vec<Vec<u8>> = (0..2u8)
+            .map(|f| {
+                let mut data = Vec::new();
+                for row in 0..h as u8 {
+                    for x in 0..w as u8 {
+                        data.extend_from_slice(&[f * 100 + row * 10 + x, 1, 2]);
+                    }
+                    data.extend_from_slice(&[0, 0, 0]);
+                }
+                chunk(b"00db", &data)
+            })
+            .collect();
+
+        let hdrl = list(b"LIST", b"hdrl", &[chunk(b"avih", &avih), list(b"LIST", b"strl", &[chunk(b"strh", &strh), chunk(b"strf", &strf)])]);
+        let movi = list(b"LIST", b"movi", &frames);
+        let riff = list(b"RIFF", b"AVI ", &[hdrl, movi]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.avi");
+        File::create(&path).unwrap().write_all(&riff).unwrap();
+
+        let seq = AviSequence::open(path.to_str().unwrap()).unwrap();
+        assert_eq!(seq.frame_count(), 2);
+        assert_eq!(seq.timestamp(1), Some(0.04));
+        let planes = seq.get_planes(1).unwrap();
+        assert_eq!(planes[0].dim(), (2, 3));
+        assert_eq!(planes[2][[0, 2]], 112.0);
+        assert_eq!(planes[2][[1, 0]], 100.0);
+        assert_eq!(planes[0][[1, 0]], 2.0);
+        assert!(seq.get_frame(2).is_err());
+    }
+}
@@ -1,5 +1,4 @@
 use std::fs;
-use std::path::PathBuf;
 
 use anyhow::{Context, Result};
 use ndarray::{Array2, Array3};
@@ -255,22 +254,8 @@ pub fn process_cube(
     use crate::infra::render::render_grayscale;
     use std::fs::File;
 
-    let (actual_fits_path, _tmp_holder) = if input_path.to_lowercase().ends_with(".zip") {
-        let resolved = crate::infra::fits::dispatcher::resolve_input(std::path::Path::new(input_path))
-            .with_context(|| format!("Failed to resolve ZIP input {}", input_path))?;
-        match resolved {
-            crate::infra::fits::dispatcher::ResolvedInput::ExtractedFromZip { files, _tmp } => {
-                let first = files
-                    .into_iter()
-                    .next()
-                    .context("No .fits in ZIP")?;
-                (first, Some(_tmp))
-            }
-            _ => unreachable!(),
-        }
-    } else {
-        (PathBuf::from(input_path), None)
-    };
+    let (actual_fits_path, _tmp_holder) = crate::infra::fits::dispatcher::resolve_single_image(input_path)
+        .with_context(|| format!("Failed to resolve input {}", input_path))?;
 
     let file = File::open(&actual_fits_path)
         .with_context(|| format!("Failed to open FITS {:?}", actual_fits_path))?;
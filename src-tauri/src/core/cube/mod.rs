@@ -1,2 +1,5 @@
+pub mod avi;
 pub mod eager;
 pub mod lazy;
+pub mod ser;
+pub mod video;
@@ -0,0 +1,283 @@
+use std::fs::File;
+use std::sync::Mutex;
+
+use anyhow::{bail, Context, Result};
+use memmap2::Mmap;
+use ndarray::Array2;
+
+use crate::core::cube::lazy::LruFrameCache;
+use crate::infra::fits::reader::create_mmap_random;
+
+const SER_HEADER_BYTES: usize = 178;
+const SER_FILE_ID: &[u8] = b"LUCAM-RECORDER";
+const TICKS_PER_SECOND: f64 = 10_000_000.0;
+const UNIX_EPOCH_TICKS: i64 = 621_355_968_000_000_000;
+const DEFAULT_CACHE_SIZE: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SerColor {
+    Mono,
+    Bayer,
+    Rgb,
+    Bgr,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SerHeader {
+    pub color_id: i32,
+    pub color: SerColor,
+    pub cfa_pattern: Option<String>,
+    pub little_endian: bool,
+    pub width: usize,
+    pub height: usize,
+    pub bit_depth: usize,
+    pub frame_count: usize,
+    pub observer: String,
+    pub instrument: String,
+    pub telescope: String,
+    pub start_time: Option<f64>,
+    pub start_time_utc: Option<f64>,
+}
+
+impl SerHeader {
+    pub fn planes(&self) -> usize {
+        match self.color {
+            SerColor::Rgb | SerColor::Bgr => 3,
+            _ => 1,
+        }
+    }
+
+    pub fn bytes_per_sample(&self) -> usize {
+        if self.bit_depth > 8 { 2 } else { 1 }
+    }
+
+    pub fn frame_bytes(&self) -> usize {
+        self.width * self.height * self.planes() * self.bytes_per_sample()
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < SER_HEADER_BYTES {
+            bail!("SER header truncated: {} bytes", data.len());
+        }
+        if &data[..SER_FILE_ID.len()] != SER_FILE_ID {
+            bail!("Not a SER file (missing LUCAM-RECORDER signature)");
+        }
+
+        let int = |offset: usize| i32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        let text = |offset: usize| {
+            String::from_utf8_lossy(&data[offset..offset + 40])
+                .trim_end_matches(['\0', ' '])
+                .to_string()
+        };
+        let time = |offset: usize| ticks_to_unix(i64::from_le_bytes(data[offset..offset + 8].try_into().unwrap()));
+
+        let color_id = int(18);
+        let (color, cfa_pattern) = match color_id {
+            0 => (SerColor::Mono, None),
+            8 => (SerColor::Bayer, Some("RGGB")),
+            9 => (SerColor::Bayer, Some("GRBG")),
+            10 => (SerColor::Bayer, Some("GBRG")),
+            11 => (SerColor::Bayer, Some("BGGR")),
+            16 => (SerColor::Bayer, Some("CYYM")),
+            17 => (SerColor::Bayer, Some("YCMY")),
+            18 => (SerColor::Bayer, Some("YMCY")),
+            19 => (SerColor::Bayer, Some("MYYC")),
+            100 => (SerColor::Rgb, None),
+            101 => (SerColor::Bgr, None),
+            other => bail!("Unsupported SER ColorID {}", other),
+        };
+
+        let (width, height, bit_depth, frame_count) = (int(26), int(30), int(34), int(38));
+        if width <= 0 || height <= 0 || frame_count < 0 {
+            bail!("Invalid SER geometry {}x{} with {} frames", width, height, frame_count);
+        }
+        if !(1..=16).contains(&bit_depth) {
+            bail!("Unsupported SER pixel depth {}", bit_depth);
+        }
+
+        Ok(Self {
+            color_id,
+            color,
+            cfa_pattern: cfa_pattern.map(str::to_string),
+            // SER spec says LittleEndian=1 means little-endian, but FireCapture/SharpCap/AutoStakkert write 0 for little-endian; follow the de-facto convention.
+            little_endian: int(22) == 0,
+            width: width as usize,
+            height: height as usize,
+            bit_depth: bit_depth as usize,
+            frame_count: frame_count as usize,
+            observer: text(42),
+            instrument: text(82),
+            telescope: text(122),
+            start_time: time(162),
+            start_time_utc: time(170),
+        })
+    }
+}
+
+fn ticks_to_unix(ticks: i64) -> Option<f64> {
+    (ticks > 0).then(|| (ticks - UNIX_EPOCH_TICKS) as f64 / TICKS_PER_SECOND)
+}
+
+pub struct SerSequence {
+    _file: File,
+    mmap: Mmap,
+    pub header: SerHeader,
+    pub timestamps: Option<Vec<f64>>,
+    cache: Mutex<LruFrameCache>,
+}
+
+impl SerSequence {
+    pub fn open(path: &str) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to open SER file {}", path))?;
+        let mmap = create_mmap_random(&file).context("mmap failed for SER file")?;
+        let mut header = SerHeader::parse(&mmap)?;
+
+        let available = (mmap.len() - SER_HEADER_BYTES) / header.frame_bytes().max(1);
+        if available < header.frame_count {
+            log::warn!(
+                "SER {}: header declares {} frames but only {} are present",
+                path, header.frame_count, available
+            );
+            header.frame_count = available;
+        }
+
+        let trailer_start = SER_HEADER_BYTES + header.frame_count * header.frame_bytes();
+        let timestamps = (mmap.len() >= trailer_start + header.frame_count * 8)
+            .then(|| {
+                mmap[trailer_start..trailer_start + header.frame_count * 8]
+                    .chunks_exact(8)
+                    .map(|b| ticks_to_unix(i64::from_le_bytes(b.try_into().unwrap())))
+                    .collect::<Option<Vec<f64>>>()
+            })
+            .flatten()
+            .filter(|t| !t.is_empty());
+
+        Ok(Self {
+            _file: file,
+            mmap,
+            header,
+            timestamps,
+            cache: Mutex::new(LruFrameCache::new(DEFAULT_CACHE_SIZE)),
+        })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.header.frame_count
+    }
+
+    pub fn timestamp(&self, index: usize) -> Option<f64> {
+        self.timestamps.as_ref().and_then(|t| t.get(index).copied())
+    }
+
+    pub fn get_planes(&self, index: usize) -> Result<Vec<Array2<f32>>> {
+        let h = &self.header;
+        if index >= h.frame_count {
+            bail!("Frame index {} out of range (frames={})", index, h.frame_count);
+        }
+        let start = SER_HEADER_BYTES + index * h.frame_bytes();
+        let raw = &self.mmap[start..start + h.frame_bytes()];
+        let samples = decode_samples(raw, h.bytes_per_sample(), h.little_endian);
+
+        let planes = h.planes();
+        let npix = h.width * h.height;
+        let mut out: Vec<Vec<f32>> = (0..planes).map(|_| Vec::with_capacity(npix)).collect();
+        for px in samples.chunks_exact(planes) {
+            for (p, &v) in px.iter().enumerate() {
+                out[p].push(v);
+            }
+        }
+        if h.color == SerColor::Bgr {
+            out.swap(0, 2);
+        }
+        out.into_iter()
+            .map(|data| Array2::from_shape_vec((h.height, h.width), data).context("Failed to reshape SER frame"))
+            .collect()
+    }
+
+    pub fn get_frame(&self, index: usize) -> Result<Array2<f32>> {
+        if let Some(frame) = self.cache.lock().unwrap().get(index) {
+            return Ok(frame);
+        }
+        let mut planes = self.get_planes(index)?;
+        let frame = if planes.len() == 1 {
+            planes.pop().unwrap()
+        } else {
+            let n = planes.len() as f32;
+            planes.iter().skip(1).fold(planes[0].clone(), |acc, p| acc + p) / n
+        };
+        self.cache.lock().unwrap().insert(index, frame.clone());
+        Ok(frame)
+    }
+}
+
+fn decode_samples(raw: &[u8], bytes_per_sample: usize, little_endian: bool) -> Vec<f32> {
+    match (bytes_per_sample, little_endian) {
+        (1, _) => raw.iter().map(|&b| b as f32).collect(),
+        (_, true) => raw.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]]) as f32).collect(),
+        (_, false) => raw.chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]]) as f32).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_ser(path: &std::path::Path, color_id: i32, depth: i32, frames: &[Vec<u16>], w: i32, h: i32) {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(SER_FILE_ID);
+        for v in [0, color_id, 0, w, h, depth, frames.len() as i32] {
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+        let mut observer = b"observer".to_vec();
+        observer.resize(40, 0);
+        buf.extend_from_slice(&observer);
+        buf.extend_from_slice(&[0u8; 80]);
+        buf.extend_from_slice(&[0u8; 16]);
+        for f in frames {
+            for &v in f {
+                if depth > 8 {
+                    buf.extend_from_slice(&v.to_le_bytes());
+                } else {
+                    buf.push(v as u8);
+                }
+            }
+        }
+        for i in 0..frames.len() as i64 {
+            buf.extend_from_slice(&(UNIX_EPOCH_TICKS + (1_700_000_000 + i) * 10_000_000).to_le_bytes());
+        }
+        File::create(path).unwrap().write_all(&buf).unwrap();
+    }
+
+    #[test]
+    fn test_reads_bayer_frames_and_timestamps() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.ser");
+        let frames: Vec<Vec<u16>> = (0..3).map(|f| (0..12).map(|i| (f * 1000 + i) as u16).collect()).collect();
+        write_ser(&path, 8, 12, &frames, 4, 3);
+
+        let seq = SerSequence::open(path.to_str().unwrap()).unwrap();
+        assert_eq!(seq.frame_count(), 3);
+        assert_eq!(seq.header.cfa_pattern.as_deref(), Some("RGGB"));
+        assert_eq!(seq.header.observer, "observer");
+        let frame = seq.get_frame(2).unwrap();
+        assert_eq!(frame.dim(), (3, 4));
+        assert_eq!(frame[[1, 2]], 2006.0);
+        assert_eq!(seq.timestamp(1), Some(1_700_000_001.0));
+        assert!(seq.get_frame(3).is_err());
+    }
+
+    #[test]
+    fn test_bgr_frames_are_reordered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("color.ser");
+        write_ser(&path, 101, 8, &[vec![10, 20, 30, 40, 50, 60]], 2, 1);
+
+        let seq = SerSequence::open(path.to_str().unwrap()).unwrap();
+        let planes = seq.get_planes(0).unwrap();
+        assert_eq!(planes[0][[0, 0]], 30.0);
+        assert_eq!(planes[2][[0, 1]], 40.0);
+        assert_eq!(seq.get_frame(0).unwrap()[[0, 0]], 20.0);
+    }
+}
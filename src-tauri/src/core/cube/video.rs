@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use ndarray::Array2;
+
+use crate::core::cube::avi::AviSequence;
+use crate::core::cube::ser::{SerColor, SerSequence};
+use crate::types::HduHeader;
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct VideoInfo {
+    pub format: &'static str,
+    pub width: usize,
+    pub height: usize,
+    pub frame_count: usize,
+    pub bit_depth: usize,
+    pub planes: usize,
+    pub cfa_pattern: Option<String>,
+    pub has_timestamps: bool,
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+    pub frame_interval_s: Option<f64>,
+    pub observer: Option<String>,
+    pub instrument: Option<String>,
+    pub telescope: Option<String>,
+}
+
+pub enum VideoSequence {
+    Ser(SerSequence),
+    Avi(AviSequence),
+}
+
+impl VideoSequence {
+    pub fn open(path: &str) -> Result<Self> {
+        let ext = Path::new(path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "ser" => Ok(Self::Ser(SerSequence::open(path)?)),
+            "avi" => Ok(Self::Avi(AviSequence::open(path)?)),
+            _ => bail!("Unsupported video format: {}", path),
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        match self {
+            Self::Ser(s) => s.frame_count(),
+            Self::Avi(a) => a.frame_count(),
+        }
+    }
+
+    pub fn get_frame(&self, index: usize) -> Result<Array2<f32>> {
+        match self {
+            Self::Ser(s) => s.get_frame(index),
+            Self::Avi(a) => a.get_frame(index),
+        }
+    }
+
+    pub fn get_planes(&self, index: usize) -> Result<Vec<Array2<f32>>> {
+        match self {
+            Self::Ser(s) => s.get_planes(index),
+            Self::Avi(a) => a.get_planes(index),
+        }
+    }
+
+    pub fn timestamp(&self, index: usize) -> Option<f64> {
+        match self {
+            Self::Ser(s) => s.timestamp(index),
+            Self::Avi(a) => a.timestamp(index),
+        }
+    }
+
+    pub fn cfa_pattern(&self) -> Option<&str> {
+        match self {
+            Self::Ser(s) => s.header.cfa_pattern.as_deref(),
+            Self::Avi(_) => None,
+        }
+    }
+
+    pub fn info(&self) -> VideoInfo {
+        let count = self.frame_count();
+        match self {
+            Self::Ser(s) => {
+                let h = &s.header;
+                let text = |v: &str| (!v.is_empty()).then(|| v.to_string());
+                let start_time = s.timestamp(0).or(h.start_time_utc);
+                let end_time = count.checked_sub(1).and_then(|i| s.timestamp(i));
+                VideoInfo {
+                    format: "ser",
+                    width: h.width,
+                    height: h.height,
+                    frame_count: count,
+                    bit_depth: h.bit_depth,
+                    planes: h.planes(),
+                    cfa_pattern: h.cfa_pattern.clone(),
+                    has_timestamps: s.timestamps.is_some(),
+                    start_time,
+                    end_time,
+                    frame_interval_s: start_time
+                        .zip(end_time)
+                        .filter(|_| count > 1)
+                        .map(|(a, b)| (b - a) / (count - 1) as f64),
+                    observer: text(&h.observer),
+                    instrument: text(&h.instrument),
+                    telescope: text(&h.telescope),
+                }
+            }
+            Self::Avi(a) => {
+                let h = &a.header;
+                VideoInfo {
+                    format: "avi",
+                    width: h.width,
+                    height: h.height,
+                    frame_count: count,
+                    bit_depth: 8,
+                    planes: h.planes(),
+                    cfa_pattern: None,
+                    has_timestamps: false,
+                    start_time: None,
+                    end_time: None,
+                    frame_interval_s: h.frame_interval_s,
+                    observer: None,
+                    instrument: None,
+                    telescope: None,
+                }
+            }
+        }
+    }
+
+    pub fn frame_header(&self, index: usize) -> HduHeader {
+        let info = self.info();
+        let mut header = HduHeader { cards: Vec::new(), index: HashMap::new() };
+        header.set("SIMPLE", "T".into());
+        header.set("BITPIX", if info.bit_depth > 8 { "16" } else { "8" }.into());
+        header.set("NAXIS", "2".into());
+        header.set("NAXIS1", info.width.to_string());
+        header.set("NAXIS2", info.height.to_string());
+        header.set("FRAMES", info.frame_count.to_string());
+        header.set("FRAMEIDX", index.to_string());
+        if let Some(pattern) = &info.cfa_pattern {
+            header.set("BAYERPAT", pattern.clone());
+        }
+        if matches!(self, Self::Ser(s) if s.header.color != SerColor::Mono) && info.planes == 3 {
+            header.set("COLORSPC", "RGB".into());
+        }
+        if let Some(t) = self.timestamp(index).filter(|_| info.has_timestamps) {
+            header.set("DATE-OBS", unix_to_iso(t));
+        }
+        for (key, value) in [("OBSERVER", &info.observer), ("INSTRUME", &info.instrument), ("TELESCOP", &info.telescope)] {
+            if let Some(v) = value {
+                header.set(key, v.clone());
+            }
+        }
+        header
+    }
+}
+
+pub(crate) fn unix_to_iso(seconds: f64) -> String {
+    let days = (seconds / SECONDS_PER_DAY).floor();
+    let secs = seconds - days * SECONDS_PER_DAY;
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let hour = (secs / 3600.0).floor();
+    let minute = ((secs - hour * 3600.0) / 60.0).floor();
+    let second = secs - hour * 3600.0 - minute * 60.0;
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:06.3}", year, month, day, hour as u32, minute as u32, second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::stacking::comet::parse_iso_datetime;
+
+    #[test]
+    fn test_iso_round_trip() {
+        for t in [0.0, 951_782_400.5, 1_700_000_123.25] {
+            let iso = unix_to_iso(t);
+            let back = parse_iso_datetime(&iso).unwrap();
+            assert!((back - t).abs() < 1e-3, "{} -> {} -> {}", t, iso, back);
+        }
+    }
+}
@@ -414,7 +414,7 @@ fn compute_rms_residual(
     (sum_sq / samples.len() as f64).sqrt()
 }
 
-fn solve_linear_system(a: &mut [f64], b: &mut [f64], n: usize) -> Result<()> {
+pub(crate) fn solve_linear_system(a: &mut [f64], b: &mut [f64], n: usize) -> Result<()> {
     for col in 0..n {
         let mut max_row = col;
         let mut max_val = a[col * n + col].abs();
@@ -1,6 +1,7 @@
 use ndarray::{Array2, Array3};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use crate::core::stacking::dark_scaling::{scale_for_light, DarkScaling, FrameExposure};
 use crate::math::median::f32_cmp;
 use crate::types::constants::MAD_TO_SIGMA;
 #[derive(Debug, Clone)]
@@ -8,11 +9,15 @@ pub struct CalibrationMasters {
     pub dark: Option<Array2<f32>>,
     pub flat: Option<Array2<f32>>,
     pub bias: Option<Array2<f32>>,
+    pub dark_exposure: Option<FrameExposure>,
+    pub dark_exposure_ratio: f32,
+    pub dark_scaling: DarkScaling,
 }
 
 #[derive(Debug, Clone)]
 pub struct ChannelInput {
     pub lights: Vec<Array2<f32>>,
+    pub exposures: Vec<FrameExposure>,
     pub label: String,
 }
 
@@ -53,6 +58,7 @@ pub struct BatchPipelineResult {
     pub master_channels: Vec<(String, Array2<f32>)>,
     pub rgb: Option<Array3<f32>>,
     pub stats: BatchPipelineStats,
+    pub dark_scales: Vec<Vec<f32>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,8 +78,28 @@ pub struct BatchChannelStats {
     pub stddev: f64,
 }
 
+pub fn light_dark_scale(
+    light: &Array2<f32>,
+    exposure: Option<&FrameExposure>,
+    masters: &CalibrationMasters,
+) -> f32 {
+    match &masters.dark {
+        Some(dark) if dark.dim() == light.dim() => scale_for_light(
+            light,
+            &exposure.copied().unwrap_or_default(),
+            dark,
+            masters.bias.as_ref().filter(|b| b.dim() == light.dim()),
+            &masters.dark_exposure.unwrap_or_default(),
+            &masters.dark_scaling,
+            masters.dark_exposure_ratio,
+        ),
+        _ => masters.dark_exposure_ratio,
+    }
+}
+
 pub fn calibrate_light(
     light: &Array2<f32>,
+    dark_scale: f32,
     masters: &CalibrationMasters,
 ) -> Array2<f32> {
     let (rows, cols) = light.dim();
@@ -99,7 +125,7 @@ pub fn calibrate_light(
             }
             if dark_ok {
                 if let Some(d) = dark_slice {
-                    v -= d[i];
+                    v -= d[i] * dark_scale;
                 }
             }
             if flat_ok {
@@ -110,7 +136,7 @@ pub fn calibrate_light(
                     }
                 }
             }
-            if v < 0.0 { 0.0 } else { v }
+            v
         })
         .collect();
 
@@ -149,13 +175,19 @@ pub fn run_batch_pipeline(
     };
 
     let mut master_channels: Vec<(String, Array2<f32>)> = Vec::new();
+    let mut dark_scales: Vec<Vec<f32>> = Vec::new();
 
     for channel in &channels {
-        let calibrated: Vec<Array2<f32>> = channel
+        let (calibrated, scales): (Vec<Array2<f32>>, Vec<f32>) = channel
             .lights
             .par_iter()
-            .map(|l| calibrate_light(l, masters))
-            .collect();
+            .enumerate()
+            .map(|(i, l)| {
+                let scale = light_dark_scale(l, channel.exposures.get(i), masters);
+                (calibrate_light(l, scale, masters), scale)
+            })
+            .unzip();
+        dark_scales.push(scales);
 
         let normalized = if config.stack.normalize_before_stack {
             normalize_frames(&calibrated)
@@ -190,6 +222,7 @@ pub fn run_batch_pipeline(
         master_channels,
         rgb,
         stats: pipeline_stats,
+        dark_scales,
     })
 }
 
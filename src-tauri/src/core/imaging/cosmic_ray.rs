@@ -0,0 +1,354 @@
+use anyhow::{bail, Result};
+use ndarray::Array2;
+use rayon::prelude::*;
+
+use crate::infra::progress::ProgressHandle;
+use crate::math::median::median_f32_mut;
+use crate::types::error::AppError;
+use crate::types::header::HduHeader;
+
+const GAIN_KEYS: &[&str] = &["GAIN", "EGAIN", "CCDGAIN"];
+const READ_NOISE_KEYS: &[&str] = &["RDNOISE", "READNOIS", "RON", "READNOISE"];
+
+const MIN_NOISE_SIGNAL: f32 = 1e-4;
+const MIN_FINE_STRUCTURE: f32 = 0.01;
+const REPLACE_RADIUS: isize = 2;
+
+#[derive(Debug, Clone)]
+pub struct LaCosmicConfig {
+    pub sigclip: f32,
+    pub sigfrac: f32,
+    pub objlim: f32,
+    pub gain: f32,
+    pub read_noise: f32,
+    pub max_iterations: usize,
+}
+
+impl Default for LaCosmicConfig {
+    fn default() -> Self {
+        Self {
+            sigclip: 4.5,
+            sigfrac: 0.3,
+            objlim: 5.0,
+            gain: 1.0,
+            read_noise: 6.5,
+            max_iterations: 4,
+        }
+    }
+}
+
+impl LaCosmicConfig {
+    pub fn detector_from_header(header: &HduHeader) -> (Option<f32>, Option<f32>) {
+        let lookup = |keys: &[&str]| {
+            keys.iter()
+                .filter_map(|k| header.get_f64(k))
+                .find(|v| v.is_finite() && *v > 0.0)
+                .map(|v| v as f32)
+        };
+        (lookup(GAIN_KEYS), lookup(READ_NOISE_KEYS))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LaCosmicResult {
+    pub cleaned: Array2<f32>,
+    pub mask: Array2<u8>,
+    pub cr_pixels: usize,
+    pub iterations: usize,
+    pub elapsed_ms: u64,
+}
+
+fn median_filter(src: &[f32], rows: usize, cols: usize, radius: usize) -> Vec<f32> {
+    let r = radius as isize;
+    let mut out = vec![0.0f32; rows * cols];
+    out.par_chunks_mut(cols).enumerate().for_each(|(y, row)| {
+        let mut vals = Vec::with_capacity((2 * radius + 1) * (2 * radius + 1));
+        let y0 = (y as isize - r).max(0) as usize;
+        let y1 = (y as isize + r).min(rows as isize - 1) as usize;
+        for (x, slot) in row.iter_mut().enumerate() {
+            let x0 = (x as isize - r).max(0) as usize;
+            let x1 = (x as isize + r).min(cols as isize - 1) as usize;
+            vals.clear();
+            for ny in y0..=y1 {
+                vals.extend(src[ny * cols + x0..=ny * cols + x1].iter().copied().filter(|v| v.is_finite()));
+            }
+            *slot = if vals.is_empty() { 0.0 } else { median_f32_mut(&mut vals) };
+        }
+    });
+    out
+}
+
+fn subsample2(src: &[f32], rows: usize, cols: usize) -> Vec<f32> {
+    let sub_cols = 2 * cols;
+    let mut out = vec![0.0f32; 4 * rows * cols];
+    out.par_chunks_mut(sub_cols).enumerate().for_each(|(y, row)| {
+        let base = (y / 2) * cols;
+        for (x, slot) in row.iter_mut().enumerate() {
+            *slot = src[base + x / 2];
+        }
+    });
+    out
+}
+
+fn rebin2(src: &[f32], rows: usize, cols: usize) -> Vec<f32> {
+    let sub_cols = 2 * cols;
+    let mut out = vec![0.0f32; rows * cols];
+    out.par_chunks_mut(cols).enumerate().for_each(|(y, row)| {
+        let top = 2 * y * sub_cols;
+        let bottom = top + sub_cols;
+        for (x, slot) in row.iter_mut().enumerate() {
+            let sx = 2 * x;
+            *slot = 0.25 * (src[top + sx] + src[top + sx + 1] + src[bottom + sx] + src[bottom + sx + 1]);
+        }
+    });
+    out
+}
+
+fn laplacian_clipped(src: &[f32], rows: usize, cols: usize) -> Vec<f32> {
+    let mut out = vec![0.0f32; rows * cols];
+    out.par_chunks_mut(cols).enumerate().for_each(|(y, row)| {
+        let up_row = y.saturating_sub(1) * cols;
+        let down_row = (y + 1).min(rows - 1) * cols;
+        let base = y * cols;
+        for (x, slot) in row.iter_mut().enumerate() {
+            let up = src[up_row + x];
+            let down = src[down_row + x];
+            let left = src[base + x.saturating_sub(1)];
+            let right = src[base + (x + 1).min(cols - 1)];
+            *slot = (4.0 * src[base + x] - up - down - left - right).max(0.0);
+        }
+    });
+    out
+}
+
+fn laplacian_plus(src: &[f32], rows: usize, cols: usize) -> Vec<f32> {
+    let sub = subsample2(src, rows, cols);
+    let lap = laplacian_clipped(&sub, 2 * rows, 2 * cols);
+    rebin2(&lap, rows, cols)
+}
+
+fn dilate3(mask: &[bool], rows: usize, cols: usize) -> Vec<bool> {
+    let mut out = vec![false; rows * cols];
+    out.par_chunks_mut(cols).enumerate().for_each(|(y, row)| {
+        let y0 = y.saturating_sub(1);
+        let y1 = (y + 1).min(rows - 1);
+        for (x, slot) in row.iter_mut().enumerate() {
+            let x0 = x.saturating_sub(1);
+            let x1 = (x + 1).min(cols - 1);
+            *slot = (y0..=y1).any(|ny| mask[ny * cols + x0..=ny * cols + x1].iter().any(|&m| m));
+        }
+    });
+    out
+}
+
+fn replace_masked(data: &mut [f32], mask: &[bool], rows: usize, cols: usize) {
+    let replacements: Vec<(usize, f32)> = mask
+        .par_iter()
+        .enumerate()
+        .filter(|(_, &m)| m)
+        .filter_map(|(idx, _)| {
+            let y = (idx / cols) as isize;
+            let x = (idx % cols) as isize;
+            let mut vals = Vec::with_capacity(24);
+            for ny in (y - REPLACE_RADIUS).max(0)..=(y + REPLACE_RADIUS).min(rows as isize - 1) {
+                for nx in (x - REPLACE_RADIUS).max(0)..=(x + REPLACE_RADIUS).min(cols as isize - 1) {
+                    let n = ny as usize * cols + nx as usize;
+                    if !mask[n] && data[n].is_finite() {
+                        vals.push(data[n]);
+                    }
+                }
+            }
+            if vals.is_empty() {
+                None
+            } else {
+                Some((idx, median_f32_mut(&mut vals)))
+            }
+        })
+        .collect();
+
+    for (idx, v) in replacements {
+        data[idx] = v;
+    }
+}
+
+pub fn la_cosmic(
+    image: &Array2<f32>,
+    config: &LaCosmicConfig,
+    progress: Option<&ProgressHandle>,
+) -> Result<LaCosmicResult> {
+    let start = std::time::Instant::now();
+    let (rows, cols) = image.dim();
+    if rows < 8 || cols < 8 {
+        bail!("Image too small for cosmic ray detection: {}x{}", cols, rows);
+    }
+    if config.gain <= 0.0 {
+        bail!("Gain must be positive, got {}", config.gain);
+    }
+
+    let max_iterations = config.max_iterations.max(1);
+    if let Some(p) = progress {
+        p.set_total(max_iterations as u64);
+    }
+
+    let npix = rows * cols;
+    let gain = config.gain;
+    let rn2 = config.read_noise * config.read_noise;
+    let mut data = image.as_slice().expect("contiguous").to_vec();
+    let mut cr_mask = vec![false; npix];
+    let mut iterations = 0;
+
+    for iter in 0..max_iterations {
+        if let Some(p) = progress {
+            if p.is_cancelled() {
+                return Err(AppError::Cancelled.into());
+            }
+            p.tick_with_stage(&format!("cosmic ray pass {}/{}", iter + 1, max_iterations));
+        }
+        iterations = iter + 1;
+
+        let lplus = laplacian_plus(&data, rows, cols);
+        let med5 = median_filter(&data, rows, cols, 2);
+        let noise: Vec<f32> = med5
+            .par_iter()
+            .map(|&m| (m.max(MIN_NOISE_SIGNAL) * gain + rn2).sqrt() / gain)
+            .collect();
+
+        let s: Vec<f32> = lplus
+            .par_iter()
+            .zip(noise.par_iter())
+            .map(|(&l, &n)| l / (2.0 * n))
+            .collect();
+        let s_med = median_filter(&s, rows, cols, 2);
+        let s_prime: Vec<f32> = s.par_iter().zip(s_med.par_iter()).map(|(&a, &b)| a - b).collect();
+
+        let med3 = median_filter(&data, rows, cols, 1);
+        let med7 = median_filter(&med3, rows, cols, 3);
+        let fine: Vec<f32> = med3
+            .par_iter()
+            .zip(med7.par_iter())
+            .zip(noise.par_iter())
+            .map(|((&a, &b), &n)| ((a - b) / n).max(MIN_FINE_STRUCTURE))
+            .collect();
+
+        let candidates: Vec<bool> = (0..npix)
+            .into_par_iter()
+            .map(|i| s_prime[i] > config.sigclip && s_prime[i] / fine[i] > config.objlim)
+            .collect();
+
+        let grown = dilate3(&candidates, rows, cols);
+        let grown: Vec<bool> = (0..npix)
+            .into_par_iter()
+            .map(|i| grown[i] && s_prime[i] > config.sigclip)
+            .collect();
+        let low = config.sigfrac * config.sigclip;
+        let neighbours = dilate3(&grown, rows, cols);
+        let found: Vec<bool> = (0..npix)
+            .into_par_iter()
+            .map(|i| neighbours[i] && s_prime[i] > low)
+            .collect();
+
+        let mut new_count = 0usize;
+        for (m, &f) in cr_mask.iter_mut().zip(found.iter()) {
+            if f && !*m {
+                *m = true;
+                new_count += 1;
+            }
+        }
+
+        log::debug!("la_cosmic pass {}: {} new cosmic ray pixels", iter + 1, new_count);
+
+        if new_count == 0 {
+            break;
+        }
+
+        replace_masked(&mut data, &cr_mask, rows, cols);
+    }
+
+    let cr_pixels = cr_mask.iter().filter(|&&m| m).count();
+    let mask = Array2::from_shape_vec(
+        (rows, cols),
+        cr_mask.iter().map(|&m| m as u8).collect(),
+    )?;
+    let cleaned = Array2::from_shape_vec((rows, cols), data)?;
+
+    Ok(LaCosmicResult {
+        cleaned,
+        mask,
+        cr_pixels,
+        iterations,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sky(rows: usize, cols: usize) -> Array2<f32> {
+        Array2::from_shape_fn((rows, cols), |(y, x)| {
+            let h = ((y * 7919 + x * 104729) % 1000) as f32 / 1000.0 - 0.5;
+            500.0 + h * 20.0
+        })
+    }
+
+    fn add_star(img: &mut Array2<f32>, cy: f32, cx: f32, amp: f32, sigma: f32) {
+        let (rows, cols) = img.dim();
+        for y in 0..rows {
+            for x in 0..cols {
+                let d2 = (y as f32 - cy).powi(2) + (x as f32 - cx).powi(2);
+                img[[y, x]] += amp * (-d2 / (2.0 * sigma * sigma)).exp();
+            }
+        }
+    }
+
+    #[test]
+    fn test_laplacian_plus_ignores_pixels_next_to_a_hit() {
+        let mut data = vec![100.0f32; 25];
+        data[12] = 1100.0;
+        let lplus = laplacian_plus(&data, 5, 5);
+        assert!((lplus[12] - 2000.0).abs() < 1e-3, "{}", lplus[12]);
+        for i in [7, 11, 13, 17] {
+            assert!(lplus[i].abs() < 1e-3, "pixel {} = {}", i, lplus[i]);
+        }
+        assert!(lplus[0].abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_detects_single_pixel_hit() {
+        let mut img = sky(64, 64);
+        img[[20, 30]] += 3000.0;
+        let config = LaCosmicConfig { gain: 2.0, read_noise: 5.0, ..Default::default() };
+        let result = la_cosmic(&img, &config, None).unwrap();
+        assert_eq!(result.mask[[20, 30]], 1);
+        assert!(result.cleaned[[20, 30]] < 600.0);
+    }
+
+    #[test]
+    fn test_detects_streak() {
+        let mut img = sky(64, 64);
+        for i in 0..6 {
+            img[[40 + i, 10 + i]] += 2500.0;
+        }
+        let result = la_cosmic(&img, &LaCosmicConfig::default(), None).unwrap();
+        for i in 0..6 {
+            assert_eq!(result.mask[[40 + i, 10 + i]], 1, "streak pixel {} missed", i);
+        }
+    }
+
+    #[test]
+    fn test_preserves_stars() {
+        let mut img = sky(64, 64);
+        add_star(&mut img, 32.0, 32.0, 4000.0, 2.0);
+        let result = la_cosmic(&img, &LaCosmicConfig::default(), None).unwrap();
+        assert_eq!(result.mask[[32, 32]], 0);
+        assert!((result.cleaned[[32, 32]] - img[[32, 32]]).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_clean_image_untouched() {
+        let img = sky(32, 32);
+        let result = la_cosmic(&img, &LaCosmicConfig::default(), None).unwrap();
+        assert_eq!(result.cr_pixels, 0);
+        assert_eq!(result.iterations, 1);
+    }
+}
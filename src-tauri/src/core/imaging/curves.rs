@@ -109,7 +109,7 @@ impl SplineLut {
     }
 }
 
-fn fritsch_carlson_tangents(pts: &[(f64, f64)]) -> Vec<f64> {
+pub(crate) fn fritsch_carlson_tangents(pts: &[(f64, f64)]) -> Vec<f64> {
     let n = pts.len();
     if n < 2 { return vec![0.0; n]; }
     if n == 2 {
@@ -155,7 +155,7 @@ fn fritsch_carlson_tangents(pts: &[(f64, f64)]) -> Vec<f64> {
     m
 }
 
-fn hermite_eval(pts: &[(f64, f64)], tangents: &[f64], n: usize, x: f64) -> f64 {
+pub(crate) fn hermite_eval(pts: &[(f64, f64)], tangents: &[f64], n: usize, x: f64) -> f64 {
     if x <= pts[0].0 { return pts[0].1; }
     if x >= pts[n - 1].0 { return pts[n - 1].1; }
 
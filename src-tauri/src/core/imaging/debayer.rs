@@ -0,0 +1,670 @@
+use anyhow::{bail, Result};
+use ndarray::Array2;
+use rayon::prelude::*;
+
+use crate::types::header::HduHeader;
+
+pub const CFA_RED: u8 = 0;
+pub const CFA_GREEN: u8 = 1;
+pub const CFA_BLUE: u8 = 2;
+
+const PATTERN_KEYS: &[&str] = &["BAYERPAT", "CFA-PAT", "COLORTYP"];
+const XOFFSET_KEYS: &[&str] = &["XBAYROFF", "BAYOFFX"];
+const YOFFSET_KEYS: &[&str] = &["YBAYROFF", "BAYOFFY"];
+
+const VNG_K1: f32 = 1.5;
+const VNG_K2: f32 = 0.5;
+const BAND_ROWS: usize = 128;
+const BAND_MARGIN: usize = 6;
+const GREEN_SEARCH: isize = 3;
+
+const DIR_H: (isize, isize) = (0, 1);
+const DIR_V: (isize, isize) = (1, 0);
+const DIR_D1: (isize, isize) = (1, 1);
+const DIR_D2: (isize, isize) = (1, -1);
+const VNG_DIRS: [(isize, isize); 8] = [
+    (-1, 0), (-1, 1), (0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BayerPattern {
+    Rggb,
+    Bggr,
+    Grbg,
+    Gbrg,
+}
+
+impl BayerPattern {
+    fn tile(&self) -> [[u8; 2]; 2] {
+        match self {
+            BayerPattern::Rggb => [[CFA_RED, CFA_GREEN], [CFA_GREEN, CFA_BLUE]],
+            BayerPattern::Bggr => [[CFA_BLUE, CFA_GREEN], [CFA_GREEN, CFA_RED]],
+            BayerPattern::Grbg => [[CFA_GREEN, CFA_RED], [CFA_BLUE, CFA_GREEN]],
+            BayerPattern::Gbrg => [[CFA_GREEN, CFA_BLUE], [CFA_RED, CFA_GREEN]],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CfaKind {
+    Bayer(BayerPattern),
+    XTrans([[u8; 6]; 6]),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CfaLayout {
+    pub kind: CfaKind,
+    pub x_offset: usize,
+    pub y_offset: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DebayerMethod {
+    #[default]
+    Bilinear,
+    Vng,
+    Ahd,
+    Ahd4,
+}
+
+impl std::fmt::Display for DebayerMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DebayerMethod::Bilinear => write!(f, "bilinear"),
+            DebayerMethod::Vng => write!(f, "vng"),
+            DebayerMethod::Ahd => write!(f, "ahd"),
+            DebayerMethod::Ahd4 => write!(f, "ahd4"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DebayeredImage {
+    pub r: Array2<f32>,
+    pub g: Array2<f32>,
+    pub b: Array2<f32>,
+    pub method: DebayerMethod,
+}
+
+fn color_code(c: char) -> Option<u8> {
+    match c.to_ascii_uppercase() {
+        'R' => Some(CFA_RED),
+        'G' => Some(CFA_GREEN),
+        'B' => Some(CFA_BLUE),
+        _ => None,
+    }
+}
+
+impl CfaLayout {
+    pub fn parse(pattern: &str, x_offset: usize, y_offset: usize) -> Option<Self> {
+        let codes: Vec<u8> = pattern
+            .trim()
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(color_code)
+            .collect::<Option<_>>()?;
+
+        let kind = match codes.len() {
+            4 => {
+                let bayer = match codes.as_slice() {
+                    [0, 1, 1, 2] => BayerPattern::Rggb,
+                    [2, 1, 1, 0] => BayerPattern::Bggr,
+                    [1, 0, 2, 1] => BayerPattern::Grbg,
+                    [1, 2, 0, 1] => BayerPattern::Gbrg,
+                    _ => return None,
+                };
+                CfaKind::Bayer(bayer)
+            }
+            36 => {
+                let mut tile = [[0u8; 6]; 6];
+                for (i, &c) in codes.iter().enumerate() {
+                    tile[i / 6][i % 6] = c;
+                }
+                CfaKind::XTrans(tile)
+            }
+            _ => return None,
+        };
+
+        Some(Self { kind, x_offset, y_offset })
+    }
+
+    pub fn from_header(header: &HduHeader) -> Option<Self> {
+        let pattern = PATTERN_KEYS.iter().filter_map(|k| header.get(k)).find_map(|p| {
+            let trimmed = p.trim();
+            if trimmed.is_empty() { None } else { Some(trimmed.to_string()) }
+        })?;
+        let (x_offset, y_offset) = Self::header_offsets(header);
+        Self::parse(&pattern, x_offset, y_offset)
+    }
+
+    pub fn header_offsets(header: &HduHeader) -> (usize, usize) {
+        let offset = |keys: &[&str]| {
+            keys.iter()
+                .filter_map(|k| header.get_i64(k))
+                .find(|v| *v >= 0)
+                .unwrap_or(0) as usize
+        };
+        (offset(XOFFSET_KEYS), offset(YOFFSET_KEYS))
+    }
+
+    pub fn period(&self) -> usize {
+        match self.kind {
+            CfaKind::Bayer(_) => 2,
+            CfaKind::XTrans(_) => 6,
+        }
+    }
+
+    pub fn is_xtrans(&self) -> bool {
+        matches!(self.kind, CfaKind::XTrans(_))
+    }
+
+    pub fn color_at(&self, y: usize, x: usize) -> u8 {
+        let p = self.period();
+        let yy = (y + self.y_offset) % p;
+        let xx = (x + self.x_offset) % p;
+        match &self.kind {
+            CfaKind::Bayer(b) => b.tile()[yy][xx],
+            CfaKind::XTrans(t) => t[yy][xx],
+        }
+    }
+
+    pub fn pattern_string(&self) -> String {
+        let p = self.period();
+        let mut s = String::with_capacity(p * p);
+        for y in 0..p {
+            for x in 0..p {
+                s.push(match self.color_at(y, x) {
+                    CFA_RED => 'R',
+                    CFA_GREEN => 'G',
+                    _ => 'B',
+                });
+            }
+        }
+        s
+    }
+}
+
+struct Mosaic<'a> {
+    data: &'a [f32],
+    rows: usize,
+    cols: usize,
+    layout: &'a CfaLayout,
+}
+
+impl Mosaic<'_> {
+    #[inline]
+    fn clamp(&self, y: isize, x: isize) -> (usize, usize) {
+        (
+            y.clamp(0, self.rows as isize - 1) as usize,
+            x.clamp(0, self.cols as isize - 1) as usize,
+        )
+    }
+
+    #[inline]
+    fn at(&self, y: isize, x: isize) -> (f32, u8) {
+        let (yy, xx) = self.clamp(y, x);
+        (self.data[yy * self.cols + xx], self.layout.color_at(yy, xx))
+    }
+
+    fn average_of(&self, y: usize, x: usize, channel: u8) -> f32 {
+        for radius in 1..=2isize {
+            let mut sum = 0.0f32;
+            let mut n = 0u32;
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let yy = y as isize + dy;
+                    let xx = x as isize + dx;
+                    if yy < 0 || xx < 0 || yy >= self.rows as isize || xx >= self.cols as isize {
+                        continue;
+                    }
+                    let (v, c) = self.at(yy, xx);
+                    if c == channel && v.is_finite() {
+                        sum += v;
+                        n += 1;
+                    }
+                }
+            }
+            if n > 0 {
+                return sum / n as f32;
+            }
+        }
+        0.0
+    }
+
+    fn bilinear(&self, y: usize, x: usize) -> [f32; 3] {
+        let own = self.layout.color_at(y, x);
+        let v = self.data[y * self.cols + x];
+        let mut out = [0.0f32; 3];
+        for c in 0..3u8 {
+            out[c as usize] = if c == own { v } else { self.average_of(y, x, c) };
+        }
+        out
+    }
+
+    fn vng(&self, y: usize, x: usize) -> [f32; 3] {
+        let own = self.layout.color_at(y, x) as usize;
+        let (yi, xi) = (y as isize, x as isize);
+        let v = self.data[y * self.cols + x];
+
+        let grads = VNG_DIRS.map(|dir| self.vng_gradient(yi, xi, dir));
+        let g_min = grads.iter().flatten().copied().fold(f32::INFINITY, f32::min);
+        let g_max = grads.iter().flatten().copied().fold(f32::NEG_INFINITY, f32::max);
+        let threshold = VNG_K1 * g_min + VNG_K2 * (g_max - g_min);
+
+        let mut diff = [0.0f32; 3];
+        let mut n = 0u32;
+        for (&g, &(dy, dx)) in grads.iter().zip(VNG_DIRS.iter()) {
+            if !g.is_some_and(|g| g <= threshold) {
+                continue;
+            }
+            let (ny, nx) = self.clamp(yi + dy, xi + dx);
+            let est = self.bilinear(ny, nx);
+            for c in 0..3 {
+                diff[c] += est[c] - est[own];
+            }
+            n += 1;
+        }
+
+        if n == 0 {
+            return self.bilinear(y, x);
+        }
+
+        let mut out = [0.0f32; 3];
+        for c in 0..3 {
+            out[c] = if c == own { v } else { (v + diff[c] / n as f32).max(0.0) };
+        }
+        out
+    }
+
+    fn vng_gradient(&self, y: isize, x: isize, (dy, dx): (isize, isize)) -> Option<f32> {
+        let (py, px) = (dx, -dy);
+        let pairs = [
+            ((dy, dx), (-dy, -dx), 1.0),
+            ((2 * dy, 2 * dx), (0, 0), 1.0),
+            ((py + dy, px + dx), (py - dy, px - dx), 0.5),
+            ((dy - py, dx - px), (-dy - py, -dx - px), 0.5),
+            ((2 * dy + py, 2 * dx + px), (py, px), 0.5),
+            ((2 * dy - py, 2 * dx - px), (-py, -px), 0.5),
+        ];
+
+        let mut sum = 0.0f32;
+        let mut weight = 0.0f32;
+        for ((ay, ax), (by, bx), w) in pairs {
+            let (a, ca) = self.at(y + ay, x + ax);
+            let (b, cb) = self.at(y + by, x + bx);
+            if ca == cb && a.is_finite() && b.is_finite() {
+                sum += w * (a - b).abs();
+                weight += w;
+            }
+        }
+        (weight > 0.0).then(|| sum / weight)
+    }
+
+    fn find_green(&self, y: isize, x: isize, dy: isize, dx: isize) -> Option<(f32, f32)> {
+        for k in 1..=GREEN_SEARCH {
+            let yy = y + k * dy;
+            let xx = x + k * dx;
+            if yy < 0 || xx < 0 || yy >= self.rows as isize || xx >= self.cols as isize {
+                return None;
+            }
+            let (v, c) = self.at(yy, xx);
+            if c == CFA_GREEN {
+                return Some((v, k as f32));
+            }
+        }
+        None
+    }
+
+    fn directional_green(&self, y: usize, x: usize, dir: (isize, isize)) -> f32 {
+        let own = self.layout.color_at(y, x);
+        let v = self.data[y * self.cols + x];
+        if own == CFA_GREEN {
+            return v;
+        }
+        let (yi, xi) = (y as isize, x as isize);
+        let (dy, dx) = dir;
+
+        match (self.find_green(yi, xi, dy, dx), self.find_green(yi, xi, -dy, -dx)) {
+            (Some((gp, kp)), Some((gm, km))) => {
+                let mut g = (gp * km + gm * kp) / (kp + km);
+                if kp == 1.0 && km == 1.0 {
+                    let (fp, cp) = self.at(yi + 2 * dy, xi + 2 * dx);
+                    let (fm, cm) = self.at(yi - 2 * dy, xi - 2 * dx);
+                    if cp == own && cm == own {
+                        g += (2.0 * v - fp - fm) * 0.25;
+                    }
+                }
+                g.clamp(gp.min(gm), gp.max(gm))
+            }
+            (Some((g, _)), None) | (None, Some((g, _))) => g,
+            (None, None) => self.average_of(y, x, CFA_GREEN),
+        }
+    }
+}
+
+fn rgb_to_lab(rgb: [f32; 3], scale: f32) -> [f32; 3] {
+    let r = (rgb[0] * scale).max(0.0);
+    let g = (rgb[1] * scale).max(0.0);
+    let b = (rgb[2] * scale).max(0.0);
+    let x = (0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b) / 0.950_47;
+    let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b;
+    let z = (0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b) / 1.088_83;
+    let f = |t: f32| if t > 0.008_856 { t.cbrt() } else { 7.787 * t + 16.0 / 116.0 };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn homogeneity_directed(mosaic: &Mosaic, dirs: &[(isize, isize)]) -> Vec<[f32; 3]> {
+    let (rows, cols) = (mosaic.rows, mosaic.cols);
+    let max_val = mosaic
+        .data
+        .par_iter()
+        .copied()
+        .filter(|v| v.is_finite())
+        .reduce(|| 0.0f32, f32::max);
+    let scale = if max_val > 0.0 { 1.0 / max_val } else { 1.0 };
+    let nd = dirs.len();
+
+    let mut out = vec![[0.0f32; 3]; rows * cols];
+    out.par_chunks_mut(BAND_ROWS * cols)
+        .enumerate()
+        .for_each(|(band, out_band)| {
+            let y0 = band * BAND_ROWS;
+            let y1 = (y0 + BAND_ROWS).min(rows);
+            let ty0 = y0.saturating_sub(BAND_MARGIN);
+            let ty1 = (y1 + BAND_MARGIN).min(rows);
+            let th = ty1 - ty0;
+            let tn = th * cols;
+            let lidx = |ly: isize, lx: isize| -> usize {
+                let ly = ly.clamp(0, th as isize - 1) as usize;
+                let lx = lx.clamp(0, cols as isize - 1) as usize;
+                ly * cols + lx
+            };
+
+            let mut rgb_dirs: Vec<Vec<[f32; 3]>> = Vec::with_capacity(nd);
+            let mut lab_dirs: Vec<Vec<[f32; 3]>> = Vec::with_capacity(nd);
+            for &dir in dirs {
+                let mut green = vec![0.0f32; tn];
+                for ly in 0..th {
+                    for x in 0..cols {
+                        green[ly * cols + x] = mosaic.directional_green(ty0 + ly, x, dir);
+                    }
+                }
+
+                let mut rgb = vec![[0.0f32; 3]; tn];
+                for ly in 0..th {
+                    let y = ty0 + ly;
+                    for x in 0..cols {
+                        let i = ly * cols + x;
+                        let own = mosaic.layout.color_at(y, x);
+                        let v = mosaic.data[y * cols + x];
+                        let g = green[i];
+                        let mut px = [0.0f32; 3];
+                        px[CFA_GREEN as usize] = g;
+                        for c in [CFA_RED, CFA_BLUE] {
+                            if c == own {
+                                px[c as usize] = v;
+                                continue;
+                            }
+                            let mut sum = 0.0f32;
+                            let mut n = 0u32;
+                            for radius in 1..=2isize {
+                                for dy in -radius..=radius {
+                                    for dx in -radius..=radius {
+                                        let sy = y as isize + dy;
+                                        let sx = x as isize + dx;
+                                        if sy < ty0 as isize || sy >= ty1 as isize || sx < 0 || sx >= cols as isize {
+                                            continue;
+                                        }
+                                        let (sv, sc) = mosaic.at(sy, sx);
+                                        if sc == c {
+                                            sum += sv - green[lidx(sy - ty0 as isize, sx)];
+                                            n += 1;
+                                        }
+                                    }
+                                }
+                                if n > 0 {
+                                    break;
+                                }
+                            }
+                            px[c as usize] = if n > 0 { (g + sum / n as f32).max(0.0) } else { g };
+                        }
+                        rgb[i] = px;
+                    }
+                }
+
+                let lab: Vec<[f32; 3]> = rgb.iter().map(|&p| rgb_to_lab(p, scale)).collect();
+                rgb_dirs.push(rgb);
+                lab_dirs.push(lab);
+            }
+
+            let neighbours = [(-1isize, 0isize), (1, 0), (0, -1), (0, 1)];
+            let mut homo: Vec<Vec<u8>> = vec![vec![0u8; tn]; nd];
+            for ly in 0..th as isize {
+                for x in 0..cols as isize {
+                    let i = lidx(ly, x);
+                    let mut eps_l = f32::INFINITY;
+                    let mut eps_c = f32::INFINITY;
+                    for lab in &lab_dirs {
+                        let p = lab[i];
+                        let mut max_l = 0.0f32;
+                        let mut max_c = 0.0f32;
+                        for &(dy, dx) in &neighbours {
+                            let q = lab[lidx(ly + dy, x + dx)];
+                            max_l = max_l.max((p[0] - q[0]).abs());
+                            max_c = max_c.max((p[1] - q[1]).powi(2) + (p[2] - q[2]).powi(2));
+                        }
+                        eps_l = eps_l.min(max_l);
+                        eps_c = eps_c.min(max_c);
+                    }
+                    for (k, lab) in lab_dirs.iter().enumerate() {
+                        let p = lab[i];
+                        let count = neighbours
+                            .iter()
+                            .filter(|&&(dy, dx)| {
+                                let q = lab[lidx(ly + dy, x + dx)];
+                                (p[0] - q[0]).abs() <= eps_l
+                                    && (p[1] - q[1]).powi(2) + (p[2] - q[2]).powi(2) <= eps_c
+                            })
+                            .count();
+                        homo[k][i] = count as u8;
+                    }
+                }
+            }
+
+            for y in y0..y1 {
+                let ly = (y - ty0) as isize;
+                for x in 0..cols {
+                    let mut scores = vec![0u32; nd];
+                    for (k, score) in scores.iter_mut().enumerate() {
+                        for dy in -1..=1isize {
+                            for dx in -1..=1isize {
+                                *score += homo[k][lidx(ly + dy, x as isize + dx)] as u32;
+                            }
+                        }
+                    }
+                    let best = scores.iter().copied().max().unwrap_or(0);
+                    let i = lidx(ly, x as isize);
+                    let mut acc = [0.0f32; 3];
+                    let mut n = 0.0f32;
+                    for (k, &s) in scores.iter().enumerate() {
+                        if s == best {
+                            for c in 0..3 {
+                                acc[c] += rgb_dirs[k][i][c];
+                            }
+                            n += 1.0;
+                        }
+                    }
+                    out_band[(y - y0) * cols + x] = [acc[0] / n, acc[1] / n, acc[2] / n];
+                }
+            }
+        });
+
+    out
+}
+
+pub fn debayer(
+    cfa: &Array2<f32>,
+    layout: &CfaLayout,
+    method: DebayerMethod,
+) -> Result<DebayeredImage> {
+    let (rows, cols) = cfa.dim();
+    if rows < 6 || cols < 6 {
+        bail!("CFA image too small to debayer: {}x{}", cols, rows);
+    }
+
+    match (method, layout.is_xtrans()) {
+        (DebayerMethod::Ahd, true) => {
+            bail!("{} demosaicing needs a Bayer pattern, got X-Trans; use {}", method, DebayerMethod::Ahd4)
+        }
+        (DebayerMethod::Ahd4, false) => {
+            bail!("{} demosaicing needs an X-Trans pattern, got {}; use {}", method, layout.pattern_string(), DebayerMethod::Ahd)
+        }
+        _ => {}
+    }
+
+    let mosaic = Mosaic {
+        data: cfa.as_slice().expect("contiguous"),
+        rows,
+        cols,
+        layout,
+    };
+
+    let pixels: Vec<[f32; 3]> = match method {
+        DebayerMethod::Bilinear => (0..rows * cols)
+            .into_par_iter()
+            .map(|i| mosaic.bilinear(i / cols, i % cols))
+            .collect(),
+        DebayerMethod::Vng => (0..rows * cols)
+            .into_par_iter()
+            .map(|i| mosaic.vng(i / cols, i % cols))
+            .collect(),
+        DebayerMethod::Ahd => homogeneity_directed(&mosaic, &[DIR_H, DIR_V]),
+        DebayerMethod::Ahd4 => homogeneity_directed(&mosaic, &[DIR_H, DIR_V, DIR_D1, DIR_D2]),
+    };
+
+    let plane = |c: usize| {
+        Array2::from_shape_vec((rows, cols), pixels.iter().map(|p| p[c]).collect())
+    };
+
+    Ok(DebayeredImage {
+        r: plane(0)?,
+        g: plane(1)?,
+        b: plane(2)?,
+        method,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mosaic_from(layout: &CfaLayout, rows: usize, cols: usize, rgb: impl Fn(usize, usize) -> [f32; 3]) -> Array2<f32> {
+        Array2::from_shape_fn((rows, cols), |(y, x)| rgb(y, x)[layout.color_at(y, x) as usize])
+    }
+
+    fn header_with(cards: &[(&str, &str)]) -> HduHeader {
+        let cards: Vec<(String, String)> = cards
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let index = cards.iter().cloned().collect();
+        HduHeader { cards, index }
+    }
+
+    #[test]
+    fn test_layout_from_header_with_offsets() {
+        let h = header_with(&[("BAYERPAT", "RGGB"), ("XBAYROFF", "1"), ("YBAYROFF", "0")]);
+        let layout = CfaLayout::from_header(&h).unwrap();
+        assert_eq!(layout.kind, CfaKind::Bayer(BayerPattern::Rggb));
+        assert_eq!(layout.color_at(0, 0), CFA_GREEN);
+        assert_eq!(layout.color_at(0, 1), CFA_RED);
+        assert_eq!(layout.pattern_string(), "GRBG");
+    }
+
+    #[test]
+    fn test_header_offsets_without_pattern() {
+        let h = header_with(&[("XBAYROFF", "1"), ("YBAYROFF", "1")]);
+        assert!(CfaLayout::from_header(&h).is_none());
+        assert_eq!(CfaLayout::header_offsets(&h), (1, 1));
+        assert_eq!(CfaLayout::header_offsets(&header_with(&[])), (0, 0));
+    }
+
+    #[test]
+    fn test_xtrans_pattern_parse() {
+        let layout = CfaLayout::parse("GGRGGBGGBGGRBRGRBGGGBGGRGGRGGBRBGBRG", 0, 0).unwrap();
+        assert!(layout.is_xtrans());
+        assert_eq!(layout.period(), 6);
+        assert_eq!(layout.color_at(0, 2), CFA_RED);
+        assert_eq!(layout.color_at(6, 8), CFA_RED);
+        assert!(CfaLayout::parse("RGBX", 0, 0).is_none());
+    }
+
+    #[test]
+    fn test_uniform_colour_recovered_by_all_methods() {
+        let layout = CfaLayout::parse("RGGB", 0, 0).unwrap();
+        let cfa = mosaic_from(&layout, 24, 24, |_, _| [100.0, 60.0, 30.0]);
+        for method in [DebayerMethod::Bilinear, DebayerMethod::Vng, DebayerMethod::Ahd] {
+            let out = debayer(&cfa, &layout, method).unwrap();
+            for (y, x) in [(5, 5), (10, 11), (11, 10), (12, 12)] {
+                assert!((out.r[[y, x]] - 100.0).abs() < 1e-3, "{} r at {},{}", method, y, x);
+                assert!((out.g[[y, x]] - 60.0).abs() < 1e-3, "{} g at {},{}", method, y, x);
+                assert!((out.b[[y, x]] - 30.0).abs() < 1e-3, "{} b at {},{}", method, y, x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_xtrans_uniform() {
+        let layout = CfaLayout::parse("GGRGGBGGBGGRBRGRBGGGBGGRGGRGGBRBGBRG", 0, 0).unwrap();
+        let cfa = mosaic_from(&layout, 36, 36, |_, _| [80.0, 50.0, 20.0]);
+        for method in [DebayerMethod::Bilinear, DebayerMethod::Vng, DebayerMethod::Ahd4] {
+            let out = debayer(&cfa, &layout, method).unwrap();
+            assert_eq!(out.method, method);
+            for (y, x) in [(10, 10), (17, 20), (25, 13)] {
+                assert!((out.r[[y, x]] - 80.0).abs() < 1e-3, "{} r at {},{}", method, y, x);
+                assert!((out.g[[y, x]] - 50.0).abs() < 1e-3, "{} g at {},{}", method, y, x);
+                assert!((out.b[[y, x]] - 20.0).abs() < 1e-3, "{} b at {},{}", method, y, x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_method_must_match_pattern() {
+        let bayer = CfaLayout::parse("RGGB", 0, 0).unwrap();
+        let xtrans = CfaLayout::parse("GGRGGBGGBGGRBRGRBGGGBGGRGGRGGBRBGBRG", 0, 0).unwrap();
+        let cfa = Array2::from_elem((12, 12), 10.0f32);
+        assert!(debayer(&cfa, &bayer, DebayerMethod::Ahd4).is_err());
+        assert!(debayer(&cfa, &xtrans, DebayerMethod::Ahd).is_err());
+    }
+
+    #[test]
+    fn test_vng_gradients_compare_same_colour_pixels() {
+        let layout = CfaLayout::parse("RGGB", 0, 0).unwrap();
+        let cfa = mosaic_from(&layout, 16, 16, |_, _| [200.0, 50.0, 10.0]);
+        let mosaic = Mosaic { data: cfa.as_slice().unwrap(), rows: 16, cols: 16, layout: &layout };
+        for dir in VNG_DIRS {
+            for (y, x) in [(6, 6), (6, 7), (7, 6), (7, 7)] {
+                assert_eq!(mosaic.vng_gradient(y, x, dir), Some(0.0), "{:?} at {},{}", dir, y, x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ahd_follows_vertical_edge() {
+        let layout = CfaLayout::parse("RGGB", 0, 0).unwrap();
+        let cfa = mosaic_from(&layout, 32, 32, |_, x| {
+            if x < 16 { [20.0, 20.0, 20.0] } else { [200.0, 200.0, 200.0] }
+        });
+        let ahd = debayer(&cfa, &layout, DebayerMethod::Ahd).unwrap();
+        let bil = debayer(&cfa, &layout, DebayerMethod::Bilinear).unwrap();
+        let err = |img: &DebayeredImage| -> f32 {
+            (0..32)
+                .map(|y| {
+                    (img.g[[y, 15]] - 20.0).abs() + (img.g[[y, 16]] - 200.0).abs()
+                })
+                .sum()
+        };
+        assert!(err(&ahd) < err(&bil), "ahd={} bilinear={}", err(&ahd), err(&bil));
+    }
+}
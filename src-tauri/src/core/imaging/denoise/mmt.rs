@@ -0,0 +1,107 @@
+use std::sync::OnceLock;
+
+use ndarray::Array2;
+use rayon::prelude::*;
+
+use crate::core::imaging::wavelet::MAX_SCALES;
+use crate::types::image::DenoiseParams;
+
+const MEDIAN_RADIUS: isize = 2;
+const CALIBRATION_SIZE: usize = 256;
+
+fn median_smooth(src: &[f32], rows: usize, cols: usize, step: usize) -> Vec<f32> {
+    let s = step as isize;
+    let mut out = vec![0.0f32; rows * cols];
+    out.par_chunks_mut(cols).enumerate().for_each(|(y, row)| {
+        let mut window = Vec::with_capacity(((2 * MEDIAN_RADIUS + 1) * (2 * MEDIAN_RADIUS + 1)) as usize);
+        for (x, out) in row.iter_mut().enumerate() {
+            window.clear();
+            for ky in -MEDIAN_RADIUS..=MEDIAN_RADIUS {
+                let sy = (y as isize + ky * s).clamp(0, rows as isize - 1) as usize;
+                for kx in -MEDIAN_RADIUS..=MEDIAN_RADIUS {
+                    let sx = (x as isize + kx * s).clamp(0, cols as isize - 1) as usize;
+                    window.push(src[sy * cols + sx]);
+                }
+            }
+            let mid = window.len() / 2;
+            *out = *window.select_nth_unstable_by(mid, f32::total_cmp).1;
+        }
+    });
+    out
+}
+
+fn decompose(image: &[f32], rows: usize, cols: usize, scales: usize) -> (Vec<Vec<f32>>, Vec<f32>) {
+    let mut current = image.to_vec();
+    let mut details = Vec::with_capacity(scales);
+    for j in 0..scales {
+        let next = median_smooth(&current, rows, cols, 1 << j);
+        details.push(current.par_iter().zip(next.par_iter()).map(|(c, s)| c - s).collect());
+        current = next;
+    }
+    (details, current)
+}
+
+fn unit_noise_response() -> &'static [f32] {
+    static TABLE: OnceLock<Vec<f32>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut uniform = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            ((state >> 40) as f32 + 0.5) / (1u64 << 24) as f32
+        };
+        let noise: Vec<f32> = (0..CALIBRATION_SIZE * CALIBRATION_SIZE)
+            .map(|_| (-2.0 * uniform().ln()).sqrt() * (std::f32::consts::TAU * uniform()).cos())
+            .collect();
+        let (details, _) = decompose(&noise, CALIBRATION_SIZE, CALIBRATION_SIZE, MAX_SCALES);
+        details
+            .iter()
+            .map(|d| (d.iter().map(|v| v * v).sum::<f32>() / d.len() as f32).sqrt())
+            .collect()
+    })
+}
+
+pub fn mmt_denoise(image: &Array2<f32>, sigma: &Array2<f32>, params: &DenoiseParams) -> Array2<f32> {
+    let (rows, cols) = image.dim();
+    let scales = params.num_scales.clamp(1, MAX_SCALES);
+    let sig = sigma.as_slice().expect("contiguous");
+    let response = unit_noise_response();
+    let (mut details, residual) = decompose(image.as_slice().expect("contiguous"), rows, cols, scales);
+
+    details.par_iter_mut().enumerate().for_each(|(j, layer)| {
+        let k = params.scale_thresholds.get(j).copied().unwrap_or(0.0) as f32 * params.strength as f32 * response[j];
+        if k <= 0.0 {
+            return;
+        }
+        layer.iter_mut().zip(sig).for_each(|(d, &s)| {
+            let t = k * s;
+            *d = d.signum() * (d.abs() - t).max(0.0);
+        });
+    });
+
+    let out = details.iter().fold(residual, |mut acc, d| {
+        acc.par_iter_mut().zip(d.par_iter()).for_each(|(a, v)| *a += v);
+        acc
+    });
+    Array2::from_shape_vec((rows, cols), out).expect("mmt shape")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_noise_response_and_exact_reconstruction() {
+        let response = unit_noise_response();
+        assert_eq!(response.len(), MAX_SCALES);
+        assert!(response[0] > 0.5 && response[0] < 1.0, "{:?}", response);
+        assert!(response[0] > response[2]);
+
+        let image = Array2::from_shape_fn((20, 24), |(y, x)| ((y * 7 + x * 13) % 11) as f32 * 0.1);
+        let sigma = Array2::zeros((20, 24));
+        let params = DenoiseParams { scale_thresholds: vec![], ..Default::default() };
+        let out = mmt_denoise(&image, &sigma, &params);
+        assert!(out.iter().zip(image.iter()).all(|(a, b)| (a - b).abs() < 1e-5));
+    }
+}
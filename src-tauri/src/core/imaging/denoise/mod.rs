@@ -0,0 +1,300 @@
+pub mod mmt;
+pub mod nlm;
+pub mod tgv;
+
+use anyhow::{bail, Result};
+use ndarray::{Array2, Zip};
+use rayon::prelude::*;
+
+use crate::core::compose::color_adjust::mask_weight;
+use crate::core::imaging::multiscale::normalized;
+use crate::core::imaging::wavelet::{atrous_decompose, atrous_smooth, layer_noise_sigma, MAX_SCALES};
+use crate::types::image::{DenoiseMethod, DenoiseParams};
+
+const MAX_STRENGTH: f64 = 10.0;
+const MAX_PATCH_RADIUS: usize = 5;
+const MAX_SEARCH_RADIUS: usize = 21;
+const MAX_ITERATIONS: usize = 2000;
+
+pub enum NoiseModel {
+    Global(f32),
+    Detector { gain: f32, read_noise: f32 },
+    ErrPlane(Array2<f32>),
+}
+
+impl NoiseModel {
+    pub fn estimate(image: &Array2<f32>) -> Self {
+        let layers = atrous_decompose(image, 1);
+        Self::Global(layer_noise_sigma(layers.details[0].as_slice().expect("contiguous")) as f32)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Global(_) => "global",
+            Self::Detector { .. } => "detector",
+            Self::ErrPlane(_) => "err_plane",
+        }
+    }
+
+    pub fn sigma_map(&self, image: &Array2<f32>) -> Result<Array2<f32>> {
+        let (rows, cols) = image.dim();
+        match self {
+            Self::Global(sigma) => Ok(Array2::from_elem((rows, cols), sigma.max(0.0))),
+            Self::Detector { gain, read_noise } => {
+                if !(*gain > 0.0 && gain.is_finite() && *read_noise >= 0.0) {
+                    bail!("Detector noise model needs a positive gain and a non-negative read noise");
+                }
+                let signal = atrous_smooth(finite_or_zero(image).as_slice().expect("contiguous"), rows, cols, 1);
+                let rn = read_noise / gain;
+                let sigma: Vec<f32> = signal.par_iter().map(|&s| (s.max(0.0) / gain + rn * rn).sqrt()).collect();
+                Ok(Array2::from_shape_vec((rows, cols), sigma)?)
+            }
+            Self::ErrPlane(err) => {
+                if err.dim() != (rows, cols) {
+                    bail!("Error plane is {:?} but the image is {:?}", err.dim(), (rows, cols));
+                }
+                let mut valid: Vec<f32> = err.iter().copied().filter(|e| e.is_finite() && *e > 0.0).collect();
+                if valid.is_empty() {
+                    bail!("Error plane has no positive finite values");
+                }
+                let mid = valid.len() / 2;
+                let fill = *valid.select_nth_unstable_by(mid, f32::total_cmp).1;
+                Ok(err.mapv(|e| if e.is_finite() && e > 0.0 { e } else { fill }))
+            }
+        }
+    }
+}
+
+pub struct DenoiseResult {
+    pub image: Array2<f32>,
+    pub noise_sigma: f64,
+    pub elapsed_ms: u64,
+}
+
+pub struct RgbDenoiseResult {
+    pub channels: [Array2<f32>; 3],
+    pub noise_sigmas: [f64; 3],
+    pub elapsed_ms: u64,
+}
+
+fn validate(params: &DenoiseParams) -> Result<()> {
+    if !(params.strength > 0.0 && params.strength <= MAX_STRENGTH) {
+        bail!("Denoise strength must be within (0, {}], got {}", MAX_STRENGTH, params.strength);
+    }
+    if !(0.0..=1.0).contains(&params.amount) {
+        bail!("Denoise amount must be within [0, 1], got {}", params.amount);
+    }
+    match params.method {
+        DenoiseMethod::Nlm => {
+            if !(1..=MAX_PATCH_RADIUS).contains(&params.patch_radius) || !(1..=MAX_SEARCH_RADIUS).contains(&params.search_radius) {
+                bail!(
+                    "NLM patch radius must be within [1, {}] and search radius within [1, {}]",
+                    MAX_PATCH_RADIUS,
+                    MAX_SEARCH_RADIUS
+                );
+            }
+        }
+        DenoiseMethod::Tgv => {
+            if !(1..=MAX_ITERATIONS).contains(&params.iterations) {
+                bail!("TGV iterations must be within [1, {}], got {}", MAX_ITERATIONS, params.iterations);
+            }
+            if !(params.tgv_alpha_ratio > 0.0 && params.tgv_alpha_ratio.is_finite()) {
+                bail!("TGV alpha ratio must be positive, got {}", params.tgv_alpha_ratio);
+            }
+        }
+        DenoiseMethod::Mmt => {
+            if !(1..=MAX_SCALES).contains(&params.num_scales) {
+                bail!("MMT scales must be within [1, {}], got {}", MAX_SCALES, params.num_scales);
+            }
+            if params.scale_thresholds.iter().any(|t| !(*t >= 0.0 && t.is_finite())) {
+                bail!("MMT scale thresholds must be non-negative");
+            }
+        }
+    }
+    if let Some(m) = &params.luminance_mask {
+        if !(0.0..=1.0).contains(&m.low) || !(0.0..=1.0).contains(&m.high) {
+            bail!("Luminance mask bounds must be within [0, 1]");
+        }
+    }
+    Ok(())
+}
+
+fn finite_or_zero(image: &Array2<f32>) -> Array2<f32> {
+    let mut out = image.as_standard_layout().to_owned();
+    out.par_mapv_inplace(|v| if v.is_finite() { v } else { 0.0 });
+    out
+}
+
+fn median_sigma(sigma: &Array2<f32>) -> f64 {
+    let mut values: Vec<f32> = sigma.iter().copied().collect();
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mid = values.len() / 2;
+    *values.select_nth_unstable_by(mid, f32::total_cmp).1 as f64
+}
+
+fn denoise_plane(image: &Array2<f32>, sigma: &Array2<f32>, params: &DenoiseParams) -> Array2<f32> {
+    match params.method {
+        DenoiseMethod::Nlm => nlm::nlm_denoise(image, sigma, params),
+        DenoiseMethod::Tgv => tgv::tgv_denoise(image, sigma, params),
+        DenoiseMethod::Mmt => mmt::mmt_denoise(image, sigma, params),
+    }
+}
+
+fn luminance_weights(luminance: &Array2<f32>, params: &DenoiseParams) -> Array2<f32> {
+    let (rows, cols) = luminance.dim();
+    if params.luminance_mask.is_none() {
+        return Array2::from_elem((rows, cols), params.amount as f32);
+    }
+    let smooth = atrous_smooth(finite_or_zero(luminance).as_slice().expect("contiguous"), rows, cols, 2);
+    let lum = normalized(&Array2::from_shape_vec((rows, cols), smooth).expect("mask shape"));
+    let mask = params.luminance_mask.as_ref();
+    lum.mapv(|l| params.amount as f32 * mask_weight(l, mask))
+}
+
+fn blend(out: &mut Array2<f32>, original: &Array2<f32>, weights: &Array2<f32>) {
+    Zip::from(out).and(original).and(weights).par_for_each(|o, &orig, &w| {
+        *o = if orig.is_finite() { orig + w * (*o - orig) } else { orig };
+    });
+}
+
+pub fn denoise(image: &Array2<f32>, params: &DenoiseParams, model: &NoiseModel) -> Result<DenoiseResult> {
+    validate(params)?;
+    let t0 = std::time::Instant::now();
+    let sigma = model.sigma_map(image)?;
+    let mut out = denoise_plane(&finite_or_zero(image), &sigma, params);
+    blend(&mut out, image, &luminance_weights(image, params));
+    Ok(DenoiseResult {
+        image: out,
+        noise_sigma: median_sigma(&sigma),
+        elapsed_ms: t0.elapsed().as_millis() as u64,
+    })
+}
+
+pub fn denoise_rgb(channels: [&Array2<f32>; 3], params: &DenoiseParams, models: [&NoiseModel; 3]) -> Result<RgbDenoiseResult> {
+    validate(params)?;
+    let dim = channels[0].dim();
+    if channels.iter().any(|c| c.dim() != dim) {
+        bail!("Channel dimensions differ");
+    }
+    let t0 = std::time::Instant::now();
+    let [r, g, b] = channels;
+    let sigmas = [models[0].sigma_map(r)?, models[1].sigma_map(g)?, models[2].sigma_map(b)?];
+    let clean = [finite_or_zero(r), finite_or_zero(g), finite_or_zero(b)];
+    let luminance = (&clean[0] + &clean[1] + &clean[2]) / 3.0;
+
+    let mut out = if params.chrominance_only {
+        let chroma_sigma = |wr: f32, wg: f32, wb: f32| {
+            let mut s = Array2::zeros(dim);
+            Zip::from(&mut s).and(&sigmas[0]).and(&sigmas[1]).and(&sigmas[2]).par_for_each(|o, &sr, &sg, &sb| {
+                *o = ((wr * sr).powi(2) + (wg * sg).powi(2) + (wb * sb).powi(2)).sqrt();
+            });
+            s
+        };
+        let third = 1.0 / 3.0;
+        let cr = &clean[0] - &luminance;
+        let cb = &clean[2] - &luminance;
+        let (cr, cb) = rayon::join(
+            || denoise_plane(&cr, &chroma_sigma(2.0 * third, third, third), params),
+            || denoise_plane(&cb, &chroma_sigma(third, third, 2.0 * third), params),
+        );
+        let g = &luminance - &cr - &cb;
+        [&luminance + &cr, g, &luminance + &cb]
+    } else {
+        let (dr, (dg, db)) = rayon::join(
+            || denoise_plane(&clean[0], &sigmas[0], params),
+            || rayon::join(|| denoise_plane(&clean[1], &sigmas[1], params), || denoise_plane(&clean[2], &sigmas[2], params)),
+        );
+        [dr, dg, db]
+    };
+
+    let weights = luminance_weights(&luminance, params);
+    for (o, orig) in out.iter_mut().zip(channels) {
+        blend(o, orig, &weights);
+    }
+    Ok(RgbDenoiseResult {
+        channels: out,
+        noise_sigmas: [median_sigma(&sigmas[0]), median_sigma(&sigmas[1]), median_sigma(&sigmas[2])],
+        elapsed_ms: t0.elapsed().as_millis() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::image::LuminanceMask;
+
+    fn noisy_scene(rows: usize, cols: usize, sigma: f32, seed: u64) -> (Array2<f32>, Array2<f32>) {
+        let mut state = seed;
+        let mut gauss = move || {
+            let mut next = || {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                ((state >> 40) as f32 + 0.5) / (1u64 << 24) as f32
+            };
+            let (u1, u2) = (next(), next());
+            (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+        };
+        let clean = Array2::from_shape_fn((rows, cols), |(y, x)| {
+            let (dx, dy) = (x as f32 - cols as f32 / 2.0, y as f32 - rows as f32 / 2.0);
+            let step = if x < cols / 3 { 0.2 } else { 0.0 };
+            0.1 + step + 0.6 * (-(dx * dx + dy * dy) / 40.0).exp()
+        });
+        let noisy = clean.mapv(|v| v + sigma * gauss());
+        (clean, noisy)
+    }
+
+    fn rms_error(a: &Array2<f32>, b: &Array2<f32>) -> f32 {
+        (a.iter().zip(b.iter()).map(|(x, y)| (x - y) * (x - y)).sum::<f32>() / a.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_methods_reduce_noise() {
+        let (clean, noisy) = noisy_scene(48, 48, 0.05, 7);
+        let model = NoiseModel::estimate(&noisy);
+        let NoiseModel::Global(sigma) = model else { panic!("expected a global model") };
+        assert!((sigma - 0.05).abs() < 0.015, "estimated sigma {}", sigma);
+
+        let before = rms_error(&noisy, &clean);
+        for method in [DenoiseMethod::Nlm, DenoiseMethod::Tgv, DenoiseMethod::Mmt] {
+            let params = DenoiseParams { method, ..Default::default() };
+            let out = denoise(&noisy, &params, &model).unwrap();
+            let after = rms_error(&out.image, &clean);
+            assert!(after < before * 0.7, "{:?}: {} vs {}", method, after, before);
+        }
+        assert!(denoise(&noisy, &DenoiseParams { strength: 0.0, ..Default::default() }, &model).is_err());
+    }
+
+    #[test]
+    fn test_noise_models_mask_and_chrominance_only() {
+        let (_, noisy) = noisy_scene(32, 32, 0.05, 3);
+        let detector = NoiseModel::Detector { gain: 2.0, read_noise: 4.0 };
+        let sigma = detector.sigma_map(&noisy.mapv(|v| v * 1000.0)).unwrap();
+        assert!(sigma[[16, 16]] > sigma[[2, 30]]);
+        let err = NoiseModel::ErrPlane(Array2::from_elem((32, 32), f32::NAN));
+        assert!(err.sigma_map(&noisy).is_err());
+
+        let masked = DenoiseParams {
+            luminance_mask: Some(LuminanceMask { low: 0.5, high: 0.6, invert: false }),
+            ..Default::default()
+        };
+        let out = denoise(&noisy, &masked, &NoiseModel::Global(0.05)).unwrap().image;
+        assert_eq!(out[[2, 30]], noisy[[2, 30]]);
+        assert!(out[[16, 16]] != noisy[[16, 16]]);
+
+        let (_, g) = noisy_scene(32, 32, 0.05, 11);
+        let (_, b) = noisy_scene(32, 32, 0.05, 19);
+        let model = NoiseModel::Global(0.05);
+        let params = DenoiseParams { chrominance_only: true, ..Default::default() };
+        let res = denoise_rgb([&noisy, &g, &b], &params, [&model, &model, &model]).unwrap();
+        let [dr, dg, db] = &res.channels;
+        for i in [0usize, 100, 517, 1023] {
+            let (y, x) = (i / 32, i % 32);
+            let before = noisy[[y, x]] + g[[y, x]] + b[[y, x]];
+            let after = dr[[y, x]] + dg[[y, x]] + db[[y, x]];
+            assert!((before - after).abs() < 1e-4);
+        }
+        let spread = |a: &Array2<f32>, c: &Array2<f32>| rms_error(a, c);
+        assert!(spread(dr, db) < spread(&noisy, &b) * 0.7);
+    }
+}
@@ -0,0 +1,94 @@
+use ndarray::Array2;
+use rayon::prelude::*;
+
+use crate::types::image::DenoiseParams;
+
+const NLM_H_FACTOR: f32 = 0.4;
+
+fn box_mean(src: &[f32], tmp: &mut [f32], dst: &mut [f32], rows: usize, cols: usize, radius: usize) {
+    let r = radius as isize;
+    tmp.par_chunks_mut(cols).enumerate().for_each(|(y, row)| {
+        let line = &src[y * cols..(y + 1) * cols];
+        for (x, out) in row.iter_mut().enumerate() {
+            let lo = (x as isize - r).max(0) as usize;
+            let hi = (x as isize + r).min(cols as isize - 1) as usize;
+            *out = line[lo..=hi].iter().sum::<f32>() / (hi - lo + 1) as f32;
+        }
+    });
+    let tmp: &[f32] = tmp;
+    dst.par_chunks_mut(cols).enumerate().for_each(|(y, row)| {
+        let lo = (y as isize - r).max(0) as usize;
+        let hi = (y as isize + r).min(rows as isize - 1) as usize;
+        let n = (hi - lo + 1) as f32;
+        for (x, out) in row.iter_mut().enumerate() {
+            *out = (lo..=hi).map(|yy| tmp[yy * cols + x]).sum::<f32>() / n;
+        }
+    });
+}
+
+pub fn nlm_denoise(image: &Array2<f32>, sigma: &Array2<f32>, params: &DenoiseParams) -> Array2<f32> {
+    let (rows, cols) = image.dim();
+    let src = image.as_slice().expect("contiguous");
+    let sig = sigma.as_slice().expect("contiguous");
+    let n = rows * cols;
+    let search = params.search_radius as isize;
+    let h_scale = NLM_H_FACTOR * params.strength as f32;
+
+    let mut acc = vec![0.0f32; n];
+    let mut wsum = vec![0.0f32; n];
+    let mut wmax = vec![0.0f32; n];
+    let mut diff = vec![0.0f32; n];
+    let mut tmp = vec![0.0f32; n];
+    let mut dist = vec![0.0f32; n];
+
+    for dy in -search..=search {
+        for dx in -search..=search {
+            if dy == 0 && dx == 0 {
+                continue;
+            }
+            let shifted = |y: usize, x: usize| {
+                let sy = (y as isize + dy).clamp(0, rows as isize - 1) as usize;
+                let sx = (x as isize + dx).clamp(0, cols as isize - 1) as usize;
+                sy * cols + sx
+            };
+            diff.par_chunks_mut(cols).enumerate().for_each(|(y, row)| {
+                for (x, d) in row.iter_mut().enumerate() {
+                    let e = src[y * cols + x] - src[shifted(y, x)];
+                    *d = e * e;
+                }
+            });
+            box_mean(&diff, &mut tmp, &mut dist, rows, cols, params.patch_radius);
+
+            let dist: &[f32] = &dist;
+            acc.par_chunks_mut(cols)
+                .zip(wsum.par_chunks_mut(cols))
+                .zip(wmax.par_chunks_mut(cols))
+                .enumerate()
+                .for_each(|(y, ((acc_row, wsum_row), wmax_row))| {
+                    for x in 0..cols {
+                        let i = y * cols + x;
+                        let q = shifted(y, x);
+                        let (sp, sq) = (sig[i], sig[q]);
+                        let h = h_scale * sp;
+                        if h <= 0.0 {
+                            continue;
+                        }
+                        let excess = (dist[i] - (sp * sp + sq * sq)).max(0.0);
+                        let w = (-excess / (h * h)).exp();
+                        acc_row[x] += w * src[q];
+                        wsum_row[x] += w;
+                        wmax_row[x] = wmax_row[x].max(w);
+                    }
+                });
+        }
+    }
+
+    let out: Vec<f32> = (0..n)
+        .into_par_iter()
+        .map(|i| {
+            let total = wsum[i] + wmax[i];
+            if total > 0.0 { (acc[i] + wmax[i] * src[i]) / total } else { src[i] }
+        })
+        .collect();
+    Array2::from_shape_vec((rows, cols), out).expect("nlm shape")
+}
@@ -0,0 +1,124 @@
+use ndarray::Array2;
+use rayon::prelude::*;
+
+use crate::types::image::DenoiseParams;
+
+const MAX_FIDELITY: f32 = 1.0e4;
+
+struct Grid {
+    rows: usize,
+    cols: usize,
+}
+
+impl Grid {
+    fn dx_fwd(&self, v: &[f32], y: usize, x: usize) -> f32 {
+        let i = y * self.cols + x;
+        if x + 1 < self.cols { v[i + 1] - v[i] } else { 0.0 }
+    }
+
+    fn dy_fwd(&self, v: &[f32], y: usize, x: usize) -> f32 {
+        let i = y * self.cols + x;
+        if y + 1 < self.rows { v[i + self.cols] - v[i] } else { 0.0 }
+    }
+
+    fn dx_bwd(&self, v: &[f32], y: usize, x: usize) -> f32 {
+        let i = y * self.cols + x;
+        let here = if x + 1 < self.cols { v[i] } else { 0.0 };
+        let prev = if x > 0 { v[i - 1] } else { 0.0 };
+        here - prev
+    }
+
+    fn dy_bwd(&self, v: &[f32], y: usize, x: usize) -> f32 {
+        let i = y * self.cols + x;
+        let here = if y + 1 < self.rows { v[i] } else { 0.0 };
+        let prev = if y > 0 { v[i - self.cols] } else { 0.0 };
+        here - prev
+    }
+}
+
+pub fn tgv_denoise(image: &Array2<f32>, sigma: &Array2<f32>, params: &DenoiseParams) -> Array2<f32> {
+    let (rows, cols) = image.dim();
+    let n = rows * cols;
+    let mut sorted: Vec<f32> = sigma.iter().copied().filter(|s| *s > 0.0).collect();
+    if sorted.is_empty() || rows < 2 || cols < 2 {
+        return image.clone();
+    }
+    let mid = sorted.len() / 2;
+    let reference = *sorted.select_nth_unstable_by(mid, f32::total_cmp).1;
+
+    let f: Vec<f32> = image.iter().map(|&v| v / reference).collect();
+    let lambda: Vec<f32> = sigma
+        .iter()
+        .map(|&s| if s > 0.0 { (reference / s).powi(2).min(MAX_FIDELITY) } else { MAX_FIDELITY })
+        .collect();
+    let alpha1 = params.strength as f32;
+    let alpha0 = alpha1 * params.tgv_alpha_ratio as f32;
+    let step = 1.0 / 12.0f32.sqrt();
+    let grid = Grid { rows, cols };
+
+    let mut u = f.clone();
+    let mut ub = f.clone();
+    let (mut w1, mut w2) = (vec![0.0f32; n], vec![0.0f32; n]);
+    let (mut wb1, mut wb2) = (vec![0.0f32; n], vec![0.0f32; n]);
+    let (mut p1, mut p2) = (vec![0.0f32; n], vec![0.0f32; n]);
+    let (mut q11, mut q22, mut q12) = (vec![0.0f32; n], vec![0.0f32; n], vec![0.0f32; n]);
+
+    for _ in 0..params.iterations {
+        p1.par_chunks_mut(cols).zip(p2.par_chunks_mut(cols)).enumerate().for_each(|(y, (r1, r2))| {
+            for x in 0..cols {
+                let i = y * cols + x;
+                let a = r1[x] + step * (grid.dx_fwd(&ub, y, x) - wb1[i]);
+                let b = r2[x] + step * (grid.dy_fwd(&ub, y, x) - wb2[i]);
+                let scale = ((a * a + b * b).sqrt() / alpha1).max(1.0);
+                r1[x] = a / scale;
+                r2[x] = b / scale;
+            }
+        });
+
+        q11.par_chunks_mut(cols)
+            .zip(q22.par_chunks_mut(cols))
+            .zip(q12.par_chunks_mut(cols))
+            .enumerate()
+            .for_each(|(y, ((r11, r22), r12))| {
+                for x in 0..cols {
+                    let a = r11[x] + step * grid.dx_bwd(&wb1, y, x);
+                    let b = r22[x] + step * grid.dy_bwd(&wb2, y, x);
+                    let c = r12[x] + step * 0.5 * (grid.dy_bwd(&wb1, y, x) + grid.dx_bwd(&wb2, y, x));
+                    let scale = ((a * a + b * b + 2.0 * c * c).sqrt() / alpha0).max(1.0);
+                    r11[x] = a / scale;
+                    r22[x] = b / scale;
+                    r12[x] = c / scale;
+                }
+            });
+
+        u.par_chunks_mut(cols).zip(ub.par_chunks_mut(cols)).enumerate().for_each(|(y, (ru, rb))| {
+            for x in 0..cols {
+                let i = y * cols + x;
+                let div = grid.dx_bwd(&p1, y, x) + grid.dy_bwd(&p2, y, x);
+                let old = ru[x];
+                let new = (old + step * div + step * lambda[i] * f[i]) / (1.0 + step * lambda[i]);
+                ru[x] = new;
+                rb[x] = 2.0 * new - old;
+            }
+        });
+
+        w1.par_chunks_mut(cols)
+            .zip(w2.par_chunks_mut(cols))
+            .zip(wb1.par_chunks_mut(cols).zip(wb2.par_chunks_mut(cols)))
+            .enumerate()
+            .for_each(|(y, ((r1, r2), (rb1, rb2)))| {
+                for x in 0..cols {
+                    let i = y * cols + x;
+                    let (old1, old2) = (r1[x], r2[x]);
+                    let new1 = old1 + step * (p1[i] + grid.dx_fwd(&q11, y, x) + grid.dy_fwd(&q12, y, x));
+                    let new2 = old2 + step * (p2[i] + grid.dx_fwd(&q12, y, x) + grid.dy_fwd(&q22, y, x));
+                    r1[x] = new1;
+                    r2[x] = new2;
+                    rb1[x] = 2.0 * new1 - old1;
+                    rb2[x] = 2.0 * new2 - old2;
+                }
+            });
+    }
+
+    Array2::from_shape_vec((rows, cols), u.into_par_iter().map(|v| v * reference).collect()).expect("tgv shape")
+}
@@ -0,0 +1,317 @@
+use anyhow::{bail, Result};
+use ndarray::{Array2, Zip};
+use rayon::prelude::*;
+
+use crate::core::imaging::stats::compute_image_stats;
+use crate::types::image::{GhsParams, Histogram};
+
+const MIN_LOCAL_INTENSITY: f64 = -5.0;
+const MAX_LOCAL_INTENSITY: f64 = 15.0;
+const MAX_STRETCH: f64 = 20.0;
+const INVERSE_LUT_SIZE: usize = 65_536;
+const PREVIEW_INPUT_BINS: usize = 16_384;
+
+pub struct GhsTransform {
+    d: f64,
+    b: f64,
+    sp: f64,
+    lp: f64,
+    hp: f64,
+    t_lp: f64,
+    slope_lp: f64,
+    t_hp: f64,
+    slope_hp: f64,
+    t0: f64,
+    inv_range: f64,
+    inverse_lut: Option<Vec<f64>>,
+}
+
+impl GhsTransform {
+    pub fn new(params: &GhsParams) -> Result<Self> {
+        let (sp, lp, hp) = (params.symmetry_point, params.shadow_protection, params.highlight_protection);
+        if !(0.0..=1.0).contains(&sp) || !(0.0..=sp).contains(&lp) || !(sp..=1.0).contains(&hp) {
+            bail!("GHS requires 0 <= LP <= SP <= HP <= 1 (got LP={}, SP={}, HP={})", lp, sp, hp);
+        }
+        if !(0.0..=MAX_STRETCH).contains(&params.stretch) {
+            bail!("GHS stretch ln(D+1) must be within [0, {}], got {}", MAX_STRETCH, params.stretch);
+        }
+        if !(MIN_LOCAL_INTENSITY..=MAX_LOCAL_INTENSITY).contains(&params.local_intensity) {
+            bail!(
+                "GHS local intensity b must be within [{}, {}], got {}",
+                MIN_LOCAL_INTENSITY, MAX_LOCAL_INTENSITY, params.local_intensity
+            );
+        }
+
+        let d = params.stretch.exp_m1();
+        let b = params.local_intensity;
+        let mut t = Self {
+            d,
+            b,
+            sp,
+            lp,
+            hp,
+            t_lp: 0.0,
+            slope_lp: 0.0,
+            t_hp: 0.0,
+            slope_hp: 0.0,
+            t0: 0.0,
+            inv_range: 1.0,
+            inverse_lut: None,
+        };
+        if d <= 0.0 {
+            return Ok(t);
+        }
+
+        t.t_lp = -t.base(sp - lp);
+        t.slope_lp = t.base_slope(sp - lp);
+        t.t_hp = t.base(hp - sp);
+        t.slope_hp = t.base_slope(hp - sp);
+        t.t0 = t.raw(0.0);
+        let range = t.raw(1.0) - t.t0;
+        t.inv_range = if range > 0.0 { 1.0 / range } else { 1.0 };
+
+        if params.inverse {
+            let n = INVERSE_LUT_SIZE;
+            t.inverse_lut = Some((0..=n).map(|i| t.forward(i as f64 / n as f64)).collect());
+        }
+        Ok(t)
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.d <= 0.0
+    }
+
+    fn base(&self, u: f64) -> f64 {
+        let (d, b) = (self.d, self.b);
+        if (b + 1.0).abs() < 1e-9 {
+            (d * u).ln_1p()
+        } else if b < 0.0 {
+            (1.0 - (1.0 - b * d * u).powf((b + 1.0) / b)) / (d * (b + 1.0))
+        } else if b == 0.0 {
+            -(-d * u).exp_m1()
+        } else {
+            1.0 - (1.0 + b * d * u).powf(-1.0 / b)
+        }
+    }
+
+    fn base_slope(&self, u: f64) -> f64 {
+        let (d, b) = (self.d, self.b);
+        if (b + 1.0).abs() < 1e-9 {
+            d / (1.0 + d * u)
+        } else if b < 0.0 {
+            (1.0 - b * d * u).powf(1.0 / b)
+        } else if b == 0.0 {
+            d * (-d * u).exp()
+        } else {
+            d * (1.0 + b * d * u).powf(-(1.0 + b) / b)
+        }
+    }
+
+    fn raw(&self, x: f64) -> f64 {
+        if x < self.lp {
+            self.t_lp + self.slope_lp * (x - self.lp)
+        } else if x < self.sp {
+            -self.base(self.sp - x)
+        } else if x <= self.hp {
+            self.base(x - self.sp)
+        } else {
+            self.t_hp + self.slope_hp * (x - self.hp)
+        }
+    }
+
+    pub fn forward(&self, x: f64) -> f64 {
+        let x = x.clamp(0.0, 1.0);
+        if self.is_identity() {
+            return x;
+        }
+        ((self.raw(x) - self.t0) * self.inv_range).clamp(0.0, 1.0)
+    }
+
+    pub fn apply(&self, x: f64) -> f64 {
+        let Some(lut) = &self.inverse_lut else {
+            return self.forward(x);
+        };
+        let y = x.clamp(0.0, 1.0);
+        let i = lut.partition_point(|&v| v < y);
+        if i == 0 {
+            return 0.0;
+        }
+        if i >= lut.len() {
+            return 1.0;
+        }
+        let (lo, hi) = (lut[i - 1], lut[i]);
+        let frac = if hi > lo { (y - lo) / (hi - lo) } else { 0.0 };
+        ((i - 1) as f64 + frac) / (lut.len() - 1) as f64
+    }
+}
+
+fn normalization(min: f64, max: f64) -> (f64, f64) {
+    if min >= 0.0 && max <= 1.0 {
+        return (0.0, 1.0);
+    }
+    let range = max - min;
+    if range < 1e-12 { (min, 1.0) } else { (min, 1.0 / range) }
+}
+
+fn ghs_with_normalization(data: &Array2<f32>, transform: &GhsTransform, offset: f64, scale: f64) -> Array2<f32> {
+    let mut out = Array2::zeros(data.raw_dim());
+    Zip::from(&mut out).and(data).par_for_each(|o, &v| {
+        *o = if v.is_finite() {
+            transform.apply((v as f64 - offset) * scale) as f32
+        } else {
+            0.0
+        };
+    });
+    out
+}
+
+pub fn ghs_stretch(data: &Array2<f32>, params: &GhsParams) -> Result<Array2<f32>> {
+    let transform = GhsTransform::new(params)?;
+    let stats = compute_image_stats(data);
+    let (offset, scale) = normalization(stats.min, stats.max);
+    Ok(ghs_with_normalization(data, &transform, offset, scale))
+}
+
+pub fn ghs_stretch_rgb(
+    r: &Array2<f32>,
+    g: &Array2<f32>,
+    b: &Array2<f32>,
+    params: &GhsParams,
+    linked: bool,
+) -> Result<(Array2<f32>, Array2<f32>, Array2<f32>)> {
+    let transform = GhsTransform::new(params)?;
+    let (sr, (sg, sb)) = rayon::join(
+        || compute_image_stats(r),
+        || rayon::join(|| compute_image_stats(g), || compute_image_stats(b)),
+    );
+    let norms = if linked {
+        let n = normalization(sr.min.min(sg.min).min(sb.min), sr.max.max(sg.max).max(sb.max));
+        [n; 3]
+    } else {
+        [normalization(sr.min, sr.max), normalization(sg.min, sg.max), normalization(sb.min, sb.max)]
+    };
+
+    let t = &transform;
+    let (ro, (go, bo)) = rayon::join(
+        || ghs_with_normalization(r, t, norms[0].0, norms[0].1),
+        || rayon::join(
+            || ghs_with_normalization(g, t, norms[1].0, norms[1].1),
+            || ghs_with_normalization(b, t, norms[2].0, norms[2].1),
+        ),
+    );
+    Ok((ro, go, bo))
+}
+
+pub fn ghs_curve(params: &GhsParams, samples: usize) -> Result<Vec<[f64; 2]>> {
+    let transform = GhsTransform::new(params)?;
+    let n = samples.max(2) - 1;
+    Ok((0..=n)
+        .map(|i| {
+            let x = i as f64 / n as f64;
+            [x, transform.apply(x)]
+        })
+        .collect())
+}
+
+pub fn ghs_histogram_preview(data: &Array2<f32>, params: &GhsParams, bins: usize) -> Result<Histogram> {
+    let transform = GhsTransform::new(params)?;
+    let bins = bins.max(1);
+    let stats = compute_image_stats(data);
+    let (offset, scale) = normalization(stats.min, stats.max);
+
+    let slice = data.as_slice().expect("contiguous");
+    let input_last = PREVIEW_INPUT_BINS - 1;
+    let input = slice
+        .par_iter()
+        .fold(
+            || vec![0u32; PREVIEW_INPUT_BINS],
+            |mut acc, &v| {
+                if v.is_finite() {
+                    let x = ((v as f64 - offset) * scale).clamp(0.0, 1.0);
+                    acc[((x * PREVIEW_INPUT_BINS as f64) as usize).min(input_last)] += 1;
+                }
+                acc
+            },
+        )
+        .reduce(
+            || vec![0u32; PREVIEW_INPUT_BINS],
+            |mut a, b| {
+                a.iter_mut().zip(&b).for_each(|(x, y)| *x += y);
+                a
+            },
+        );
+
+    let mut output = vec![0u32; bins];
+    for (i, &count) in input.iter().enumerate().filter(|(_, &c)| c > 0) {
+        let y = transform.apply((i as f64 + 0.5) / PREVIEW_INPUT_BINS as f64);
+        output[((y * bins as f64) as usize).min(bins - 1)] += count;
+    }
+
+    Ok(Histogram {
+        bins: output,
+        bin_edges: (0..=bins).map(|i| i as f64 / bins as f64).collect(),
+        min: 0.0,
+        max: 1.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(stretch: f64, b: f64, sp: f64, lp: f64, hp: f64) -> GhsParams {
+        GhsParams {
+            stretch,
+            local_intensity: b,
+            symmetry_point: sp,
+            shadow_protection: lp,
+            highlight_protection: hp,
+            inverse: false,
+        }
+    }
+
+    #[test]
+    fn test_transform_is_monotonic_and_normalized() {
+        for b in [-1.5, -1.0, -0.5, 0.0, 2.0, 8.0] {
+            let t = GhsTransform::new(&params(4.0, b, 0.1, 0.05, 0.8)).unwrap();
+            assert!(t.forward(0.0).abs() < 1e-9 && (t.forward(1.0) - 1.0).abs() < 1e-9, "b={}", b);
+            let mut prev = 0.0;
+            for i in 1..=1000 {
+                let y = t.forward(i as f64 / 1000.0);
+                assert!(y >= prev - 1e-12, "b={} not monotonic at {}", b, i);
+                prev = y;
+            }
+            assert!(t.forward(0.15) > 0.15, "b={} should brighten above SP", b);
+        }
+        let identity = GhsTransform::new(&params(0.0, 0.0, 0.0, 0.0, 1.0)).unwrap();
+        assert_eq!(identity.forward(0.3), 0.3);
+        assert!(GhsTransform::new(&params(1.0, 0.0, 0.5, 0.6, 1.0)).is_err());
+    }
+
+    #[test]
+    fn test_protection_regions_are_linear() {
+        let t = GhsTransform::new(&params(3.0, 1.0, 0.3, 0.2, 0.7)).unwrap();
+        let step = |x: f64| t.forward(x + 0.01) - t.forward(x);
+        assert!((step(0.02) - step(0.12)).abs() < 1e-9);
+        assert!((step(0.8) - step(0.9)).abs() < 1e-9);
+        assert!((step(0.4) - step(0.5)).abs() > 1e-4);
+    }
+
+    #[test]
+    fn test_inverse_round_trip_and_preview() {
+        let forward = params(5.0, 2.0, 0.02, 0.0, 1.0);
+        let inverse = GhsParams { inverse: true, ..forward };
+        let data = Array2::from_shape_fn((32, 32), |(y, x)| (y * 32 + x) as f32 / 1023.0);
+        let stretched = ghs_stretch(&data, &forward).unwrap();
+        let restored = ghs_stretch(&stretched, &inverse).unwrap();
+        for (a, b) in restored.iter().zip(data.iter()) {
+            assert!((a - b).abs() < 1e-3, "{} vs {}", a, b);
+        }
+
+        let hist = ghs_histogram_preview(&data, &forward, 64).unwrap();
+        let direct = crate::core::imaging::stats::compute_histogram(&stretched, 64);
+        assert_eq!(hist.bins.iter().sum::<u32>(), 1024);
+        let diff: u32 = hist.bins.iter().zip(&direct.bins).map(|(a, b)| a.abs_diff(*b)).sum();
+        assert!(diff < 64, "preview differs by {}", diff);
+    }
+}
@@ -125,7 +125,7 @@ pub struct MaskedStretchRgbResult {
     pub shared_stars_masked: usize,
 }
 
-fn compute_luminance(
+pub(crate) fn compute_luminance(
     r: &Array2<f32>,
     g: &Array2<f32>,
     b: &Array2<f32>,
@@ -1,15 +1,22 @@
 pub mod background;
 pub mod boundary;
 pub mod calibration_pipeline;
+pub mod cosmic_ray;
 pub mod curves;
+pub mod debayer;
+pub mod denoise;
+pub mod ghs;
 pub mod masked_stretch;
+pub mod multiscale;
 pub mod normalize;
 pub mod psf_estimation;
 pub mod resample;
 pub mod sampling;
 pub mod scnr;
 pub mod star_mask;
+pub mod star_removal;
 pub mod stats;
 pub mod stf;
 pub mod stretch;
+pub mod trails;
 pub mod wavelet;
@@ -0,0 +1,235 @@
+use anyhow::{bail, Result};
+use ndarray::{Array2, Zip};
+use rayon::prelude::*;
+
+use crate::core::compose::color_adjust::mask_weight;
+use crate::core::imaging::wavelet::{atrous_decompose, atrous_noise_scaling, layer_noise_sigma, WaveletLayers, MAX_SCALES};
+use crate::types::image::{MultiscaleParams, WaveletLayerParams};
+
+const MIN_BIAS: f64 = -1.0;
+const MAX_BIAS: f64 = 15.0;
+const MAX_ENHANCE: f64 = 10.0;
+const SHARPEN_NOISE_K: f32 = 3.0;
+
+pub struct MultiscaleResult {
+    pub image: Array2<f32>,
+    pub layer_sigmas: Vec<f64>,
+    pub scales: usize,
+}
+
+fn validate(params: &MultiscaleParams) -> Result<()> {
+    for (i, l) in params.layers.iter().enumerate() {
+        if !(MIN_BIAS..=MAX_BIAS).contains(&l.bias) {
+            bail!("Layer {} bias must be within [{}, {}], got {}", i + 1, MIN_BIAS, MAX_BIAS, l.bias);
+        }
+        if !(l.noise_threshold >= 0.0 && l.noise_threshold.is_finite() && (0.0..=1.0).contains(&l.noise_reduction)) {
+            bail!("Layer {} needs a non-negative noise threshold and a reduction amount within [0, 1]", i + 1);
+        }
+    }
+    if !(-1.0..=MAX_ENHANCE).contains(&params.sharpen) || !(-1.0..=MAX_ENHANCE).contains(&params.local_contrast) {
+        bail!("Sharpening and local contrast amounts must be within [-1, {}]", MAX_ENHANCE);
+    }
+    if params.contrast_layers[0] > params.contrast_layers[1] {
+        bail!("Local contrast layer range is reversed: {:?}", params.contrast_layers);
+    }
+    if !(params.residual_gain >= 0.0 && params.residual_gain.is_finite()) {
+        bail!("Residual gain must be non-negative, got {}", params.residual_gain);
+    }
+    if let Some(m) = &params.luminance_mask {
+        if !(0.0..=1.0).contains(&m.low) || !(0.0..=1.0).contains(&m.high) {
+            bail!("Luminance mask bounds must be within [0, 1]");
+        }
+    }
+    Ok(())
+}
+
+fn layer_rms(layer: &Array2<f32>) -> f32 {
+    let slice = layer.as_slice().expect("contiguous");
+    let sum: f64 = slice.par_iter().map(|&v| (v as f64) * (v as f64)).sum();
+    (sum / slice.len().max(1) as f64).sqrt() as f32
+}
+
+fn process_layer(layer: &mut Array2<f32>, index: usize, lp: &WaveletLayerParams, params: &MultiscaleParams, sigma: f32) {
+    if !lp.enabled {
+        layer.fill(0.0);
+        return;
+    }
+    let threshold = lp.noise_threshold as f32 * sigma;
+    let reduction = lp.noise_reduction as f32;
+    let sharpen = if index < params.sharpen_layers { params.sharpen as f32 } else { 0.0 };
+    let sharpen_floor = (SHARPEN_NOISE_K * sigma).powi(2);
+    let [first, last] = params.contrast_layers;
+    let contrast = if (first..=last).contains(&index) { params.local_contrast as f32 } else { 0.0 };
+    let rms = if contrast != 0.0 { layer_rms(layer) } else { 0.0 };
+    let gain = (1.0 + lp.bias) as f32;
+
+    layer.par_mapv_inplace(|mut d| {
+        if threshold > 0.0 {
+            let shrunk = d.signum() * (d.abs() - threshold).max(0.0);
+            d += reduction * (shrunk - d);
+        }
+        if sharpen != 0.0 {
+            let e = d * d;
+            let w = if e + sharpen_floor > 0.0 { e / (e + sharpen_floor) } else { 0.0 };
+            d *= 1.0 + sharpen * w;
+        }
+        if contrast != 0.0 && rms > 0.0 {
+            d *= 1.0 + contrast * rms / (d.abs() + rms);
+        }
+        d * gain
+    });
+}
+
+pub(crate) fn normalized(arr: &Array2<f32>) -> Array2<f32> {
+    let (lo, hi) = arr
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    let range = hi - lo;
+    if range > 0.0 { arr.mapv(|v| (v - lo) / range) } else { Array2::zeros(arr.raw_dim()) }
+}
+
+pub fn rgb_luminance_mask(channels: [&Array2<f32>; 3], params: &MultiscaleParams) -> Option<Array2<f32>> {
+    params.luminance_mask?;
+    let mut luminance = Array2::zeros(channels[0].raw_dim());
+    Zip::from(&mut luminance).and(channels[0]).and(channels[1]).and(channels[2]).par_for_each(|l, &r, &g, &b| {
+        let y = (r + g + b) / 3.0;
+        *l = if y.is_finite() { y } else { 0.0 };
+    });
+    let scales = params.num_scales.clamp(1, MAX_SCALES);
+    Some(normalized(&atrous_decompose(&luminance, scales).residual))
+}
+
+pub fn multiscale_process(image: &Array2<f32>, params: &MultiscaleParams) -> Result<MultiscaleResult> {
+    multiscale_process_with_mask(image, params, None)
+}
+
+pub fn multiscale_process_with_mask(
+    image: &Array2<f32>,
+    params: &MultiscaleParams,
+    luminance: Option<&Array2<f32>>,
+) -> Result<MultiscaleResult> {
+    validate(params)?;
+    if luminance.is_some_and(|l| l.dim() != image.dim()) {
+        bail!("Luminance mask dimensions do not match the image");
+    }
+    let scales = params.num_scales.clamp(1, MAX_SCALES);
+    let WaveletLayers { mut details, residual } = atrous_decompose(image, scales);
+
+    let base_sigma = layer_noise_sigma(details[0].as_slice().expect("contiguous"));
+    let layer_sigmas: Vec<f64> = (0..scales).map(|j| base_sigma * atrous_noise_scaling(j)).collect();
+
+    details.par_iter_mut().enumerate().for_each(|(j, layer)| {
+        let lp = params.layers.get(j).copied().unwrap_or_default();
+        process_layer(layer, j, &lp, params, layer_sigmas[j] as f32);
+    });
+
+    let mask = params.luminance_mask.map(|_| luminance.cloned().unwrap_or_else(|| normalized(&residual)));
+    let base = if params.residual { residual * params.residual_gain as f32 } else { Array2::zeros(image.raw_dim()) };
+    let mut out = details.iter().fold(base, |acc, d| acc + d);
+
+    Zip::from(&mut out).and(image).par_for_each(|o, &orig| {
+        if !orig.is_finite() {
+            *o = orig;
+        }
+    });
+    if let Some(lum) = mask {
+        let m = params.luminance_mask.as_ref();
+        Zip::from(&mut out).and(image).and(&lum).par_for_each(|o, &orig, &l| {
+            if orig.is_finite() {
+                *o = orig + mask_weight(l, m) * (*o - orig);
+            }
+        });
+    }
+
+    Ok(MultiscaleResult { image: out, layer_sigmas, scales })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::image::LuminanceMask;
+
+    fn test_image() -> Array2<f32> {
+        Array2::from_shape_fn((64, 64), |(y, x)| {
+            let (dx, dy) = (x as f32 - 32.0, y as f32 - 32.0);
+            let noise = (((y * 64 + x) as u64).wrapping_mul(6364136223846793005) >> 40) as f32 / (1u64 << 24) as f32 - 0.5;
+            0.1 + 0.6 * (-(dx * dx + dy * dy) / 18.0).exp() + 0.01 * noise
+        })
+    }
+
+    #[test]
+    fn test_identity_and_layer_round_trip() {
+        let image = test_image();
+        let layers = atrous_decompose(&image, 4);
+        assert_eq!(layers.details.len(), 4);
+        let rebuilt = layers.reconstruct();
+        assert!(rebuilt.iter().zip(image.iter()).all(|(a, b)| (a - b).abs() < 1e-5));
+
+        let result = multiscale_process(&image, &MultiscaleParams::default()).unwrap();
+        assert!(result.image.iter().zip(image.iter()).all(|(a, b)| (a - b).abs() < 1e-5));
+        assert!(result.layer_sigmas[0] > result.layer_sigmas[1]);
+
+        let detail_only = MultiscaleParams { residual: false, ..MultiscaleParams::default() };
+        let out = multiscale_process(&image, &detail_only).unwrap();
+        assert!(out.image.iter().map(|v| v.abs()).sum::<f32>() / 4096.0 < 0.05);
+    }
+
+    #[test]
+    fn test_bias_sharpen_denoise_and_mask() {
+        let image = test_image();
+        let peak = |a: &Array2<f32>| a[[32, 32]] - a[[32, 44]];
+        let boosted = MultiscaleParams {
+            layers: vec![WaveletLayerParams::default(), WaveletLayerParams { bias: 1.0, ..Default::default() }],
+            sharpen: 1.0,
+            ..MultiscaleParams::default()
+        };
+        let out = multiscale_process(&image, &boosted).unwrap().image;
+        assert!(peak(&out) > peak(&image) * 1.05, "{} vs {}", peak(&out), peak(&image));
+
+        let denoise = MultiscaleParams {
+            layers: vec![WaveletLayerParams { noise_threshold: 3.0, ..Default::default() }; 2],
+            ..MultiscaleParams::default()
+        };
+        let smooth = multiscale_process(&image, &denoise).unwrap().image;
+        let roughness = |a: &Array2<f32>| (1..63).map(|x| (a[[2, x]] - a[[2, x - 1]]).abs()).sum::<f32>();
+        assert!(roughness(&smooth) < roughness(&image) * 0.5);
+
+        let masked = MultiscaleParams {
+            luminance_mask: Some(LuminanceMask { low: 0.5, high: 0.6, invert: false }),
+            ..boosted
+        };
+        let out = multiscale_process(&image, &masked).unwrap().image;
+        assert_eq!(out[[2, 2]], image[[2, 2]]);
+        assert!(out[[32, 32]] != image[[32, 32]]);
+        assert!(multiscale_process(&image, &MultiscaleParams { sharpen: 20.0, ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn test_shared_rgb_luminance_mask() {
+        let image = test_image();
+        let params = MultiscaleParams {
+            sharpen: 1.0,
+            luminance_mask: Some(LuminanceMask { low: 0.5, high: 0.6, invert: false }),
+            ..MultiscaleParams::default()
+        };
+        let flat = image.mapv(|v| 0.1 + (v * 1000.0).sin() * 0.01);
+        let mask = rgb_luminance_mask([&image, &flat, &flat], &params).unwrap();
+        let outside: Vec<usize> = mask
+            .iter()
+            .enumerate()
+            .filter(|(_, &l)| mask_weight(l, params.luminance_mask.as_ref()) == 0.0)
+            .map(|(i, _)| i)
+            .collect();
+        assert!(!outside.is_empty());
+
+        let unchanged = |out: &Array2<f32>, orig: &Array2<f32>| {
+            let (o, a) = (out.as_slice().unwrap(), orig.as_slice().unwrap());
+            outside.iter().all(|&i| o[i] == a[i])
+        };
+        let red = multiscale_process_with_mask(&image, &params, Some(&mask)).unwrap().image;
+        let green = multiscale_process_with_mask(&flat, &params, Some(&mask)).unwrap().image;
+        assert!(unchanged(&red, &image) && unchanged(&green, &flat));
+        assert!(!unchanged(&multiscale_process(&flat, &params).unwrap().image, &flat));
+        assert!(multiscale_process_with_mask(&image, &params, Some(&Array2::zeros((4, 4)))).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use anyhow::{bail, Result};
+use ndarray::Array2;
+use rayon::prelude::*;
+
+use crate::core::analysis::star_detection::detect_stars;
+use crate::core::imaging::masked_stretch::compute_luminance;
+use crate::core::imaging::star_mask::{generate_star_mask_from_detection, StarMaskConfig};
+use crate::core::imaging::wavelet::atrous_smooth;
+use crate::math::median::median_f32_mut;
+
+const HOLE_MASK_LEVEL: f32 = 0.5;
+const MIN_SUPPORT: f32 = 0.2;
+const MAX_FILL_SCALES: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct StarRemovalConfig {
+    pub detection_sigma: f64,
+    pub min_fwhm: f64,
+    pub max_fwhm: f64,
+    pub growth_factor: f64,
+    pub softness: f64,
+}
+
+impl Default for StarRemovalConfig {
+    fn default() -> Self {
+        Self {
+            detection_sigma: 4.0,
+            min_fwhm: 1.0,
+            max_fwhm: 20.0,
+            growth_factor: 2.0,
+            softness: 3.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StarRemovalResult {
+    pub starless: Vec<Array2<f32>>,
+    pub stars: Vec<Array2<f32>>,
+    pub mask: Array2<f32>,
+    pub stars_removed: usize,
+    pub coverage_fraction: f64,
+    pub fill_scales: usize,
+}
+
+pub fn remove_stars(channels: &[&Array2<f32>], config: &StarRemovalConfig) -> Result<StarRemovalResult> {
+    let luminance = match channels {
+        [mono] => (*mono).clone(),
+        [r, g, b] => compute_luminance(r, g, b).map_err(anyhow::Error::msg)?,
+        _ => bail!("Star removal expects 1 or 3 channels, got {}", channels.len()),
+    };
+    let (rows, cols) = luminance.dim();
+    if channels.iter().any(|c| c.dim() != (rows, cols)) {
+        bail!("Channel dimensions differ");
+    }
+
+    let detection = detect_stars(&luminance, config.detection_sigma);
+    let mask_config = StarMaskConfig {
+        growth_factor: config.growth_factor,
+        softness: config.softness,
+        detection_sigma: config.detection_sigma,
+        min_fwhm: config.min_fwhm,
+        max_fwhm: config.max_fwhm,
+        ..StarMaskConfig::default()
+    };
+    let mask = generate_star_mask_from_detection(&luminance, &detection, &mask_config).map_err(anyhow::Error::msg)?;
+    log::info!(
+        "Star removal: {} stars within FWHM {:.1}-{:.1}px, coverage {:.2}%",
+        mask.stars_masked, config.min_fwhm, config.max_fwhm, mask.coverage_fraction * 100.0
+    );
+
+    let weights = mask.mask.as_slice().expect("contiguous");
+    let hole: Vec<bool> = weights.iter().map(|&m| m >= HOLE_MASK_LEVEL).collect();
+
+    let filled: Vec<(Array2<f32>, Array2<f32>, usize)> = channels
+        .par_iter()
+        .map(|channel| {
+            let src = channel.as_slice().expect("contiguous");
+            let (fill, scales) = multiscale_fill(src, &hole, rows, cols);
+            let mut starless = vec![0.0f32; rows * cols];
+            let mut stars = vec![0.0f32; rows * cols];
+            starless
+                .par_iter_mut()
+                .zip(stars.par_iter_mut())
+                .enumerate()
+                .for_each(|(i, (s, r))| {
+                    let (v, m) = (src[i], weights[i]);
+                    if !v.is_finite() || m <= 0.0 {
+                        *s = v;
+                        return;
+                    }
+                    *s = (v * (1.0 - m) + fill[i] * m).min(v);
+                    *r = v - *s;
+                });
+            (
+                Array2::from_shape_vec((rows, cols), starless).unwrap(),
+                Array2::from_shape_vec((rows, cols), stars).unwrap(),
+                scales,
+            )
+        })
+        .collect();
+
+    let fill_scales = filled.iter().map(|f| f.2).max().unwrap_or(0);
+    let (starless, stars) = filled.into_iter().map(|(s, r, _)| (s, r)).unzip();
+    Ok(StarRemovalResult {
+        starless,
+        stars,
+        stars_removed: mask.stars_masked,
+        coverage_fraction: mask.coverage_fraction,
+        mask: mask.mask,
+        fill_scales,
+    })
+}
+
+fn multiscale_fill(src: &[f32], hole: &[bool], rows: usize, cols: usize) -> (Vec<f32>, usize) {
+    let valid = |i: usize| !hole[i] && src[i].is_finite();
+    let mut num: Vec<f32> = (0..src.len()).map(|i| if valid(i) { src[i] } else { 0.0 }).collect();
+    let mut den: Vec<f32> = (0..src.len()).map(|i| if valid(i) { 1.0 } else { 0.0 }).collect();
+    let mut fill = vec![f32::NAN; src.len()];
+    let mut pending = hole.iter().filter(|&&h| h).count();
+    let mut scales = 0;
+
+    while pending > 0 && scales < MAX_FILL_SCALES {
+        let step = 1usize << scales;
+        let (n, d) = rayon::join(
+            || atrous_smooth(&num, rows, cols, step),
+            || atrous_smooth(&den, rows, cols, step),
+        );
+        num = n;
+        den = d;
+        scales += 1;
+        pending -= fill
+            .par_iter_mut()
+            .enumerate()
+            .filter(|(i, f)| hole[*i] && f.is_nan() && den[*i] >= MIN_SUPPORT)
+            .map(|(i, f)| *f = num[i] / den[i])
+            .count();
+    }
+
+    if pending > 0 {
+        let mut background: Vec<f32> = (0..src.len()).filter(|&i| valid(i)).map(|i| src[i]).collect();
+        let fallback = if background.is_empty() { 0.0 } else { median_f32_mut(&mut background) };
+        fill.par_iter_mut().enumerate().filter(|(i, f)| hole[*i] && f.is_nan()).for_each(|(i, f)| {
+            *f = if den[i] > 0.0 { num[i] / den[i] } else { fallback };
+        });
+    }
+    (fill, scales)
+}
+
+pub fn screen_blend(base: &Array2<f32>, stars: &Array2<f32>) -> Array2<f32> {
+    ndarray::Zip::from(base).and(stars).par_map_collect(|&a, &b| {
+        let a = if a.is_finite() { a.clamp(0.0, 1.0) } else { 0.0 };
+        let b = if b.is_finite() { b.clamp(0.0, 1.0) } else { 0.0 };
+        1.0 - (1.0 - a) * (1.0 - b)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STARS: [(f64, f64); 4] = [(30.0, 30.0), (60.0, 90.0), (95.0, 40.0), (100.0, 100.0)];
+
+    fn field() -> Array2<f32> {
+        let sigma = 2.5 / 2.3548;
+        Array2::from_shape_fn((128, 128), |(y, x)| {
+            let noise = ((y * 7919 + x * 104_729) % 97) as f64 / 97.0 - 0.5;
+            let stars: f64 = STARS
+                .iter()
+                .map(|&(sy, sx)| {
+                    let r2 = (y as f64 - sy).powi(2) + (x as f64 - sx).powi(2);
+                    3000.0 * (-r2 / (2.0 * sigma * sigma)).exp()
+                })
+                .sum();
+            (200.0 + 0.3 * x as f64 + 4.0 * noise + stars) as f32
+        })
+    }
+
+    #[test]
+    fn test_removes_stars_and_keeps_background() {
+        let image = field();
+        let result = remove_stars(&[&image], &StarRemovalConfig::default()).unwrap();
+        assert!(result.stars_removed >= STARS.len(), "removed {}", result.stars_removed);
+
+        let (starless, stars) = (&result.starless[0], &result.stars[0]);
+        for &(sy, sx) in &STARS {
+            let (y, x) = (sy as usize, sx as usize);
+            let expected = 200.0 + 0.3 * sx as f32;
+            assert!((starless[[y, x]] - expected).abs() < 8.0, "starless {} at ({}, {})", starless[[y, x]], y, x);
+            assert!(stars[[y, x]] > 2900.0, "stars {} at ({}, {})", stars[[y, x]], y, x);
+        }
+        assert_eq!(stars[[5, 64]], 0.0);
+        assert!(starless.iter().zip(image.iter()).all(|(&s, &v)| s <= v));
+        let recombined = &result.starless[0] + &result.stars[0];
+        assert!(recombined.iter().zip(image.iter()).all(|(&a, &b)| (a - b).abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_size_limits_and_screen_blend() {
+        let image = field();
+        let config = StarRemovalConfig { max_fwhm: 1.2, ..StarRemovalConfig::default() };
+        let result = remove_stars(&[&image], &config).unwrap();
+        assert_eq!(result.stars_removed, 0);
+        assert_eq!(result.starless[0], image);
+
+        let base = Array2::from_elem((2, 2), 0.5f32);
+        let stars = Array2::from_shape_vec((2, 2), vec![0.0, 0.5, 1.0, f32::NAN]).unwrap();
+        let screen = screen_blend(&base, &stars);
+        assert_eq!(screen.as_slice().unwrap(), &[0.5, 0.75, 1.0, 0.5]);
+    }
+}
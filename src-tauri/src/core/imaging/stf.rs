@@ -47,7 +47,7 @@ fn mtf_balance(m: f64, t: f64) -> f64 {
 }
 
 #[inline(always)]
-fn mtf(x: f64, m: f64) -> f64 {
+pub(crate) fn mtf(x: f64, m: f64) -> f64 {
     if x <= 0.0 {
         return 0.0;
     }
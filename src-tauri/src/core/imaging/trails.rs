@@ -0,0 +1,386 @@
+use std::collections::VecDeque;
+
+use ndarray::Array2;
+use rayon::prelude::*;
+
+pub use crate::types::stacking::{DetectedTrail, FrameTrailReport};
+
+use crate::core::analysis::star_detection::estimate_background;
+
+const BACKGROUND_TILE: usize = 64;
+const THETA_STEPS: usize = 360;
+const STAR_MAX_EXTENT: usize = 12;
+const STAR_MIN_PIXELS: usize = 5;
+const MAX_HOUGH_POINTS: usize = 200_000;
+const LINE_TOLERANCE_PX: f64 = 4.0;
+const WIDTH_SEARCH_PX: i32 = 12;
+const MAX_GAP_PX: f64 = 25.0;
+const MIN_FILL: f64 = 0.35;
+const MAX_TRAIL_WIDTH: f64 = 25.0;
+const MAX_PEAK_CHECKS: usize = 32;
+const PEAK_SUPPRESS_RHO: usize = 4;
+const PEAK_SUPPRESS_THETA: usize = 4;
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct TrailConfig {
+    pub sigma: f32,
+    pub min_length: f64,
+    pub widen_px: f64,
+    pub max_trails: usize,
+}
+
+impl Default for TrailConfig {
+    fn default() -> Self {
+        Self {
+            sigma: 3.0,
+            min_length: 60.0,
+            widen_px: 4.0,
+            max_trails: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrailDetection {
+    pub trails: Vec<DetectedTrail>,
+    pub mask: Array2<u8>,
+    pub masked_pixels: usize,
+}
+
+impl TrailDetection {
+    pub fn report(&self, index: usize) -> FrameTrailReport {
+        FrameTrailReport {
+            index,
+            trails: self.trails.clone(),
+            masked_pixels: self.masked_pixels,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Line {
+    cx: f64,
+    cy: f64,
+    dir: (f64, f64),
+}
+
+impl Line {
+    fn project(&self, x: f64, y: f64) -> (f64, f64) {
+        let (dx, dy) = (x - self.cx, y - self.cy);
+        (dx * self.dir.0 + dy * self.dir.1, dy * self.dir.0 - dx * self.dir.1)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    line: Line,
+    t0: f64,
+    t1: f64,
+    half_width: f64,
+}
+
+pub fn detect_trails(image: &Array2<f32>, config: &TrailConfig) -> TrailDetection {
+    let (rows, cols) = image.dim();
+    let (background, sigma) = estimate_background(image, BACKGROUND_TILE);
+    let threshold = (background + sigma * config.sigma as f64) as f32;
+
+    let mut points = candidate_points(image, threshold);
+    if points.len() > MAX_HOUGH_POINTS {
+        let stride = points.len().div_ceil(MAX_HOUGH_POINTS);
+        points = points.into_iter().step_by(stride).collect();
+    }
+
+    let diag = ((rows * rows + cols * cols) as f64).sqrt().ceil() as usize;
+    let n_rho = 2 * diag + 1;
+    let trig: Vec<(f64, f64)> = (0..THETA_STEPS)
+        .map(|i| {
+            let theta = i as f64 * std::f64::consts::PI / THETA_STEPS as f64;
+            (theta.cos(), theta.sin())
+        })
+        .collect();
+
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut trails: Vec<DetectedTrail> = Vec::new();
+    let mut accumulator = hough(&points, &trig, diag, n_rho);
+    let mut checks = 0;
+
+    while trails.len() < config.max_trails && checks < MAX_PEAK_CHECKS && !points.is_empty() {
+        checks += 1;
+        let (peak, votes) = accumulator
+            .iter()
+            .enumerate()
+            .max_by_key(|&(_, v)| *v)
+            .map(|(i, v)| (i, *v))
+            .unwrap_or((0, 0));
+        if (votes as f64) < config.min_length * MIN_FILL {
+            break;
+        }
+
+        let (ti, ri) = (peak / n_rho, peak % n_rho);
+        let rho = ri as f64 - diag as f64;
+        let (c, s) = trig[ti];
+        let seed = Line { cx: rho * c, cy: rho * s, dir: (-s, c) };
+
+        match measure_segment(&points, seed, config.min_length) {
+            Some(segment) if segment.half_width * 2.0 <= MAX_TRAIL_WIDTH => {
+                let (x0, y0) = (
+                    segment.line.cx + segment.t0 * segment.line.dir.0,
+                    segment.line.cy + segment.t0 * segment.line.dir.1,
+                );
+                let (x1, y1) = (
+                    segment.line.cx + segment.t1 * segment.line.dir.0,
+                    segment.line.cy + segment.t1 * segment.line.dir.1,
+                );
+                let angle = segment.line.dir.1.atan2(segment.line.dir.0).to_degrees().rem_euclid(180.0);
+                trails.push(DetectedTrail {
+                    x0,
+                    y0,
+                    x1,
+                    y1,
+                    angle_deg: angle,
+                    length: segment.t1 - segment.t0,
+                    width: segment.half_width * 2.0,
+                    votes,
+                });
+                log::info!(
+                    "trail: ({:.0}, {:.0}) -> ({:.0}, {:.0}), angle={:.1}deg, width={:.1}px, votes={}",
+                    x0, y0, x1, y1, angle, segment.half_width * 2.0, votes
+                );
+
+                points.retain(|&(x, y)| {
+                    let (t, d) = segment.line.project(x, y);
+                    d.abs() > segment.half_width + LINE_TOLERANCE_PX || t < segment.t0 || t > segment.t1
+                });
+                segments.push(segment);
+                accumulator = hough(&points, &trig, diag, n_rho);
+            }
+            _ => suppress_peak(&mut accumulator, ti, ri, n_rho),
+        }
+    }
+
+    let mask = paint_mask(rows, cols, &segments, config.widen_px);
+    let masked_pixels = mask.iter().filter(|&&m| m != 0).count();
+    TrailDetection { trails, mask, masked_pixels }
+}
+
+fn candidate_points(image: &Array2<f32>, threshold: f32) -> Vec<(f64, f64)> {
+    let (rows, cols) = image.dim();
+    let above: Vec<bool> = image.iter().map(|&v| v.is_finite() && v > threshold).collect();
+    let mut visited = vec![false; rows * cols];
+    let mut points = Vec::new();
+    let mut queue = VecDeque::new();
+    let mut component = Vec::new();
+
+    for start in 0..rows * cols {
+        if !above[start] || visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        queue.push_back(start);
+        component.clear();
+        let (mut min_y, mut max_y, mut min_x, mut max_x) = (usize::MAX, 0, usize::MAX, 0);
+
+        while let Some(idx) = queue.pop_front() {
+            component.push(idx);
+            let (y, x) = (idx / cols, idx % cols);
+            min_y = min_y.min(y);
+            max_y = max_y.max(y);
+            min_x = min_x.min(x);
+            max_x = max_x.max(x);
+            for ny in y.saturating_sub(1)..=(y + 1).min(rows - 1) {
+                for nx in x.saturating_sub(1)..=(x + 1).min(cols - 1) {
+                    let n = ny * cols + nx;
+                    if above[n] && !visited[n] {
+                        visited[n] = true;
+                        queue.push_back(n);
+                    }
+                }
+            }
+        }
+
+        let extent = (max_y - min_y).max(max_x - min_x) + 1;
+        if extent <= STAR_MAX_EXTENT && component.len() >= STAR_MIN_PIXELS {
+            continue;
+        }
+        points.extend(component.iter().map(|&idx| ((idx % cols) as f64, (idx / cols) as f64)));
+    }
+    points
+}
+
+fn hough(points: &[(f64, f64)], trig: &[(f64, f64)], diag: usize, n_rho: usize) -> Vec<u32> {
+    let mut acc = vec![0u32; trig.len() * n_rho];
+    acc.par_chunks_mut(n_rho).zip(trig.par_iter()).for_each(|(column, &(c, s))| {
+        for &(x, y) in points {
+            let rho = (x * c + y * s).round() as isize + diag as isize;
+            if rho >= 0 && (rho as usize) < n_rho {
+                column[rho as usize] += 1;
+            }
+        }
+    });
+    acc
+}
+
+fn suppress_peak(acc: &mut [u32], ti: usize, ri: usize, n_rho: usize) {
+    let n_theta = acc.len() / n_rho;
+    for dt in -(PEAK_SUPPRESS_THETA as isize)..=PEAK_SUPPRESS_THETA as isize {
+        let t = (ti as isize + dt).rem_euclid(n_theta as isize) as usize;
+        for r in ri.saturating_sub(PEAK_SUPPRESS_RHO)..=(ri + PEAK_SUPPRESS_RHO).min(n_rho - 1) {
+            acc[t * n_rho + r] = 0;
+        }
+    }
+}
+
+fn fit_line(points: &[(f64, f64)], fallback: Line) -> Line {
+    if points.len() < 2 {
+        return fallback;
+    }
+    let n = points.len() as f64;
+    let cx = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let cy = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+    for &(x, y) in points {
+        let (dx, dy) = (x - cx, y - cy);
+        sxx += dx * dx;
+        sxy += dx * dy;
+        syy += dy * dy;
+    }
+    let angle = 0.5 * (2.0 * sxy).atan2(sxx - syy);
+    let mut dir = (angle.cos(), angle.sin());
+    if dir.0 * fallback.dir.0 + dir.1 * fallback.dir.1 < 0.0 {
+        dir = (-dir.0, -dir.1);
+    }
+    Line { cx, cy, dir }
+}
+
+fn measure_segment(points: &[(f64, f64)], seed: Line, min_length: f64) -> Option<Segment> {
+    let near = |line: Line, tol: f64| -> Vec<(f64, f64)> {
+        points
+            .iter()
+            .copied()
+            .filter(|&(x, y)| line.project(x, y).1.abs() <= tol)
+            .collect()
+    };
+
+    let line = fit_line(&near(seed, LINE_TOLERANCE_PX), seed);
+    let inliers = near(line, LINE_TOLERANCE_PX);
+    if inliers.len() < 2 {
+        return None;
+    }
+
+    let mut ts: Vec<f64> = inliers.iter().map(|&(x, y)| line.project(x, y).0).collect();
+    ts.sort_unstable_by(f64::total_cmp);
+    let (mut best, mut run_start) = ((0usize, 0usize), 0usize);
+    for i in 1..=ts.len() {
+        if i == ts.len() || ts[i] - ts[i - 1] > MAX_GAP_PX {
+            if ts[i - 1] - ts[run_start] > ts[best.1] - ts[best.0] {
+                best = (run_start, i - 1);
+            }
+            run_start = i;
+        }
+    }
+    let (t0, t1) = (ts[best.0], ts[best.1]);
+    let length = t1 - t0;
+    if length < min_length {
+        return None;
+    }
+
+    let covered = ts[best.0..=best.1]
+        .iter()
+        .map(|t| t.floor() as i64)
+        .fold((0usize, i64::MIN), |(count, last), bin| if bin != last { (count + 1, bin) } else { (count, last) })
+        .0;
+    if (covered as f64) < length * MIN_FILL {
+        return None;
+    }
+
+    let mut profile = vec![0u32; (2 * WIDTH_SEARCH_PX + 1) as usize];
+    for &(x, y) in points {
+        let (t, d) = line.project(x, y);
+        if t >= t0 && t <= t1 && d.abs() <= WIDTH_SEARCH_PX as f64 {
+            profile[(d.round() as i32 + WIDTH_SEARCH_PX) as usize] += 1;
+        }
+    }
+    let peak = profile.iter().copied().max().unwrap_or(0);
+    let occupied = profile.iter().filter(|&&c| c * 2 >= peak && c > 0).count();
+    let half_width = (occupied as f64 * 0.5).max(0.5);
+
+    Some(Segment { line, t0, t1, half_width })
+}
+
+fn paint_mask(rows: usize, cols: usize, segments: &[Segment], widen: f64) -> Array2<u8> {
+    let mut mask = vec![0u8; rows * cols];
+    if !segments.is_empty() {
+        mask.par_chunks_mut(cols).enumerate().for_each(|(y, row)| {
+            for (x, slot) in row.iter_mut().enumerate() {
+                let hit = segments.iter().any(|seg| {
+                    let (t, d) = seg.line.project(x as f64, y as f64);
+                    d.abs() <= seg.half_width + widen && t >= seg.t0 - widen && t <= seg.t1 + widen
+                });
+                if hit {
+                    *slot = 1;
+                }
+            }
+        });
+    }
+    Array2::from_shape_vec((rows, cols), mask).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(x: usize, y: usize) -> f32 {
+        let mut h = (x as u32).wrapping_mul(73_856_093) ^ (y as u32).wrapping_mul(19_349_663);
+        let mut sum = 0.0f32;
+        for _ in 0..4 {
+            h ^= h << 13;
+            h ^= h >> 17;
+            h ^= h << 5;
+            sum += (h % 10_000) as f32 / 10_000.0;
+        }
+        (sum - 2.0) * 1.7
+    }
+
+    fn field(rows: usize, cols: usize) -> Array2<f32> {
+        let stars = [(30.0, 40.0), (150.0, 60.0), (90.0, 170.0), (180.0, 180.0), (60.0, 120.0)];
+        Array2::from_shape_fn((rows, cols), |(y, x)| {
+            let star: f64 = stars
+                .iter()
+                .map(|&(sx, sy): &(f64, f64)| {
+                    600.0 * (-((x as f64 - sx).powi(2) + (y as f64 - sy).powi(2)) / 4.0).exp()
+                })
+                .sum();
+            100.0 + star as f32 + noise(x, y)
+        })
+    }
+
+    #[test]
+    fn test_detects_faint_trail_and_masks_it() {
+        let mut image = field(200, 220);
+        for x in 0..220 {
+            let yc = 20.0 + x as f64 * 0.6;
+            for y in 0..200 {
+                let d = (y as f64 - yc).abs() / (1.0f64 + 0.36).sqrt();
+                if d < 1.5 {
+                    image[[y, x]] += 8.0;
+                }
+            }
+        }
+
+        let result = detect_trails(&image, &TrailConfig::default());
+        assert_eq!(result.trails.len(), 1, "{:?}", result.trails);
+        let trail = &result.trails[0];
+        assert!((trail.angle_deg - 0.6f64.atan().to_degrees()).abs() < 1.0, "{}", trail.angle_deg);
+        assert!(trail.length > 180.0);
+        assert_eq!(result.mask[[80, 100]], 1);
+        assert_eq!(result.mask[[60, 150]], 0);
+        assert!(result.masked_pixels < 220 * 20);
+    }
+
+    #[test]
+    fn test_star_field_has_no_trails() {
+        let result = detect_trails(&field(200, 220), &TrailConfig::default());
+        assert!(result.trails.is_empty(), "{:?}", result.trails);
+        assert_eq!(result.masked_pixels, 0);
+    }
+}
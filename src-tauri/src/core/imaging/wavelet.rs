@@ -34,6 +34,7 @@ pub struct WaveletResult {
 
 static B3_KERNEL_1D: [f32; 5] = [1.0 / 16.0, 4.0 / 16.0, 6.0 / 16.0, 4.0 / 16.0, 1.0 / 16.0];
 
+pub const MAX_SCALES: usize = 8;
 const TRANSPOSE_BLOCK: usize = 64;
 const TRANSPOSE_THRESHOLD_STEP: usize = 16;
 const TRANSPOSE_THRESHOLD_ROWS: usize = 256;
@@ -44,41 +45,22 @@ pub fn wavelet_denoise(
     progress: Option<&ProgressHandle>,
 ) -> Result<WaveletResult> {
     let start = std::time::Instant::now();
-    let num_scales = config.num_scales.clamp(1, 8);
+    let num_scales = config.num_scales.clamp(1, MAX_SCALES);
     let (rows, cols) = image.dim();
-    let npix = rows * cols;
 
     if let Some(p) = progress {
         p.set_total((num_scales * 2 + 1) as u64);
     }
 
-    let mut scales: Vec<Vec<f32>> = Vec::with_capacity(num_scales);
-    let mut current = image.as_slice().unwrap().to_vec();
-    let mut h_buf = vec![0.0f32; npix];
-    let mut buf_a = vec![0.0f32; npix];
-    let mut t_buf = vec![0.0f32; npix];
-
-    for scale_idx in 0..num_scales {
+    let (mut scales, mut current) = decompose_slices(image.as_slice().unwrap().to_vec(), rows, cols, num_scales, |scale_idx| {
         if let Some(p) = progress {
             if p.is_cancelled() {
                 return Err(AppError::Cancelled.into());
             }
             p.tick_with_stage(&format!("decomposing scale {}/{}", scale_idx + 1, num_scales));
         }
-
-        let step = 1usize << scale_idx;
-        atrous_smooth_buffers(&current, rows, cols, step, &mut h_buf, &mut buf_a, &mut t_buf);
-
-        let detail: Vec<f32> = current
-            .par_iter()
-            .zip(buf_a.par_iter())
-            .map(|(&c, &s)| c - s)
-            .collect();
-        scales.push(detail);
-
-        std::mem::swap(&mut current, &mut buf_a);
-        buf_a.par_iter_mut().for_each(|v| *v = 0.0);
-    }
+        Ok(())
+    })?;
 
     let noise_sigma = estimate_noise_sigma(&scales[0]);
 
@@ -132,6 +114,74 @@ pub fn wavelet_denoise(
     })
 }
 
+pub struct WaveletLayers {
+    pub details: Vec<Array2<f32>>,
+    pub residual: Array2<f32>,
+}
+
+impl WaveletLayers {
+    pub fn reconstruct(&self) -> Array2<f32> {
+        self.details.iter().fold(self.residual.clone(), |acc, d| acc + d)
+    }
+}
+
+fn decompose_slices(
+    mut current: Vec<f32>,
+    rows: usize,
+    cols: usize,
+    num_scales: usize,
+    mut on_scale: impl FnMut(usize) -> Result<()>,
+) -> Result<(Vec<Vec<f32>>, Vec<f32>)> {
+    let npix = rows * cols;
+    let mut scales: Vec<Vec<f32>> = Vec::with_capacity(num_scales);
+    let mut h_buf = vec![0.0f32; npix];
+    let mut buf_a = vec![0.0f32; npix];
+    let mut t_buf = vec![0.0f32; npix];
+
+    for scale_idx in 0..num_scales {
+        on_scale(scale_idx)?;
+
+        let step = 1usize << scale_idx;
+        atrous_smooth_buffers(&current, rows, cols, step, &mut h_buf, &mut buf_a, &mut t_buf);
+
+        let detail: Vec<f32> = current
+            .par_iter()
+            .zip(buf_a.par_iter())
+            .map(|(&c, &s)| c - s)
+            .collect();
+        scales.push(detail);
+
+        std::mem::swap(&mut current, &mut buf_a);
+        buf_a.par_iter_mut().for_each(|v| *v = 0.0);
+    }
+    Ok((scales, current))
+}
+
+pub fn atrous_decompose(image: &Array2<f32>, num_scales: usize) -> WaveletLayers {
+    let (rows, cols) = image.dim();
+    let input: Vec<f32> = image.iter().map(|&v| if v.is_finite() { v } else { 0.0 }).collect();
+    let (details, residual) = decompose_slices(input, rows, cols, num_scales.clamp(1, MAX_SCALES), |_| Ok(()))
+        .expect("decomposition without cancellation cannot fail");
+    let to_array = |v: Vec<f32>| Array2::from_shape_vec((rows, cols), v).expect("layer shape");
+    WaveletLayers {
+        details: details.into_iter().map(to_array).collect(),
+        residual: to_array(residual),
+    }
+}
+
+pub(crate) fn layer_noise_sigma(finest_layer: &[f32]) -> f64 {
+    estimate_noise_sigma(finest_layer) / atrous_noise_scaling(0)
+}
+
+pub(crate) fn atrous_smooth(input: &[f32], rows: usize, cols: usize, step: usize) -> Vec<f32> {
+    let npix = rows * cols;
+    let mut h_buf = vec![0.0f32; npix];
+    let mut out = vec![0.0f32; npix];
+    let mut t_buf = vec![0.0f32; npix];
+    atrous_smooth_buffers(input, rows, cols, step, &mut h_buf, &mut out, &mut t_buf);
+    out
+}
+
 fn atrous_smooth_buffers(
     input: &[f32],
     rows: usize,
@@ -215,7 +265,7 @@ fn estimate_noise_sigma(finest_scale: &[f32]) -> f64 {
     (median as f64) * MAD_TO_SIGMA
 }
 
-fn atrous_noise_scaling(scale: usize) -> f64 {
+pub(crate) fn atrous_noise_scaling(scale: usize) -> f64 {
     const TABLE: [f64; 7] = [0.8908, 0.2007, 0.0856, 0.0413, 0.0205, 0.0103, 0.0051];
     if scale < TABLE.len() {
         TABLE[scale]
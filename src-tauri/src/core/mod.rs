@@ -7,3 +7,4 @@ pub mod astrometry;
 pub mod cube;
 pub mod metadata;
 pub mod synth;
+pub mod pixelmath;
@@ -0,0 +1,321 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use ndarray::Array2;
+use rayon::prelude::*;
+
+use crate::core::imaging::stats::compute_image_stats;
+use crate::core::imaging::stf::mtf;
+use crate::core::pixelmath::parser::{BinaryOp, Expr, Func, ImageRef, Stat, UnaryOp, Var};
+use crate::types::image::ImageStats;
+
+enum Node {
+    Const(f64),
+    X,
+    Y,
+    Pixel(usize),
+    Unary(UnaryOp, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Cond(Box<Node>, Box<Node>, Box<Node>),
+    Call(Func, Vec<Node>),
+}
+
+pub struct Program {
+    root: Node,
+    images: Vec<Arc<Array2<f32>>>,
+    dims: (usize, usize),
+}
+
+struct Compiler {
+    refs: Vec<ImageRef>,
+    images: Vec<Arc<Array2<f32>>>,
+    stats: Vec<Option<ImageStats>>,
+    dims: (usize, usize),
+}
+
+fn collect_refs(expr: &Expr, out: &mut Vec<ImageRef>) {
+    match expr {
+        Expr::Image(r) | Expr::Stat(_, r) => {
+            if !out.contains(r) {
+                out.push(r.clone());
+            }
+        }
+        Expr::Unary(_, a) => collect_refs(a, out),
+        Expr::Binary(_, a, b) => {
+            collect_refs(a, out);
+            collect_refs(b, out);
+        }
+        Expr::Cond(c, a, b) => {
+            collect_refs(c, out);
+            collect_refs(a, out);
+            collect_refs(b, out);
+        }
+        Expr::Call(_, args) => args.iter().for_each(|a| collect_refs(a, out)),
+        Expr::Num(_) | Expr::Var(_) => {}
+    }
+}
+
+fn describe(r: &ImageRef) -> String {
+    match r {
+        ImageRef::Target => "$T".to_string(),
+        ImageRef::Alias(name) => format!("${}", name),
+        ImageRef::Key(key) => format!("\"{}\"", key),
+    }
+}
+
+impl Compiler {
+    fn index_of(&self, r: &ImageRef) -> usize {
+        self.refs.iter().position(|x| x == r).expect("reference collected")
+    }
+
+    fn stat(&mut self, stat: Stat, r: &ImageRef) -> f64 {
+        let i = self.index_of(r);
+        let image = &self.images[i];
+        let s = self.stats[i].get_or_insert_with(|| compute_image_stats(image));
+        match stat {
+            Stat::Median => s.median,
+            Stat::Mad => s.mad,
+            Stat::Mean => s.mean,
+            Stat::Sdev => s.sigma,
+            Stat::Min => s.min,
+            Stat::Max => s.max,
+        }
+    }
+
+    fn lower(&mut self, expr: &Expr) -> Node {
+        match expr {
+            Expr::Num(v) => Node::Const(*v),
+            Expr::Var(Var::X) => Node::X,
+            Expr::Var(Var::Y) => Node::Y,
+            Expr::Var(Var::Width) => Node::Const(self.dims.1 as f64),
+            Expr::Var(Var::Height) => Node::Const(self.dims.0 as f64),
+            Expr::Image(r) => Node::Pixel(self.index_of(r)),
+            Expr::Stat(stat, r) => Node::Const(self.stat(*stat, r)),
+            Expr::Unary(op, a) => fold(Node::Unary(*op, Box::new(self.lower(a)))),
+            Expr::Binary(op, a, b) => fold(Node::Binary(*op, Box::new(self.lower(a)), Box::new(self.lower(b)))),
+            Expr::Cond(c, a, b) => match self.lower(c) {
+                Node::Const(v) => if truthy(v) { self.lower(a) } else { self.lower(b) },
+                c => Node::Cond(Box::new(c), Box::new(self.lower(a)), Box::new(self.lower(b))),
+            },
+            Expr::Call(func, args) => fold(Node::Call(*func, args.iter().map(|a| self.lower(a)).collect())),
+        }
+    }
+}
+
+fn fold(node: Node) -> Node {
+    let constant = match &node {
+        Node::Unary(_, a) => matches!(**a, Node::Const(_)),
+        Node::Binary(_, a, b) => matches!((&**a, &**b), (Node::Const(_), Node::Const(_))),
+        Node::Call(_, args) => args.iter().all(|a| matches!(a, Node::Const(_))),
+        _ => false,
+    };
+    if constant {
+        Node::Const(eval(&node, &[], 0, 0, 0))
+    } else {
+        node
+    }
+}
+
+impl Program {
+    pub fn compile<R>(expr: &Expr, mut resolve: R, dims: Option<(usize, usize)>) -> Result<Self>
+    where
+        R: FnMut(&ImageRef) -> Result<Arc<Array2<f32>>>,
+    {
+        let mut refs = Vec::new();
+        collect_refs(expr, &mut refs);
+        let images: Vec<Arc<Array2<f32>>> = refs.iter().map(&mut resolve).collect::<Result<_>>()?;
+
+        let Some(dims) = dims.or_else(|| images.first().map(|img| img.dim())) else {
+            bail!("Expression references no images; provide a target to define the output size");
+        };
+        for (r, image) in refs.iter().zip(&images) {
+            if image.dim() != dims {
+                bail!(
+                    "Image {} is {}x{} but the output is {}x{}",
+                    describe(r), image.ncols(), image.nrows(), dims.1, dims.0
+                );
+            }
+            if image.as_slice().is_none() {
+                bail!("Image {} is not contiguous", describe(r));
+            }
+        }
+
+        let mut compiler = Compiler { stats: vec![None; refs.len()], refs, images, dims };
+        let root = compiler.lower(expr);
+        Ok(Self { root, images: compiler.images, dims })
+    }
+
+    pub fn evaluate(&self) -> Array2<f32> {
+        let (rows, cols) = self.dims;
+        let slices: Vec<&[f32]> = self.images.iter().map(|img| img.as_slice().expect("contiguous")).collect();
+        let mut out = vec![0.0f32; rows * cols];
+        out.par_chunks_mut(cols.max(1)).enumerate().for_each(|(y, row)| {
+            for (x, px) in row.iter_mut().enumerate() {
+                *px = eval(&self.root, &slices, y * cols + x, y, x) as f32;
+            }
+        });
+        Array2::from_shape_vec((rows, cols), out).unwrap()
+    }
+}
+
+pub fn rescale_to_unit(image: &mut Array2<f32>) {
+    let (lo, hi) = image
+        .iter()
+        .filter(|v| v.is_finite())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    let range = hi - lo;
+    if !range.is_finite() || range <= 0.0 {
+        return;
+    }
+    image.par_mapv_inplace(|v| (v - lo) / range);
+}
+
+#[inline]
+fn truthy(v: f64) -> bool {
+    v != 0.0 && !v.is_nan()
+}
+
+#[inline]
+fn flag(b: bool) -> f64 {
+    if b { 1.0 } else { 0.0 }
+}
+
+fn eval(node: &Node, images: &[&[f32]], idx: usize, y: usize, x: usize) -> f64 {
+    match node {
+        Node::Const(v) => *v,
+        Node::X => x as f64,
+        Node::Y => y as f64,
+        Node::Pixel(i) => images[*i][idx] as f64,
+        Node::Unary(op, a) => {
+            let a = eval(a, images, idx, y, x);
+            match op {
+                UnaryOp::Neg => -a,
+                UnaryOp::Not => flag(!truthy(a)),
+            }
+        }
+        Node::Binary(BinaryOp::And, a, b) => {
+            flag(truthy(eval(a, images, idx, y, x)) && truthy(eval(b, images, idx, y, x)))
+        }
+        Node::Binary(BinaryOp::Or, a, b) => {
+            flag(truthy(eval(a, images, idx, y, x)) || truthy(eval(b, images, idx, y, x)))
+        }
+        Node::Binary(op, a, b) => {
+            let (a, b) = (eval(a, images, idx, y, x), eval(b, images, idx, y, x));
+            match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Sub => a - b,
+                BinaryOp::Mul => a * b,
+                BinaryOp::Div => a / b,
+                BinaryOp::Pow => a.powf(b),
+                BinaryOp::Lt => flag(a < b),
+                BinaryOp::Le => flag(a <= b),
+                BinaryOp::Gt => flag(a > b),
+                BinaryOp::Ge => flag(a >= b),
+                BinaryOp::Eq => flag(a == b),
+                BinaryOp::Ne => flag(a != b),
+                BinaryOp::And | BinaryOp::Or => unreachable!(),
+            }
+        }
+        Node::Cond(c, a, b) => {
+            if truthy(eval(c, images, idx, y, x)) {
+                eval(a, images, idx, y, x)
+            } else {
+                eval(b, images, idx, y, x)
+            }
+        }
+        Node::Call(Func::Iif, args) => {
+            if truthy(eval(&args[0], images, idx, y, x)) {
+                eval(&args[1], images, idx, y, x)
+            } else {
+                eval(&args[2], images, idx, y, x)
+            }
+        }
+        Node::Call(func, args) => {
+            let arg = |i: usize| eval(&args[i], images, idx, y, x);
+            match func {
+                Func::Abs => arg(0).abs(),
+                Func::Sqrt => arg(0).sqrt(),
+                Func::Ln => arg(0).ln(),
+                Func::Log => arg(0).log10(),
+                Func::Log2 => arg(0).log2(),
+                Func::Exp => arg(0).exp(),
+                Func::Pow => arg(0).powf(arg(1)),
+                Func::Asinh => arg(0).asinh(),
+                Func::Floor => arg(0).floor(),
+                Func::Ceil => arg(0).ceil(),
+                Func::Round => arg(0).round(),
+                Func::Clamp => arg(0).clamp(arg(1), arg(2).max(arg(1))),
+                Func::Mtf => mtf(arg(1), arg(0)),
+                Func::Min => (0..args.len()).map(arg).fold(f64::INFINITY, f64::min),
+                Func::Max => (0..args.len()).map(arg).fold(f64::NEG_INFINITY, f64::max),
+                Func::Mean => (0..args.len()).map(arg).sum::<f64>() / args.len() as f64,
+                Func::Median => {
+                    let mut values: Vec<f64> = (0..args.len()).map(arg).collect();
+                    values.sort_by(|a, b| a.total_cmp(b));
+                    let mid = values.len() / 2;
+                    if values.len().is_multiple_of(2) {
+                        (values[mid - 1] + values[mid]) * 0.5
+                    } else {
+                        values[mid]
+                    }
+                }
+                Func::Iif => unreachable!(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::pixelmath::parser::parse;
+
+    fn run(source: &str, images: &[(&str, Array2<f32>)]) -> Result<Array2<f32>> {
+        let expr = parse(source)?;
+        let program = Program::compile(
+            &expr,
+            |r| {
+                let name = match r {
+                    ImageRef::Target => "T",
+                    ImageRef::Alias(name) | ImageRef::Key(name) => name.as_str(),
+                };
+                images
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, img)| Arc::new(img.clone()))
+                    .ok_or_else(|| anyhow::anyhow!("missing {}", name))
+            },
+            None,
+        )?;
+        Ok(program.evaluate())
+    }
+
+    #[test]
+    fn test_arithmetic_conditionals_and_statistics() {
+        let t = Array2::from_shape_fn((4, 5), |(y, x)| (y * 5 + x) as f32);
+        let b = Array2::from_elem((4, 5), 2.0f32);
+        let out = run("$T > med($T) ? max($T, $b) - x : iif(y == 0, -1, $b ^ 2 / 4)", &[("T", t.clone()), ("b", b.clone())]).unwrap();
+        let median = compute_image_stats(&t).median as f32;
+        for ((y, x), &v) in out.indexed_iter() {
+            let expected = if t[[y, x]] > median {
+                t[[y, x]].max(2.0) - x as f32
+            } else if y == 0 {
+                -1.0
+            } else {
+                1.0
+            };
+            assert_eq!(v, expected, "at ({}, {})", y, x);
+        }
+
+        let mixed = run("median($T, 'other', 1) + mtf(0.5, 0.25) + w * h", &[("T", t), ("other", b)]).unwrap();
+        assert!((mixed[[3, 4]] - (2.0 + 0.25 + 20.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_dimension_mismatch_and_missing_size() {
+        let a = Array2::from_elem((4, 4), 1.0f32);
+        let b = Array2::from_elem((3, 4), 1.0f32);
+        assert!(run("$a + $b", &[("a", a), ("b", b)]).is_err());
+        assert!(run("1 + 2", &[]).is_err());
+    }
+}
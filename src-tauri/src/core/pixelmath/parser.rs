@@ -0,0 +1,420 @@
+use anyhow::{bail, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ImageRef {
+    Target,
+    Alias(String),
+    Key(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Var {
+    X,
+    Y,
+    Width,
+    Height,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Func {
+    Abs,
+    Sqrt,
+    Ln,
+    Log,
+    Log2,
+    Exp,
+    Pow,
+    Asinh,
+    Floor,
+    Ceil,
+    Round,
+    Min,
+    Max,
+    Median,
+    Mean,
+    Clamp,
+    Mtf,
+    Iif,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stat {
+    Median,
+    Mad,
+    Mean,
+    Sdev,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Num(f64),
+    Var(Var),
+    Image(ImageRef),
+    Stat(Stat, ImageRef),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+    Call(Func, Vec<Expr>),
+}
+
+impl Func {
+    fn lookup(name: &str) -> Option<(Self, usize, Option<usize>)> {
+        let entry = match name {
+            "abs" => (Self::Abs, 1, Some(1)),
+            "sqrt" => (Self::Sqrt, 1, Some(1)),
+            "ln" => (Self::Ln, 1, Some(1)),
+            "log" => (Self::Log, 1, Some(1)),
+            "log2" => (Self::Log2, 1, Some(1)),
+            "exp" => (Self::Exp, 1, Some(1)),
+            "pow" => (Self::Pow, 2, Some(2)),
+            "asinh" => (Self::Asinh, 1, Some(1)),
+            "floor" => (Self::Floor, 1, Some(1)),
+            "ceil" => (Self::Ceil, 1, Some(1)),
+            "round" => (Self::Round, 1, Some(1)),
+            "min" => (Self::Min, 1, None),
+            "max" => (Self::Max, 1, None),
+            "median" => (Self::Median, 1, None),
+            "mean" => (Self::Mean, 1, None),
+            "clamp" | "range" => (Self::Clamp, 3, Some(3)),
+            "mtf" => (Self::Mtf, 2, Some(2)),
+            "iif" => (Self::Iif, 3, Some(3)),
+            _ => return None,
+        };
+        Some(entry)
+    }
+}
+
+impl Stat {
+    fn lookup(name: &str) -> Option<Self> {
+        match name {
+            "med" => Some(Self::Median),
+            "mad" => Some(Self::Mad),
+            "avg" => Some(Self::Mean),
+            "sdev" => Some(Self::Sdev),
+            "minval" => Some(Self::Min),
+            "maxval" => Some(Self::Max),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Image(String),
+    Str(String),
+    Op(&'static str),
+}
+
+const TWO_CHAR_OPS: &[&str] = &["<=", ">=", "==", "!=", "&&", "||"];
+const ONE_CHAR_OPS: &[&str] = &["+", "-", "*", "/", "^", "<", ">", "!", "(", ")", ",", "?", ":"];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            if i < chars.len() && matches!(chars[i], 'e' | 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && matches!(chars[j], '+' | '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let value = text.parse::<f64>().map_err(|_| anyhow::anyhow!("Invalid number '{}' at {}", text, start))?;
+            tokens.push((Token::Num(value), start));
+            continue;
+        }
+        if c.is_ascii_alphabetic() || c == '_' || c == '$' {
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            if let Some(name) = text.strip_prefix('$') {
+                if name.is_empty() {
+                    bail!("Expected image identifier after '$' at {}", start);
+                }
+                tokens.push((Token::Image(name.to_string()), start));
+            } else {
+                tokens.push((Token::Ident(text), start));
+            }
+            continue;
+        }
+        if c == '"' || c == '\'' {
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                i += 1;
+            }
+            if i >= chars.len() {
+                bail!("Unterminated string starting at {}", start);
+            }
+            tokens.push((Token::Str(chars[start + 1..i].iter().collect()), start));
+            i += 1;
+            continue;
+        }
+        let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+        if let Some(op) = TWO_CHAR_OPS.iter().find(|&&op| op == two) {
+            tokens.push((Token::Op(op), start));
+            i += 2;
+            continue;
+        }
+        if let Some(op) = ONE_CHAR_OPS.iter().find(|&&op| op.starts_with(c)) {
+            tokens.push((Token::Op(op), start));
+            i += 1;
+            continue;
+        }
+        bail!("Unexpected character '{}' at {}", c, start);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens.get(self.pos).map(|&(_, o)| o).unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(t, _)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Op(o)) if *o == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<()> {
+        if !self.eat(op) {
+            bail!("Expected '{}' at {}", op, self.offset());
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self) -> Result<Expr> {
+        let cond = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(cond);
+        }
+        let then = self.expression()?;
+        self.expect(":")?;
+        let otherwise = self.expression()?;
+        Ok(Expr::Cond(Box::new(cond), Box::new(then), Box::new(otherwise)))
+    }
+
+    fn binary(&mut self, min_prec: u8) -> Result<Expr> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Op(op)) = self.peek() {
+            let Some((binop, prec)) = binary_op(op) else {
+                break;
+            };
+            if prec < min_prec {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.binary(prec + 1)?;
+            lhs = Expr::Binary(binop, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat("-") {
+            return Ok(Expr::Unary(UnaryOp::Neg, Box::new(self.unary()?)));
+        }
+        if self.eat("+") {
+            return self.unary();
+        }
+        if self.eat("!") {
+            return Ok(Expr::Unary(UnaryOp::Not, Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expr> {
+        let base = self.primary()?;
+        if self.eat("^") {
+            let exponent = self.unary()?;
+            return Ok(Expr::Binary(BinaryOp::Pow, Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        let offset = self.offset();
+        match self.next() {
+            Some(Token::Num(v)) => Ok(Expr::Num(v)),
+            Some(Token::Image(name)) => Ok(Expr::Image(image_ref(name))),
+            Some(Token::Str(key)) => Ok(Expr::Image(ImageRef::Key(key))),
+            Some(Token::Op("(")) => {
+                let inner = self.expression()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Some(Token::Ident(name)) => {
+                if self.eat("(") {
+                    return self.call(&name, offset);
+                }
+                match name.as_str() {
+                    "x" => Ok(Expr::Var(Var::X)),
+                    "y" => Ok(Expr::Var(Var::Y)),
+                    "w" | "width" => Ok(Expr::Var(Var::Width)),
+                    "h" | "height" => Ok(Expr::Var(Var::Height)),
+                    "pi" => Ok(Expr::Num(std::f64::consts::PI)),
+                    "e" => Ok(Expr::Num(std::f64::consts::E)),
+                    _ => bail!("Unknown identifier '{}' at {} (images are referenced as $name)", name, offset),
+                }
+            }
+            Some(other) => bail!("Unexpected token {:?} at {}", other, offset),
+            None => bail!("Unexpected end of expression"),
+        }
+    }
+
+    fn call(&mut self, name: &str, offset: usize) -> Result<Expr> {
+        let mut args = Vec::new();
+        if !self.eat(")") {
+            loop {
+                args.push(self.expression()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+
+        if let Some(stat) = Stat::lookup(name) {
+            return match args.as_slice() {
+                [Expr::Image(r)] => Ok(Expr::Stat(stat, r.clone())),
+                _ => bail!("{}() at {} takes a single image reference", name, offset),
+            };
+        }
+        let Some((func, min_args, max_args)) = Func::lookup(name) else {
+            bail!("Unknown function '{}' at {}", name, offset);
+        };
+        if args.len() < min_args || max_args.is_some_and(|m| args.len() > m) {
+            bail!("Wrong number of arguments for {}() at {}: got {}", name, offset, args.len());
+        }
+        Ok(Expr::Call(func, args))
+    }
+}
+
+fn binary_op(op: &str) -> Option<(BinaryOp, u8)> {
+    let entry = match op {
+        "||" => (BinaryOp::Or, 1),
+        "&&" => (BinaryOp::And, 2),
+        "==" => (BinaryOp::Eq, 3),
+        "!=" => (BinaryOp::Ne, 3),
+        "<" => (BinaryOp::Lt, 4),
+        "<=" => (BinaryOp::Le, 4),
+        ">" => (BinaryOp::Gt, 4),
+        ">=" => (BinaryOp::Ge, 4),
+        "+" => (BinaryOp::Add, 5),
+        "-" => (BinaryOp::Sub, 5),
+        "*" => (BinaryOp::Mul, 6),
+        "/" => (BinaryOp::Div, 6),
+        _ => return None,
+    };
+    Some(entry)
+}
+
+fn image_ref(name: String) -> ImageRef {
+    if name == "T" {
+        ImageRef::Target
+    } else {
+        ImageRef::Alias(name)
+    }
+}
+
+pub fn parse(source: &str) -> Result<Expr> {
+    let tokens = tokenize(source)?;
+    if tokens.is_empty() {
+        bail!("Empty expression");
+    }
+    let mut parser = Parser { tokens, pos: 0, end: source.chars().count() };
+    let expr = parser.expression()?;
+    if parser.pos < parser.tokens.len() {
+        bail!("Unexpected trailing input at {}", parser.offset());
+    }
+    Ok(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precedence_and_references() {
+        let expr = parse("-$T^2 + 3 * \"stack.fits\" > med($T) ? 1 : 0").unwrap();
+        let Expr::Cond(cond, _, _) = expr else { panic!("expected conditional") };
+        let Expr::Binary(BinaryOp::Gt, lhs, rhs) = *cond else { panic!("expected comparison") };
+        assert_eq!(*rhs, Expr::Stat(Stat::Median, ImageRef::Target));
+        let Expr::Binary(BinaryOp::Add, neg, mul) = *lhs else { panic!("expected sum") };
+        assert!(matches!(*neg, Expr::Unary(UnaryOp::Neg, ref p) if matches!(**p, Expr::Binary(BinaryOp::Pow, _, _))));
+        assert!(matches!(*mul, Expr::Binary(BinaryOp::Mul, _, ref k) if **k == Expr::Image(ImageRef::Key("stack.fits".into()))));
+    }
+
+    #[test]
+    fn test_rejects_malformed_expressions() {
+        for bad in ["", "1 +", "foo", "abs(1, 2)", "med(1)", "(1", "1 2", "'open"] {
+            assert!(parse(bad).is_err(), "{} should fail", bad);
+        }
+        assert!(parse("min($a, $b, 0.5) * 1e-3").is_ok());
+    }
+}
@@ -3,6 +3,8 @@ use ndarray::Array2;
 use rayon::prelude::*;
 
 use crate::core::alignment::affine;
+use crate::core::alignment::distortion::{self, DistortionModel};
+use crate::core::alignment::mapping::warp_mapped;
 use crate::core::alignment::phase_correlation;
 use crate::core::imaging::sampling::bicubic_sample;
 use crate::types::compose::AlignMethod;
@@ -16,6 +18,7 @@ pub struct AlignPairResult {
     pub matched_stars: usize,
     pub inliers: usize,
     pub residual_px: f64,
+    pub is_flipped: bool,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -70,7 +73,10 @@ pub fn estimate_offset(
                 confidence: pc.confidence,
             }
         }
-        AlignMethod::Affine => {
+        AlignMethod::Affine
+        | AlignMethod::Polynomial2
+        | AlignMethod::Polynomial3
+        | AlignMethod::ThinPlate => {
             let result = affine::align_channel_affine(reference, target);
             OffsetEstimate {
                 dy: result.transform.ty,
@@ -100,6 +106,7 @@ pub fn align_pair(
                 matched_stars: 0,
                 inliers: 0,
                 residual_px: 0.0,
+                is_flipped: false,
             })
         }
         AlignMethod::Affine => {
@@ -113,6 +120,23 @@ pub fn align_pair(
                 matched_stars: result.matched_stars,
                 inliers: result.inliers,
                 residual_px: result.residual_px,
+                is_flipped: result.is_flipped,
+            })
+        }
+        AlignMethod::Polynomial2 | AlignMethod::Polynomial3 | AlignMethod::ThinPlate => {
+            let model = DistortionModel::from_align_method(method).expect("distortion method");
+            let result = distortion::align_channel_distortion(reference, target, model);
+            let warped = warp_mapped(target, &result.forward, rows, cols);
+            let (dx, dy) = result.forward.offset_at(cols as f64 * 0.5, rows as f64 * 0.5);
+            Ok(AlignPairResult {
+                aligned: warped,
+                offset: (-dy, -dx),
+                confidence: if result.inliers > 0 { 1.0 } else { 0.0 },
+                method_used: result.method_label(),
+                matched_stars: result.matched_stars,
+                inliers: result.inliers,
+                residual_px: result.residual_px,
+                is_flipped: result.affine.is_flipped,
             })
         }
     }
@@ -137,9 +161,12 @@ pub fn align_pair_with_label(
                 result.confidence,
             );
         }
-        AlignMethod::Affine => {
+        AlignMethod::Affine
+        | AlignMethod::Polynomial2
+        | AlignMethod::Polynomial3
+        | AlignMethod::ThinPlate => {
             log::info!(
-                "{} alignment: method={}, stars={}, inliers={}, residual={:.3}px, tx={:.2}, ty={:.2}",
+                "{} alignment: method={}, stars={}, inliers={}, residual={:.3}px, tx={:.2}, ty={:.2}, flipped={}",
                 label,
                 result.method_used,
                 result.matched_stars,
@@ -147,6 +174,7 @@ pub fn align_pair_with_label(
                 result.residual_px,
                 result.offset.1,
                 result.offset.0,
+                result.is_flipped,
             );
         }
     }
@@ -2,14 +2,59 @@ use anyhow::{bail, Context, Result};
 use ndarray::Array2;
 use rayon::prelude::*;
 
+use crate::core::alignment::mapping::FrameMapping;
+use crate::core::alignment::registration::RegistrationSet;
+use crate::core::stacking::cosmetic::{
+    apply_cosmetic_correction, build_defect_map, detect_light_hot_pixels, CosmeticConfig,
+    DefectMap,
+};
+use crate::core::stacking::dark_scaling::{
+    dark_scale_factor, scale_for_light, DarkScaling, FrameExposure,
+};
+use crate::core::imaging::debayer::CfaLayout;
+use crate::core::imaging::trails::{self, TrailConfig};
+use crate::core::stacking::drizzle;
 use crate::math::median::f32_cmp;
-pub(crate) use crate::infra::fits::reader::load_fits_image;
+use crate::types::header::HduHeader;
+use crate::types::constants::{DQ_DO_NOT_USE, EXT_DQ, EXT_ERR};
+use crate::types::stacking::{
+    CalibrationReport, DrizzleFrameWeights, DrizzleGeometry, DrizzleWeighting, FrameTrailReport, ReferenceChoice,
+};
+pub(crate) use crate::infra::fits::reader::{
+    load_fits_extension, load_fits_image, load_fits_image_with_header,
+};
 
 pub struct CalibrationConfig {
     pub master_bias: Option<Array2<f32>>,
     pub master_dark: Option<Array2<f32>>,
     pub master_flat: Option<Array2<f32>>,
     pub dark_exposure_ratio: f32,
+    pub dark_scaling: DarkScaling,
+    pub dark_exposure: FrameExposure,
+    pub defect_map: Option<DefectMap>,
+    pub light_hot_sigma: Option<f32>,
+}
+
+impl Default for CalibrationConfig {
+    fn default() -> Self {
+        Self {
+            master_bias: None,
+            master_dark: None,
+            master_flat: None,
+            dark_exposure_ratio: 1.0,
+            dark_scaling: DarkScaling::default(),
+            dark_exposure: FrameExposure::default(),
+            defect_map: None,
+            light_hot_sigma: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CalibratedFrame {
+    pub image: Array2<f32>,
+    pub dark_scale: Option<f32>,
+    pub cosmetic_corrected: usize,
 }
 
 pub fn subtract_bias(image: &Array2<f32>, master_bias: &Array2<f32>) -> Array2<f32> {
@@ -44,7 +89,73 @@ pub fn divide_flat(image: &Array2<f32>, master_flat: &Array2<f32>) -> Array2<f32
     Array2::from_shape_vec((rows, cols), result).unwrap()
 }
 
+pub fn resolve_dark_scale(
+    light: &Array2<f32>,
+    light_exposure: &FrameExposure,
+    config: &CalibrationConfig,
+) -> f32 {
+    match &config.master_dark {
+        Some(master_dark) => scale_for_light(
+            light,
+            light_exposure,
+            master_dark,
+            config.master_bias.as_ref(),
+            &config.dark_exposure,
+            &config.dark_scaling,
+            config.dark_exposure_ratio,
+        ),
+        None => config.dark_exposure_ratio,
+    }
+}
+
+pub fn calibrate_frame(
+    raw: &Array2<f32>,
+    light_exposure: &FrameExposure,
+    config: &CalibrationConfig,
+) -> CalibratedFrame {
+    let dark_scale = config
+        .master_dark
+        .as_ref()
+        .map(|_| resolve_dark_scale(raw, light_exposure, config));
+    let image = calibrate_image_with_dark_scale(
+        raw,
+        config,
+        dark_scale.unwrap_or(config.dark_exposure_ratio),
+    );
+    let (image, cosmetic_corrected) = apply_cosmetic(image, config);
+    CalibratedFrame { image, dark_scale, cosmetic_corrected }
+}
+
 pub fn calibrate_image(raw: &Array2<f32>, config: &CalibrationConfig) -> Array2<f32> {
+    let image = calibrate_image_with_dark_scale(raw, config, config.dark_exposure_ratio);
+    apply_cosmetic(image, config).0
+}
+
+fn apply_cosmetic(image: Array2<f32>, config: &CalibrationConfig) -> (Array2<f32>, usize) {
+    let light_hot = config
+        .light_hot_sigma
+        .map(|sigma| detect_light_hot_pixels(&image, sigma))
+        .unwrap_or_default();
+    let defect_map = config.defect_map.as_ref();
+
+    if defect_map.is_none() && light_hot.is_empty() {
+        return (image, 0);
+    }
+
+    match apply_cosmetic_correction(&image, defect_map, &light_hot) {
+        Ok(corrected) => corrected,
+        Err(e) => {
+            log::warn!("Cosmetic correction skipped: {}", e);
+            (image, 0)
+        }
+    }
+}
+
+fn calibrate_image_with_dark_scale(
+    raw: &Array2<f32>,
+    config: &CalibrationConfig,
+    dark_ratio: f32,
+) -> Array2<f32> {
     let (rows, cols) = raw.dim();
     let npix = rows * cols;
     let src = raw.as_slice().expect("contiguous");
@@ -52,7 +163,6 @@ pub fn calibrate_image(raw: &Array2<f32>, config: &CalibrationConfig) -> Array2<
     let bias_slice = config.master_bias.as_ref().and_then(|b| b.as_slice());
     let dark_slice = config.master_dark.as_ref().and_then(|d| d.as_slice());
     let flat_slice = config.master_flat.as_ref().and_then(|f| f.as_slice());
-    let dark_ratio = config.dark_exposure_ratio;
 
     let result: Vec<f32> = (0..npix)
         .into_par_iter()
@@ -74,7 +184,7 @@ pub fn calibrate_image(raw: &Array2<f32>, config: &CalibrationConfig) -> Array2<
                 }
             }
 
-            if v < 0.0 { 0.0 } else { v }
+            v
         })
         .collect();
 
@@ -156,12 +266,21 @@ pub fn create_master_dark(
     dark_paths: &[String],
     master_bias: Option<&Array2<f32>>,
 ) -> Result<Array2<f32>> {
+    create_master_dark_with_exposure(dark_paths, master_bias).map(|(dark, _)| dark)
+}
+
+pub fn create_master_dark_with_exposure(
+    dark_paths: &[String],
+    master_bias: Option<&Array2<f32>>,
+) -> Result<(Array2<f32>, FrameExposure)> {
     if dark_paths.is_empty() {
         bail!("No dark frames provided");
     }
 
-    let first = load_fits_image(&dark_paths[0])?;
+    let (first, first_header) = load_fits_image_with_header(&dark_paths[0])?;
     let (rows, cols) = first.dim();
+    let mut exposures = Vec::with_capacity(dark_paths.len());
+    exposures.push(FrameExposure::from_header(&first_header));
 
     let first = match master_bias {
         Some(bias) => subtract_bias(&first, bias),
@@ -172,7 +291,7 @@ pub fn create_master_dark(
     frames.push(first);
 
     for path in &dark_paths[1..] {
-        let mut frame = load_fits_image(path)?;
+        let (mut frame, header) = load_fits_image_with_header(path)?;
         if frame.dim() != (rows, cols) {
             bail!(
                 "Dimension mismatch: expected ({}, {}), got {:?}",
@@ -182,49 +301,56 @@ pub fn create_master_dark(
         if let Some(bias) = master_bias {
             frame = subtract_bias(&frame, bias);
         }
+        exposures.push(FrameExposure::from_header(&header));
         frames.push(frame);
     }
 
     let result = median_combine_row_major(frames, rows, cols);
+    let dark = Array2::from_shape_vec((rows, cols), result)
+        .context("Failed to reshape master dark")?;
 
-    Ok(Array2::from_shape_vec((rows, cols), result)
-        .context("Failed to reshape master dark")?)
+    Ok((dark, FrameExposure::median_of(&exposures)))
 }
 
 pub fn create_master_flat(
     flat_paths: &[String],
     master_bias: Option<&Array2<f32>>,
     master_dark: Option<&Array2<f32>>,
+    dark_exposure: Option<&FrameExposure>,
 ) -> Result<Array2<f32>> {
     if flat_paths.is_empty() {
         bail!("No flat frames provided");
     }
 
-    let first = load_fits_image(&flat_paths[0])?;
+    let (first, first_header) = load_fits_image_with_header(&flat_paths[0])?;
     let (rows, cols) = first.dim();
 
-    let preprocess = |mut frame: Array2<f32>| -> Array2<f32> {
+    let preprocess = |mut frame: Array2<f32>, header: &HduHeader| -> Array2<f32> {
         if let Some(bias) = master_bias {
             frame = subtract_bias(&frame, bias);
         }
         if let Some(dark) = master_dark {
-            frame = subtract_dark(&frame, dark, 1.0);
+            let ratio = dark_exposure
+                .filter(|_| master_bias.is_some())
+                .and_then(|d| dark_scale_factor(&FrameExposure::from_header(header), d, None))
+                .unwrap_or(1.0);
+            frame = subtract_dark(&frame, dark, ratio);
         }
         frame
     };
 
     let mut frames = Vec::with_capacity(flat_paths.len());
-    frames.push(preprocess(first));
+    frames.push(preprocess(first, &first_header));
 
     for path in &flat_paths[1..] {
-        let frame = load_fits_image(path)?;
+        let (frame, header) = load_fits_image_with_header(path)?;
         if frame.dim() != (rows, cols) {
             bail!(
                 "Dimension mismatch: expected ({}, {}), got {:?}",
                 rows, cols, frame.dim()
             );
         }
-        frames.push(preprocess(frame));
+        frames.push(preprocess(frame, &header));
     }
 
     let mut result = median_combine_row_major(frames, rows, cols);
@@ -260,19 +386,43 @@ pub fn calibrate_from_paths(
     dark_paths: Option<&[String]>,
     flat_paths: Option<&[String]>,
     dark_exposure_ratio: f32,
-) -> Result<Array2<f32>> {
-    let science = load_fits_image(science_path)?;
+    dark_scaling: DarkScaling,
+    cosmetic: Option<&CosmeticConfig>,
+) -> Result<(CalibratedFrame, Option<DefectMap>)> {
+    let (science, science_header) = load_fits_image_with_header(science_path)?;
+    let config = prepare_calibration(
+        bias_paths,
+        dark_paths,
+        flat_paths,
+        dark_exposure_ratio,
+        dark_scaling,
+        cosmetic,
+        science_path,
+    )?;
+    let frame = calibrate_frame(&science, &FrameExposure::from_header(&science_header), &config);
+    Ok((frame, config.defect_map))
+}
 
+pub fn prepare_calibration(
+    bias_paths: Option<&[String]>,
+    dark_paths: Option<&[String]>,
+    flat_paths: Option<&[String]>,
+    dark_exposure_ratio: f32,
+    dark_scaling: DarkScaling,
+    cosmetic: Option<&CosmeticConfig>,
+    reference_light: &str,
+) -> Result<CalibrationConfig> {
     let master_bias = match bias_paths {
         Some(paths) if !paths.is_empty() => Some(create_master_bias(paths)?),
         _ => None,
     };
 
-    let master_dark = match dark_paths {
+    let (master_dark, dark_exposure) = match dark_paths {
         Some(paths) if !paths.is_empty() => {
-            Some(create_master_dark(paths, master_bias.as_ref())?)
+            let (dark, exposure) = create_master_dark_with_exposure(paths, master_bias.as_ref())?;
+            (Some(dark), exposure)
         }
-        _ => None,
+        _ => (None, FrameExposure::default()),
     };
 
     let master_flat = match flat_paths {
@@ -280,66 +430,357 @@ pub fn calibrate_from_paths(
             paths,
             master_bias.as_ref(),
             master_dark.as_ref(),
+            Some(&dark_exposure),
         )?),
         _ => None,
     };
 
-    let config = CalibrationConfig {
+    let defect_map = match cosmetic {
+        Some(cfg) => {
+            let dims = match master_bias.as_ref().or(master_dark.as_ref()).or(master_flat.as_ref()) {
+                Some(master) => master.dim(),
+                None => load_fits_image(reference_light)?.dim(),
+            };
+            resolve_defect_map(cfg, master_dark.as_ref(), master_flat.as_ref(), dims)?
+        }
+        None => None,
+    };
+
+    Ok(CalibrationConfig {
         master_bias,
         master_dark,
         master_flat,
         dark_exposure_ratio,
+        dark_scaling,
+        dark_exposure,
+        defect_map,
+        light_hot_sigma: cosmetic
+            .filter(|c| c.detect_in_lights)
+            .map(|c| c.light_hot_sigma),
+    })
+}
+
+fn resolve_defect_map(
+    cfg: &CosmeticConfig,
+    master_dark: Option<&Array2<f32>>,
+    master_flat: Option<&Array2<f32>>,
+    dims: (usize, usize),
+) -> Result<Option<DefectMap>> {
+    let has_lines = !cfg.bad_columns.is_empty() || !cfg.bad_rows.is_empty();
+    let mut map = match &cfg.defect_map {
+        Some(map) => map.clone(),
+        None if master_dark.is_some() || master_flat.is_some() => build_defect_map(master_dark, master_flat, cfg)?,
+        None if has_lines => DefectMap::empty(dims.0, dims.1),
+        None => return Ok(None),
+    };
+    if has_lines {
+        map.add_columns(&cfg.bad_columns);
+        map.add_rows(&cfg.bad_rows);
+    }
+    Ok(Some(map))
+}
+
+pub(crate) fn load_calibrated_frame(
+    path: &str,
+    index: usize,
+    calibration: Option<&CalibrationConfig>,
+) -> Result<(CalibratedFrame, HduHeader)> {
+    let (img, header) = load_fits_image_with_header(path)?;
+    let cal = match calibration {
+        Some(cal) => cal,
+        None => return Ok((CalibratedFrame { image: img, dark_scale: None, cosmetic_corrected: 0 }, header)),
     };
 
-    Ok(calibrate_image(&science, &config))
+    let frame = calibrate_frame(&img, &FrameExposure::from_header(&header), cal);
+    if let Some(k) = frame.dark_scale {
+        log::info!(
+            "frame_{} calibration: dark_scale={:.4} (mode={})",
+            index, k, cal.dark_scaling.mode
+        );
+    }
+    if frame.cosmetic_corrected > 0 {
+        log::info!(
+            "frame_{} calibration: {} defective pixels corrected",
+            index, frame.cosmetic_corrected
+        );
+    }
+    Ok((frame, header))
 }
 
 pub fn stack_from_paths(
     paths: &[String],
     config: &crate::types::stacking::StackConfig,
     calibration: Option<&CalibrationConfig>,
+    registration: Option<&RegistrationSet>,
+    trail_config: Option<&TrailConfig>,
 ) -> Result<crate::types::stacking::StackResult> {
+    let (images, _, report) = load_calibrated_frames(paths, calibration)?;
+    let (masks, reports) = match trail_config {
+        Some(cfg) => detect_frame_trails(&images, cfg),
+        None => (Vec::new(), Vec::new()),
+    };
+
+    let mut result = match registration {
+        Some(set) => crate::core::stacking::combine::stack_registered(&images, &masks, paths, set, config)?,
+        None => crate::core::stacking::combine::stack_images_with(&images, &masks, config)?,
+    };
+    result.trails = reports;
+    result.calibration = report;
+    Ok(result)
+}
+
+fn detect_frame_trails(
+    images: &[Array2<f32>],
+    config: &TrailConfig,
+) -> (Vec<Option<Array2<u8>>>, Vec<FrameTrailReport>) {
+    images
+        .par_iter()
+        .enumerate()
+        .map(|(i, image)| {
+            let detection = trails::detect_trails(image, config);
+            if !detection.trails.is_empty() {
+                log::info!(
+                    "frame_{}: {} trail(s), {} pixels masked",
+                    i, detection.trails.len(), detection.masked_pixels
+                );
+            }
+            let report = detection.report(i);
+            ((!detection.trails.is_empty()).then_some(detection.mask), report)
+        })
+        .unzip()
+}
+
+pub(crate) fn load_calibrated_frames(
+    paths: &[String],
+    calibration: Option<&CalibrationConfig>,
+) -> Result<(Vec<Array2<f32>>, Vec<HduHeader>, CalibrationReport)> {
     if paths.is_empty() {
         bail!("No image paths provided");
     }
+    let mut images = Vec::with_capacity(paths.len());
+    let mut headers = Vec::with_capacity(paths.len());
+    let mut report = CalibrationReport::default();
+    for (i, path) in paths.iter().enumerate() {
+        let (frame, header) = load_calibrated_frame(path, i, calibration)?;
+        report.dark_scales.push(frame.dark_scale);
+        report.cosmetic_corrected.push(frame.cosmetic_corrected);
+        images.push(frame.image);
+        headers.push(header);
+    }
+    Ok((images, headers, report))
+}
 
-    let images: Vec<Array2<f32>> = paths
-        .iter()
-        .map(|path| {
-            let img = load_fits_image(path)?;
-            match calibration {
-                Some(cal) => Ok(calibrate_image(&img, cal)),
-                None => Ok(img),
+fn dq_mask(dq: &Array2<f32>, bad_bits: u32) -> Array2<u8> {
+    dq.mapv(|v| (v.is_finite() && v >= 0.0 && (v as u32) & bad_bits != 0) as u8)
+}
+
+fn load_frame_weights(
+    path: &str,
+    index: usize,
+    image: &Array2<f32>,
+    header: &HduHeader,
+    weighting: &DrizzleWeighting,
+    reference_exposure: Option<f64>,
+) -> Result<DrizzleFrameWeights> {
+    let dims = image.dim();
+    let check = |arr: &Array2<f32>, what: &str| -> Result<()> {
+        if arr.dim() != dims {
+            bail!(
+                "{} for frame_{} is {}x{}, frame is {}x{}",
+                what, index, arr.dim().1, arr.dim().0, dims.1, dims.0
+            );
+        }
+        Ok(())
+    };
+
+    let weight = match weighting.weight_paths.as_ref().and_then(|p| p.get(index)) {
+        Some(weight_path) => {
+            let w = load_fits_image(weight_path)?;
+            check(&w, "Weight map")?;
+            Some(w)
+        }
+        None if weighting.use_err => match load_fits_extension(path, EXT_ERR)? {
+            Some(err) => {
+                check(&err, "ERR extension")?;
+                Some(err.mapv(|e| if e.is_finite() && e > 0.0 { 1.0 / (e * e) } else { 0.0 }))
             }
-        })
-        .collect::<Result<_>>()?;
+            None => {
+                log::warn!("frame_{}: no ERR extension in {}, using uniform weight", index, path);
+                None
+            }
+        },
+        None => None,
+    };
 
-    crate::core::stacking::combine::stack_images(&images, config)
+    let mut masks: Vec<Array2<u8>> = Vec::new();
+    if let Some(mask_path) = weighting.mask_paths.as_ref().and_then(|p| p.get(index)) {
+        let m = load_fits_image(mask_path)?;
+        check(&m, "Mask")?;
+        masks.push(m.mapv(|v| (v.is_finite() && v != 0.0) as u8));
+    }
+    if weighting.use_dq {
+        if let Some(dq) = load_fits_extension(path, EXT_DQ)? {
+            check(&dq, "DQ extension")?;
+            masks.push(dq_mask(&dq, weighting.dq_bad_bits.unwrap_or(DQ_DO_NOT_USE)));
+        }
+    }
+    if let Some(level) = weighting.saturation {
+        masks.push(image.mapv(|v| (v >= level) as u8));
+    }
+    let mask = masks.into_iter().reduce(|mut acc, m| {
+        acc.zip_mut_with(&m, |a, &b| *a |= b);
+        acc
+    });
+
+    let frame_weight = if weighting.exposure_weight {
+        match (FrameExposure::from_header(header).exposure, reference_exposure) {
+            (Some(exp), Some(reference)) if reference > 0.0 => (exp / reference) as f32,
+            _ => 1.0,
+        }
+    } else {
+        1.0
+    };
+
+    Ok(DrizzleFrameWeights { weight, mask, frame_weight })
+}
+
+struct DrizzleInputs {
+    images: Vec<Array2<f32>>,
+    headers: Vec<HduHeader>,
+    weights: Vec<DrizzleFrameWeights>,
+    trails: Vec<FrameTrailReport>,
+    calibration: CalibrationReport,
+}
+
+fn load_drizzle_inputs(
+    paths: &[String],
+    calibration: Option<&CalibrationConfig>,
+    weighting: Option<&DrizzleWeighting>,
+) -> Result<DrizzleInputs> {
+    let (images, headers, report) = load_calibrated_frames(paths, calibration)?;
+
+    let mut weights = match weighting {
+        Some(w) => {
+            let exposures: Vec<FrameExposure> = headers.iter().map(FrameExposure::from_header).collect();
+            let reference = FrameExposure::median_of(&exposures).exposure;
+            paths
+                .iter()
+                .enumerate()
+                .map(|(i, path)| load_frame_weights(path, i, &images[i], &headers[i], w, reference))
+                .collect::<Result<Vec<_>>>()?
+        }
+        None => Vec::new(),
+    };
+
+    let trails = match weighting.and_then(|w| w.trail_sigma) {
+        Some(sigma) => {
+            let config = TrailConfig { sigma, ..TrailConfig::default() };
+            let (masks, reports) = detect_frame_trails(&images, &config);
+            for (fw, trail_mask) in weights.iter_mut().zip(masks) {
+                let Some(trail_mask) = trail_mask else { continue };
+                match fw.mask.as_mut() {
+                    Some(mask) => mask.zip_mut_with(&trail_mask, |a, &b| *a |= b),
+                    None => fw.mask = Some(trail_mask),
+                }
+            }
+            reports
+        }
+        None => Vec::new(),
+    };
+
+    Ok(DrizzleInputs { images, headers, weights, trails, calibration: report })
 }
 
 pub fn drizzle_from_paths(
     paths: &[String],
     config: &crate::types::stacking::DrizzleConfig,
     calibration: Option<&CalibrationConfig>,
+    weighting: Option<&DrizzleWeighting>,
+    registration: Option<&RegistrationSet>,
 ) -> Result<crate::types::stacking::DrizzleResult> {
-    if paths.is_empty() {
-        bail!("No image paths provided");
+    let DrizzleInputs { images, headers, weights, trails, calibration } =
+        load_drizzle_inputs(paths, calibration, weighting)?;
+
+    let images_ref: Vec<&Array2<f32>> = images.iter().collect();
+    let mapped = drizzle_mappings(paths, &images_ref, &headers, config, registration)?;
+    let mut result = drizzle::drizzle_stack_with(
+        &images,
+        mapped.as_ref().map(|(m, _)| m.as_slice()),
+        &weights,
+        config,
+    )?;
+    if let Some((_, reference)) = mapped {
+        result.reference = reference;
     }
+    result.trails = trails;
+    result.calibration = calibration;
+    Ok(result)
+}
 
-    let mut images: Vec<Array2<f32>> = Vec::with_capacity(paths.len());
-    for path in paths {
-        let mut img = load_fits_image(path)?;
-        if let Some(cal) = calibration {
-            img = calibrate_image(&img, cal);
+pub fn drizzle_cfa_from_paths(
+    paths: &[String],
+    pattern: Option<&str>,
+    config: &crate::types::stacking::DrizzleConfig,
+    calibration: Option<&CalibrationConfig>,
+    weighting: Option<&DrizzleWeighting>,
+    registration: Option<&RegistrationSet>,
+) -> Result<(crate::types::stacking::CfaDrizzleResult, CfaLayout)> {
+    let DrizzleInputs { images, headers, weights, trails, calibration } =
+        load_drizzle_inputs(paths, calibration, weighting)?;
+
+    let layout = match pattern {
+        Some(p) => {
+            let (x_offset, y_offset) = CfaLayout::header_offsets(&headers[0]);
+            CfaLayout::parse(p, x_offset, y_offset)
+                .with_context(|| format!("Unrecognised CFA pattern: {}", p))?
         }
-        images.push(img);
+        None => headers
+            .iter()
+            .find_map(CfaLayout::from_header)
+            .with_context(|| format!("No BAYERPAT in {}; specify the CFA pattern", paths[0]))?,
+    };
+    let luminance: Vec<Array2<f32>> = if registration.is_none() && config.geometry == DrizzleGeometry::Wcs {
+        images.iter().map(|f| drizzle::cfa_luminance(f, &layout)).collect()
+    } else {
+        Vec::new()
+    };
+    let luminance_ref: Vec<&Array2<f32>> = luminance.iter().collect();
+    let mapped = drizzle_mappings(paths, &luminance_ref, &headers, config, registration)?;
+    let mut result = drizzle::drizzle_cfa_stack_with(
+        &images,
+        &layout,
+        mapped.as_ref().map(|(m, _)| m.as_slice()),
+        &weights,
+        config,
+    )?;
+    if let Some((_, reference)) = mapped {
+        result.reference = reference;
     }
+    result.trails = trails;
+    result.calibration = calibration;
+    Ok((result, layout))
+}
 
-    crate::core::stacking::drizzle::drizzle_stack(&images, config)
+fn drizzle_mappings(
+    paths: &[String],
+    images: &[&Array2<f32>],
+    headers: &[HduHeader],
+    config: &crate::types::stacking::DrizzleConfig,
+    registration: Option<&RegistrationSet>,
+) -> Result<Option<(Vec<FrameMapping>, ReferenceChoice)>> {
+    if let Some(set) = registration {
+        return Ok(Some((set.inverse_mappings(paths)?, set.reference_for(paths))));
+    }
+    if config.geometry == DrizzleGeometry::Wcs {
+        let reference = drizzle::drizzle_reference(images, config)?;
+        return Ok(Some((drizzle::wcs_mappings(headers, reference.index)?, reference)));
+    }
+    Ok(None)
 }
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::stacking::dark_scaling::DarkScaleMode;
 
     #[test]
     fn test_subtract_bias() {
@@ -402,10 +843,156 @@ mod tests {
             master_dark: Some(dark),
             master_flat: Some(flat),
             dark_exposure_ratio: 1.0,
+            ..Default::default()
         };
 
         let result = calibrate_image(&raw, &config);
         assert!((result[[0, 0]] - 95.0).abs() < 1e-4);
         assert!((result[[2, 2]] - 175.0).abs() < 1e-4);
     }
+
+    #[test]
+    fn test_calibration_keeps_negative_residuals() {
+        let raw = Array2::from_shape_vec((2, 2), vec![100.0, 104.0, 96.0, 100.0]).unwrap();
+        let config = CalibrationConfig {
+            master_bias: Some(Array2::from_elem((2, 2), 100.0)),
+            ..Default::default()
+        };
+
+        let result = calibrate_image(&raw, &config);
+        assert!((result[[0, 1]] - 4.0).abs() < 1e-4);
+        assert!((result[[1, 0]] + 4.0).abs() < 1e-4);
+        assert!(result.sum().abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_calibrate_frame_scales_dark_by_exposure() {
+        let raw = Array2::from_shape_vec((2, 2), vec![300.0; 4]).unwrap();
+        let dark = Array2::from_shape_vec((2, 2), vec![40.0; 4]).unwrap();
+
+        let mut config = CalibrationConfig {
+            master_bias: Some(Array2::from_elem((2, 2), 10.0)),
+            master_dark: Some(dark),
+            dark_scaling: DarkScaling {
+                mode: DarkScaleMode::Exposure,
+                temperature_doubling: None,
+            },
+            dark_exposure: FrameExposure { exposure: Some(600.0), temperature: None },
+            ..Default::default()
+        };
+
+        let light = FrameExposure { exposure: Some(150.0), temperature: None };
+        let frame = calibrate_frame(&raw, &light, &config);
+        assert_eq!(frame.dark_scale, Some(0.25));
+        assert!((frame.image[[1, 1]] - 280.0).abs() < 1e-4);
+
+        config.master_bias = None;
+        let frame = calibrate_frame(&raw, &light, &config);
+        assert_eq!(frame.dark_scale, Some(1.0));
+        assert!((frame.image[[1, 1]] - 260.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_stack_loader_applies_per_frame_dark_scale() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let paths: Vec<String> = [60.0, 120.0]
+            .iter()
+            .enumerate()
+            .map(|(i, &exposure)| {
+                let mut header = crate::infra::raster::synthetic_header(4, 4, 1, -32);
+                header.set_f64("EXPTIME", exposure);
+                let path = dir.path().join(format!("light_{}.fits", i)).to_string_lossy().into_owned();
+                let light = Array2::from_elem((4, 4), 100.0 + exposure as f32);
+                crate::infra::fits::writer::write_fits_mono(&path, &light, Some(&header)).unwrap();
+                path
+            })
+            .collect();
+
+        let config = CalibrationConfig {
+            master_bias: Some(Array2::from_elem((4, 4), 10.0)),
+            master_dark: Some(Array2::from_elem((4, 4), 20.0)),
+            dark_scaling: DarkScaling { mode: DarkScaleMode::Exposure, temperature_doubling: None },
+            dark_exposure: FrameExposure { exposure: Some(120.0), temperature: None },
+            ..Default::default()
+        };
+        let (images, _, report) = load_calibrated_frames(&paths, Some(&config)).unwrap();
+        assert_eq!(report.dark_scales, vec![Some(0.5), Some(1.0)]);
+        assert_eq!(report.cosmetic_corrected, vec![0, 0]);
+        assert!((images[0][[1, 1]] - 140.0).abs() < 1e-3, "{}", images[0][[1, 1]]);
+        assert!((images[1][[1, 1]] - 190.0).abs() < 1e-3, "{}", images[1][[1, 1]]);
+    }
+
+    #[test]
+    fn test_cfa_drizzle_pattern_override_keeps_header_offsets() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let paths: Vec<String> = (0..2)
+            .map(|i| {
+                let mut header = crate::infra::raster::synthetic_header(16, 16, 1, -32);
+                header.set("XBAYROFF", "1".to_string());
+                let path = dir.path().join(format!("cfa_{}.fits", i)).to_string_lossy().into_owned();
+                let frame = Array2::from_elem((16, 16), 100.0f32);
+                crate::infra::fits::writer::write_fits_mono(&path, &frame, Some(&header)).unwrap();
+                path
+            })
+            .collect();
+
+        let config = crate::types::stacking::DrizzleConfig { align: false, ..Default::default() };
+        let (_, layout) = drizzle_cfa_from_paths(&paths, Some("RGGB"), &config, None, None, None).unwrap();
+        assert_eq!(layout.x_offset, 1);
+        assert_eq!(layout.y_offset, 0);
+        assert_eq!(layout.pattern_string(), "GRBG");
+    }
+
+    #[test]
+    fn test_calibrate_frame_without_dark_reports_no_scale() {
+        let raw = Array2::from_shape_vec((2, 2), vec![300.0; 4]).unwrap();
+        let frame = calibrate_frame(&raw, &FrameExposure::default(), &CalibrationConfig::default());
+        assert!(frame.dark_scale.is_none());
+        assert!((frame.image[[0, 0]] - 300.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_dq_mask_ignores_informational_bits() {
+        let dq = Array2::from_shape_vec((1, 4), vec![0.0, 1.0, 4.0, 5.0 + 262144.0]).unwrap();
+        assert_eq!(dq_mask(&dq, DQ_DO_NOT_USE).iter().copied().collect::<Vec<u8>>(), vec![0, 1, 0, 1]);
+        assert_eq!(dq_mask(&dq, 4).iter().copied().collect::<Vec<u8>>(), vec![0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn test_loaded_defect_map_merges_bad_lines() {
+        let mut mask = Array2::zeros((4, 6));
+        mask[[1, 1]] = crate::core::stacking::cosmetic::DEFECT_HOT;
+        let loaded = DefectMap::from_codes(mask);
+        let cfg = CosmeticConfig {
+            bad_columns: vec![4],
+            bad_rows: vec![3],
+            defect_map: Some(loaded),
+            ..Default::default()
+        };
+
+        let map = resolve_defect_map(&cfg, None, None, (4, 6)).unwrap().unwrap();
+        assert_eq!(map.hot_count, 1);
+        assert_eq!(map.line_count, 9);
+        assert_eq!(map.mask[[0, 4]], crate::core::stacking::cosmetic::DEFECT_LINE);
+        assert_eq!(map.mask[[3, 0]], crate::core::stacking::cosmetic::DEFECT_LINE);
+    }
+
+    #[test]
+    fn test_calibrate_frame_applies_defect_map() {
+        let mut raw = Array2::from_elem((5, 5), 200.0f32);
+        raw[[2, 2]] = 4000.0;
+        let dark = Array2::from_elem((5, 5), 0.0f32);
+        let mut map = DefectMap::empty(5, 5);
+        map.mask[[2, 2]] = crate::core::stacking::cosmetic::DEFECT_HOT;
+
+        let config = CalibrationConfig {
+            master_dark: Some(dark),
+            defect_map: Some(map),
+            ..Default::default()
+        };
+
+        let frame = calibrate_frame(&raw, &FrameExposure::default(), &config);
+        assert_eq!(frame.cosmetic_corrected, 1);
+        assert!((frame.image[[2, 2]] - 200.0).abs() < 1e-4);
+    }
 }
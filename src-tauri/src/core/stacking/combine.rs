@@ -5,10 +5,13 @@ use ndarray::Array2;
 use rayon::prelude::*;
 
 pub use crate::types::stacking::{StackConfig, StackResult};
+use crate::core::analysis::subframe::{select_reference, SubframeWeightConfig};
+use crate::types::stacking::{CalibrationReport, ReferenceChoice};
 use crate::math::median::f32_cmp;
 use crate::types::compose::AlignMethod;
 use crate::types::constants::MAD_TO_SIGMA;
 
+use crate::core::alignment::registration::RegistrationSet;
 use crate::core::stacking::align;
 
 pub fn sigma_clip_combine(
@@ -91,13 +94,24 @@ pub fn sigma_clip_combine(
     (mean as f32, rejected)
 }
 
+const MASK_THRESHOLD: f32 = 0.1;
+
 pub fn stack_images(
     images: &[Array2<f32>],
     config: &StackConfig,
+) -> Result<StackResult> {
+    stack_images_with(images, &[], config)
+}
+
+pub fn stack_images_with(
+    images: &[Array2<f32>],
+    masks: &[Option<Array2<u8>>],
+    config: &StackConfig,
 ) -> Result<StackResult> {
     if images.is_empty() {
         bail!("No images to stack");
     }
+    check_masks(images, masks)?;
 
     let n = images.len();
 
@@ -112,38 +126,134 @@ pub fn stack_images(
         img.slice(ndarray::s![..min_rows, ..min_cols]).to_owned()
     };
 
-    let ref_cropped = crop(&images[0]);
+    let reference = if config.align {
+        let refs: Vec<&Array2<f32>> = images.iter().collect();
+        select_reference(&refs, config.reference, &SubframeWeightConfig::default())?
+    } else {
+        ReferenceChoice::fixed(0, "alignment disabled")
+    };
+    let ref_cropped = crop(&images[reference.index]);
 
     let mut aligned: Vec<Array2<f32>> = Vec::with_capacity(n);
     let mut offsets: Vec<(i32, i32)> = Vec::with_capacity(n);
 
-    aligned.push(ref_cropped.clone());
-    offsets.push((0, 0));
-
-    for i in 1..n {
-        let cropped = crop(&images[i]);
-
-        if config.align {
+    for (i, image) in images.iter().enumerate() {
+        let (mut frame, offset) = if i == reference.index {
+            (ref_cropped.clone(), (0.0, 0.0))
+        } else if config.align {
             let result = align::align_pair_with_label(
                 &ref_cropped,
-                &cropped,
+                &crop(image),
                 AlignMethod::PhaseCorrelation,
                 min_rows,
                 min_cols,
                 &format!("frame_{}", i),
             )?;
-            let dy = result.offset.0.round() as i32;
-            let dx = result.offset.1.round() as i32;
-            offsets.push((dy, dx));
-            aligned.push(result.aligned);
+            (result.aligned, result.offset)
         } else {
-            offsets.push((0, 0));
-            aligned.push(cropped);
+            (crop(image), (0.0, 0.0))
+        };
+
+        if let Some(mask) = masks.get(i).and_then(|m| m.as_ref()) {
+            let shifted = align::shift_image_subpixel(&crop_mask(mask, min_rows, min_cols), offset.0, offset.1);
+            reject_masked(&mut frame, &shifted);
+        }
+        offsets.push((offset.0.round() as i32, offset.1.round() as i32));
+        aligned.push(frame);
+    }
+
+    let (image, rejected_pixels) = combine_aligned(&aligned, config)?;
+    Ok(StackResult {
+        image,
+        frame_count: n,
+        rejected_pixels,
+        offsets,
+        reference,
+        trails: Vec::new(),
+        calibration: CalibrationReport::default(),
+    })
+}
+
+fn check_masks(images: &[Array2<f32>], masks: &[Option<Array2<u8>>]) -> Result<()> {
+    if !masks.is_empty() && masks.len() != images.len() {
+        bail!("Got {} masks for {} frames", masks.len(), images.len());
+    }
+    for (i, (image, mask)) in images.iter().zip(masks).enumerate() {
+        if mask.as_ref().is_some_and(|m| m.dim() != image.dim()) {
+            bail!("Mask for frame_{} does not match the frame size", i);
+        }
+    }
+    Ok(())
+}
+
+fn crop_mask(mask: &Array2<u8>, rows: usize, cols: usize) -> Array2<f32> {
+    mask.slice(ndarray::s![..rows, ..cols]).mapv(|m| if m != 0 { 1.0 } else { 0.0 })
+}
+
+fn reject_masked(image: &mut Array2<f32>, mask: &Array2<f32>) {
+    image.zip_mut_with(mask, |v, &m| {
+        if m > MASK_THRESHOLD {
+            *v = f32::NAN;
         }
+    });
+}
+
+pub fn stack_registered(
+    images: &[Array2<f32>],
+    masks: &[Option<Array2<u8>>],
+    paths: &[String],
+    registration: &RegistrationSet,
+    config: &StackConfig,
+) -> Result<StackResult> {
+    if images.is_empty() {
+        bail!("No images to stack");
+    }
+    if images.len() != paths.len() {
+        bail!("{} images but {} paths", images.len(), paths.len());
     }
+    check_masks(images, masks)?;
+
+    let frames = registration.frames_for(paths)?;
+    let (rows, cols) = (registration.rows, registration.cols);
+    let cx = cols as f64 * 0.5;
+    let cy = rows as f64 * 0.5;
+    let aligned: Vec<Array2<f32>> = images
+        .par_iter()
+        .zip(frames.par_iter())
+        .enumerate()
+        .map(|(i, (img, frame))| {
+            let mut warped = frame.warp_fill(img, rows, cols, f32::NAN);
+            if let Some(mask) = masks.get(i).and_then(|m| m.as_ref()) {
+                let as_f32 = mask.mapv(|m| if m != 0 { 1.0 } else { 0.0 });
+                reject_masked(&mut warped, &frame.warp(&as_f32, rows, cols));
+            }
+            warped
+        })
+        .collect();
+    let offsets = frames
+        .iter()
+        .map(|frame| {
+            let (dx, dy) = frame.offset_at(cx, cy);
+            (dy.round() as i32, dx.round() as i32)
+        })
+        .collect();
+
+    let reference = registration.reference_for(paths);
+
+    let (image, rejected_pixels) = combine_aligned(&aligned, config)?;
+    Ok(StackResult {
+        image,
+        frame_count: images.len(),
+        rejected_pixels,
+        offsets,
+        reference,
+        trails: Vec::new(),
+        calibration: CalibrationReport::default(),
+    })
+}
 
-    let rows = min_rows;
-    let cols = min_cols;
+pub(crate) fn combine_aligned(aligned: &[Array2<f32>], config: &StackConfig) -> Result<(Array2<f32>, u64)> {
+    let (rows, cols) = aligned[0].dim();
     let npix = rows * cols;
     let sigma_low = config.sigma_low;
     let sigma_high = config.sigma_high;
@@ -182,14 +292,9 @@ pub fn stack_images(
         });
 
     let rejected_pixels = total_rejected.load(Ordering::Relaxed);
-
-    Ok(StackResult {
-        image: Array2::from_shape_vec((rows, cols), result_data)
-            .context("Failed to reshape stacked image")?,
-        frame_count: n,
-        rejected_pixels,
-        offsets,
-    })
+    let image = Array2::from_shape_vec((rows, cols), result_data)
+        .context("Failed to reshape stacked image")?;
+    Ok((image, rejected_pixels))
 }
 
 #[cfg(test)]
@@ -276,10 +381,70 @@ mod tests {
             sigma_high: 3.0,
             max_iterations: 5,
             align: false,
+            reference: Default::default(),
         };
 
         let result = stack_images(&images, &config).unwrap();
         assert!((result.image[[2, 2]] - 100.0).abs() < 1.0);
         assert!(result.rejected_pixels > 0);
     }
+
+    #[test]
+    fn test_masked_pixels_are_excluded() {
+        let clean = Array2::from_shape_vec((4, 4), vec![100.0; 16]).unwrap();
+        let mut streaked = clean.clone();
+        streaked.row_mut(1).fill(900.0);
+        let mut mask = Array2::<u8>::zeros((4, 4));
+        mask.row_mut(1).fill(1);
+
+        let images = vec![clean.clone(), streaked, clean];
+        let masks = vec![None, Some(mask), None];
+        let config = StackConfig { align: false, max_iterations: 0, ..Default::default() };
+
+        let unmasked = stack_images(&images, &config).unwrap();
+        assert!(unmasked.image[[1, 2]] > 300.0);
+        let result = stack_images_with(&images, &masks, &config).unwrap();
+        assert!((result.image[[1, 2]] - 100.0).abs() < 1e-4);
+        assert!((result.image[[2, 2]] - 100.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_registered_edges_exclude_out_of_frame_pixels() {
+        use crate::core::alignment::mapping::FrameMapping;
+        use crate::core::alignment::registration::{FrameRegistration, REGISTRATION_VERSION};
+
+        let frame = |path: &str, mapping: FrameMapping| FrameRegistration {
+            path: path.to_string(),
+            forward: mapping.clone(),
+            inverse: mapping,
+            method: "translation".to_string(),
+            matched_stars: 0,
+            inliers: 0,
+            residual_px: 0.0,
+            confidence: 1.0,
+            is_flipped: false,
+            residuals: Vec::new(),
+            distortion_map: None,
+        };
+        let paths = vec!["a.fits".to_string(), "b.fits".to_string()];
+        let set = RegistrationSet {
+            version: REGISTRATION_VERSION,
+            method: "translation".to_string(),
+            reference_index: 0,
+            reference_path: paths[0].clone(),
+            reference: Default::default(),
+            rows: 8,
+            cols: 8,
+            frames: vec![
+                frame(&paths[0], FrameMapping::from_offset(0.0, 0.0)),
+                frame(&paths[1], FrameMapping::from_offset(3.0, 0.0)),
+            ],
+        };
+        let images = vec![Array2::from_elem((8, 8), 100.0f32); 2];
+        let config = StackConfig { align: false, max_iterations: 0, ..Default::default() };
+
+        let result = stack_registered(&images, &[], &paths, &set, &config).unwrap();
+        assert!((result.image[[4, 0]] - 100.0).abs() < 1e-4, "{}", result.image[[4, 0]]);
+        assert!((result.image[[4, 5]] - 100.0).abs() < 1e-3);
+    }
 }
@@ -0,0 +1,467 @@
+use anyhow::{bail, Context, Result};
+use ndarray::Array2;
+use rayon::prelude::*;
+
+pub use crate::types::stacking::{CometConfig, CometStackResult};
+
+use crate::core::analysis::star_detection::detect_stars;
+use crate::core::analysis::subframe::{select_reference, SubframeWeightConfig};
+use crate::core::stacking::align;
+use crate::core::stacking::calibration::{load_calibrated_frames, CalibrationConfig};
+use crate::core::stacking::combine::combine_aligned;
+use crate::math::median::{exact_mad_mut, median_f32_mut};
+use crate::types::compose::AlignMethod;
+use crate::types::constants::MAD_TO_SIGMA;
+use crate::types::header::HduHeader;
+use crate::types::stacking::{CalibrationReport, ReferenceChoice};
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+const SECONDS_PER_HOUR: f64 = 3_600.0;
+const MJD_UNIX_EPOCH: f64 = 40_587.0;
+const JD_MJD_OFFSET: f64 = 2_400_000.5;
+const DETECTION_BLUR_RADIUS: usize = 3;
+const DETECTION_MIN_SIGMA: f32 = 5.0;
+const CENTROID_RADIUS: isize = 6;
+const STAR_MASK_SIGMA: f64 = 5.0;
+const STAR_MASK_FWHM_SCALE: f64 = 1.5;
+const STAR_MASK_MIN_RADIUS: f64 = 3.0;
+const COMET_EXCLUSION_PX: f64 = 20.0;
+
+type Point = (f64, f64);
+
+pub fn observation_time(header: &HduHeader) -> Option<f64> {
+    let start = header
+        .get("DATE-OBS")
+        .and_then(parse_iso_datetime)
+        .or_else(|| header.get_f64("MJD-OBS").map(mjd_to_unix))
+        .or_else(|| header.get_f64("JD").map(|jd| mjd_to_unix(jd - JD_MJD_OFFSET)))?;
+    let exposure = header
+        .get_f64("EXPTIME")
+        .or_else(|| header.get_f64("EXPOSURE"))
+        .unwrap_or(0.0);
+    Some(start + exposure.max(0.0) * 0.5)
+}
+
+fn mjd_to_unix(mjd: f64) -> f64 {
+    (mjd - MJD_UNIX_EPOCH) * SECONDS_PER_DAY
+}
+
+pub fn parse_iso_datetime(value: &str) -> Option<f64> {
+    let value = value.trim().trim_end_matches('Z');
+    let (date, time) = value.split_once('T').unwrap_or((value, ""));
+
+    let mut parts = date.split('-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let mut seconds = 0.0;
+    if !time.is_empty() {
+        let mut hms = time.split(':');
+        let h: f64 = hms.next()?.parse().ok()?;
+        let m: f64 = hms.next().unwrap_or("0").parse().ok()?;
+        let s: f64 = hms.next().unwrap_or("0").parse().ok()?;
+        seconds = h * SECONDS_PER_HOUR + m * 60.0 + s;
+    }
+
+    Some(days_from_civil(year, month, day) as f64 * SECONDS_PER_DAY + seconds)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+pub fn comet_stack(
+    images: &[Array2<f32>],
+    times: &[Option<f64>],
+    config: &CometConfig,
+) -> Result<CometStackResult> {
+    let n = images.len();
+    if n < 2 {
+        bail!("Comet stacking needs at least 2 frames");
+    }
+    if times.len() != n {
+        bail!("{} frames but {} timestamps", n, times.len());
+    }
+
+    let rows = images.iter().map(|img| img.dim().0).min().unwrap();
+    let cols = images.iter().map(|img| img.dim().1).min().unwrap();
+    let frames: Vec<Array2<f32>> = images
+        .iter()
+        .map(|img| {
+            if img.dim() == (rows, cols) && img.is_standard_layout() {
+                img.clone()
+            } else {
+                img.slice(ndarray::s![..rows, ..cols]).to_owned()
+            }
+        })
+        .collect();
+
+    let timestamps_used = times.iter().all(Option::is_some);
+    let times: Vec<f64> = if timestamps_used {
+        times.iter().map(|t| t.unwrap_or(0.0)).collect()
+    } else {
+        log::warn!("comet stack: missing DATE-OBS on some frames; assuming even spacing in frame order");
+        (0..n).map(|i| i as f64).collect()
+    };
+
+    let reference = if config.stack.align {
+        let refs: Vec<&Array2<f32>> = frames.iter().collect();
+        select_reference(&refs, config.stack.reference, &SubframeWeightConfig::default())?
+    } else {
+        ReferenceChoice::fixed(0, "alignment disabled")
+    };
+    let ref_frame = &frames[reference.index];
+
+    let star_offsets: Vec<Point> = frames
+        .par_iter()
+        .enumerate()
+        .map(|(i, frame)| {
+            if i == reference.index || !config.stack.align {
+                return (0.0, 0.0);
+            }
+            let est = align::estimate_offset(ref_frame, frame, AlignMethod::PhaseCorrelation);
+            (est.dy, est.dx)
+        })
+        .collect();
+    let star_aligned: Vec<Array2<f32>> = frames
+        .par_iter()
+        .zip(&star_offsets)
+        .map(|(frame, &(dy, dx))| align::shift_image_subpixel(frame, dy, dx))
+        .collect();
+
+    let first = (0..n).min_by(|&a, &b| times[a].total_cmp(&times[b])).unwrap();
+    let last = (0..n).max_by(|&a, &b| times[a].total_cmp(&times[b])).unwrap();
+    let span = times[last] - times[first];
+    if span <= 0.0 {
+        bail!("Frames need distinct observation times for comet stacking");
+    }
+
+    let to_reference = |p: Point, i: usize| (p.0 - star_offsets[i].1, p.1 - star_offsets[i].0);
+    let (start, end, detected) = match (config.first_position, config.last_position) {
+        (Some(a), Some(b)) => (to_reference(a, first), to_reference(b, last), false),
+        (a, b) => {
+            let margin = star_offsets
+                .iter()
+                .map(|&(dy, dx)| dy.abs().max(dx.abs()))
+                .fold(0.0f64, f64::max)
+                .ceil() as usize;
+            let (found_start, found_end) = detect_moving_object(&star_aligned[first], &star_aligned[last], margin)
+                .context("Could not find the moving object; give its position in the first and last frames")?;
+            (
+                a.map_or(found_start, |p| to_reference(p, first)),
+                b.map_or(found_end, |p| to_reference(p, last)),
+                true,
+            )
+        }
+    };
+
+    let velocity = ((end.0 - start.0) / span, (end.1 - start.1) / span);
+    let t_ref = times[reference.index];
+    let drifts: Vec<Point> = times
+        .iter()
+        .map(|&t| (velocity.0 * (t - t_ref), velocity.1 * (t - t_ref)))
+        .collect();
+    let comet_offsets: Vec<Point> = star_offsets
+        .iter()
+        .zip(&drifts)
+        .map(|(&(dy, dx), &(mx, my))| (dy + my, dx + mx))
+        .collect();
+    log::info!(
+        "comet stack: track ({:.1}, {:.1}) -> ({:.1}, {:.1}), {} frames, detected={}",
+        start.0, start.1, end.0, end.1, n, detected
+    );
+
+    let comet_aligned: Vec<Array2<f32>> = frames
+        .par_iter()
+        .zip(&comet_offsets)
+        .map(|(frame, &(dy, dx))| align::shift_image_subpixel(frame, dy, dx))
+        .collect();
+    drop(frames);
+
+    let (star_stack, star_rejected) = combine_aligned(&star_aligned, &config.stack)?;
+    drop(star_aligned);
+    let (comet_stack, comet_rejected) = combine_aligned(&comet_aligned, &config.stack)?;
+
+    let (combined, masked_stars) = if config.reject_stars {
+        let stars = stars_off_track(&star_stack, start, end);
+        let combined = combine_starless(comet_aligned, &drifts, &stars, &star_stack, &comet_stack, config)?;
+        (Some(combined), stars.len())
+    } else {
+        (None, 0)
+    };
+
+    let motion = velocity.0.hypot(velocity.1) * SECONDS_PER_HOUR;
+    Ok(CometStackResult {
+        star_stack,
+        comet_stack,
+        combined,
+        frame_count: n,
+        star_offsets,
+        comet_offsets,
+        comet_start: start,
+        comet_end: end,
+        motion_px_per_hour: timestamps_used.then_some(motion),
+        detected,
+        timestamps_used,
+        masked_stars,
+        rejected_pixels: star_rejected + comet_rejected,
+        reference,
+        calibration: CalibrationReport::default(),
+    })
+}
+
+pub fn comet_stack_from_paths(
+    paths: &[String],
+    config: &CometConfig,
+    calibration: Option<&CalibrationConfig>,
+) -> Result<CometStackResult> {
+    let (images, headers, report) = load_calibrated_frames(paths, calibration)?;
+    let times: Vec<Option<f64>> = headers.iter().map(observation_time).collect();
+    let mut result = comet_stack(&images, &times, config)?;
+    result.calibration = report;
+    Ok(result)
+}
+
+fn detect_moving_object(first: &Array2<f32>, last: &Array2<f32>, margin: usize) -> Option<(Point, Point)> {
+    let (rows, cols) = first.dim();
+    let border = margin + DETECTION_BLUR_RADIUS;
+    if rows <= 2 * border || cols <= 2 * border {
+        return None;
+    }
+
+    let diff: Vec<f32> = first.iter().zip(last.iter()).map(|(a, b)| a - b).collect();
+    let blurred = box_blur(&diff, rows, cols, DETECTION_BLUR_RADIUS);
+
+    let mut sample: Vec<f32> = blurred.iter().copied().filter(|v| v.is_finite()).collect();
+    let median = median_f32_mut(&mut sample);
+    let sigma = (exact_mad_mut(&mut sample, median) as f64 * MAD_TO_SIGMA).max(1e-10) as f32;
+
+    let mut peak = (f32::MIN, 0usize);
+    let mut trough = (f32::MAX, 0usize);
+    for y in border..rows - border {
+        for x in border..cols - border {
+            let v = blurred[y * cols + x];
+            if v > peak.0 {
+                peak = (v, y * cols + x);
+            }
+            if v < trough.0 {
+                trough = (v, y * cols + x);
+            }
+        }
+    }
+    if peak.0 - median < DETECTION_MIN_SIGMA * sigma || median - trough.0 < DETECTION_MIN_SIGMA * sigma {
+        return None;
+    }
+
+    let at = |idx: usize| ((idx % cols) as f64, (idx / cols) as f64);
+    Some((centroid(first, at(peak.1)), centroid(last, at(trough.1))))
+}
+
+fn box_blur(data: &[f32], rows: usize, cols: usize, radius: usize) -> Vec<f32> {
+    let w = cols + 1;
+    let mut sat = vec![0.0f64; (rows + 1) * w];
+    for y in 0..rows {
+        let mut run = 0.0f64;
+        for x in 0..cols {
+            run += data[y * cols + x] as f64;
+            sat[(y + 1) * w + x + 1] = sat[y * w + x + 1] + run;
+        }
+    }
+
+    let mut out = vec![0.0f32; rows * cols];
+    out.par_chunks_mut(cols).enumerate().for_each(|(y, row)| {
+        let y0 = y.saturating_sub(radius);
+        let y1 = (y + radius + 1).min(rows);
+        for (x, slot) in row.iter_mut().enumerate() {
+            let x0 = x.saturating_sub(radius);
+            let x1 = (x + radius + 1).min(cols);
+            let sum = sat[y1 * w + x1] - sat[y0 * w + x1] - sat[y1 * w + x0] + sat[y0 * w + x0];
+            *slot = (sum / ((y1 - y0) * (x1 - x0)) as f64) as f32;
+        }
+    });
+    out
+}
+
+fn centroid(image: &Array2<f32>, seed: Point) -> Point {
+    let (rows, cols) = image.dim();
+    let (sx, sy) = (seed.0 as isize, seed.1 as isize);
+    let mut window = Vec::new();
+    for y in (sy - CENTROID_RADIUS).max(0)..=(sy + CENTROID_RADIUS).min(rows as isize - 1) {
+        for x in (sx - CENTROID_RADIUS).max(0)..=(sx + CENTROID_RADIUS).min(cols as isize - 1) {
+            window.push((x as f64, y as f64, image[[y as usize, x as usize]]));
+        }
+    }
+
+    let mut values: Vec<f32> = window.iter().map(|w| w.2).filter(|v| v.is_finite()).collect();
+    let background = median_f32_mut(&mut values);
+    let (mut sum, mut mx, mut my) = (0.0f64, 0.0f64, 0.0f64);
+    for &(x, y, v) in &window {
+        let w = (v - background).max(0.0) as f64;
+        if w.is_finite() {
+            sum += w;
+            mx += w * x;
+            my += w * y;
+        }
+    }
+    if sum > 0.0 {
+        (mx / sum, my / sum)
+    } else {
+        seed
+    }
+}
+
+fn distance_to_segment(p: Point, a: Point, b: Point) -> f64 {
+    let (vx, vy) = (b.0 - a.0, b.1 - a.1);
+    let len2 = vx * vx + vy * vy;
+    let t = if len2 > 0.0 {
+        (((p.0 - a.0) * vx + (p.1 - a.1) * vy) / len2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (p.0 - a.0 - t * vx).hypot(p.1 - a.1 - t * vy)
+}
+
+fn stars_off_track(star_stack: &Array2<f32>, start: Point, end: Point) -> Vec<(f64, f64, f64)> {
+    detect_stars(star_stack, STAR_MASK_SIGMA)
+        .stars
+        .into_iter()
+        .filter(|s| distance_to_segment((s.x, s.y), start, end) > COMET_EXCLUSION_PX)
+        .map(|s| (s.x, s.y, (s.fwhm * STAR_MASK_FWHM_SCALE).max(STAR_MASK_MIN_RADIUS)))
+        .collect()
+}
+
+fn star_disc_mask(rows: usize, cols: usize, stars: &[(f64, f64, f64)], shift: Point) -> Vec<bool> {
+    let mut mask = vec![false; rows * cols];
+    for &(x, y, r) in stars {
+        let (cx, cy) = (x - shift.0, y - shift.1);
+        let y0 = (cy - r).floor().max(0.0) as usize;
+        let y1 = ((cy + r).ceil().min(rows as f64 - 1.0)).max(-1.0) as isize;
+        let x0 = (cx - r).floor().max(0.0) as usize;
+        let x1 = ((cx + r).ceil().min(cols as f64 - 1.0)).max(-1.0) as isize;
+        for yy in y0 as isize..=y1 {
+            for xx in x0 as isize..=x1 {
+                let (px, py) = (xx as f64, yy as f64);
+                if (px - cx).hypot(py - cy) <= r {
+                    mask[yy as usize * cols + xx as usize] = true;
+                }
+            }
+        }
+    }
+    mask
+}
+
+fn combine_starless(
+    mut frames: Vec<Array2<f32>>,
+    drifts: &[Point],
+    stars: &[(f64, f64, f64)],
+    star_stack: &Array2<f32>,
+    comet_stack: &Array2<f32>,
+    config: &CometConfig,
+) -> Result<Array2<f32>> {
+    let (rows, cols) = star_stack.dim();
+    frames.par_iter_mut().zip(drifts).for_each(|(frame, &drift)| {
+        let mask = star_disc_mask(rows, cols, stars, drift);
+        let data = frame.as_slice_mut().expect("contiguous");
+        for (v, m) in data.iter_mut().zip(mask) {
+            if m {
+                *v = f32::NAN;
+            }
+        }
+    });
+    let (starless, _) = combine_aligned(&frames, &config.stack)?;
+
+    let slices: Vec<&[f32]> = frames.iter().map(|f| f.as_slice().expect("contiguous")).collect();
+    let star_mask = star_disc_mask(rows, cols, stars, (0.0, 0.0));
+    let star_data = star_stack.as_slice().expect("contiguous");
+    let comet_data = comet_stack.as_slice().expect("contiguous");
+    let starless_data = starless.as_slice().expect("contiguous");
+
+    let mut out = vec![0.0f32; rows * cols];
+    out.par_chunks_mut(cols).enumerate().for_each(|(y, row)| {
+        for (x, slot) in row.iter_mut().enumerate() {
+            let idx = y * cols + x;
+            *slot = if star_mask[idx] {
+                star_data[idx]
+            } else if slices.iter().any(|s| s[idx].is_finite()) {
+                starless_data[idx]
+            } else {
+                comet_data[idx]
+            };
+        }
+    });
+
+    Array2::from_shape_vec((rows, cols), out).context("Failed to reshape combined comet stack")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const STARS: [(f64, f64); 12] = [
+        (12.0, 15.0), (100.0, 20.0), (60.0, 10.0), (20.0, 110.0), (110.0, 105.0), (75.0, 60.0),
+        (40.0, 95.0), (95.0, 45.0), (15.0, 60.0), (55.0, 118.0), (118.0, 75.0), (35.0, 30.0),
+    ];
+
+    fn gaussian(x: f64, y: f64, cx: f64, cy: f64, sigma: f64, amp: f64) -> f64 {
+        amp * (-((x - cx).powi(2) + (y - cy).powi(2)) / (2.0 * sigma * sigma)).exp()
+    }
+
+    fn frame(comet: (f64, f64)) -> Array2<f32> {
+        Array2::from_shape_fn((128, 128), |(y, x)| {
+            let (fx, fy) = (x as f64, y as f64);
+            let stars: f64 = STARS.iter().map(|&(sx, sy)| gaussian(fx, fy, sx, sy, 1.3, 900.0)).sum();
+            let noise = (((x * 31 + y * 17) % 23) as f64 - 11.0) * 0.4;
+            (100.0 + stars + gaussian(fx, fy, comet.0, comet.1, 3.0, 350.0) + noise) as f32
+        })
+    }
+
+    #[test]
+    fn test_parse_date_obs_and_mid_exposure() {
+        let a = parse_iso_datetime("2024-03-01T23:59:30.5").unwrap();
+        let b = parse_iso_datetime("2024-03-02T00:00:00").unwrap();
+        assert!((b - a - 29.5).abs() < 1e-9);
+        assert!((parse_iso_datetime("1970-01-02").unwrap() - SECONDS_PER_DAY).abs() < 1e-9);
+        assert!(parse_iso_datetime("2024-13-01").is_none());
+
+        let mut index = HashMap::new();
+        index.insert("DATE-OBS".to_string(), "1970-01-01T00:01:00".to_string());
+        index.insert("EXPTIME".to_string(), "120".to_string());
+        let header = HduHeader { cards: Vec::new(), index };
+        assert!((observation_time(&header).unwrap() - 120.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_comet_stack_detects_and_freezes_moving_object() {
+        let track = |k: f64| (30.0 + 60.0 * k, 40.0 + 40.0 * k);
+        let images: Vec<Array2<f32>> = (0..5).map(|i| frame(track(i as f64 / 4.0))).collect();
+        let times: Vec<Option<f64>> = (0..5).map(|i| Some(600.0 * i as f64)).collect();
+        let config = CometConfig { reject_stars: true, ..Default::default() };
+
+        let result = comet_stack(&images, &times, &config).unwrap();
+        assert!(result.detected);
+        assert!((result.comet_start.0 - 30.0).abs() < 1.5 && (result.comet_start.1 - 40.0).abs() < 1.5);
+        assert!((result.comet_end.0 - 90.0).abs() < 1.5 && (result.comet_end.1 - 80.0).abs() < 1.5);
+        assert!((result.motion_px_per_hour.unwrap() - 108.2).abs() < 4.0);
+
+        let (cx, cy) = track(result.reference.index as f64 / 4.0);
+        let (cx, cy) = (cx.round() as usize, cy.round() as usize);
+        assert!(result.comet_stack[[cy, cx]] > result.star_stack[[cy, cx]] + 150.0);
+        assert!(result.star_stack[[20, 100]] > result.comet_stack[[20, 100]] + 300.0);
+
+        let combined = result.combined.unwrap();
+        assert!(result.masked_stars > 0);
+        assert!(combined[[20, 100]] > 700.0);
+        assert!(combined[[cy, cx]] > 350.0);
+    }
+}
@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use ndarray::Array2;
+use rayon::prelude::*;
+
+use crate::infra::fits::reader::load_fits_image;
+use crate::infra::fits::writer::write_fits_mono;
+use crate::math::median::median_f32_mut;
+use crate::types::constants::MAD_TO_SIGMA;
+use crate::types::header::HduHeader;
+
+pub const DEFECT_NONE: u8 = 0;
+pub const DEFECT_HOT: u8 = 1;
+pub const DEFECT_COLD: u8 = 2;
+pub const DEFECT_LINE: u8 = 3;
+
+const MAX_SAMPLES: usize = 500_000;
+const NEIGHBOUR_RADIUS_MAX: isize = 3;
+const LOCAL_RADIUS: isize = 2;
+const LIGHT_NEIGHBOUR_RATIO: f32 = 0.25;
+
+#[derive(Debug, Clone)]
+pub struct CosmeticConfig {
+    pub hot_sigma: f32,
+    pub cold_sigma: f32,
+    pub detect_in_lights: bool,
+    pub light_hot_sigma: f32,
+    pub bad_columns: Vec<usize>,
+    pub bad_rows: Vec<usize>,
+    pub defect_map: Option<DefectMap>,
+}
+
+impl Default for CosmeticConfig {
+    fn default() -> Self {
+        Self {
+            hot_sigma: 5.0,
+            cold_sigma: 5.0,
+            detect_in_lights: false,
+            light_hot_sigma: 8.0,
+            bad_columns: Vec::new(),
+            bad_rows: Vec::new(),
+            defect_map: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DefectMap {
+    pub mask: Array2<u8>,
+    pub hot_count: usize,
+    pub cold_count: usize,
+    pub line_count: usize,
+}
+
+impl DefectMap {
+    pub fn empty(rows: usize, cols: usize) -> Self {
+        Self {
+            mask: Array2::zeros((rows, cols)),
+            hot_count: 0,
+            cold_count: 0,
+            line_count: 0,
+        }
+    }
+
+    pub fn dim(&self) -> (usize, usize) {
+        self.mask.dim()
+    }
+
+    pub fn defect_count(&self) -> usize {
+        self.hot_count + self.cold_count + self.line_count
+    }
+
+    pub fn from_codes(mask: Array2<u8>) -> Self {
+        let mut map = Self {
+            mask,
+            hot_count: 0,
+            cold_count: 0,
+            line_count: 0,
+        };
+        map.recount();
+        map
+    }
+
+    fn recount(&mut self) {
+        let (mut hot, mut cold, mut line) = (0, 0, 0);
+        for &v in self.mask.iter() {
+            match v {
+                DEFECT_HOT => hot += 1,
+                DEFECT_COLD => cold += 1,
+                DEFECT_LINE => line += 1,
+                _ => {}
+            }
+        }
+        self.hot_count = hot;
+        self.cold_count = cold;
+        self.line_count = line;
+    }
+
+    pub fn add_columns(&mut self, columns: &[usize]) {
+        let (rows, cols) = self.dim();
+        for &c in columns.iter().filter(|&&c| c < cols) {
+            for r in 0..rows {
+                self.mask[[r, c]] = DEFECT_LINE;
+            }
+        }
+        self.recount();
+    }
+
+    pub fn add_rows(&mut self, row_list: &[usize]) {
+        let (rows, cols) = self.dim();
+        for &r in row_list.iter().filter(|&&r| r < rows) {
+            for c in 0..cols {
+                self.mask[[r, c]] = DEFECT_LINE;
+            }
+        }
+        self.recount();
+    }
+
+    pub fn save_fits(&self, path: &str) -> Result<()> {
+        let data = self.mask.mapv(|v| v as f32);
+        let cards = vec![
+            ("DEFMAP".to_string(), "T".to_string()),
+            ("NHOT".to_string(), self.hot_count.to_string()),
+            ("NCOLD".to_string(), self.cold_count.to_string()),
+            ("NLINE".to_string(), self.line_count.to_string()),
+        ];
+        let index: HashMap<String, String> = cards.iter().cloned().collect();
+        let header = HduHeader { cards, index };
+        write_fits_mono(path, &data, Some(&header))
+    }
+
+    pub fn load_fits(path: &str) -> Result<Self> {
+        let data = load_fits_image(path)?;
+        if data.iter().any(|v| !v.is_finite() || *v < 0.0 || *v > DEFECT_LINE as f32) {
+            bail!("{} is not a defect map (unexpected pixel codes)", path);
+        }
+        Ok(Self::from_codes(data.mapv(|v| v.round() as u8)))
+    }
+}
+
+fn robust_stats(image: &Array2<f32>) -> (f32, f32) {
+    let slice = image.as_slice().expect("contiguous");
+    let step = (slice.len() / MAX_SAMPLES).max(1);
+    let mut samples: Vec<f32> = slice
+        .iter()
+        .step_by(step)
+        .copied()
+        .filter(|v| v.is_finite())
+        .collect();
+    if samples.is_empty() {
+        return (0.0, 0.0);
+    }
+    let median = median_f32_mut(&mut samples);
+    for v in samples.iter_mut() {
+        *v = (*v - median).abs();
+    }
+    let sigma = (median_f32_mut(&mut samples) as f64 * MAD_TO_SIGMA) as f32;
+    (median, sigma.max(1e-10))
+}
+
+fn local_residual(image: &Array2<f32>, relative: bool) -> Array2<f32> {
+    let (rows, cols) = image.dim();
+    let src = image.as_slice().expect("contiguous");
+    let r = LOCAL_RADIUS;
+    let mut out = vec![0.0f32; rows * cols];
+
+    out.par_chunks_mut(cols).enumerate().for_each(|(y, row)| {
+        let mut vals = Vec::with_capacity(((2 * r + 1) * (2 * r + 1)) as usize);
+        for (x, slot) in row.iter_mut().enumerate() {
+            let v = src[y * cols + x];
+            if !v.is_finite() {
+                *slot = f32::NAN;
+                continue;
+            }
+            vals.clear();
+            for ny in (y as isize - r).max(0)..=(y as isize + r).min(rows as isize - 1) {
+                for nx in (x as isize - r).max(0)..=(x as isize + r).min(cols as isize - 1) {
+                    if ny as usize == y && nx as usize == x {
+                        continue;
+                    }
+                    let n = src[ny as usize * cols + nx as usize];
+                    if n.is_finite() {
+                        vals.push(n);
+                    }
+                }
+            }
+            if vals.is_empty() {
+                continue;
+            }
+            let med = median_f32_mut(&mut vals);
+            *slot = if relative {
+                if med.abs() > 1e-6 { v / med - 1.0 } else { 0.0 }
+            } else {
+                v - med
+            };
+        }
+    });
+
+    Array2::from_shape_vec((rows, cols), out).unwrap()
+}
+
+pub fn build_defect_map(
+    master_dark: Option<&Array2<f32>>,
+    master_flat: Option<&Array2<f32>>,
+    config: &CosmeticConfig,
+) -> Result<DefectMap> {
+    let dims = match (master_dark, master_flat) {
+        (Some(d), Some(f)) if d.dim() != f.dim() => bail!(
+            "Master dark {:?} and master flat {:?} dimensions differ",
+            d.dim(),
+            f.dim()
+        ),
+        (Some(d), _) => d.dim(),
+        (None, Some(f)) => f.dim(),
+        (None, None) => bail!("A master dark or master flat is required to build a defect map"),
+    };
+
+    let mut map = DefectMap::empty(dims.0, dims.1);
+
+    if let Some(dark) = master_dark {
+        let residual = local_residual(dark, false);
+        let (center, sigma) = robust_stats(&residual);
+        let threshold = center + config.hot_sigma * sigma;
+        ndarray::Zip::from(&mut map.mask)
+            .and(&residual)
+            .for_each(|m, &v| {
+                if v.is_finite() && v > threshold {
+                    *m = DEFECT_HOT;
+                }
+            });
+    }
+
+    if let Some(flat) = master_flat {
+        let residual = local_residual(flat, true);
+        let (center, sigma) = robust_stats(&residual);
+        let threshold = center - config.cold_sigma * sigma;
+        ndarray::Zip::from(&mut map.mask)
+            .and(&residual)
+            .and(flat)
+            .for_each(|m, &r, &v| {
+                if *m == DEFECT_NONE && (!v.is_finite() || r < threshold) {
+                    *m = DEFECT_COLD;
+                }
+            });
+    }
+
+    map.add_columns(&config.bad_columns);
+    map.add_rows(&config.bad_rows);
+
+    Ok(map)
+}
+
+pub fn detect_light_hot_pixels(image: &Array2<f32>, sigma: f32) -> Vec<usize> {
+    let (rows, cols) = image.dim();
+    if rows < 3 || cols < 3 {
+        return Vec::new();
+    }
+    let (background, noise) = robust_stats(image);
+    let threshold = sigma * noise;
+    let src = image.as_slice().expect("contiguous");
+
+    (1..rows - 1)
+        .into_par_iter()
+        .flat_map_iter(|y| {
+            let mut found = Vec::new();
+            let mut neighbours = [0.0f32; 8];
+            for x in 1..cols - 1 {
+                let v = src[y * cols + x];
+                if !v.is_finite() {
+                    continue;
+                }
+                let mut n = 0;
+                for dy in -1isize..=1 {
+                    for dx in -1isize..=1 {
+                        if dy == 0 && dx == 0 {
+                            continue;
+                        }
+                        let idx = (y as isize + dy) as usize * cols + (x as isize + dx) as usize;
+                        neighbours[n] = src[idx];
+                        n += 1;
+                    }
+                }
+                let med = median_f32_mut(&mut neighbours);
+                let excess = v - med;
+                if excess > threshold && (med - background) < LIGHT_NEIGHBOUR_RATIO * excess {
+                    found.push(y * cols + x);
+                }
+            }
+            found
+        })
+        .collect()
+}
+
+pub fn apply_cosmetic_correction(
+    image: &Array2<f32>,
+    defect_map: Option<&DefectMap>,
+    extra_defects: &[usize],
+) -> Result<(Array2<f32>, usize)> {
+    let (rows, cols) = image.dim();
+    if let Some(map) = defect_map {
+        if map.dim() != (rows, cols) {
+            bail!(
+                "Defect map {:?} does not match image dimensions {:?}",
+                map.dim(),
+                (rows, cols)
+            );
+        }
+    }
+
+    let mut bad = match defect_map {
+        Some(map) => map.mask.mapv(|v| v != DEFECT_NONE),
+        None => Array2::from_elem((rows, cols), false),
+    };
+    for &idx in extra_defects.iter().filter(|&&i| i < rows * cols) {
+        bad[[idx / cols, idx % cols]] = true;
+    }
+
+    let bad_slice = bad.as_slice().expect("contiguous");
+    let positions: Vec<usize> = bad_slice
+        .iter()
+        .enumerate()
+        .filter(|(_, &b)| b)
+        .map(|(i, _)| i)
+        .collect();
+    if positions.is_empty() {
+        return Ok((image.to_owned(), 0));
+    }
+
+    let src = image.as_slice().expect("contiguous");
+    let replacements: Vec<(usize, f32)> = positions
+        .par_iter()
+        .filter_map(|&idx| {
+            let y = (idx / cols) as isize;
+            let x = (idx % cols) as isize;
+            let mut vals: Vec<f32> = Vec::with_capacity(24);
+            for radius in 1..=NEIGHBOUR_RADIUS_MAX {
+                vals.clear();
+                for ny in (y - radius)..=(y + radius) {
+                    if ny < 0 || ny >= rows as isize {
+                        continue;
+                    }
+                    for nx in (x - radius)..=(x + radius) {
+                        if nx < 0 || nx >= cols as isize {
+                            continue;
+                        }
+                        let n = ny as usize * cols + nx as usize;
+                        if !bad_slice[n] && src[n].is_finite() {
+                            vals.push(src[n]);
+                        }
+                    }
+                }
+                if vals.len() >= 3 {
+                    break;
+                }
+            }
+            if vals.is_empty() {
+                None
+            } else {
+                Some((idx, median_f32_mut(&mut vals)))
+            }
+        })
+        .collect();
+
+    let mut out = image.to_owned();
+    let corrected = replacements.len();
+    let out_slice = out.as_slice_mut().expect("contiguous");
+    for (idx, v) in replacements {
+        out_slice[idx] = v;
+    }
+
+    Ok((out, corrected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn textured(rows: usize, cols: usize, base: f32) -> Array2<f32> {
+        Array2::from_shape_fn((rows, cols), |(y, x)| {
+            base + ((y * 31 + x * 17) % 11) as f32 * 0.1
+        })
+    }
+
+    #[test]
+    fn test_defect_map_from_dark_and_flat() {
+        let mut dark = textured(32, 32, 10.0);
+        dark[[5, 7]] = 500.0;
+        dark[[20, 3]] = 800.0;
+        let mut flat = textured(32, 32, 100.0).mapv(|v| v / 100.0);
+        flat[[12, 12]] = 0.1;
+
+        let config = CosmeticConfig {
+            bad_columns: vec![30],
+            ..Default::default()
+        };
+        let map = build_defect_map(Some(&dark), Some(&flat), &config).unwrap();
+        assert_eq!(map.mask[[5, 7]], DEFECT_HOT);
+        assert_eq!(map.mask[[20, 3]], DEFECT_HOT);
+        assert_eq!(map.mask[[12, 12]], DEFECT_COLD);
+        assert_eq!(map.mask[[0, 30]], DEFECT_LINE);
+        assert_eq!(map.hot_count, 2);
+        assert_eq!(map.cold_count, 1);
+        assert_eq!(map.line_count, 32);
+    }
+
+    #[test]
+    fn test_correction_replaces_with_neighbour_median() {
+        let mut img = Array2::from_elem((9, 9), 100.0f32);
+        img[[4, 4]] = 9000.0;
+        let mut map = DefectMap::empty(9, 9);
+        map.mask[[4, 4]] = DEFECT_HOT;
+        let (out, corrected) = apply_cosmetic_correction(&img, Some(&map), &[]).unwrap();
+        assert_eq!(corrected, 1);
+        assert!((out[[4, 4]] - 100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_bad_column_repaired() {
+        let mut img = textured(16, 16, 50.0);
+        for r in 0..16 {
+            img[[r, 8]] = 0.0;
+        }
+        let mut map = DefectMap::empty(16, 16);
+        map.add_columns(&[8]);
+        let (out, corrected) = apply_cosmetic_correction(&img, Some(&map), &[]).unwrap();
+        assert_eq!(corrected, 16);
+        assert!(out.column(8).iter().all(|&v| v > 49.0));
+    }
+
+    #[test]
+    fn test_light_hot_pixel_detection_ignores_stars() {
+        let mut img = textured(64, 64, 100.0);
+        img[[10, 10]] = 5000.0;
+        for dy in -2i32..=2 {
+            for dx in -2i32..=2 {
+                let d2 = (dx * dx + dy * dy) as f32;
+                img[[(40 + dy) as usize, (40 + dx) as usize]] += 4000.0 * (-d2 / 2.0).exp();
+            }
+        }
+        let found = detect_light_hot_pixels(&img, 8.0);
+        assert!(found.contains(&(10 * 64 + 10)));
+        assert!(!found.contains(&(40 * 64 + 40)));
+    }
+
+    #[test]
+    fn test_dimension_mismatch_rejected() {
+        let img = Array2::from_elem((8, 8), 1.0f32);
+        let map = DefectMap::empty(4, 4);
+        assert!(apply_cosmetic_correction(&img, Some(&map), &[]).is_err());
+    }
+}
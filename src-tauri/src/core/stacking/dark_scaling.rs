@@ -0,0 +1,288 @@
+use ndarray::Array2;
+use serde::Serialize;
+
+use crate::math::median::median_f32_mut;
+use crate::types::header::HduHeader;
+use crate::types::constants::MAD_TO_SIGMA;
+
+const EXPOSURE_KEYS: &[&str] = &["EXPTIME", "EXPOSURE", "EFFEXPTM", "XPOSURE"];
+const TEMPERATURE_KEYS: &[&str] = &["CCD-TEMP", "CCD_TEMP", "SET-TEMP", "DET_TEMP", "TEMPERAT"];
+
+const OPTIMIZE_MAX_SAMPLES: usize = 400_000;
+const OPTIMIZE_LIGHT_CLIP: f64 = 8.0;
+const OPTIMIZE_DARK_FEATURE: f64 = 3.0;
+const OPTIMIZE_RANGE_FACTOR: f64 = 3.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct FrameExposure {
+    pub exposure: Option<f64>,
+    pub temperature: Option<f64>,
+}
+
+impl FrameExposure {
+    pub fn from_header(header: &HduHeader) -> Self {
+        let exposure = EXPOSURE_KEYS
+            .iter()
+            .filter_map(|k| header.get_f64(k))
+            .find(|v| v.is_finite() && *v > 0.0);
+        let temperature = TEMPERATURE_KEYS
+            .iter()
+            .filter_map(|k| header.get_f64(k))
+            .find(|v| v.is_finite() && *v > -273.15);
+        Self { exposure, temperature }
+    }
+
+    pub fn median_of(frames: &[FrameExposure]) -> Self {
+        let median = |mut vals: Vec<f64>| -> Option<f64> {
+            if vals.is_empty() {
+                return None;
+            }
+            vals.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            Some(vals[vals.len() / 2])
+        };
+        Self {
+            exposure: median(frames.iter().filter_map(|f| f.exposure).collect()),
+            temperature: median(frames.iter().filter_map(|f| f.temperature).collect()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DarkScaleMode {
+    #[default]
+    Fixed,
+    Exposure,
+    Optimize,
+}
+
+impl std::fmt::Display for DarkScaleMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DarkScaleMode::Fixed => write!(f, "fixed"),
+            DarkScaleMode::Exposure => write!(f, "exposure"),
+            DarkScaleMode::Optimize => write!(f, "optimize"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DarkScaling {
+    pub mode: DarkScaleMode,
+    pub temperature_doubling: Option<f64>,
+}
+
+pub fn dark_scale_factor(
+    light: &FrameExposure,
+    dark: &FrameExposure,
+    temperature_doubling: Option<f64>,
+) -> Option<f32> {
+    let (light_exp, dark_exp) = (light.exposure?, dark.exposure?);
+    if dark_exp <= 0.0 {
+        return None;
+    }
+    let mut ratio = light_exp / dark_exp;
+
+    if let (Some(doubling), Some(t_light), Some(t_dark)) =
+        (temperature_doubling, light.temperature, dark.temperature)
+    {
+        if doubling > 0.0 {
+            ratio *= 2f64.powf((t_light - t_dark) / doubling);
+        }
+    }
+
+    if ratio.is_finite() && ratio > 0.0 {
+        Some(ratio as f32)
+    } else {
+        None
+    }
+}
+
+pub fn scale_for_light(
+    light: &Array2<f32>,
+    light_exposure: &FrameExposure,
+    master_dark: &Array2<f32>,
+    master_bias: Option<&Array2<f32>>,
+    dark_exposure: &FrameExposure,
+    scaling: &DarkScaling,
+    fixed_ratio: f32,
+) -> f32 {
+    let Some(bias) = master_bias else {
+        return fixed_ratio;
+    };
+    let from_exposure = || {
+        dark_scale_factor(light_exposure, dark_exposure, scaling.temperature_doubling)
+            .unwrap_or(fixed_ratio)
+    };
+    match scaling.mode {
+        DarkScaleMode::Fixed => fixed_ratio,
+        DarkScaleMode::Exposure => from_exposure(),
+        DarkScaleMode::Optimize => optimize_dark_scale(light, master_dark, Some(bias), from_exposure()),
+    }
+}
+
+pub fn optimize_dark_scale(
+    light: &Array2<f32>,
+    master_dark: &Array2<f32>,
+    master_bias: Option<&Array2<f32>>,
+    initial: f32,
+) -> f32 {
+    let (rows, cols) = light.dim();
+    if rows < 2 || cols < 2 || master_dark.dim() != (rows, cols) {
+        return initial;
+    }
+    let bias_ok = master_bias.is_none_or(|b| b.dim() == (rows, cols));
+
+    let total = rows * (cols - 1);
+    let step = (total / OPTIMIZE_MAX_SAMPLES).max(1);
+
+    let mut light_diff: Vec<f32> = Vec::with_capacity(total / step + 1);
+    let mut dark_diff: Vec<f32> = Vec::with_capacity(total / step + 1);
+
+    let mut i = 0usize;
+    while i < total {
+        let y = i / (cols - 1);
+        let x = i % (cols - 1);
+        let mut l0 = light[[y, x]];
+        let mut l1 = light[[y, x + 1]];
+        if bias_ok {
+            if let Some(bias) = master_bias {
+                l0 -= bias[[y, x]];
+                l1 -= bias[[y, x + 1]];
+            }
+        }
+        let d0 = master_dark[[y, x]];
+        let d1 = master_dark[[y, x + 1]];
+        if l0.is_finite() && l1.is_finite() && d0.is_finite() && d1.is_finite() {
+            light_diff.push(l1 - l0);
+            dark_diff.push(d1 - d0);
+        }
+        i += step;
+    }
+
+    if light_diff.len() < 16 {
+        return initial;
+    }
+
+    let (light_med, light_sigma) = robust_center_sigma(&light_diff);
+    let (dark_med, dark_sigma) = robust_center_sigma(&dark_diff);
+
+    let mut sum_ld = 0.0f64;
+    let mut sum_dd = 0.0f64;
+    let mut sum_l = 0.0f64;
+    let mut sum_d = 0.0f64;
+    let mut n = 0usize;
+    for (&l, &d) in light_diff.iter().zip(&dark_diff) {
+        let light_outlier = (l - light_med).abs() as f64 > OPTIMIZE_LIGHT_CLIP * light_sigma;
+        let dark_feature = (d - dark_med).abs() as f64 > OPTIMIZE_DARK_FEATURE * dark_sigma;
+        if light_outlier && !dark_feature {
+            continue;
+        }
+        let (l, d) = (l as f64, d as f64);
+        sum_ld += l * d;
+        sum_dd += d * d;
+        sum_l += l;
+        sum_d += d;
+        n += 1;
+    }
+
+    let start = if initial.is_finite() && initial > 0.0 { initial as f64 } else { 1.0 };
+    if n < 16 {
+        return start as f32;
+    }
+
+    let nf = n as f64;
+    let cov = sum_ld / nf - (sum_l / nf) * (sum_d / nf);
+    let var = sum_dd / nf - (sum_d / nf).powi(2);
+    if var <= 1e-12 {
+        return start as f32;
+    }
+
+    (cov / var).clamp(0.0, start * OPTIMIZE_RANGE_FACTOR) as f32
+}
+
+fn robust_center_sigma(values: &[f32]) -> (f32, f64) {
+    let mut buf = values.to_vec();
+    let med = median_f32_mut(&mut buf);
+    for v in buf.iter_mut() {
+        *v = (*v - med).abs();
+    }
+    let sigma = (median_f32_mut(&mut buf) as f64 * MAD_TO_SIGMA).max(1e-10);
+    (med, sigma)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_with(cards: &[(&str, &str)]) -> HduHeader {
+        let cards: Vec<(String, String)> = cards
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let index = cards.iter().cloned().collect();
+        HduHeader { cards, index }
+    }
+
+    #[test]
+    fn test_frame_exposure_from_header() {
+        let h = header_with(&[("EXPOSURE", "300.0"), ("CCD-TEMP", "-10.5")]);
+        let meta = FrameExposure::from_header(&h);
+        assert_eq!(meta.exposure, Some(300.0));
+        assert_eq!(meta.temperature, Some(-10.5));
+    }
+
+    #[test]
+    fn test_dark_scale_exposure_only() {
+        let light = FrameExposure { exposure: Some(300.0), temperature: Some(-10.0) };
+        let dark = FrameExposure { exposure: Some(600.0), temperature: Some(-4.0) };
+        let k = dark_scale_factor(&light, &dark, None).unwrap();
+        assert!((k - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_dark_scale_with_temperature() {
+        let light = FrameExposure { exposure: Some(300.0), temperature: Some(-10.0) };
+        let dark = FrameExposure { exposure: Some(300.0), temperature: Some(-4.0) };
+        let k = dark_scale_factor(&light, &dark, Some(6.0)).unwrap();
+        assert!((k - 0.5).abs() < 1e-6, "k={}", k);
+    }
+
+    #[test]
+    fn test_dark_scale_missing_exposure() {
+        let light = FrameExposure { exposure: None, temperature: None };
+        let dark = FrameExposure { exposure: Some(300.0), temperature: None };
+        assert!(dark_scale_factor(&light, &dark, None).is_none());
+    }
+
+    #[test]
+    fn test_scale_for_light_needs_separate_bias() {
+        let light = Array2::from_elem((4, 4), 500.0f32);
+        let dark = Array2::from_elem((4, 4), 50.0f32);
+        let bias = Array2::from_elem((4, 4), 10.0f32);
+        let light_exp = FrameExposure { exposure: Some(60.0), temperature: None };
+        let dark_exp = FrameExposure { exposure: Some(240.0), temperature: None };
+        let scaling = DarkScaling { mode: DarkScaleMode::Exposure, temperature_doubling: None };
+
+        let k = scale_for_light(&light, &light_exp, &dark, Some(&bias), &dark_exp, &scaling, 1.0);
+        assert!((k - 0.25).abs() < 1e-6);
+        let k = scale_for_light(&light, &light_exp, &dark, None, &dark_exp, &scaling, 1.0);
+        assert_eq!(k, 1.0);
+    }
+
+    #[test]
+    fn test_optimize_recovers_true_scale() {
+        let (rows, cols) = (64, 64);
+        let dark = Array2::from_shape_fn((rows, cols), |(y, x)| {
+            let h = (y * 131 + x * 197) % 23;
+            if h == 0 { 400.0 } else { 10.0 + (h as f32) * 3.0 }
+        });
+        let mut light = Array2::from_shape_fn((rows, cols), |(y, x)| {
+            let noise = (((y * 73 + x * 151) * 2654435761usize) % 1000) as f32 / 1000.0 - 0.5;
+            1000.0 + 0.7 * dark[[y, x]] + noise * 4.0
+        });
+        light[[20, 20]] += 5000.0;
+        light[[40, 33]] += 8000.0;
+        let k = optimize_dark_scale(&light, &dark, None, 1.0);
+        assert!((k - 0.7).abs() < 0.02, "k={}", k);
+    }
+}
@@ -1,44 +1,82 @@
-use anyhow::{bail, Result};
+use std::borrow::Cow;
+
+use anyhow::{bail, Context, Result};
 use ndarray::Array2;
 use rayon::prelude::*;
 
-pub use crate::types::stacking::{AlignmentMethod, DrizzleConfig, DrizzleKernel, DrizzleResult};
+pub use crate::types::stacking::{
+    AlignmentMethod, CfaDrizzleResult, DrizzleConfig, DrizzleFrameWeights, DrizzleGeometry,
+    DrizzleKernel, DrizzleResult,
+};
 
+use crate::core::alignment::affine::{align_channel_affine, AffineAlignMethod};
+use crate::core::alignment::mapping::FrameMapping;
 use crate::core::alignment::phase_correlation;
+use crate::core::analysis::subframe::{select_reference, SubframeWeightConfig};
 use crate::core::imaging::boundary::clamp_index;
+use crate::core::imaging::debayer::{CfaLayout, CFA_BLUE, CFA_GREEN, CFA_RED};
 use crate::core::stacking::align;
 use crate::math::median::median_f32_mut;
 use crate::types::compose::AlignMethod;
 use crate::types::constants::MAD_TO_SIGMA;
+use crate::types::header::HduHeader;
+use crate::types::stacking::{CalibrationReport, ReferenceChoice};
+
+const CLIP_CAPACITY: usize = 20;
+const CONTEXT_BITS: usize = 32;
+
+#[derive(Debug, Clone, Copy)]
+struct DropParams {
+    scale: f64,
+    pixfrac: f64,
+    kernel: DrizzleKernel,
+}
 
 struct DrizzleAccumulator {
     storage: Vec<f32>,
+    sample_weights: Option<Vec<f32>>,
     counts: Vec<u16>,
     weights: Vec<f64>,
+    context: Vec<u32>,
+    context_planes: usize,
     max_per_pixel: usize,
     out_rows: usize,
     out_cols: usize,
+    params: DropParams,
 }
 
 impl DrizzleAccumulator {
-    fn new(out_rows: usize, out_cols: usize, n_frames: usize) -> Self {
+    fn new(out_rows: usize, out_cols: usize, n_frames: usize, params: DropParams, weighted: bool) -> Self {
         let n = out_rows * out_cols;
         let max_per_pixel = (n_frames * 2).max(4);
+        let context_planes = n_frames.div_ceil(CONTEXT_BITS).max(1);
         Self {
             storage: vec![0.0f32; n * max_per_pixel],
+            sample_weights: weighted.then(|| vec![0.0f32; n * max_per_pixel]),
             counts: vec![0u16; n],
             weights: vec![0.0; n],
+            context: vec![0u32; n * context_planes],
+            context_planes,
             max_per_pixel,
             out_rows,
             out_cols,
+            params,
         }
     }
 
     #[inline]
-    fn push(&mut self, idx: usize, val: f32, w: f64) {
+    fn push(&mut self, idx: usize, val: f32, w: f64, frame_index: usize) {
+        let plane = frame_index / CONTEXT_BITS;
+        if plane < self.context_planes {
+            self.context[plane * self.out_rows * self.out_cols + idx] |= 1 << (frame_index % CONTEXT_BITS);
+        }
         let count = self.counts[idx] as usize;
         if count < self.max_per_pixel {
-            self.storage[idx * self.max_per_pixel + count] = val;
+            let slot = idx * self.max_per_pixel + count;
+            self.storage[slot] = val;
+            if let Some(sw) = self.sample_weights.as_mut() {
+                sw[slot] = w as f32;
+            }
             self.counts[idx] += 1;
             self.weights[idx] += w;
         }
@@ -47,16 +85,25 @@ impl DrizzleAccumulator {
     fn drizzle_frame(
         &mut self,
         frame: &Array2<f32>,
-        dx: f64,
-        dy: f64,
-        scale: f64,
-        pixfrac: f64,
-        kernel: DrizzleKernel,
+        mapping: &FrameMapping,
+        frame_index: usize,
+        input: Option<&DrizzleFrameWeights>,
+        channel: Option<(&CfaLayout, u8)>,
     ) {
+        let DropParams { scale, pixfrac, kernel } = self.params;
         let (in_rows, in_cols) = frame.dim();
         let src = frame.as_slice().expect("contiguous");
         let out_rows = self.out_rows;
         let out_cols = self.out_cols;
+        let axis_aligned = mapping.is_translation();
+        let shrink = pixfrac * 0.5;
+        let mask = input
+            .and_then(|fw| fw.mask.as_ref())
+            .map(|m| m.as_slice().expect("contiguous"));
+        let weight_map = input
+            .and_then(|fw| fw.weight.as_ref())
+            .map(|w| w.as_slice().expect("contiguous"));
+        let frame_weight = input.map_or(1.0, |fw| fw.frame_weight);
 
         let row_contribs: Vec<Vec<(usize, f32, f64)>> = (0..in_rows)
             .into_par_iter()
@@ -64,15 +111,59 @@ impl DrizzleAccumulator {
                 let mut contribs = Vec::new();
                 let row_base = iy * in_cols;
                 for ix in 0..in_cols {
+                    if let Some((layout, color)) = channel {
+                        if layout.color_at(iy, ix) != color {
+                            continue;
+                        }
+                    }
                     let val = src[row_base + ix];
-                    if !val.is_finite() {
+                    if !val.is_finite() || mask.is_some_and(|m| m[row_base + ix] != 0) {
+                        continue;
+                    }
+                    let input_w = weight_map.map_or(1.0, |w| w[row_base + ix]) * frame_weight;
+                    if !(input_w.is_finite() && input_w > 0.0) {
+                        continue;
+                    }
+                    let input_w = input_w as f64;
+
+                    let (x, y) = (ix as f64, iy as f64);
+                    let (mx, my) = mapping.map(x, y);
+                    let cx = mx * scale;
+                    let cy = my * scale;
+
+                    if kernel == DrizzleKernel::Point {
+                        let (ox, oy) = (cx.floor(), cy.floor());
+                        if ox >= 0.0 && oy >= 0.0 && (ox as usize) < out_cols && (oy as usize) < out_rows {
+                            contribs.push((oy as usize * out_cols + ox as usize, val, input_w));
+                        }
                         continue;
                     }
 
-                    let cx = (ix as f64 + dx) * scale;
-                    let cy = (iy as f64 + dy) * scale;
+                    if kernel == DrizzleKernel::Square && !axis_aligned {
+                        let quad = footprint(mapping, x, y, shrink, scale);
+                        let (lo_x, lo_y, hi_x, hi_y) = quad.iter().fold(
+                            (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+                            |(a, b, c, d), &(px, py)| (a.min(px), b.min(py), c.max(px), d.max(py)),
+                        );
+                        if hi_x < 0.0 || hi_y < 0.0 || lo_x >= out_cols as f64 || lo_y >= out_rows as f64 {
+                            continue;
+                        }
+                        for oy in clamp_index(lo_y.floor() as i64, out_rows)..=clamp_index(hi_y.floor() as i64, out_rows) {
+                            for ox in clamp_index(lo_x.floor() as i64, out_cols)..=clamp_index(hi_x.floor() as i64, out_cols) {
+                                let w = polygon_pixel_overlap(&quad, ox as f64, oy as f64);
+                                if w > 1e-12 {
+                                    contribs.push((oy * out_cols + ox, val, w * input_w));
+                                }
+                            }
+                        }
+                        continue;
+                    }
 
-                    let half = pixfrac * scale * 0.5;
+                    let half = if kernel == DrizzleKernel::Turbo && !axis_aligned {
+                        0.5 * polygon_area(&footprint(mapping, x, y, shrink, scale)).sqrt()
+                    } else {
+                        pixfrac * scale * 0.5
+                    };
                     let ox_min = clamp_index((cx - half).floor() as i64, out_cols);
                     let ox_max = clamp_index((cx + half).ceil() as i64, out_cols);
                     let oy_min = clamp_index((cy - half).floor() as i64, out_rows);
@@ -81,7 +172,7 @@ impl DrizzleAccumulator {
                     for oy in oy_min..=oy_max {
                         for ox in ox_min..=ox_max {
                             let w = match kernel {
-                                DrizzleKernel::Square => {
+                                DrizzleKernel::Square | DrizzleKernel::Turbo | DrizzleKernel::Point => {
                                     overlap_area(
                                         cx - half, cy - half, cx + half, cy + half,
                                         ox as f64, oy as f64, ox as f64 + 1.0, oy as f64 + 1.0,
@@ -102,7 +193,7 @@ impl DrizzleAccumulator {
 
                             if w > 1e-12 {
                                 let idx = oy * out_cols + ox;
-                                contribs.push((idx, val, w));
+                                contribs.push((idx, val, w * input_w));
                             }
                         }
                     }
@@ -113,7 +204,7 @@ impl DrizzleAccumulator {
 
         for contribs in row_contribs {
             for (idx, val, w) in contribs {
-                self.push(idx, val, w);
+                self.push(idx, val, w, frame_index);
             }
         }
     }
@@ -139,7 +230,9 @@ impl DrizzleAccumulator {
                 }
 
                 let base = i * mpp;
-                let mut active: Vec<f32> = self.storage[base..base + count].to_vec();
+                let values = &self.storage[base..base + count];
+                let mut keep = vec![true; count];
+                let mut active: Vec<f32> = values.to_vec();
                 let mut rejected = 0u64;
 
                 for _ in 0..sigma_iterations {
@@ -152,26 +245,38 @@ impl DrizzleAccumulator {
                     let mad = median_f32_mut(&mut devs);
                     let sigma = (mad as f64 * MAD_TO_SIGMA).max(1e-10) as f32;
 
-                    let before = active.len();
-                    active.retain(|&v| {
-                        let dev = v - median;
-                        dev >= -sigma_low * sigma && dev <= sigma_high * sigma
-                    });
-                    let removed = before - active.len();
-                    rejected += removed as u64;
+                    let lo = median - sigma_low * sigma;
+                    let hi = median + sigma_high * sigma;
+                    let mut removed = 0u64;
+                    for (k, &v) in keep.iter_mut().zip(values) {
+                        if *k && (v < lo || v > hi) {
+                            *k = false;
+                            removed += 1;
+                        }
+                    }
+                    rejected += removed;
                     if removed == 0 {
                         break;
                     }
+                    active = values.iter().zip(&keep).filter(|(_, &k)| k).map(|(&v, _)| v).collect();
                 }
 
                 if active.is_empty() {
-                    let sum: f64 = self.storage[base..base + count]
-                        .iter()
-                        .map(|v| *v as f64)
-                        .sum();
+                    let sum: f64 = values.iter().map(|v| *v as f64).sum();
                     return ((sum / count as f64) as f32, self.weights[i] as f32, rejected);
                 }
 
+                if let Some(sw) = self.sample_weights.as_ref() {
+                    let (mut sum_wv, mut sum_w) = (0.0f64, 0.0f64);
+                    for ((&v, &w), _) in values.iter().zip(&sw[base..base + count]).zip(&keep).filter(|(_, &k)| k) {
+                        sum_wv += w as f64 * v as f64;
+                        sum_w += w as f64;
+                    }
+                    if sum_w > 0.0 {
+                        return ((sum_wv / sum_w) as f32, self.weights[i] as f32, rejected);
+                    }
+                }
+
                 let mean = active.iter().map(|v| *v as f64).sum::<f64>() / active.len() as f64;
                 (mean as f32, self.weights[i] as f32, rejected)
             })
@@ -191,6 +296,14 @@ impl DrizzleAccumulator {
         let weights = Array2::from_shape_vec((self.out_rows, self.out_cols), wgt_data).unwrap();
         (image, weights, total_rejected)
     }
+
+    fn context_images(&self) -> Vec<Array2<u32>> {
+        let n = self.out_rows * self.out_cols;
+        self.context
+            .chunks(n)
+            .map(|plane| Array2::from_shape_vec((self.out_rows, self.out_cols), plane.to_vec()).unwrap())
+            .collect()
+    }
 }
 
 #[inline]
@@ -203,6 +316,66 @@ fn overlap_area(
     ox * oy
 }
 
+fn footprint(mapping: &FrameMapping, x: f64, y: f64, half: f64, scale: f64) -> [(f64, f64); 4] {
+    [(-half, -half), (half, -half), (half, half), (-half, half)].map(|(ox, oy)| {
+        let (mx, my) = mapping.map(x + ox, y + oy);
+        (mx * scale, my * scale)
+    })
+}
+
+fn clip_edge(
+    poly: &[(f64, f64)],
+    along_x: bool,
+    bound: f64,
+    keep_above: bool,
+    out: &mut [(f64, f64); CLIP_CAPACITY],
+) -> usize {
+    let coord = |p: (f64, f64)| if along_x { p.0 } else { p.1 };
+    let inside = |p: (f64, f64)| if keep_above { coord(p) >= bound } else { coord(p) <= bound };
+    let len = poly.len();
+    let mut n = 0;
+    for i in 0..len {
+        let cur = poly[i];
+        let prev = poly[(i + len - 1) % len];
+        let (cur_in, prev_in) = (inside(cur), inside(prev));
+        if cur_in != prev_in && n < CLIP_CAPACITY {
+            let t = (bound - coord(prev)) / (coord(cur) - coord(prev));
+            out[n] = (prev.0 + t * (cur.0 - prev.0), prev.1 + t * (cur.1 - prev.1));
+            n += 1;
+        }
+        if cur_in && n < CLIP_CAPACITY {
+            out[n] = cur;
+            n += 1;
+        }
+    }
+    n
+}
+
+fn polygon_area(poly: &[(f64, f64)]) -> f64 {
+    let len = poly.len();
+    if len < 3 {
+        return 0.0;
+    }
+    let twice: f64 = (0..len)
+        .map(|i| {
+            let (x0, y0) = poly[i];
+            let (x1, y1) = poly[(i + 1) % len];
+            x0 * y1 - x1 * y0
+        })
+        .sum();
+    0.5 * twice.abs()
+}
+
+fn polygon_pixel_overlap(quad: &[(f64, f64); 4], px: f64, py: f64) -> f64 {
+    let mut a = [(0.0, 0.0); CLIP_CAPACITY];
+    let mut b = [(0.0, 0.0); CLIP_CAPACITY];
+    let n = clip_edge(quad, true, px, true, &mut a);
+    let n = clip_edge(&a[..n], true, px + 1.0, false, &mut b);
+    let n = clip_edge(&b[..n], false, py, true, &mut a);
+    let n = clip_edge(&a[..n], false, py + 1.0, false, &mut b);
+    polygon_area(&b[..n])
+}
+
 #[inline]
 fn lanczos3(x: f64) -> f64 {
     if x.abs() < 1e-12 {
@@ -223,17 +396,7 @@ fn map_alignment_method(m: AlignmentMethod) -> AlignMethod {
     }
 }
 
-pub fn drizzle_stack(
-    images: &[Array2<f32>],
-    config: &DrizzleConfig,
-) -> Result<DrizzleResult> {
-    if images.is_empty() {
-        bail!("No images to drizzle");
-    }
-    if images.len() < 2 {
-        bail!("Drizzle requires at least 2 frames for sub-pixel reconstruction");
-    }
-
+fn harmonize_frames(images: &[Array2<f32>]) -> Result<Vec<Cow<'_, Array2<f32>>>> {
     let min_rows = images.iter().map(|img| img.dim().0).min().unwrap();
     let min_cols = images.iter().map(|img| img.dim().1).min().unwrap();
     let max_rows = images.iter().map(|img| img.dim().0).max().unwrap();
@@ -250,40 +413,40 @@ pub fn drizzle_stack(
         );
     }
 
-    let in_rows = min_rows;
-    let in_cols = min_cols;
-
-    let needs_crop = row_diff > 0 || col_diff > 0;
-    let cropped: Vec<Array2<f32>>;
-    let images_ref: Vec<&Array2<f32>> = if needs_crop {
-        cropped = images.iter().map(|img| {
+    Ok(images
+        .iter()
+        .map(|img| {
             let (r, c) = img.dim();
-            if r == in_rows && c == in_cols {
-                img.clone()
+            if r == min_rows && c == min_cols {
+                Cow::Borrowed(img)
             } else {
-                img.slice(ndarray::s![..in_rows, ..in_cols]).to_owned()
+                Cow::Owned(img.slice(ndarray::s![..min_rows, ..min_cols]).to_owned())
             }
-        }).collect();
-        cropped.iter().collect()
-    } else {
-        images.iter().collect()
-    };
+        })
+        .collect())
+}
 
-    let scale = config.scale.clamp(1.0, 4.0);
-    let pixfrac = config.pixfrac.clamp(0.1, 1.0);
-    let out_rows = (in_rows as f64 * scale).ceil() as usize;
-    let out_cols = (in_cols as f64 * scale).ceil() as usize;
+pub(crate) fn drizzle_reference(images_ref: &[&Array2<f32>], config: &DrizzleConfig) -> Result<ReferenceChoice> {
+    if config.align || config.geometry == DrizzleGeometry::Wcs {
+        select_reference(images_ref, config.reference, &SubframeWeightConfig::default())
+    } else {
+        Ok(ReferenceChoice::fixed(0, "alignment disabled"))
+    }
+}
 
-    let reference = images_ref[0];
-    let mut offsets: Vec<(f64, f64)> = Vec::with_capacity(images_ref.len());
-    offsets.push((0.0, 0.0));
+fn compute_offsets(images_ref: &[&Array2<f32>], reference_index: usize, config: &DrizzleConfig) -> Vec<(f64, f64)> {
+    let reference = images_ref[reference_index];
 
     if config.align {
         match config.alignment_method {
             AlignmentMethod::PhaseCorrelation => {
-                let computed: Vec<(f64, f64)> = images_ref[1..]
+                images_ref
                     .par_iter()
-                    .map(|target| {
+                    .enumerate()
+                    .map(|(i, target)| {
+                        if i == reference_index {
+                            return (0.0, 0.0);
+                        }
                         let pc = phase_correlation::phase_correlate(reference, target);
                         if phase_correlation::is_low_confidence(pc.confidence) {
                             let fallback = align::estimate_offset(
@@ -296,35 +459,201 @@ pub fn drizzle_stack(
                             (pc.dx, pc.dy)
                         }
                     })
-                    .collect();
-                offsets.extend(computed);
+                    .collect()
             }
             AlignmentMethod::Zncc => {
                 log::warn!(
                     "ZNCC alignment requested; routing to star-based Affine (ZNCC path was removed)"
                 );
                 let mapped = map_alignment_method(AlignmentMethod::Zncc);
-                let computed: Vec<(f64, f64)> = images_ref[1..]
+                images_ref
                     .par_iter()
-                    .map(|target| {
+                    .enumerate()
+                    .map(|(i, target)| {
+                        if i == reference_index {
+                            return (0.0, 0.0);
+                        }
                         let est = align::estimate_offset(reference, target, mapped);
                         (est.dx, est.dy)
                     })
-                    .collect();
-                offsets.extend(computed);
+                    .collect()
             }
         }
     } else {
-        for _ in 1..images_ref.len() {
-            offsets.push((0.0, 0.0));
+        vec![(0.0, 0.0); images_ref.len()]
+    }
+}
+
+pub(crate) fn cfa_luminance(cfa: &Array2<f32>, layout: &CfaLayout) -> Array2<f32> {
+    let (rows, cols) = cfa.dim();
+    let src = cfa.as_slice().expect("contiguous");
+    let radius: isize = if layout.is_xtrans() { 1 } else { 0 };
+    let span = if layout.is_xtrans() { 3 } else { 2 };
+    let mut out = vec![0.0f32; rows * cols];
+
+    out.par_chunks_mut(cols).enumerate().for_each(|(y, row)| {
+        for (x, slot) in row.iter_mut().enumerate() {
+            let mut sum = 0.0f32;
+            let mut n = 0u32;
+            for dy in 0..span {
+                for dx in 0..span {
+                    let yy = (y as isize + dy - radius).clamp(0, rows as isize - 1) as usize;
+                    let xx = (x as isize + dx - radius).clamp(0, cols as isize - 1) as usize;
+                    let v = src[yy * cols + xx];
+                    if v.is_finite() {
+                        sum += v;
+                        n += 1;
+                    }
+                }
+            }
+            *slot = if n > 0 { sum / n as f32 } else { 0.0 };
         }
+    });
+
+    Array2::from_shape_vec((rows, cols), out).unwrap()
+}
+
+fn compute_mappings(images_ref: &[&Array2<f32>], reference_index: usize, config: &DrizzleConfig) -> Vec<FrameMapping> {
+    if config.align && config.geometry == DrizzleGeometry::Affine {
+        let reference = images_ref[reference_index];
+        return images_ref.par_iter().enumerate().map(|(i, target)| {
+            if i == reference_index {
+                return FrameMapping::identity();
+            }
+            let fit = align_channel_affine(reference, target);
+            match fit.transform.inverse() {
+                Some(inv) if fit.method != AffineAlignMethod::Identity => {
+                    log::info!(
+                        "drizzle frame_{}: {:?} fit, rot={:.3}deg, {} inliers, residual={:.3}px",
+                        i, fit.method, fit.transform.rotation_deg(), fit.inliers, fit.residual_px
+                    );
+                    FrameMapping::Affine(inv)
+                }
+                _ => {
+                    log::warn!("drizzle frame_{}: affine fit failed, using identity", i);
+                    FrameMapping::identity()
+                }
+            }
+        }).collect();
     }
 
-    let mut accumulator = DrizzleAccumulator::new(out_rows, out_cols, images_ref.len());
+    if config.geometry == DrizzleGeometry::Wcs {
+        log::warn!("WCS drizzle geometry needs frame headers; falling back to translation");
+    }
+    compute_offsets(images_ref, reference_index, config)
+        .into_iter()
+        .map(|(dx, dy)| FrameMapping::from_offset(dx, dy))
+        .collect()
+}
 
-    for (i, img) in images_ref.iter().enumerate() {
-        let (dx, dy) = offsets[i];
-        accumulator.drizzle_frame(img, -dx, -dy, scale, pixfrac, config.kernel);
+pub fn wcs_mappings(headers: &[HduHeader], reference_index: usize) -> Result<Vec<FrameMapping>> {
+    let reference = headers.get(reference_index).context("No reference header for WCS mapping")?;
+    headers
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            FrameMapping::from_wcs_headers(h, reference)
+                .with_context(|| format!("WCS mapping for frame_{}", i))
+        })
+        .collect()
+}
+
+fn mapping_offsets(mappings: &[FrameMapping], dims: (usize, usize)) -> Vec<(f64, f64)> {
+    let cx = dims.1 as f64 * 0.5;
+    let cy = dims.0 as f64 * 0.5;
+    mappings.iter().map(|m| m.offset_at(cx, cy)).collect()
+}
+
+fn drop_params(in_dims: (usize, usize), config: &DrizzleConfig) -> (DropParams, usize, usize) {
+    let scale = config.scale.clamp(1.0, 4.0);
+    let pixfrac = config.pixfrac.clamp(0.1, 1.0);
+    let out_rows = (in_dims.0 as f64 * scale).ceil() as usize;
+    let out_cols = (in_dims.1 as f64 * scale).ceil() as usize;
+    (DropParams { scale, pixfrac, kernel: config.kernel }, out_rows, out_cols)
+}
+
+fn harmonize_weights(
+    weights: &[DrizzleFrameWeights],
+    n_frames: usize,
+    dims: (usize, usize),
+) -> Result<Vec<Cow<'_, DrizzleFrameWeights>>> {
+    if weights.is_empty() {
+        return Ok(Vec::new());
+    }
+    if weights.len() != n_frames {
+        bail!("Got {} weight entries for {} frames", weights.len(), n_frames);
+    }
+
+    weights
+        .iter()
+        .enumerate()
+        .map(|(i, fw)| {
+            let too_small = |d: (usize, usize)| d.0 < dims.0 || d.1 < dims.1;
+            if fw.weight.as_ref().is_some_and(|w| too_small(w.dim()))
+                || fw.mask.as_ref().is_some_and(|m| too_small(m.dim()))
+            {
+                bail!("Weight map or mask for frame_{} is smaller than the frame", i);
+            }
+            let fits = |d: (usize, usize)| d == dims;
+            if fw.weight.as_ref().is_none_or(|w| fits(w.dim()))
+                && fw.mask.as_ref().is_none_or(|m| fits(m.dim()))
+            {
+                return Ok(Cow::Borrowed(fw));
+            }
+            Ok(Cow::Owned(DrizzleFrameWeights {
+                weight: fw.weight.as_ref().map(|w| w.slice(ndarray::s![..dims.0, ..dims.1]).to_owned()),
+                mask: fw.mask.as_ref().map(|m| m.slice(ndarray::s![..dims.0, ..dims.1]).to_owned()),
+                frame_weight: fw.frame_weight,
+            }))
+        })
+        .collect()
+}
+
+fn is_weighted(weights: &[Cow<'_, DrizzleFrameWeights>]) -> bool {
+    weights.iter().any(|fw| fw.weight.is_some() || (fw.frame_weight - 1.0).abs() > 1e-6)
+}
+
+pub fn drizzle_stack(
+    images: &[Array2<f32>],
+    config: &DrizzleConfig,
+) -> Result<DrizzleResult> {
+    drizzle_stack_with(images, None, &[], config)
+}
+
+pub fn drizzle_stack_with(
+    images: &[Array2<f32>],
+    mappings: Option<&[FrameMapping]>,
+    weights: &[DrizzleFrameWeights],
+    config: &DrizzleConfig,
+) -> Result<DrizzleResult> {
+    if images.is_empty() {
+        bail!("No images to drizzle");
+    }
+    if images.len() < 2 {
+        bail!("Drizzle requires at least 2 frames for sub-pixel reconstruction");
+    }
+    if mappings.is_some_and(|m| m.len() != images.len()) {
+        bail!("Mapping count does not match the {} frames", images.len());
+    }
+
+    let frames = harmonize_frames(images)?;
+    let images_ref: Vec<&Array2<f32>> = frames.iter().map(|f| f.as_ref()).collect();
+    let (in_rows, in_cols) = images_ref[0].dim();
+    let weights = harmonize_weights(weights, images_ref.len(), (in_rows, in_cols))?;
+    let (mappings, reference): (Cow<'_, [FrameMapping]>, _) = match mappings {
+        Some(m) => (Cow::Borrowed(m), ReferenceChoice::fixed(0, "supplied mappings")),
+        None => {
+            let reference = drizzle_reference(&images_ref, config)?;
+            (Cow::Owned(compute_mappings(&images_ref, reference.index, config)), reference)
+        }
+    };
+
+    let (params, out_rows, out_cols) = drop_params((in_rows, in_cols), config);
+    let mut accumulator =
+        DrizzleAccumulator::new(out_rows, out_cols, images_ref.len(), params, is_weighted(&weights));
+
+    for (i, (img, mapping)) in images_ref.iter().zip(mappings.iter()).enumerate() {
+        accumulator.drizzle_frame(img, mapping, i, weights.get(i).map(|w| w.as_ref()), None);
     }
 
     let (image, weight_map, rejected_pixels) = accumulator.finalize(
@@ -336,11 +665,344 @@ pub fn drizzle_stack(
     Ok(DrizzleResult {
         image,
         weight_map,
+        context: accumulator.context_images(),
         frame_count: images_ref.len(),
-        output_scale: scale,
+        output_scale: params.scale,
         input_dims: (in_rows, in_cols),
         output_dims: (out_rows, out_cols),
-        offsets,
+        offsets: mapping_offsets(&mappings, (in_rows, in_cols)),
         rejected_pixels,
+        reference,
+        trails: Vec::new(),
+        calibration: CalibrationReport::default(),
     })
 }
+
+pub fn drizzle_cfa_stack(
+    images: &[Array2<f32>],
+    layout: &CfaLayout,
+    config: &DrizzleConfig,
+) -> Result<CfaDrizzleResult> {
+    drizzle_cfa_stack_with(images, layout, None, &[], config)
+}
+
+pub fn drizzle_cfa_stack_with(
+    images: &[Array2<f32>],
+    layout: &CfaLayout,
+    mappings: Option<&[FrameMapping]>,
+    weights: &[DrizzleFrameWeights],
+    config: &DrizzleConfig,
+) -> Result<CfaDrizzleResult> {
+    if images.len() < 2 {
+        bail!("Bayer drizzle requires at least 2 dithered frames");
+    }
+    if mappings.is_some_and(|m| m.len() != images.len()) {
+        bail!("Mapping count does not match the {} frames", images.len());
+    }
+
+    let frames = harmonize_frames(images)?;
+    let (in_rows, in_cols) = frames[0].dim();
+    let weights = harmonize_weights(weights, frames.len(), (in_rows, in_cols))?;
+    let (params, out_rows, out_cols) = drop_params((in_rows, in_cols), config);
+
+    let (mappings, reference): (Cow<'_, [FrameMapping]>, _) = match mappings {
+        Some(m) => (Cow::Borrowed(m), ReferenceChoice::fixed(0, "supplied mappings")),
+        None => {
+            let luminance: Vec<Array2<f32>> =
+                frames.par_iter().map(|f| cfa_luminance(f, layout)).collect();
+            let luminance_ref: Vec<&Array2<f32>> = luminance.iter().collect();
+            let reference = drizzle_reference(&luminance_ref, config)?;
+            (Cow::Owned(compute_mappings(&luminance_ref, reference.index, config)), reference)
+        }
+    };
+
+    let weighted = is_weighted(&weights);
+    let mut context: Vec<Array2<u32>> = Vec::new();
+    let channels: Vec<(Array2<f32>, Array2<f32>, u64)> = [CFA_RED, CFA_GREEN, CFA_BLUE]
+        .iter()
+        .map(|&color| {
+            let mut accumulator =
+                DrizzleAccumulator::new(out_rows, out_cols, frames.len(), params, weighted);
+            for (i, (frame, mapping)) in frames.iter().zip(mappings.iter()).enumerate() {
+                accumulator.drizzle_frame(
+                    frame,
+                    mapping,
+                    i,
+                    weights.get(i).map(|w| w.as_ref()),
+                    Some((layout, color)),
+                );
+            }
+            let planes = accumulator.context_images();
+            if context.is_empty() {
+                context = planes;
+            } else {
+                for (acc, plane) in context.iter_mut().zip(&planes) {
+                    *acc |= plane;
+                }
+            }
+            accumulator.finalize(config.sigma_low, config.sigma_high, config.sigma_iterations)
+        })
+        .collect();
+
+    let mut it = channels.into_iter();
+    let (r, weight_r, rej_r) = it.next().unwrap();
+    let (g, weight_g, rej_g) = it.next().unwrap();
+    let (b, weight_b, rej_b) = it.next().unwrap();
+
+    Ok(CfaDrizzleResult {
+        r,
+        g,
+        b,
+        weight_r,
+        weight_g,
+        weight_b,
+        context,
+        frame_count: frames.len(),
+        output_scale: params.scale,
+        input_dims: (in_rows, in_cols),
+        output_dims: (out_rows, out_cols),
+        offsets: mapping_offsets(&mappings, (in_rows, in_cols)),
+        rejected_pixels: rej_r + rej_g + rej_b,
+        reference,
+        trails: Vec::new(),
+        calibration: CalibrationReport::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uniform_cfa(layout: &CfaLayout, rows: usize, cols: usize, rgb: [f32; 3]) -> Array2<f32> {
+        Array2::from_shape_fn((rows, cols), |(y, x)| rgb[layout.color_at(y, x) as usize])
+    }
+
+    #[test]
+    fn test_cfa_drizzle_separates_channels() {
+        let layout = CfaLayout::parse("RGGB", 0, 0).unwrap();
+        let frames: Vec<Array2<f32>> = (0..4)
+            .map(|_| uniform_cfa(&layout, 32, 32, [300.0, 200.0, 100.0]))
+            .collect();
+        let config = DrizzleConfig {
+            scale: 1.0,
+            pixfrac: 1.0,
+            align: false,
+            ..Default::default()
+        };
+        let result = drizzle_cfa_stack(&frames, &layout, &config).unwrap();
+        assert_eq!(result.output_dims, (32, 32));
+        for (img, weights, expected) in [
+            (&result.r, &result.weight_r, 300.0),
+            (&result.g, &result.weight_g, 200.0),
+            (&result.b, &result.weight_b, 100.0),
+        ] {
+            let covered: Vec<f32> = img
+                .iter()
+                .zip(weights.iter())
+                .filter(|(_, &w)| w > 0.0)
+                .map(|(&v, _)| v)
+                .collect();
+            assert!(!covered.is_empty());
+            assert!(covered.iter().all(|&v| (v - expected).abs() < 1e-3));
+        }
+    }
+
+    #[test]
+    fn test_cfa_drizzle_green_weight_exceeds_red() {
+        let layout = CfaLayout::parse("RGGB", 0, 0).unwrap();
+        let frames: Vec<Array2<f32>> = (0..3)
+            .map(|_| uniform_cfa(&layout, 16, 16, [1.0, 1.0, 1.0]))
+            .collect();
+        let config = DrizzleConfig { align: false, ..Default::default() };
+        let result = drizzle_cfa_stack(&frames, &layout, &config).unwrap();
+        let total = |w: &Array2<f32>| w.iter().map(|&v| v as f64).sum::<f64>();
+        assert!(total(&result.weight_g) > 1.5 * total(&result.weight_r));
+    }
+
+    #[test]
+    fn test_cfa_luminance_removes_checkerboard() {
+        let layout = CfaLayout::parse("RGGB", 0, 0).unwrap();
+        let cfa = uniform_cfa(&layout, 8, 8, [400.0, 200.0, 0.0]);
+        let luma = cfa_luminance(&cfa, &layout);
+        assert!((luma[[3, 3]] - luma[[3, 4]]).abs() < 1e-4);
+        assert!((luma[[3, 3]] - 200.0).abs() < 1e-4);
+    }
+
+    fn accumulator(size: usize, scale: f64, pixfrac: f64, kernel: DrizzleKernel) -> DrizzleAccumulator {
+        DrizzleAccumulator::new(size, size, 1, DropParams { scale, pixfrac, kernel }, false)
+    }
+
+    fn rotation_about(center: f64, deg: f64) -> FrameMapping {
+        let (sin, cos) = deg.to_radians().sin_cos();
+        FrameMapping::Affine(crate::core::alignment::affine::AffineTransform {
+            a: cos,
+            b: -sin,
+            tx: center - cos * center + sin * center,
+            c: sin,
+            d: cos,
+            ty: center - sin * center - cos * center,
+        })
+    }
+
+    #[test]
+    fn test_polygon_overlap_rotated_square() {
+        let mapping = rotation_about(0.5, 45.0);
+        let quad = footprint(&mapping, 0.5, 0.5, 0.5, 1.0);
+        let center = polygon_pixel_overlap(&quad, 0.0, 0.0);
+        assert!((center - 2.0 * (2f64.sqrt() - 1.0)).abs() < 1e-9, "overlap={}", center);
+        let total: f64 = (-2..=2)
+            .flat_map(|oy| (-2..=2).map(move |ox| (ox as f64, oy as f64)))
+            .map(|(ox, oy)| polygon_pixel_overlap(&quad, ox, oy))
+            .sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_square_matches_box_for_translation() {
+        let frame = Array2::from_shape_fn((12, 12), |(y, x)| (y * 12 + x) as f32);
+        let mapping = FrameMapping::from_offset(0.3, -0.6);
+        let mut boxed = accumulator(24, 2.0, 0.7, DrizzleKernel::Square);
+        boxed.drizzle_frame(&frame, &mapping, 0, None, None);
+        let shifted = FrameMapping::Affine(crate::core::alignment::affine::AffineTransform::translation(-0.3, 0.6));
+        let mut exact = accumulator(24, 2.0, 0.7, DrizzleKernel::Square);
+        exact.drizzle_frame(&frame, &shifted, 0, None, None);
+        for (a, b) in boxed.weights.iter().zip(&exact.weights) {
+            assert!((a - b).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_rotated_frame_conserves_footprint_area() {
+        let frame = Array2::from_elem((32, 32), 1.0f32);
+        let mapping = rotation_about(15.5, 30.0);
+        let mut acc = accumulator(64, 2.0, 0.5, DrizzleKernel::Square);
+        acc.drizzle_frame(&frame, &mapping, 0, None, None);
+        let total: f64 = acc.weights.iter().sum();
+
+        let corners = |x: f64, y: f64| footprint(&mapping, x, y, 0.25, 2.0);
+        let inside = |p: &(f64, f64)| p.0 >= 0.0 && p.1 >= 0.0 && p.0 <= 64.0 && p.1 <= 64.0;
+        let (mut full, mut touching) = (0usize, 0usize);
+        for y in 0..32 {
+            for x in 0..32 {
+                let q = corners(x as f64, y as f64);
+                if q.iter().all(inside) {
+                    full += 1;
+                }
+                if q.iter().any(inside) {
+                    touching += 1;
+                }
+            }
+        }
+        assert!(total >= full as f64 - 1e-6 && total <= touching as f64 + 1e-6, "total={}", total);
+    }
+
+    #[test]
+    fn test_rotated_drizzle_registers_gradient() {
+        let reference = Array2::from_shape_fn((40, 40), |(_, x)| x as f32);
+        let mapping = rotation_about(19.5, 20.0);
+        let rotated = Array2::from_shape_fn((40, 40), |(y, x)| mapping.map(x as f64, y as f64).0 as f32);
+        let config = DrizzleConfig { scale: 1.0, pixfrac: 1.0, align: false, ..Default::default() };
+        let result = drizzle_stack_with(
+            &[reference, rotated],
+            Some(&[FrameMapping::identity(), mapping]),
+            &[],
+            &config,
+        )
+        .unwrap();
+        for y in 12..28 {
+            for x in 12..28 {
+                let v = result.image[[y, x]];
+                assert!((v - (x as f32 + 0.5)).abs() < 0.35, "({}, {}) = {}", x, y, v);
+            }
+        }
+    }
+
+    #[test]
+    fn test_point_kernel_hits_single_pixel() {
+        let frame = Array2::from_elem((8, 8), 5.0f32);
+        let mut acc = accumulator(16, 2.0, 1.0, DrizzleKernel::Point);
+        acc.drizzle_frame(&frame, &FrameMapping::identity(), 0, None, None);
+        assert_eq!(acc.counts[2 * 16 + 2], 1);
+        assert_eq!(acc.counts[3 * 16 + 3], 0);
+        assert_eq!(acc.weights.iter().sum::<f64>(), 64.0);
+    }
+
+    #[test]
+    fn test_point_and_square_centroids_agree() {
+        let star = |dx: f64, dy: f64| {
+            Array2::from_shape_fn((16, 16), |(y, x)| {
+                let r2 = (x as f64 - 7.3 - dx).powi(2) + (y as f64 - 8.6 - dy).powi(2);
+                (1000.0 * (-r2 / 4.0).exp()) as f32
+            })
+        };
+        let centroid = |kernel: DrizzleKernel| {
+            let params = DropParams { scale: 2.0, pixfrac: 1.0, kernel };
+            let mut acc = DrizzleAccumulator::new(32, 32, 16, params, true);
+            for (i, (dx, dy)) in [0.1, 0.35, 0.6, 0.85]
+                .iter()
+                .flat_map(|&dx| [0.15, 0.4, 0.65, 0.9].map(|dy| (dx, dy)))
+                .enumerate()
+            {
+                acc.drizzle_frame(&star(dx, dy), &FrameMapping::from_offset(dx, dy), i, None, None);
+            }
+            let sw = acc.sample_weights.as_ref().unwrap();
+            let (mut sx, mut sy, mut total) = (0.0, 0.0, 0.0);
+            for idx in 0..32 * 32 {
+                let start = idx * acc.max_per_pixel;
+                let flux: f64 = (start..start + acc.counts[idx] as usize)
+                    .map(|slot| acc.storage[slot] as f64 * sw[slot] as f64)
+                    .sum();
+                sx += flux * (idx % 32) as f64;
+                sy += flux * (idx / 32) as f64;
+                total += flux;
+            }
+            (sx / total, sy / total)
+        };
+        let (px, py) = centroid(DrizzleKernel::Point);
+        let (qx, qy) = centroid(DrizzleKernel::Square);
+        assert!((px - qx).abs() < 0.1 && (py - qy).abs() < 0.1, "point=({}, {}) square=({}, {})", px, py, qx, qy);
+    }
+
+    #[test]
+    fn test_masked_pixels_are_skipped() {
+        let frames: Vec<Array2<f32>> = (0..2).map(|_| Array2::from_elem((16, 16), 10.0f32)).collect();
+        let mut mask = Array2::<u8>::zeros((16, 16));
+        mask[[8, 8]] = 1;
+        let mut hot = frames[1].clone();
+        hot[[8, 8]] = 5000.0;
+        let weights = vec![
+            DrizzleFrameWeights::default(),
+            DrizzleFrameWeights { mask: Some(mask), ..Default::default() },
+        ];
+        let config = DrizzleConfig {
+            scale: 1.0,
+            kernel: DrizzleKernel::Point,
+            align: false,
+            ..Default::default()
+        };
+        let result = drizzle_stack_with(&[frames[0].clone(), hot], None, &weights, &config).unwrap();
+        assert!(result.image.iter().all(|&v| (v - 10.0).abs() < 1e-4));
+        assert_eq!(result.context.len(), 1);
+        assert_eq!(result.context[0][[4, 4]], 0b11);
+        assert_eq!(result.context[0][[8, 8]] & 0b10, 0);
+    }
+
+    #[test]
+    fn test_weight_maps_bias_the_mean() {
+        let a = Array2::from_elem((16, 16), 100.0f32);
+        let b = Array2::from_elem((16, 16), 200.0f32);
+        let weights = vec![
+            DrizzleFrameWeights { weight: Some(Array2::from_elem((16, 16), 3.0)), ..Default::default() },
+            DrizzleFrameWeights::default(),
+        ];
+        let config = DrizzleConfig {
+            scale: 2.0,
+            pixfrac: 0.5,
+            align: false,
+            sigma_iterations: 0,
+            ..Default::default()
+        };
+        let result = drizzle_stack_with(&[a, b], None, &weights, &config).unwrap();
+        assert!((result.image[[16, 16]] - 125.0).abs() < 1e-3, "got {}", result.image[[16, 16]]);
+    }
+}
@@ -0,0 +1,348 @@
+use std::sync::Mutex;
+
+use anyhow::{bail, Result};
+use ndarray::Array2;
+use rayon::prelude::*;
+
+use crate::core::alignment::local::{estimate_shift_field, LocalAlignConfig, ShiftField};
+use crate::core::alignment::phase_correlation;
+use crate::core::cube::video::VideoSequence;
+use crate::core::imaging::debayer::{self, CfaLayout, DebayerMethod};
+use crate::core::stacking::calibration::{load_calibrated_frame, CalibrationConfig};
+use crate::infra::progress::ProgressHandle;
+use crate::types::error::AppError;
+use crate::types::stacking::{CalibrationReport, FrameQuality, LuckyConfig, LuckyStackResult, QualityMetric};
+
+const MAX_LOCAL_SHIFT_FRACTION: f64 = 0.25;
+const QUALITY_EPSILON: f64 = 1e-12;
+
+pub fn selected_count(frame_count: usize, keep_percent: f32) -> usize {
+    let keep = (frame_count as f64 * keep_percent.clamp(0.0, 100.0) as f64 / 100.0).ceil() as usize;
+    keep.clamp(1, frame_count.max(1))
+}
+
+pub fn frame_quality(image: &Array2<f32>, metric: QualityMetric) -> f64 {
+    let (rows, cols) = image.dim();
+    if rows < 5 || cols < 5 {
+        return 0.0;
+    }
+    let smooth = box_blur3(image);
+    let s = smooth.as_slice().expect("contiguous");
+
+    let (energy, mean, count) = (1..rows - 1)
+        .into_par_iter()
+        .map(|y| {
+            let mut energy = 0.0f64;
+            let mut sum = 0.0f64;
+            let mut count = 0usize;
+            for x in 1..cols - 1 {
+                let c = s[y * cols + x] as f64;
+                let up = s[(y - 1) * cols + x] as f64;
+                let down = s[(y + 1) * cols + x] as f64;
+                let left = s[y * cols + x - 1] as f64;
+                let right = s[y * cols + x + 1] as f64;
+                let e = match metric {
+                    QualityMetric::Laplacian => (up + down + left + right - 4.0 * c).powi(2),
+                    QualityMetric::Gradient => ((right - left) * 0.5).powi(2) + ((down - up) * 0.5).powi(2),
+                };
+                if e.is_finite() {
+                    energy += e;
+                    sum += c;
+                    count += 1;
+                }
+            }
+            (energy, sum, count)
+        })
+        .reduce(|| (0.0, 0.0, 0), |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2));
+
+    if count == 0 {
+        return 0.0;
+    }
+    let mean = mean / count as f64;
+    (energy / count as f64) / (mean * mean).max(QUALITY_EPSILON)
+}
+
+fn box_blur3(image: &Array2<f32>) -> Array2<f32> {
+    let (rows, cols) = image.dim();
+    let src = image.as_slice().expect("contiguous");
+    let mut out = vec![0.0f32; rows * cols];
+    out.par_chunks_mut(cols).enumerate().for_each(|(y, row)| {
+        for (x, px) in row.iter_mut().enumerate() {
+            let mut sum = 0.0f32;
+            let mut n = 0u32;
+            for yy in y.saturating_sub(1)..(y + 2).min(rows) {
+                for xx in x.saturating_sub(1)..(x + 2).min(cols) {
+                    let v = src[yy * cols + xx];
+                    if v.is_finite() {
+                        sum += v;
+                        n += 1;
+                    }
+                }
+            }
+            *px = if n > 0 { sum / n as f32 } else { f32::NAN };
+        }
+    });
+    Array2::from_shape_vec((rows, cols), out).unwrap()
+}
+
+pub fn grade_frames<F>(frame_count: usize, load: F, metric: QualityMetric, keep_percent: f32) -> Result<Vec<FrameQuality>>
+where
+    F: Fn(usize) -> Result<Array2<f32>> + Sync,
+{
+    let scores: Vec<f64> = (0..frame_count)
+        .into_par_iter()
+        .map(|i| load(i).map(|frame| frame_quality(&frame, metric)))
+        .collect::<Result<_>>()?;
+
+    let mut order: Vec<usize> = (0..frame_count).collect();
+    order.sort_by(|&a, &b| {
+        let (sa, sb) = (scores[a], scores[b]);
+        sb.partial_cmp(&sa).unwrap_or_else(|| sa.is_nan().cmp(&sb.is_nan()))
+    });
+
+    let keep = selected_count(frame_count, keep_percent);
+    let mut quality: Vec<FrameQuality> = scores
+        .iter()
+        .enumerate()
+        .map(|(index, &score)| FrameQuality { index, score, ..Default::default() })
+        .collect();
+    for (rank, &i) in order.iter().enumerate() {
+        quality[i].rank = rank;
+        quality[i].selected = rank < keep;
+    }
+    Ok(quality)
+}
+
+pub fn lucky_stack<F>(
+    frame_count: usize,
+    load: F,
+    config: &LuckyConfig,
+    progress: Option<&ProgressHandle>,
+) -> Result<LuckyStackResult>
+where
+    F: Fn(usize) -> Result<Array2<f32>> + Sync,
+{
+    if frame_count == 0 {
+        bail!("No frames provided");
+    }
+    if let Some(p) = progress {
+        p.tick_with_stage("grade frames");
+    }
+
+    let quality = grade_frames(frame_count, &load, config.metric, config.keep_percent)?;
+    let mut ranked: Vec<&FrameQuality> = quality.iter().filter(|q| q.selected).collect();
+    ranked.sort_by_key(|q| q.rank);
+    let best_index = ranked[0].index;
+    log::info!(
+        "Lucky: graded {} frames ({:?}), keeping {}, best frame_{} score={:.4e}",
+        frame_count, config.metric, ranked.len(), best_index, ranked[0].score
+    );
+
+    if let Some(p) = progress {
+        p.tick_with_stage("build reference");
+    }
+    let best = load(best_index)?;
+    let dims = best.dim();
+    let reference_count = config.reference_frames.clamp(1, ranked.len());
+    let mut reference = Accumulator::new(dims);
+    reference.add(&best);
+    for q in &ranked[1..reference_count] {
+        let frame = load_checked(&load, q.index, dims)?;
+        let pc = phase_correlation::phase_correlate(&best, &frame);
+        reference.add(&ShiftField::uniform(dims.0, dims.1, pc.dy, pc.dx).warp(&frame));
+    }
+    let reference = reference.finish();
+
+    let local = LocalAlignConfig {
+        patch_size: config.patch_size,
+        grid_step: config.grid_step,
+        min_confidence: config.min_patch_confidence,
+        max_shift_px: config.patch_size as f64 * MAX_LOCAL_SHIFT_FRACTION,
+    };
+
+    let mut stack = Accumulator::new(dims);
+    let mut offsets = Vec::with_capacity(ranked.len());
+    let mut patch_count = 0usize;
+    let mut valid_total = 0usize;
+    let mut max_local_shift_px = 0.0f64;
+
+    for (n, q) in ranked.iter().enumerate() {
+        if let Some(p) = progress {
+            if p.is_cancelled() {
+                return Err(AppError::Cancelled.into());
+            }
+            p.tick_with_stage(&format!("align frame {}/{}", n + 1, ranked.len()));
+        }
+
+        let frame = load_checked(&load, q.index, dims)?;
+        let pc = phase_correlation::phase_correlate(&reference, &frame);
+        let field = if config.local_align {
+            estimate_shift_field(&reference, &frame, (pc.dy, pc.dx), &local)
+        } else {
+            ShiftField::uniform(dims.0, dims.1, pc.dy, pc.dx)
+        };
+
+        patch_count = field.patch_count();
+        valid_total += field.valid_patches;
+        max_local_shift_px = max_local_shift_px.max(field.max_local_deviation());
+        offsets.push((pc.dy, pc.dx));
+        log::debug!(
+            "Lucky: frame_{} global=({:.2}, {:.2}) patches={}/{} max_local={:.2}px",
+            q.index, pc.dy, pc.dx, field.valid_patches, field.patch_count(), field.max_local_deviation()
+        );
+
+        stack.add(&field.warp(&frame));
+    }
+
+    let selected_count = ranked.len();
+    Ok(LuckyStackResult {
+        image: stack.finish(),
+        frame_count,
+        selected_count,
+        best_index,
+        quality,
+        offsets,
+        patch_count,
+        mean_valid_patches: valid_total as f64 / selected_count as f64,
+        max_local_shift_px,
+        calibration: CalibrationReport::default(),
+    })
+}
+
+pub fn lucky_stack_from_paths(
+    paths: &[String],
+    config: &LuckyConfig,
+    calibration: Option<&CalibrationConfig>,
+    progress: Option<&ProgressHandle>,
+) -> Result<LuckyStackResult> {
+    if paths.is_empty() {
+        bail!("No image paths provided");
+    }
+    let report = Mutex::new(CalibrationReport {
+        dark_scales: vec![None; paths.len()],
+        cosmetic_corrected: vec![0; paths.len()],
+    });
+    let load = |i: usize| {
+        let (frame, _) = load_calibrated_frame(&paths[i], i, calibration)?;
+        let mut report = report.lock().unwrap();
+        report.dark_scales[i] = frame.dark_scale;
+        report.cosmetic_corrected[i] = frame.cosmetic_corrected;
+        Ok(frame.image)
+    };
+    let mut result = lucky_stack(paths.len(), load, config, progress)?;
+    result.calibration = report.into_inner().unwrap();
+    Ok(result)
+}
+
+pub fn lucky_stack_from_video(
+    path: &str,
+    config: &LuckyConfig,
+    progress: Option<&ProgressHandle>,
+) -> Result<LuckyStackResult> {
+    let video = VideoSequence::open(path)?;
+    let layout = video.cfa_pattern().and_then(|p| CfaLayout::parse(p, 0, 0));
+    log::info!(
+        "Lucky: video {} with {} frames{}",
+        path,
+        video.frame_count(),
+        video.cfa_pattern().map(|p| format!(" (CFA {})", p)).unwrap_or_default()
+    );
+    let load = |i: usize| -> Result<Array2<f32>> {
+        let frame = video.get_frame(i)?;
+        match &layout {
+            Some(layout) => {
+                let rgb = debayer::debayer(&frame, layout, DebayerMethod::Bilinear)?;
+                Ok((&rgb.r + &rgb.g + &rgb.b) / 3.0)
+            }
+            None => Ok(frame),
+        }
+    };
+    lucky_stack(video.frame_count(), load, config, progress)
+}
+
+fn load_checked<F>(load: &F, index: usize, dims: (usize, usize)) -> Result<Array2<f32>>
+where
+    F: Fn(usize) -> Result<Array2<f32>>,
+{
+    let frame = load(index)?;
+    if frame.dim() != dims {
+        bail!(
+            "Frame {} dimensions {}x{} differ from reference {}x{}",
+            index, frame.ncols(), frame.nrows(), dims.1, dims.0
+        );
+    }
+    Ok(frame)
+}
+
+struct Accumulator {
+    sum: Array2<f64>,
+    count: Array2<u32>,
+}
+
+impl Accumulator {
+    fn new(dims: (usize, usize)) -> Self {
+        Self { sum: Array2::zeros(dims), count: Array2::zeros(dims) }
+    }
+
+    fn add(&mut self, image: &Array2<f32>) {
+        ndarray::Zip::from(&mut self.sum)
+            .and(&mut self.count)
+            .and(image)
+            .par_for_each(|s, c, &v| {
+                if v.is_finite() {
+                    *s += v as f64;
+                    *c += 1;
+                }
+            });
+    }
+
+    fn finish(self) -> Array2<f32> {
+        ndarray::Zip::from(&self.sum)
+            .and(&self.count)
+            .par_map_collect(|&s, &c| if c > 0 { (s / c as f64) as f32 } else { 0.0 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disc(dy: f64, dx: f64, blur: f64) -> Array2<f32> {
+        Array2::from_shape_fn((96, 96), |(y, x)| {
+            let (yy, xx) = (y as f64 - 48.0 - dy, x as f64 - 48.0 - dx);
+            let bands = ((yy * 0.5).sin() * 0.5 + 0.5) / (1.0 + blur);
+            let edge = 1.0 / (1.0 + ((yy.hypot(xx) - 30.0) / (0.5 + blur)).exp());
+            (100.0 + 900.0 * edge * (0.6 + 0.4 * bands)) as f32
+        })
+    }
+
+    #[test]
+    fn test_quality_prefers_sharp_frames() {
+        for metric in [QualityMetric::Laplacian, QualityMetric::Gradient] {
+            let sharp = frame_quality(&disc(0.0, 0.0, 0.0), metric);
+            let soft = frame_quality(&disc(0.0, 0.0, 3.0), metric);
+            assert!(sharp > soft * 1.5, "{:?}: sharp {} soft {}", metric, sharp, soft);
+        }
+    }
+
+    #[test]
+    fn test_lucky_stack_selects_and_aligns_best_frames() {
+        let frames: Vec<Array2<f32>> = (0..8)
+            .map(|i| {
+                let blur = if i % 2 == 0 { 0.0 } else { 4.0 };
+                disc(i as f64 * 0.7 - 2.0, 3.0 - i as f64 * 0.5, blur)
+            })
+            .collect();
+        let config = LuckyConfig { keep_percent: 50.0, reference_frames: 2, local_align: false, ..LuckyConfig::default() };
+        let result = lucky_stack(frames.len(), |i| Ok(frames[i].clone()), &config, None).unwrap();
+
+        assert_eq!(result.selected_count, 4);
+        assert!(result.quality.iter().filter(|q| q.selected).all(|q| q.index % 2 == 0));
+        let best = &frames[result.best_index];
+        let diff = (20..76)
+            .flat_map(|y| (20..76).map(move |x| (y, x)))
+            .map(|(y, x)| (result.image[[y, x]] - best[[y, x]]).abs())
+            .fold(0.0f32, f32::max);
+        assert!(diff < 60.0, "max diff {}", diff);
+    }
+}
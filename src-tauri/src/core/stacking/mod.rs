@@ -1,4 +1,8 @@
 pub mod align;
 pub mod calibration;
 pub mod combine;
+pub mod comet;
+pub mod cosmetic;
+pub mod dark_scaling;
 pub mod drizzle;
+pub mod lucky;
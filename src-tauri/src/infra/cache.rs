@@ -269,11 +269,17 @@ impl ImageCache {
     }
 
     pub fn insert_synthetic(&self, key: &str, arr: Arc<Array2<f32>>, stats: ImageStats) {
-        let entry = Arc::new(CachedImage {
-            arr,
-            stats,
-            header: None,
-        });
+        self.insert_synthetic_with_header(key, arr, stats, None);
+    }
+
+    pub fn insert_synthetic_with_header(
+        &self,
+        key: &str,
+        arr: Arc<Array2<f32>>,
+        stats: ImageStats,
+        header: Option<HduHeader>,
+    ) {
+        let entry = Arc::new(CachedImage { arr, stats, header });
         let mut cache = self.inner.write().unwrap();
         cache.put(key.to_string(), entry);
     }
@@ -23,6 +23,14 @@ impl ResolvedInput {
         }
     }
 
+    pub fn primary_image(&self) -> Option<&Path> {
+        let paths = self.image_paths();
+        paths
+            .iter()
+            .find(|p| is_fits_path(p) || is_asdf_path(p))
+            .or_else(|| paths.first())
+            .map(PathBuf::as_path)
+    }
 }
 
 pub fn resolve_input(path: &Path) -> Result<ResolvedInput> {
@@ -49,25 +57,22 @@ pub fn resolve_input(path: &Path) -> Result<ResolvedInput> {
 
 pub fn resolve_single_image(path: &str) -> Result<(PathBuf, Option<TempDir>)> {
     let p = Path::new(path);
+    if !is_zip_path(p) && !p.is_dir() {
+        return Ok((PathBuf::from(path), None));
+    }
 
-    if is_zip_path(p) {
-        let resolved = resolve_input(p)?;
-        match resolved {
-            ResolvedInput::ExtractedFromZip { files, _tmp } => {
-                let first = files
-                    .into_iter()
-                    .next()
-                    .context("No supported image files inside ZIP")?;
-                Ok((first, Some(_tmp)))
-            }
-            _ => unreachable!(),
-        }
-    } else {
-        Ok((PathBuf::from(path), None))
+    let resolved = resolve_input(p)?;
+    let first = resolved
+        .primary_image()
+        .map(Path::to_path_buf)
+        .with_context(|| format!("No supported image files in {:?}", p))?;
+    match resolved {
+        ResolvedInput::ExtractedFromZip { _tmp, .. } => Ok((first, Some(_tmp))),
+        _ => Ok((first, None)),
     }
 }
 
-fn is_fits_path(p: &Path) -> bool {
+pub fn is_fits_path(p: &Path) -> bool {
     p.extension()
         .map(|ext| {
             let e = ext.to_ascii_lowercase();
@@ -82,8 +87,30 @@ fn is_asdf_path(p: &Path) -> bool {
         .unwrap_or(false)
 }
 
+pub fn is_video_path(p: &Path) -> bool {
+    p.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("ser") || ext.eq_ignore_ascii_case("avi"))
+        .unwrap_or(false)
+}
+
+pub fn is_tiff_path(p: &Path) -> bool {
+    p.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("tif") || ext.eq_ignore_ascii_case("tiff"))
+        .unwrap_or(false)
+}
+
+pub fn is_xisf_path(p: &Path) -> bool {
+    p.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("xisf"))
+        .unwrap_or(false)
+}
+
+pub fn is_raster_path(p: &Path) -> bool {
+    is_tiff_path(p) || is_xisf_path(p)
+}
+
 fn is_supported_image(p: &Path) -> bool {
-    is_fits_path(p) || is_asdf_path(p)
+    is_fits_path(p) || is_asdf_path(p) || is_video_path(p) || is_raster_path(p)
 }
 
 fn is_zip_path(p: &Path) -> bool {
@@ -147,6 +174,9 @@ fn extract_zip_recursive(
             || entry_lower.ends_with(".fit")
             || entry_lower.ends_with(".fts")
             || entry_lower.ends_with(".asdf")
+            || entry_lower.ends_with(".tif")
+            || entry_lower.ends_with(".tiff")
+            || entry_lower.ends_with(".xisf")
         {
             let out_path = out_dir.join(&file_name);
             let mut out_file = File::create(&out_path)
@@ -204,9 +234,42 @@ mod tests {
     fn test_is_supported_image() {
         assert!(is_supported_image(Path::new("data.fits")));
         assert!(is_supported_image(Path::new("data.asdf")));
+        assert!(is_supported_image(Path::new("jupiter.ser")));
+        assert!(is_supported_image(Path::new("moon.AVI")));
+        assert!(is_supported_image(Path::new("m42.tif")));
+        assert!(is_supported_image(Path::new("m42.TIFF")));
+        assert!(is_supported_image(Path::new("integration.xisf")));
         assert!(!is_supported_image(Path::new("data.png")));
     }
 
+    #[test]
+    fn test_mixed_zip_prefers_fits() {
+        use std::io::Write;
+
+        let dir = TempDir::new().unwrap();
+        let zip_path = dir.path().join("mixed.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for name in ["a_preview.tif", "b.fits", "c.xisf"] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let (resolved, tmp) = resolve_single_image(zip_path.to_str().unwrap()).unwrap();
+        assert!(tmp.is_some());
+        assert_eq!(resolved.file_name().unwrap(), "b.fits");
+
+        let raw = dir.path().join("frames");
+        fs::create_dir(&raw).unwrap();
+        for name in ["a.ser", "b.avi", "c.fit"] {
+            File::create(raw.join(name)).unwrap();
+        }
+        let (resolved, tmp) = resolve_single_image(raw.to_str().unwrap()).unwrap();
+        assert!(tmp.is_none());
+        assert_eq!(resolved.file_name().unwrap(), "c.fit");
+    }
+
     #[test]
     fn test_is_zip_path() {
         assert!(is_zip_path(Path::new("archive.zip")));
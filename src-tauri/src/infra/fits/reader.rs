@@ -564,6 +564,29 @@ pub fn load_fits_image(path: &str) -> Result<Array2<f32>> {
     Ok(result.image)
 }
 
+pub fn load_fits_image_with_header(path: &str) -> Result<(Array2<f32>, HduHeader)> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open {}", path))?;
+    let result = extract_image_mmap(&file)
+        .with_context(|| format!("Failed to load {}", path))?;
+    Ok((result.image, result.header))
+}
+
+pub fn load_fits_extension(path: &str, extname: &str) -> Result<Option<Array2<f32>>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open {}", path))?;
+    let mmap = create_mmap(&file)?;
+    let hdus = scan_all_hdus(&mmap)?;
+    let found = hdus.iter().find(|h| {
+        h.info.has_data
+            && h.info.extname.as_deref().is_some_and(|n| n.eq_ignore_ascii_case(extname))
+    });
+    match found {
+        Some(hdu) => Ok(Some(extract_image_from_hdu(&mmap, hdu)?)),
+        None => Ok(None),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -284,6 +284,46 @@ pub fn write_fits_mono_bitpix(
     Ok(())
 }
 
+pub fn write_fits_context(path: &str, planes: &[Array2<u32>]) -> Result<()> {
+    let (rows, cols) = planes.first().context("No context planes to write")?.dim();
+    if planes.iter().any(|p| p.dim() != (rows, cols)) {
+        bail!("Context plane dimension mismatch");
+    }
+
+    let file = File::create(path).context("Failed to create FITS file")?;
+    let mut writer = BufWriter::with_capacity(2 * 1024 * 1024, file);
+    let mut bytes = 0;
+
+    bytes += write_header_card(&mut writer, "SIMPLE", "T", "FITS standard")?;
+    bytes += write_header_card(&mut writer, "BITPIX", "32", "32-bit integer")?;
+    if planes.len() > 1 {
+        bytes += write_header_card(&mut writer, "NAXIS", "3", "context planes")?;
+    } else {
+        bytes += write_header_card(&mut writer, "NAXIS", "2", "2D image")?;
+    }
+    bytes += write_header_card(&mut writer, "NAXIS1", &cols.to_string(), "width")?;
+    bytes += write_header_card(&mut writer, "NAXIS2", &rows.to_string(), "height")?;
+    if planes.len() > 1 {
+        bytes += write_header_card(&mut writer, "NAXIS3", &planes.len().to_string(), "32 frames per plane")?;
+    }
+    bytes += write_header_card(&mut writer, "BZERO", "2147483648", "unsigned 32-bit offset")?;
+    bytes += write_header_card(&mut writer, "BSCALE", "1", "")?;
+    write_header_end(&mut writer, bytes)?;
+
+    let mut data_bytes = 0;
+    for plane in planes {
+        let sl = plane.as_slice().context("Context plane not contiguous")?;
+        for &bits in sl {
+            writer.write_all(&((bits ^ 0x8000_0000) as i32).to_be_bytes())?;
+        }
+        data_bytes += sl.len() * 4;
+    }
+    pad_to_block(&mut writer, data_bytes)?;
+
+    writer.flush()?;
+    Ok(())
+}
+
 pub fn write_fits_rgb(
     path: &str,
     r: &Array2<f32>,
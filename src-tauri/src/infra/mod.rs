@@ -3,7 +3,10 @@ pub mod config;
 pub mod fits;
 pub mod ipc;
 pub mod progress;
+pub mod raster;
 pub mod render;
+pub mod tiff;
+pub mod xisf;
 pub mod asdf;
 pub mod asdf_bridge;
 pub mod astrometry;
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use ndarray::Array2;
+use rayon::prelude::*;
+
+use crate::infra::fits::dispatcher::{is_tiff_path, is_xisf_path};
+use crate::types::header::HduHeader;
+
+pub struct RasterImage {
+    pub planes: Vec<Array2<f32>>,
+    pub header: HduHeader,
+}
+
+impl RasterImage {
+    pub fn mono(&self) -> Array2<f32> {
+        if self.planes.len() == 1 {
+            return self.planes[0].clone();
+        }
+        let n = self.planes.len() as f32;
+        self.planes.iter().skip(1).fold(self.planes[0].clone(), |acc, p| acc + p) / n
+    }
+
+    pub fn into_rgb(self) -> Option<([Array2<f32>; 3], HduHeader)> {
+        let planes: [Array2<f32>; 3] = self.planes.try_into().ok()?;
+        Some((planes, self.header))
+    }
+}
+
+pub fn read_raster(path: &Path) -> Result<RasterImage> {
+    if is_tiff_path(path) {
+        return crate::infra::tiff::read_tiff(path).map(|t| t.into_raster());
+    }
+    if is_xisf_path(path) {
+        return crate::infra::xisf::reader::read_xisf(path).map(|x| x.into_raster());
+    }
+    bail!("Not a TIFF or XISF file: {}", path.display())
+}
+
+pub(crate) fn synthetic_header(cols: usize, rows: usize, channels: usize, bitpix: i32) -> HduHeader {
+    let mut cards: Vec<(String, String)> = vec![
+        ("BITPIX".into(), bitpix.to_string()),
+        ("NAXIS".into(), if channels > 1 { "3" } else { "2" }.into()),
+        ("NAXIS1".into(), cols.to_string()),
+        ("NAXIS2".into(), rows.to_string()),
+    ];
+    if channels > 1 {
+        cards.push(("NAXIS3".into(), channels.to_string()));
+    }
+    let index: HashMap<String, String> = cards.iter().cloned().collect();
+    HduHeader { cards, index }
+}
+
+pub(crate) fn integer_scale(planes: &[&Array2<f32>]) -> f32 {
+    let max = planes
+        .iter()
+        .filter_map(|p| p.as_slice())
+        .map(|s| s.par_iter().filter(|v| v.is_finite()).cloned().reduce(|| f32::MIN, f32::max))
+        .fold(f32::MIN, f32::max);
+    if max > 1.0 { 1.0 / max } else { 1.0 }
+}
+
+pub(crate) fn quantize(v: f32, scale: f32, full: f32) -> f32 {
+    if v.is_finite() { ((v * scale).clamp(0.0, 1.0) * full).round() } else { 0.0 }
+}
+
+pub(crate) fn check_planes(planes: &[&Array2<f32>]) -> Result<(usize, usize)> {
+    if !matches!(planes.len(), 1 | 3) {
+        bail!("Expected 1 or 3 channels, got {}", planes.len());
+    }
+    let dim = planes[0].dim();
+    if planes.iter().any(|p| p.dim() != dim) {
+        bail!("Channel dimensions differ");
+    }
+    if dim.0 == 0 || dim.1 == 0 {
+        bail!("Cannot write an empty image");
+    }
+    Ok(dim)
+}
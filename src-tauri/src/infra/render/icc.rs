@@ -0,0 +1,164 @@
+use crate::core::compose::colorspace::{decode_transfer, invert3, mul3, rgb_to_xyz_matrix, white_point_d65};
+use crate::types::image::RgbColorSpace;
+
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+const BRADFORD: [[f64; 3]; 3] = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+const CURVE_POINTS: usize = 1024;
+const ICC_VERSION: u32 = 0x0210_0000;
+
+pub fn profile_description(space: RgbColorSpace) -> &'static str {
+    match space {
+        RgbColorSpace::LinearSrgb => "Linear sRGB (D65)",
+        RgbColorSpace::Srgb => "sRGB IEC61966-2.1",
+        RgbColorSpace::DisplayP3 => "Display P3",
+        RgbColorSpace::AdobeRgb => "Adobe RGB (1998) compatible",
+        RgbColorSpace::Rec2020 => "ITU-R BT.2020",
+    }
+}
+
+fn bradford_d65_to_d50() -> [[f64; 3]; 3] {
+    let src = white_point_d65();
+    let cone = |w: [f64; 3]| -> [f64; 3] { std::array::from_fn(|i| (0..3).map(|k| BRADFORD[i][k] * w[k]).sum()) };
+    let (s, d) = (cone(src), cone(D50));
+    let scale = [[d[0] / s[0], 0.0, 0.0], [0.0, d[1] / s[1], 0.0], [0.0, 0.0, d[2] / s[2]]];
+    mul3(&invert3(&BRADFORD), &mul3(&scale, &BRADFORD))
+}
+
+fn s15_fixed16(v: f64) -> [u8; 4] {
+    ((v * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn xyz_tag(xyz: [f64; 3]) -> Vec<u8> {
+    let mut out = b"XYZ \0\0\0\0".to_vec();
+    xyz.iter().for_each(|&v| out.extend_from_slice(&s15_fixed16(v)));
+    out
+}
+
+fn text_tag(text: &str) -> Vec<u8> {
+    let mut out = b"text\0\0\0\0".to_vec();
+    out.extend_from_slice(text.as_bytes());
+    out.push(0);
+    out
+}
+
+fn desc_tag(text: &str) -> Vec<u8> {
+    let mut out = b"desc\0\0\0\0".to_vec();
+    out.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+    out.extend_from_slice(text.as_bytes());
+    out.push(0);
+    out.extend_from_slice(&[0u8; 8]);
+    out.extend_from_slice(&[0u8; 3]);
+    out.extend_from_slice(&[0u8; 67]);
+    out
+}
+
+fn curve_tag(space: RgbColorSpace) -> Vec<u8> {
+    let mut out = b"curv\0\0\0\0".to_vec();
+    match space {
+        RgbColorSpace::LinearSrgb => out.extend_from_slice(&0u32.to_be_bytes()),
+        RgbColorSpace::AdobeRgb => {
+            out.extend_from_slice(&1u32.to_be_bytes());
+            out.extend_from_slice(&563u16.to_be_bytes());
+        }
+        _ => {
+            out.extend_from_slice(&(CURVE_POINTS as u32).to_be_bytes());
+            for i in 0..CURVE_POINTS {
+                let v = decode_transfer(space, i as f32 / (CURVE_POINTS - 1) as f32);
+                out.extend_from_slice(&((v.clamp(0.0, 1.0) * 65535.0).round() as u16).to_be_bytes());
+            }
+        }
+    }
+    out
+}
+
+pub fn icc_profile(space: RgbColorSpace) -> Vec<u8> {
+    let adapted = mul3(&bradford_d65_to_d50(), &rgb_to_xyz_matrix(space));
+    let column = |j: usize| [adapted[0][j], adapted[1][j], adapted[2][j]];
+    let curve = curve_tag(space);
+
+    let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"desc", desc_tag(profile_description(space))),
+        (b"cprt", text_tag("No copyright, use freely")),
+        (b"wtpt", xyz_tag(D50)),
+        (b"rXYZ", xyz_tag(column(0))),
+        (b"gXYZ", xyz_tag(column(1))),
+        (b"bXYZ", xyz_tag(column(2))),
+        (b"rTRC", curve.clone()),
+        (b"gTRC", curve.clone()),
+        (b"bTRC", curve),
+    ];
+
+    let table_len = 4 + 12 * tags.len();
+    let mut offset = 128 + table_len;
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    for (sig, body) in &tags {
+        let padded = (body.len() + 3) & !3;
+        table.extend_from_slice(*sig);
+        table.extend_from_slice(&(offset as u32).to_be_bytes());
+        table.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        data.extend_from_slice(body);
+        data.resize(data.len() + padded - body.len(), 0);
+        offset += padded;
+    }
+
+    let mut header = Vec::with_capacity(128);
+    header.extend_from_slice(&(offset as u32).to_be_bytes());
+    header.extend_from_slice(&[0u8; 4]);
+    header.extend_from_slice(&ICC_VERSION.to_be_bytes());
+    header.extend_from_slice(b"mntrRGB XYZ ");
+    header.extend_from_slice(&[0u8; 12]);
+    header.extend_from_slice(b"acsp");
+    header.extend_from_slice(&[0u8; 24]);
+    header.extend_from_slice(&0u32.to_be_bytes());
+    D50.iter().for_each(|&v| header.extend_from_slice(&s15_fixed16(v)));
+    header.resize(128, 0);
+
+    let mut profile = header;
+    profile.extend_from_slice(&table);
+    profile.extend_from_slice(&data);
+    profile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_u32(buf: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(buf[at..at + 4].try_into().unwrap())
+    }
+
+    fn read_xyz(buf: &[u8], at: usize) -> [f64; 3] {
+        std::array::from_fn(|i| read_u32(buf, at + 8 + 4 * i) as i32 as f64 / 65536.0)
+    }
+
+    #[test]
+    fn test_profile_layout_and_white_balance() {
+        for space in [RgbColorSpace::Srgb, RgbColorSpace::DisplayP3, RgbColorSpace::AdobeRgb, RgbColorSpace::Rec2020, RgbColorSpace::LinearSrgb] {
+            let icc = icc_profile(space);
+            assert_eq!(read_u32(&icc, 0) as usize, icc.len());
+            assert_eq!(&icc[36..40], b"acsp");
+            assert_eq!(&icc[12..24], b"mntrRGB XYZ ");
+
+            let count = read_u32(&icc, 128) as usize;
+            assert_eq!(count, 9);
+            let mut white = [0.0; 3];
+            for t in 0..count {
+                let entry = 132 + 12 * t;
+                let (offset, size) = (read_u32(&icc, entry + 4) as usize, read_u32(&icc, entry + 8) as usize);
+                assert!(offset % 4 == 0 && offset + size <= icc.len());
+                if matches!(&icc[entry..entry + 4], b"rXYZ" | b"gXYZ" | b"bXYZ") {
+                    let xyz = read_xyz(&icc, offset);
+                    (0..3).for_each(|i| white[i] += xyz[i]);
+                }
+            }
+            for (w, d) in white.iter().zip(D50) {
+                assert!((w - d).abs() < 2e-4, "{:?}: {:?}", space, white);
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
 pub mod grayscale;
+pub mod icc;
 pub mod tiles;
 pub(crate) mod rgb;
 
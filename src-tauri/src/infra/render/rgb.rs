@@ -1,9 +1,13 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use image::codecs::jpeg::JpegEncoder;
 use image::codecs::png::PngEncoder;
+use image::codecs::tiff::TiffEncoder;
 use image::{ColorType, ImageEncoder};
 use ndarray::Array2;
 use rayon::prelude::*;
 
+const JPEG_QUALITY: u8 = 95;
+
 pub fn render_rgb(
     r: &Array2<f32>,
     g: &Array2<f32>,
@@ -46,46 +50,146 @@ pub fn render_rgb(
     Ok(())
 }
 
-pub fn render_rgb_16bit(
-    r: &Array2<f32>,
-    g: &Array2<f32>,
-    b: &Array2<f32>,
-    path: &str,
-) -> Result<()> {
-    let (rows, cols) = r.dim();
-    let r_slice = r.as_slice().context("R channel not contiguous")?;
-    let g_slice = g.as_slice().context("G channel not contiguous")?;
-    let b_slice = b.as_slice().context("B channel not contiguous")?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RgbImageFormat {
+    Png,
+    Tiff,
+    Jpeg,
+}
 
-    let npix = rows * cols;
-    let mut pixels = vec![0u16; npix * 3];
+impl RgbImageFormat {
+    pub fn from_path(path: &str) -> Result<Self> {
+        let ext = std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "png" => Ok(Self::Png),
+            "tif" | "tiff" => Ok(Self::Tiff),
+            "jpg" | "jpeg" => Ok(Self::Jpeg),
+            other => bail!("Unsupported RGB export format '.{}' (expected png, tif/tiff or jpg/jpeg)", other),
+        }
+    }
+
+    pub fn supports_16bit(self) -> bool {
+        self != Self::Jpeg
+    }
+}
 
+fn interleave<T: Copy + Default + Send>(
+    r: &[f32],
+    g: &[f32],
+    b: &[f32],
+    cols: usize,
+    quantize: impl Fn(f32) -> T + Sync,
+) -> Vec<T> {
+    let mut pixels = vec![T::default(); r.len() * 3];
     pixels
         .par_chunks_mut(cols * 3)
         .enumerate()
         .for_each(|(y, row_buf)| {
             let base = y * cols;
-            for x in 0..cols {
+            for (x, px) in row_buf.chunks_exact_mut(3).enumerate() {
                 let i = base + x;
-                let o = x * 3;
-                row_buf[o] = (r_slice[i].clamp(0.0, 1.0) * 65535.0) as u16;
-                row_buf[o + 1] = (g_slice[i].clamp(0.0, 1.0) * 65535.0) as u16;
-                row_buf[o + 2] = (b_slice[i].clamp(0.0, 1.0) * 65535.0) as u16;
+                px[0] = quantize(r[i]);
+                px[1] = quantize(g[i]);
+                px[2] = quantize(b[i]);
             }
         });
+    pixels
+}
 
-    let bytes: Vec<u8> = pixels.iter().flat_map(|&v| v.to_be_bytes()).collect();
+pub fn write_rgb_image(
+    r: &Array2<f32>,
+    g: &Array2<f32>,
+    b: &Array2<f32>,
+    path: &str,
+    bit_depth: u8,
+    icc_profile: Option<Vec<u8>>,
+) -> Result<u8> {
+    let format = RgbImageFormat::from_path(path)?;
+    let (rows, cols) = r.dim();
+    if g.dim() != (rows, cols) || b.dim() != (rows, cols) {
+        bail!("RGB channel dimensions differ");
+    }
+    let r_slice = r.as_slice().context("R channel not contiguous")?;
+    let g_slice = g.as_slice().context("G channel not contiguous")?;
+    let b_slice = b.as_slice().context("B channel not contiguous")?;
+
+    let depth = if bit_depth == 16 && format.supports_16bit() { 16 } else { 8 };
+    let (bytes, color) = if depth == 16 {
+        let pixels = interleave(r_slice, g_slice, b_slice, cols, |v| (v.clamp(0.0, 1.0) * 65535.0).round() as u16);
+        (pixels.iter().flat_map(|v| v.to_ne_bytes()).collect::<Vec<u8>>(), ColorType::Rgb16)
+    } else {
+        (interleave(r_slice, g_slice, b_slice, cols, |v| (v.clamp(0.0, 1.0) * 255.0).round() as u8), ColorType::Rgb8)
+    };
 
     let file = std::fs::File::create(path).context("Failed to create output file")?;
     let buf_writer = std::io::BufWriter::with_capacity(4 * 1024 * 1024, file);
-    let encoder = PngEncoder::new_with_quality(
-        buf_writer,
-        image::codecs::png::CompressionType::Default,
-        image::codecs::png::FilterType::Sub,
-    );
-    encoder
-        .write_image(&bytes, cols as u32, rows as u32, ColorType::Rgb16.into())
-        .context("Failed to write 16-bit RGB PNG")?;
+    let (w, h) = (cols as u32, rows as u32);
 
-    Ok(())
+    match format {
+        RgbImageFormat::Png => {
+            let mut encoder = PngEncoder::new_with_quality(
+                buf_writer,
+                image::codecs::png::CompressionType::Default,
+                image::codecs::png::FilterType::Sub,
+            );
+            if let Some(icc) = icc_profile {
+                encoder.set_icc_profile(icc).context("PNG encoder rejected ICC profile")?;
+            }
+            encoder.write_image(&bytes, w, h, color.into()).context("Failed to write RGB PNG")?;
+        }
+        RgbImageFormat::Tiff => {
+            let mut encoder = TiffEncoder::new(buf_writer);
+            if let Some(icc) = icc_profile {
+                encoder.set_icc_profile(icc).context("TIFF encoder rejected ICC profile")?;
+            }
+            encoder.write_image(&bytes, w, h, color.into()).context("Failed to write RGB TIFF")?;
+        }
+        RgbImageFormat::Jpeg => {
+            let mut encoder = JpegEncoder::new_with_quality(buf_writer, JPEG_QUALITY);
+            if let Some(icc) = icc_profile {
+                encoder.set_icc_profile(icc).context("JPEG encoder rejected ICC profile")?;
+            }
+            encoder.write_image(&bytes, w, h, color.into()).context("Failed to write RGB JPEG")?;
+        }
+    }
+
+    Ok(depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writes_tagged_png_tiff_and_jpeg() {
+        let dir = tempfile::tempdir().unwrap();
+        let r = Array2::from_shape_fn((8, 12), |(y, x)| (y * 12 + x) as f32 / 95.0);
+        let g = r.mapv(|v| 1.0 - v);
+        let b = Array2::from_elem((8, 12), 0.5f32);
+        let icc = crate::infra::render::icc::icc_profile(crate::types::image::RgbColorSpace::DisplayP3);
+
+        for (name, depth) in [("a.png", 16), ("a.tif", 16), ("a.jpg", 8)] {
+            let path = dir.path().join(name);
+            let written = write_rgb_image(&r, &g, &b, path.to_str().unwrap(), 16, Some(icc.clone())).unwrap();
+            assert_eq!(written, depth);
+
+            let embedded = if name.ends_with(".tif") {
+                let raw = std::fs::read(&path).unwrap();
+                raw.windows(icc.len()).any(|w| w == icc.as_slice())
+            } else {
+                use image::ImageDecoder;
+                let mut decoder = image::ImageReader::open(&path).unwrap().into_decoder().unwrap();
+                decoder.icc_profile().unwrap().as_deref() == Some(icc.as_slice())
+            };
+            assert!(embedded, "{} is missing its ICC profile", name);
+            let img = image::open(&path).unwrap().into_rgb16();
+            let px = img.get_pixel(11, 7);
+            assert!(px[0] > 64000 && px[1] < 1000, "{} {:?}", name, px);
+        }
+        assert!(RgbImageFormat::from_path("out.bmp").is_err());
+    }
 }
@@ -0,0 +1,210 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Seek, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use ndarray::Array2;
+use tiff::decoder::{Decoder, DecodingResult, Limits};
+use tiff::encoder::{colortype, compression::DeflateLevel, Compression, Predictor, TiffEncoder};
+use tiff::tags::Tag;
+use tiff::ColorType;
+
+use crate::infra::raster::{check_planes, integer_scale, quantize, synthetic_header, RasterImage};
+use crate::types::image::{SampleFormat, TiffCompression};
+
+pub struct TiffImage {
+    pub planes: Vec<Array2<f32>>,
+    pub sample_format: SampleFormat,
+    pub icc_profile: Option<Vec<u8>>,
+}
+
+impl TiffImage {
+    pub fn into_raster(self) -> RasterImage {
+        let (rows, cols) = self.planes[0].dim();
+        let bitpix = match self.sample_format {
+            SampleFormat::UInt8 => 8,
+            SampleFormat::UInt16 => 16,
+            SampleFormat::Float32 => -32,
+        };
+        let mut header = synthetic_header(cols, rows, self.planes.len(), bitpix);
+        header.set("TIFF_SRC", "true".into());
+        RasterImage { planes: self.planes, header }
+    }
+}
+
+fn interleaved<T: Copy + Default>(planes: &[&Array2<f32>], f: impl Fn(f32) -> T) -> Vec<T> {
+    let (rows, cols) = planes[0].dim();
+    let n = planes.len();
+    let mut out = vec![T::default(); rows * cols * n];
+    for (c, plane) in planes.iter().enumerate() {
+        for (i, &v) in plane.iter().enumerate() {
+            out[i * n + c] = f(v);
+        }
+    }
+    out
+}
+
+fn encode<C: colortype::ColorType, W: Write + Seek>(
+    encoder: &mut TiffEncoder<W>,
+    cols: usize,
+    rows: usize,
+    data: &[C::Inner],
+    icc: Option<&[u8]>,
+) -> Result<()>
+where
+    [C::Inner]: tiff::encoder::TiffValue,
+{
+    let mut image = encoder
+        .new_image::<C>(cols as u32, rows as u32)
+        .context("Failed to start TIFF image")?;
+    if let Some(profile) = icc {
+        image
+            .encoder()
+            .write_tag(Tag::IccProfile, profile)
+            .context("Failed to write TIFF ICC profile")?;
+    }
+    image.write_data(data).context("Failed to write TIFF data")?;
+    Ok(())
+}
+
+pub fn write_tiff(
+    planes: &[&Array2<f32>],
+    path: &str,
+    format: SampleFormat,
+    compression: TiffCompression,
+    icc_profile: Option<&[u8]>,
+) -> Result<()> {
+    let (rows, cols) = check_planes(planes)?;
+    let rgb = planes.len() == 3;
+
+    let file = File::create(path).with_context(|| format!("Failed to create {}", path))?;
+    let mut encoder = TiffEncoder::new(BufWriter::with_capacity(4 * 1024 * 1024, file))
+        .context("Failed to create TIFF encoder")?
+        .with_compression(match compression {
+            TiffCompression::None => Compression::Uncompressed,
+            TiffCompression::Lzw => Compression::Lzw,
+            TiffCompression::Deflate => Compression::Deflate(DeflateLevel::Balanced),
+        });
+    if compression != TiffCompression::None && format != SampleFormat::Float32 {
+        encoder = encoder.with_predictor(Predictor::Horizontal);
+    }
+
+    let scale = integer_scale(planes);
+    match (format, rgb) {
+        (SampleFormat::UInt8, false) => {
+            let data = interleaved(planes, |v| quantize(v, scale, 255.0) as u8);
+            encode::<colortype::Gray8, _>(&mut encoder, cols, rows, &data, icc_profile)
+        }
+        (SampleFormat::UInt8, true) => {
+            let data = interleaved(planes, |v| quantize(v, scale, 255.0) as u8);
+            encode::<colortype::RGB8, _>(&mut encoder, cols, rows, &data, icc_profile)
+        }
+        (SampleFormat::UInt16, false) => {
+            let data = interleaved(planes, |v| quantize(v, scale, 65535.0) as u16);
+            encode::<colortype::Gray16, _>(&mut encoder, cols, rows, &data, icc_profile)
+        }
+        (SampleFormat::UInt16, true) => {
+            let data = interleaved(planes, |v| quantize(v, scale, 65535.0) as u16);
+            encode::<colortype::RGB16, _>(&mut encoder, cols, rows, &data, icc_profile)
+        }
+        (SampleFormat::Float32, false) => {
+            let data = interleaved(planes, |v| v);
+            encode::<colortype::Gray32Float, _>(&mut encoder, cols, rows, &data, icc_profile)
+        }
+        (SampleFormat::Float32, true) => {
+            let data = interleaved(planes, |v| v);
+            encode::<colortype::RGB32Float, _>(&mut encoder, cols, rows, &data, icc_profile)
+        }
+    }
+}
+
+fn deinterleave(samples: Vec<f32>, cols: usize, rows: usize, channels: usize, stride: usize) -> Result<Vec<Array2<f32>>> {
+    if samples.len() < rows * cols * stride {
+        bail!("TIFF data is truncated: {} samples for {}x{}x{}", samples.len(), cols, rows, stride);
+    }
+    Ok((0..channels)
+        .map(|c| Array2::from_shape_fn((rows, cols), |(y, x)| samples[(y * cols + x) * stride + c]))
+        .collect())
+}
+
+pub fn read_tiff(path: &Path) -> Result<TiffImage> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut decoder = Decoder::new(BufReader::new(file))
+        .context("Failed to read TIFF header")?
+        .with_limits(Limits::unlimited());
+
+    let (w, h) = decoder.dimensions().context("Failed to read TIFF dimensions")?;
+    let (cols, rows) = (w as usize, h as usize);
+    let (channels, stride) = match decoder.colortype().context("Failed to read TIFF colour type")? {
+        ColorType::Gray(_) => (1, 1),
+        ColorType::GrayA(_) => (1, 2),
+        ColorType::RGB(_) => (3, 3),
+        ColorType::RGBA(_) => (3, 4),
+        other => bail!("Unsupported TIFF colour type {:?}", other),
+    };
+    let icc_profile = decoder.get_tag_u8_vec(Tag::IccProfile).ok();
+
+    let (samples, sample_format): (Vec<f32>, SampleFormat) = match decoder.read_image().context("Failed to decode TIFF image")? {
+        DecodingResult::U8(v) => (v.into_iter().map(|s| s as f32 / 255.0).collect(), SampleFormat::UInt8),
+        DecodingResult::U16(v) => (v.into_iter().map(|s| s as f32 / 65535.0).collect(), SampleFormat::UInt16),
+        DecodingResult::U32(v) => (v.into_iter().map(|s| (s as f64 / u32::MAX as f64) as f32).collect(), SampleFormat::Float32),
+        DecodingResult::F32(v) => (v, SampleFormat::Float32),
+        DecodingResult::F64(v) => (v.into_iter().map(|s| s as f32).collect(), SampleFormat::Float32),
+        DecodingResult::I16(v) => (v.into_iter().map(|s| s as f32).collect(), SampleFormat::Float32),
+        DecodingResult::I32(v) => (v.into_iter().map(|s| s as f32).collect(), SampleFormat::Float32),
+        _ => bail!("Unsupported TIFF sample format"),
+    };
+
+    Ok(TiffImage {
+        planes: deinterleave(samples, cols, rows, channels, stride)?,
+        sample_format,
+        icc_profile,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(rows: usize, cols: usize, offset: f32) -> Array2<f32> {
+        Array2::from_shape_fn((rows, cols), |(y, x)| (y * cols + x) as f32 / (rows * cols) as f32 * 0.8 + offset)
+    }
+
+    #[test]
+    fn test_round_trip_formats_and_compression() {
+        let dir = tempfile::tempdir().unwrap();
+        let (r, g, b) = (gradient(13, 17, 0.0), gradient(13, 17, 0.1), gradient(13, 17, 0.2));
+        for (format, tol) in [(SampleFormat::UInt8, 1.0 / 255.0), (SampleFormat::UInt16, 1.0 / 65535.0), (SampleFormat::Float32, 0.0)] {
+            for compression in [TiffCompression::None, TiffCompression::Lzw, TiffCompression::Deflate] {
+                let path = dir.path().join(format!("{:?}_{:?}.tif", format, compression));
+                let p = path.to_str().unwrap();
+
+                write_tiff(&[&r], p, format, compression, None).unwrap();
+                let mono = read_tiff(&path).unwrap();
+                assert_eq!(mono.planes.len(), 1);
+                assert_eq!(mono.sample_format, format);
+                assert!(mono.planes[0].iter().zip(r.iter()).all(|(a, b)| (a - b).abs() <= tol));
+
+                write_tiff(&[&r, &g, &b], p, format, compression, None).unwrap();
+                let rgb = read_tiff(&path).unwrap();
+                assert_eq!(rgb.planes.len(), 3);
+                assert!(rgb.planes[2].iter().zip(b.iter()).all(|(a, b)| (a - b).abs() <= tol));
+            }
+        }
+    }
+
+    #[test]
+    fn test_integer_scaling_and_icc() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scaled.tiff");
+        let data = Array2::from_shape_fn((4, 4), |(y, x)| ((y * 4 + x) * 100) as f32);
+        let icc = crate::infra::render::icc::icc_profile(crate::types::image::RgbColorSpace::Srgb);
+
+        write_tiff(&[&data, &data, &data], path.to_str().unwrap(), SampleFormat::UInt16, TiffCompression::Lzw, Some(&icc)).unwrap();
+        let back = read_tiff(&path).unwrap();
+        assert_eq!(back.planes[0][[3, 3]], 1.0);
+        assert!((back.planes[0][[0, 1]] - 100.0 / 1500.0).abs() < 1e-4);
+        assert!(back.icc_profile.as_deref() == Some(icc.as_slice()), "ICC profile not preserved");
+        assert!(write_tiff(&[&data, &data], path.to_str().unwrap(), SampleFormat::UInt8, TiffCompression::None, None).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+pub mod xml;
+pub mod reader;
+pub mod writer;
+
+use ndarray::Array2;
+
+use crate::infra::raster::{synthetic_header, RasterImage};
+use crate::types::header::HduHeader;
+use crate::types::image::SampleFormat;
+
+pub const XISF_SIGNATURE: &[u8; 8] = b"XISF0100";
+pub const XISF_NAMESPACE: &str = "http://www.pixinsight.com/xisf";
+
+const STRUCTURAL_KEYS: &[&str] = &[
+    "SIMPLE", "BITPIX", "NAXIS", "NAXIS1", "NAXIS2", "NAXIS3", "EXTEND", "BZERO", "BSCALE", "END",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FitsKeyword {
+    pub name: String,
+    pub value: String,
+    pub comment: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct XisfProperty {
+    pub id: String,
+    pub kind: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct XisfMetadata {
+    pub keywords: Vec<FitsKeyword>,
+    pub properties: Vec<XisfProperty>,
+}
+
+pub struct XisfImage {
+    pub planes: Vec<Array2<f32>>,
+    pub sample_format: SampleFormat,
+    pub metadata: XisfMetadata,
+}
+
+fn unquote(value: &str) -> String {
+    let trimmed = value.trim();
+    match trimmed.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        Some(inner) => inner.replace("''", "'").trim_end().to_string(),
+        None => trimmed.to_string(),
+    }
+}
+
+fn fits_value(value: &str) -> String {
+    let trimmed = value.trim();
+    if trimmed == "T" || trimmed == "F" || trimmed.parse::<f64>().is_ok() {
+        trimmed.to_string()
+    } else {
+        format!("'{}'", trimmed.replace('\'', "''"))
+    }
+}
+
+impl XisfMetadata {
+    pub fn from_header(header: &HduHeader) -> Self {
+        let mut meta = Self::default();
+        for (key, value) in &header.cards {
+            let key = key.trim();
+            if key.is_empty() || STRUCTURAL_KEYS.contains(&key) || key == "XISF_SRC" {
+                continue;
+            }
+            if key.contains(':') {
+                let kind = if value.trim().parse::<f64>().is_ok() { "Float64" } else { "String" };
+                meta.properties.push(XisfProperty { id: key.to_string(), kind: kind.into(), value: value.clone() });
+            } else {
+                meta.keywords.push(FitsKeyword { name: key.to_string(), value: fits_value(value), comment: String::new() });
+            }
+        }
+        meta
+    }
+}
+
+impl XisfImage {
+    pub fn into_raster(self) -> RasterImage {
+        let (rows, cols) = self.planes[0].dim();
+        let bitpix = match self.sample_format {
+            SampleFormat::UInt8 => 8,
+            SampleFormat::UInt16 => 16,
+            SampleFormat::Float32 => -32,
+        };
+        let mut header = synthetic_header(cols, rows, self.planes.len(), bitpix);
+        for kw in &self.metadata.keywords {
+            if !STRUCTURAL_KEYS.contains(&kw.name.as_str()) {
+                header.set(&kw.name, unquote(&kw.value));
+            }
+        }
+        for prop in &self.metadata.properties {
+            if header.get(&prop.id).is_none() {
+                header.set(&prop.id, prop.value.clone());
+            }
+        }
+        header.set("XISF_SRC", "true".into());
+        RasterImage { planes: self.planes, header }
+    }
+}
@@ -0,0 +1,214 @@
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use ndarray::Array2;
+
+use super::xml::{self, XmlElement};
+use super::{FitsKeyword, XisfImage, XisfMetadata, XisfProperty, XISF_SIGNATURE};
+use crate::types::image::SampleFormat;
+
+#[derive(Clone, Copy)]
+enum Sample {
+    UInt8,
+    UInt16,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl Sample {
+    fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "UInt8" => Self::UInt8,
+            "UInt16" => Self::UInt16,
+            "UInt32" => Self::UInt32,
+            "Float32" => Self::Float32,
+            "Float64" => Self::Float64,
+            other => bail!("Unsupported XISF sample format {}", other),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::UInt8 => 1,
+            Self::UInt16 => 2,
+            Self::UInt32 | Self::Float32 => 4,
+            Self::Float64 => 8,
+        }
+    }
+
+    fn decode(self, b: &[u8], little: bool) -> f32 {
+        macro_rules! num {
+            ($t:ty, $n:expr) => {{
+                let arr: [u8; $n] = b.try_into().unwrap();
+                if little { <$t>::from_le_bytes(arr) } else { <$t>::from_be_bytes(arr) }
+            }};
+        }
+        match self {
+            Self::UInt8 => b[0] as f32 / 255.0,
+            Self::UInt16 => num!(u16, 2) as f32 / 65535.0,
+            Self::UInt32 => (num!(u32, 4) as f64 / u32::MAX as f64) as f32,
+            Self::Float32 => num!(f32, 4),
+            Self::Float64 => num!(f64, 8) as f32,
+        }
+    }
+
+    fn format(self) -> SampleFormat {
+        match self {
+            Self::UInt8 => SampleFormat::UInt8,
+            Self::UInt16 => SampleFormat::UInt16,
+            _ => SampleFormat::Float32,
+        }
+    }
+}
+
+fn unshuffle(data: &[u8], item: usize) -> Vec<u8> {
+    if item <= 1 {
+        return data.to_vec();
+    }
+    let n = data.len() / item;
+    let mut out = vec![0u8; data.len()];
+    for j in 0..item {
+        for i in 0..n {
+            out[i * item + j] = data[j * n + i];
+        }
+    }
+    out[n * item..].copy_from_slice(&data[n * item..]);
+    out
+}
+
+fn decompress(data: &[u8], spec: &str) -> Result<Vec<u8>> {
+    let parts: Vec<&str> = spec.split(':').collect();
+    let codec = parts[0];
+    let size: usize = parts.get(1).and_then(|s| s.parse().ok()).context("Invalid XISF compression attribute")?;
+    let item: usize = parts.get(2).and_then(|s| s.parse().ok()).unwrap_or(1);
+
+    let raw = match codec {
+        "zlib" | "zlib+sh" => {
+            let mut out = Vec::with_capacity(size);
+            flate2::read::ZlibDecoder::new(data)
+                .read_to_end(&mut out)
+                .context("Failed to inflate XISF data block")?;
+            out
+        }
+        other => bail!("Unsupported XISF compression codec {}", other),
+    };
+    if raw.len() != size {
+        bail!("XISF data block inflated to {} bytes, expected {}", raw.len(), size);
+    }
+    Ok(if codec.ends_with("+sh") { unshuffle(&raw, item) } else { raw })
+}
+
+fn block_bytes<'a>(file: &'a [u8], el: &XmlElement) -> Result<std::borrow::Cow<'a, [u8]>> {
+    let location = el.attr("location").context("XISF image has no location attribute")?;
+    let parts: Vec<&str> = location.split(':').collect();
+    match parts.as_slice() {
+        ["attachment", pos, size] => {
+            let pos: usize = pos.parse().context("Invalid XISF attachment position")?;
+            let size: usize = size.parse().context("Invalid XISF attachment size")?;
+            let end = pos.checked_add(size).filter(|&e| e <= file.len()).context("XISF attachment exceeds file size")?;
+            Ok(std::borrow::Cow::Borrowed(&file[pos..end]))
+        }
+        ["inline", encoding] => decode_text(&el.text, encoding).map(std::borrow::Cow::Owned),
+        ["embedded"] => {
+            let data = el.children_named("Data").next().context("XISF embedded block has no Data element")?;
+            decode_text(&data.text, data.attr("encoding").unwrap_or("base64")).map(std::borrow::Cow::Owned)
+        }
+        _ => bail!("Unsupported XISF location {}", location),
+    }
+}
+
+fn decode_text(text: &str, encoding: &str) -> Result<Vec<u8>> {
+    let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    match encoding {
+        "base64" => base64::engine::general_purpose::STANDARD.decode(compact).context("Invalid base64 in XISF block"),
+        "hex" => (0..compact.len() / 2)
+            .map(|i| u8::from_str_radix(&compact[2 * i..2 * i + 2], 16).context("Invalid hex in XISF block"))
+            .collect(),
+        other => bail!("Unsupported XISF block encoding {}", other),
+    }
+}
+
+fn read_metadata(image: &XmlElement) -> XisfMetadata {
+    let keywords = image
+        .children_named("FITSKeyword")
+        .filter_map(|k| {
+            Some(FitsKeyword {
+                name: k.attr("name")?.trim().to_string(),
+                value: k.attr("value").unwrap_or("").to_string(),
+                comment: k.attr("comment").unwrap_or("").to_string(),
+            })
+        })
+        .collect();
+    let properties = image
+        .children_named("Property")
+        .filter(|p| p.attr("location").is_none())
+        .filter_map(|p| {
+            Some(XisfProperty {
+                id: p.attr("id")?.to_string(),
+                kind: p.attr("type").unwrap_or("String").to_string(),
+                value: p.attr("value").map(str::to_string).unwrap_or_else(|| p.text.clone()),
+            })
+        })
+        .collect();
+    XisfMetadata { keywords, properties }
+}
+
+pub fn parse_xisf(file: &[u8]) -> Result<XisfImage> {
+    if file.len() < 16 || &file[..8] != XISF_SIGNATURE {
+        bail!("Not an XISF 1.0 file");
+    }
+    let header_len = u32::from_le_bytes(file[8..12].try_into().unwrap()) as usize;
+    let header = file.get(16..16 + header_len).context("XISF header exceeds file size")?;
+    let root = xml::parse(std::str::from_utf8(header).context("XISF header is not UTF-8")?)?;
+    if root.local_name() != "xisf" {
+        bail!("Unexpected XISF root element <{}>", root.name);
+    }
+    let image = root.children_named("Image").next().context("XISF file contains no Image")?;
+
+    let geometry: Vec<usize> = image
+        .attr("geometry")
+        .context("XISF image has no geometry")?
+        .split(':')
+        .map(|s| s.parse().context("Invalid XISF geometry"))
+        .collect::<Result<_>>()?;
+    let [cols, rows, channels] = geometry[..] else {
+        bail!("Only 2-D XISF images are supported (geometry {:?})", geometry);
+    };
+    if cols == 0 || rows == 0 || channels == 0 {
+        bail!("Empty XISF image geometry");
+    }
+
+    let sample = Sample::parse(image.attr("sampleFormat").context("XISF image has no sampleFormat")?)?;
+    let little = image.attr("byteOrder").unwrap_or("little") != "big";
+    let planar = image.attr("pixelStorage").unwrap_or("Planar") != "Normal";
+
+    let block = block_bytes(file, image)?;
+    let data = match image.attr("compression") {
+        Some(spec) => std::borrow::Cow::Owned(decompress(&block, spec)?),
+        None => block,
+    };
+    let size = sample.size();
+    let n = cols * rows;
+    if data.len() < n * channels * size {
+        bail!("XISF data block is truncated: {} bytes for {}x{}x{}", data.len(), cols, rows, channels);
+    }
+
+    let sample_at = |c: usize, i: usize| -> f32 {
+        let idx = if planar { c * n + i } else { i * channels + c };
+        sample.decode(&data[idx * size..(idx + 1) * size], little)
+    };
+    let used = if channels >= 3 { 3 } else { 1 };
+    let planes = (0..used)
+        .map(|c| Array2::from_shape_fn((rows, cols), |(y, x)| sample_at(c, y * cols + x)))
+        .collect();
+
+    Ok(XisfImage { planes, sample_format: sample.format(), metadata: read_metadata(image) })
+}
+
+pub fn read_xisf(path: &Path) -> Result<XisfImage> {
+    let file = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_xisf(&file).with_context(|| format!("Failed to parse XISF {}", path.display()))
+}
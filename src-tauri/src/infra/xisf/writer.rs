@@ -0,0 +1,206 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use anyhow::{Context, Result};
+use ndarray::Array2;
+
+use super::xml::XmlElement;
+use super::{XisfMetadata, XISF_NAMESPACE, XISF_SIGNATURE};
+use crate::core::cube::video::unix_to_iso;
+use crate::infra::raster::{check_planes, integer_scale, quantize};
+use crate::types::image::SampleFormat;
+
+const BLOCK_ALIGNMENT: usize = 4096;
+const CREATOR: &str = "AstroBurst";
+
+fn finite_bounds(planes: &[&Array2<f32>]) -> (f32, f32) {
+    let (lo, hi) = planes
+        .iter()
+        .flat_map(|p| p.iter())
+        .filter(|v| v.is_finite())
+        .fold((f32::MAX, f32::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    if lo > hi || (lo >= 0.0 && hi <= 1.0) { (0.0, 1.0) } else if hi > lo { (lo, hi) } else { (lo, lo + 1.0) }
+}
+
+fn encode_planes(planes: &[&Array2<f32>], format: SampleFormat) -> Vec<u8> {
+    let scale = integer_scale(planes);
+    let samples = planes.iter().flat_map(|p| p.iter().copied());
+    match format {
+        SampleFormat::UInt8 => samples.map(|v| quantize(v, scale, 255.0) as u8).collect(),
+        SampleFormat::UInt16 => samples.flat_map(|v| (quantize(v, scale, 65535.0) as u16).to_le_bytes()).collect(),
+        SampleFormat::Float32 => samples.flat_map(|v| v.to_le_bytes()).collect(),
+    }
+}
+
+fn property(id: &str, kind: &str, value: &str) -> XmlElement {
+    let el = XmlElement::new("Property").with_attr("id", id).with_attr("type", kind);
+    if kind == "String" {
+        XmlElement { text: value.to_string(), ..el }
+    } else {
+        el.with_attr("value", value)
+    }
+}
+
+fn header_xml(
+    (cols, rows, channels): (usize, usize, usize),
+    format: SampleFormat,
+    bounds: (f32, f32),
+    metadata: &XisfMetadata,
+    position: usize,
+    size: usize,
+) -> String {
+    let (sample, color) = (
+        match format {
+            SampleFormat::UInt8 => "UInt8",
+            SampleFormat::UInt16 => "UInt16",
+            SampleFormat::Float32 => "Float32",
+        },
+        if channels == 3 { "RGB" } else { "Gray" },
+    );
+    let mut image = XmlElement::new("Image")
+        .with_attr("geometry", format!("{}:{}:{}", cols, rows, channels))
+        .with_attr("sampleFormat", sample)
+        .with_attr("colorSpace", color)
+        .with_attr("location", format!("attachment:{}:{}", position, size));
+    if format == SampleFormat::Float32 {
+        image = image.with_attr("bounds", format!("{}:{}", bounds.0, bounds.1));
+    }
+    for kw in &metadata.keywords {
+        image.children.push(
+            XmlElement::new("FITSKeyword")
+                .with_attr("name", kw.name.as_str())
+                .with_attr("value", kw.value.as_str())
+                .with_attr("comment", kw.comment.as_str()),
+        );
+    }
+    for prop in &metadata.properties {
+        image.children.push(property(&prop.id, &prop.kind, &prop.value));
+    }
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0);
+    let mut meta = XmlElement::new("Metadata");
+    meta.children.push(property("XISF:CreationTime", "TimePoint", &format!("{}Z", unix_to_iso(now))));
+    meta.children.push(property("XISF:CreatorApplication", "String", CREATOR));
+
+    let mut root = XmlElement::new("xisf")
+        .with_attr("version", "1.0")
+        .with_attr("xmlns", XISF_NAMESPACE)
+        .with_attr("xmlns:xsi", "http://www.w3.org/2001/XMLSchema-instance")
+        .with_attr("xsi:schemaLocation", format!("{} {}/xisf-1.0.xsd", XISF_NAMESPACE, "http://pixinsight.com/xisf"));
+    root.children.push(image);
+    root.children.push(meta);
+    format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}", root.to_xml())
+}
+
+pub fn write_xisf(
+    planes: &[&Array2<f32>],
+    path: &str,
+    format: SampleFormat,
+    metadata: &XisfMetadata,
+) -> Result<()> {
+    let (rows, cols) = check_planes(planes)?;
+    let data = encode_planes(planes, format);
+    let bounds = finite_bounds(planes);
+
+    let mut position = BLOCK_ALIGNMENT;
+    let xml = loop {
+        let xml = header_xml((cols, rows, planes.len()), format, bounds, metadata, position, data.len());
+        let needed = (16 + xml.len()).div_ceil(BLOCK_ALIGNMENT) * BLOCK_ALIGNMENT;
+        if needed <= position {
+            break xml;
+        }
+        position = needed;
+    };
+
+    let file = File::create(path).with_context(|| format!("Failed to create {}", path))?;
+    let mut writer = BufWriter::with_capacity(4 * 1024 * 1024, file);
+    writer.write_all(XISF_SIGNATURE)?;
+    writer.write_all(&(xml.len() as u32).to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(xml.as_bytes())?;
+    writer.write_all(&vec![0u8; position - 16 - xml.len()])?;
+    writer.write_all(&data)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::xisf::reader::read_xisf;
+    use crate::infra::xisf::{FitsKeyword, XisfProperty};
+
+    #[test]
+    fn test_round_trip_with_keywords_and_properties() {
+        let dir = tempfile::tempdir().unwrap();
+        let r = Array2::from_shape_fn((9, 11), |(y, x)| (y * 11 + x) as f32 * 3.5);
+        let g = r.mapv(|v| v * 0.5);
+        let b = r.mapv(|v| v + 1.0);
+        let metadata = XisfMetadata {
+            keywords: vec![
+                FitsKeyword { name: "OBJECT".into(), value: "'M 31 & <core>'".into(), comment: "target".into() },
+                FitsKeyword { name: "EXPTIME".into(), value: "300.0".into(), comment: String::new() },
+            ],
+            properties: vec![
+                XisfProperty { id: "Instrument:Filter:Name".into(), kind: "String".into(), value: "Ha 3nm".into() },
+                XisfProperty { id: "Instrument:Sensor:Temperature".into(), kind: "Float32".into(), value: "-10".into() },
+            ],
+        };
+
+        let path = dir.path().join("rgb.xisf");
+        write_xisf(&[&r, &g, &b], path.to_str().unwrap(), SampleFormat::Float32, &metadata).unwrap();
+        let back = read_xisf(&path).unwrap();
+        assert_eq!(back.planes.len(), 3);
+        assert_eq!(back.planes[0], r);
+        assert_eq!(back.planes[2], b);
+        assert_eq!(back.metadata.keywords, metadata.keywords);
+        assert_eq!(back.metadata.properties, metadata.properties);
+
+        let raster = back.into_raster();
+        assert_eq!(raster.header.get("OBJECT"), Some("M 31 & <core>"));
+        assert_eq!(raster.header.get_f64("EXPTIME"), Some(300.0));
+        assert_eq!(raster.header.get("Instrument:Filter:Name"), Some("Ha 3nm"));
+        let rebuilt = XisfMetadata::from_header(&raster.header);
+        assert!(rebuilt.keywords.iter().any(|k| k.name == "OBJECT" && k.value == "'M 31 & <core>'"));
+        assert!(rebuilt.properties.iter().any(|p| p.id == "Instrument:Sensor:Temperature" && p.value == "-10"));
+
+        let mono = dir.path().join("mono.xisf");
+        write_xisf(&[&g], mono.to_str().unwrap(), SampleFormat::UInt16, &XisfMetadata::default()).unwrap();
+        let back = read_xisf(&mono).unwrap();
+        assert_eq!(back.sample_format, SampleFormat::UInt16);
+        let max = g.iter().cloned().fold(0.0, f32::max);
+        assert!(back.planes[0].iter().zip(g.iter()).all(|(a, b)| (a - b / max).abs() < 1e-4));
+    }
+
+    #[test]
+    fn test_reads_compressed_shuffled_block() {
+        use std::io::Write as _;
+        let values: Vec<f32> = (0..12).map(|i| i as f32 * 0.25).collect();
+        let raw: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let n = values.len();
+        let shuffled: Vec<u8> = (0..4).flat_map(|j| (0..n).map(move |i| (i, j))).map(|(i, j)| raw[i * 4 + j]).collect();
+        let mut enc = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        enc.write_all(&shuffled).unwrap();
+        let block = enc.finish().unwrap();
+
+        let xml = format!(
+            "<?xml version=\"1.0\"?><xisf version=\"1.0\"><Image geometry=\"4:3:1\" sampleFormat=\"Float32\" bounds=\"0:3\" compression=\"zlib+sh:{}:4\" location=\"attachment:{{POS}}:{}\"/></xisf>",
+            raw.len(), block.len()
+        );
+        let pos = 16 + xml.len() + 8;
+        let xml = xml.replace("{POS}", &format!("{:08}", pos));
+        let mut file = XISF_SIGNATURE.to_vec();
+        file.extend_from_slice(&(xml.len() as u32).to_le_bytes());
+        file.extend_from_slice(&[0; 4]);
+        file.extend_from_slice(xml.as_bytes());
+        file.resize(pos, 0);
+        file.extend_from_slice(&block);
+
+        let image = crate::infra::xisf::reader::parse_xisf(&file).unwrap();
+        assert_eq!(image.planes[0].dim(), (3, 4));
+        assert!(image.planes[0].iter().zip(&values).all(|(a, b)| a == b));
+    }
+}
@@ -0,0 +1,254 @@
+use anyhow::{bail, Context, Result};
+
+#[derive(Debug, Clone, Default)]
+pub struct XmlElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlElement>,
+    pub text: String,
+}
+
+impl XmlElement {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), ..Self::default() }
+    }
+
+    pub fn with_attr(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.attributes.push((key.to_string(), value.into()));
+        self
+    }
+
+    pub fn local_name(&self) -> &str {
+        self.name.rsplit(':').next().unwrap_or(&self.name)
+    }
+
+    pub fn attr(&self, key: &str) -> Option<&str> {
+        self.attributes.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlElement> + 'a {
+        self.children.iter().filter(move |c| c.local_name() == name)
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut out = String::new();
+        self.write_into(&mut out);
+        out
+    }
+
+    fn write_into(&self, out: &mut String) {
+        out.push('<');
+        out.push_str(&self.name);
+        for (k, v) in &self.attributes {
+            out.push_str(&format!(" {}=\"{}\"", k, escape(v)));
+        }
+        if self.children.is_empty() && self.text.is_empty() {
+            out.push_str("/>");
+            return;
+        }
+        out.push('>');
+        out.push_str(&escape(&self.text));
+        for child in &self.children {
+            child.write_into(out);
+        }
+        out.push_str(&format!("</{}>", self.name));
+    }
+}
+
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(s: &str) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find('&') {
+        out.push_str(&rest[..pos]);
+        let end = rest[pos..].find(';').context("Unterminated XML entity")? + pos;
+        let entity = &rest[pos + 1..end];
+        match entity {
+            "amp" => out.push('&'),
+            "lt" => out.push('<'),
+            "gt" => out.push('>'),
+            "quot" => out.push('"'),
+            "apos" => out.push('\''),
+            _ => {
+                let code = if let Some(hex) = entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                    u32::from_str_radix(hex, 16).ok()
+                } else {
+                    entity.strip_prefix('#').and_then(|d| d.parse().ok())
+                };
+                out.push(code.and_then(char::from_u32).with_context(|| format!("Unknown XML entity &{};", entity))?);
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn skip_ws(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.src.len() - trimmed.len();
+    }
+
+    fn skip_past(&mut self, pat: &str) -> Result<()> {
+        let idx = self.rest().find(pat).with_context(|| format!("Expected '{}' in XML", pat))?;
+        self.pos += idx + pat.len();
+        Ok(())
+    }
+
+    fn skip_misc(&mut self) -> Result<()> {
+        loop {
+            self.skip_ws();
+            let rest = self.rest();
+            if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<!") {
+                self.skip_past(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '='))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            bail!("Expected XML name at offset {}", self.pos);
+        }
+        self.pos += len;
+        Ok(rest[..len].to_string())
+    }
+
+    fn element(&mut self) -> Result<XmlElement> {
+        if !self.rest().starts_with('<') {
+            bail!("Expected XML element at offset {}", self.pos);
+        }
+        self.pos += 1;
+        let mut el = XmlElement::new(&self.name()?);
+
+        loop {
+            self.skip_ws();
+            let rest = self.rest();
+            if let Some(after) = rest.strip_prefix("/>") {
+                self.pos = self.src.len() - after.len();
+                return Ok(el);
+            }
+            if rest.starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            let key = self.name()?;
+            self.skip_ws();
+            if !self.rest().starts_with('=') {
+                bail!("Expected '=' after XML attribute {}", key);
+            }
+            self.pos += 1;
+            self.skip_ws();
+            let quote = self.rest().chars().next().context("Unexpected end of XML")?;
+            if quote != '"' && quote != '\'' {
+                bail!("Unquoted XML attribute {}", key);
+            }
+            self.pos += 1;
+            let end = self.rest().find(quote).context("Unterminated XML attribute")?;
+            let value = unescape(&self.rest()[..end])?;
+            self.pos += end + 1;
+            el.attributes.push((key, value));
+        }
+
+        loop {
+            let rest = self.rest();
+            let lt = rest.find('<').context("Unterminated XML element")?;
+            el.text.push_str(&unescape(&rest[..lt])?);
+            self.pos += lt;
+            let rest = self.rest();
+            if let Some(after) = rest.strip_prefix("</") {
+                let end = after.find('>').context("Unterminated XML end tag")?;
+                if after[..end].trim() != el.name {
+                    bail!("Mismatched XML end tag </{}> for <{}>", after[..end].trim(), el.name);
+                }
+                self.pos += 2 + end + 1;
+                el.text = el.text.trim().to_string();
+                return Ok(el);
+            }
+            if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if let Some(after) = rest.strip_prefix("<![CDATA[") {
+                let end = after.find("]]>").context("Unterminated CDATA section")?;
+                el.text.push_str(&after[..end]);
+                self.pos += 9 + end + 3;
+            } else if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else {
+                el.children.push(self.element()?);
+            }
+        }
+    }
+}
+
+pub fn parse(src: &str) -> Result<XmlElement> {
+    let mut parser = Parser { src, pos: 0 };
+    parser.skip_misc()?;
+    let root = parser.element()?;
+    parser.skip_misc()?;
+    if !parser.rest().trim_end_matches('\0').is_empty() {
+        bail!("Trailing content after XML root element");
+    }
+    Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_serialize_round_trip() {
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- header -->
+<xisf version="1.0" xmlns="http://www.pixinsight.com/xisf">
+  <Image geometry="4:3:1" sampleFormat='Float32'>
+    <FITSKeyword name="OBJECT" value="'M42 &amp; M43'" comment="&lt;target&gt;"/>
+    <Property id="Observer:Name" type="String">Jane &#x44;oe</Property>
+  </Image>
+</xisf>"#;
+        let root = parse(src).unwrap();
+        assert_eq!(root.local_name(), "xisf");
+        let image = root.children_named("Image").next().unwrap();
+        assert_eq!(image.attr("sampleFormat"), Some("Float32"));
+        let kw = image.children_named("FITSKeyword").next().unwrap();
+        assert_eq!(kw.attr("value"), Some("'M42 & M43'"));
+        assert_eq!(kw.attr("comment"), Some("<target>"));
+        assert_eq!(image.children_named("Property").next().unwrap().text, "Jane Doe");
+
+        let again = parse(&root.to_xml()).unwrap();
+        assert_eq!(again.to_xml(), root.to_xml());
+        assert!(parse("<a><b></a>").is_err());
+    }
+}
@@ -121,7 +121,10 @@ pub fn run() {
             cmd::export::export_fits_rgb,
             cmd::export::export_png,
             cmd::export::export_rgb_png,
+            cmd::export::export_tiff,
+            cmd::export::export_xisf,
             cmd::compose::compose_rgb_cmd,
+            cmd::compose::debayer_cmd,
             cmd::metadata::get_header,
             cmd::metadata::get_full_header,
             cmd::metadata::get_fits_extensions,
@@ -137,6 +140,11 @@ pub fn run() {
             cmd::visualization::generate_tiles_rgb,
             cmd::stacking::calibrate,
             cmd::stacking::stack,
+            cmd::stacking::comet_stack_cmd,
+            cmd::stacking::lucky_stack_cmd,
+            cmd::stacking::drizzle_cfa_cmd,
+            cmd::stacking::drizzle_stack_cmd,
+            cmd::stacking::register_frames_cmd,
             cmd::stacking::run_pipeline_cmd,
             cmd::compose::restretch_composite_cmd,
             cmd::compose::clear_composite_cache_cmd,
@@ -148,23 +156,46 @@ pub fn run() {
             cmd::compose::calibrate_and_scnr_cmd,
             cmd::compose::compute_auto_wb_cmd,
             cmd::compose::reset_wb_cmd,
+            cmd::compose::adjust_color_composite_cmd,
+            cmd::compose::extract_color_components_cmd,
+            cmd::compose::get_working_color_space_cmd,
+            cmd::compose::set_working_color_space_cmd,
             cmd::processing::resample_fits_cmd,
             cmd::processing::deconvolve_rl_cmd,
+            cmd::processing::deconvolve_variant_cmd,
             cmd::processing::extract_background_cmd,
             cmd::processing::wavelet_denoise_cmd,
+            cmd::processing::wavelet_layers_cmd,
+            cmd::processing::multiscale_process_cmd,
+            cmd::processing::multiscale_process_composite_cmd,
+            cmd::processing::denoise_cmd,
+            cmd::processing::denoise_composite_cmd,
+            cmd::processing::remove_cosmic_rays_cmd,
+            cmd::processing::detect_trails_cmd,
             cmd::processing::apply_arcsinh_stretch_cmd,
             cmd::processing::masked_stretch_cmd,
             cmd::processing::arcsinh_stretch_composite_cmd,
             cmd::processing::masked_stretch_composite_cmd,
+            cmd::processing::ghs_stretch_cmd,
+            cmd::processing::ghs_stretch_composite_cmd,
+            cmd::processing::ghs_preview_cmd,
+            cmd::processing::remove_stars_cmd,
+            cmd::processing::remove_stars_composite_cmd,
+            cmd::processing::screen_blend_cmd,
             cmd::processing::apply_tone_composite_cmd,
+            cmd::processing::pixel_math_cmd,
             cmd::cube::process_cube_cmd,
             cmd::cube::process_cube_lazy_cmd,
             cmd::cube::get_cube_info,
             cmd::cube::get_cube_frame,
             cmd::cube::get_cube_spectrum,
+            cmd::cube::get_video_info,
+            cmd::cube::get_video_frame,
             cmd::astrometry::plate_solve_cmd,
             cmd::astrometry::get_wcs_info,
             cmd::psf::estimate_psf_cmd,
+            cmd::psf::load_psf_model_cmd,
+            cmd::psf::psf_match_cmd,
             cmd::spcc::spcc_calibrate_cmd,
             cmd::config::get_config,
             cmd::config::update_config,
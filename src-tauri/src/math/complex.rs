@@ -24,12 +24,16 @@ pub fn safe_normalize<T: FftFloat>(c: Complex<T>, epsilon: T) -> Complex<T> {
 }
 
 #[inline]
-pub fn cross_power_element<T: FftFloat>(a: Complex<T>, b: Complex<T>, epsilon: T) -> Complex<T> {
-    let product = Complex::new(
+pub fn conj_product<T: FftFloat>(a: Complex<T>, b: Complex<T>) -> Complex<T> {
+    Complex::new(
         a.re * b.re + a.im * b.im,
         a.im * b.re - a.re * b.im,
-    );
-    safe_normalize(product, epsilon)
+    )
+}
+
+#[inline]
+pub fn cross_power_element<T: FftFloat>(a: Complex<T>, b: Complex<T>, epsilon: T) -> Complex<T> {
+    safe_normalize(conj_product(a, b), epsilon)
 }
 
 pub fn cross_power_spectrum<T: FftFloat>(
@@ -43,6 +47,28 @@ pub fn cross_power_spectrum<T: FftFloat>(
         .collect()
 }
 
+pub fn damped_cross_power_spectrum<T: FftFloat>(
+    fa: &[Complex<T>],
+    fb: &[Complex<T>],
+    damping: T,
+    epsilon: T,
+) -> Vec<Complex<T>> {
+    let products: Vec<Complex<T>> = fa
+        .par_iter()
+        .zip(fb.par_iter())
+        .map(|(&a, &b)| conj_product(a, b))
+        .collect();
+    let peak = products.par_iter().map(|&c| norm(c)).reduce(T::zero, T::max_of);
+    let floor = (peak * damping).max_of(epsilon);
+    products
+        .into_par_iter()
+        .map(|c| {
+            let scale = T::one() / (norm(c) + floor);
+            Complex::new(c.re * scale, c.im * scale)
+        })
+        .collect()
+}
+
 pub fn pointwise_multiply<T: FftFloat>(
     a: &[Complex<T>],
     b: &[Complex<T>],
@@ -140,6 +166,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_damped_cross_power_spectrum_suppresses_weak_bins() {
+        let fa = vec![Complex::new(1000.0f64, 0.0), Complex::new(0.0, 1e-3), Complex::new(0.0, 0.0)];
+        let fb = vec![Complex::new(1000.0f64, 0.0), Complex::new(1e-3, 0.0), Complex::new(0.0, 0.0)];
+        let result = damped_cross_power_spectrum(&fa, &fb, 1e-3, 1e-15);
+        assert!((norm(result[0]) - 1.0).abs() < 1e-2);
+        assert!(norm(result[1]) < 1e-6);
+        assert!(result[1].im > 0.0);
+        assert_eq!(norm(result[2]), 0.0);
+    }
+
     #[test]
     fn test_pointwise_multiply() {
         let a = vec![Complex::new(1.0f64, 2.0), Complex::new(3.0, 4.0)];
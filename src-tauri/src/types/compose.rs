@@ -1,5 +1,5 @@
 use super::image::{ImageStats, ScnrConfig, StfParams};
-use super::stacking::DrizzleConfig;
+use super::stacking::{DrizzleConfig, ReferenceChoice};
 
 #[derive(Debug, Clone)]
 pub enum WhiteBalance {
@@ -12,6 +12,9 @@ pub enum WhiteBalance {
 pub enum AlignMethod {
     PhaseCorrelation,
     Affine,
+    Polynomial2,
+    Polynomial3,
+    ThinPlate,
 }
 
 impl Default for AlignMethod {
@@ -20,6 +23,18 @@ impl Default for AlignMethod {
     }
 }
 
+impl std::fmt::Display for AlignMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlignMethod::PhaseCorrelation => write!(f, "phase_correlation"),
+            AlignMethod::Affine => write!(f, "affine"),
+            AlignMethod::Polynomial2 => write!(f, "polynomial2"),
+            AlignMethod::Polynomial3 => write!(f, "polynomial3"),
+            AlignMethod::ThinPlate => write!(f, "thin_plate"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ChannelStats {
     pub min: f64,
@@ -123,6 +138,7 @@ pub struct DrizzleRgbResult {
     pub frame_count_g: usize,
     pub frame_count_b: usize,
     pub rejected_pixels: u64,
+    pub references: [Option<ReferenceChoice>; 3],
     pub stf_r: StfParams,
     pub stf_g: StfParams,
     pub stf_b: StfParams,
@@ -23,6 +23,10 @@ pub const EVENT_DRIZZLE_RGB_PROGRESS: &str = "drizzle-rgb-progress";
 pub const EVENT_CALIBRATE_PROGRESS: &str = "calibrate-progress";
 pub const EVENT_STACK_PROGRESS: &str = "stack-progress";
 pub const EVENT_WAVELET_PROGRESS: &str = "wavelet-progress";
+pub const EVENT_COSMIC_PROGRESS: &str = "cosmic-progress";
+pub const EVENT_REGISTER_PROGRESS: &str = "register-progress";
+pub const EVENT_COMET_PROGRESS: &str = "comet-progress";
+pub const EVENT_LUCKY_PROGRESS: &str = "lucky-progress";
 
 pub const PROGRESS_STEPS: usize = 4;
 
@@ -97,10 +101,23 @@ pub const RES_RMS_RESIDUAL: &str = "rms_residual";
 pub const RES_ITERATIONS_RUN: &str = "iterations_run";
 pub const RES_CONVERGENCE: &str = "convergence";
 
+pub const RES_CR_PIXELS: &str = "cr_pixels";
+pub const RES_CR_MASK_PATH: &str = "cr_mask_path";
+pub const RES_CR_FRACTION: &str = "cr_fraction";
+pub const RES_GAIN: &str = "gain";
+pub const RES_READ_NOISE: &str = "read_noise";
+
 pub const RES_STRETCH_FACTOR: &str = "stretch_factor";
 
 pub const RES_SCALES_PROCESSED: &str = "scales_processed";
 pub const RES_NOISE_ESTIMATE: &str = "noise_estimate";
+pub const RES_NOISE_MODEL: &str = "noise_model";
+pub const RES_LAYERS: &str = "layers";
+pub const RES_RESIDUAL: &str = "residual";
+pub const RES_LAYER_SIGMAS: &str = "layer_sigmas";
+pub const SUFFIX_MULTISCALE: &str = "multiscale";
+pub const SUFFIX_DENOISE: &str = "denoise";
+pub const SUFFIX_WAVELET_RESIDUAL: &str = "wavelet_residual";
 
 pub const RES_FRAME_COUNT: &str = "frame_count";
 pub const RES_FRAME_COUNT_R: &str = "frame_count_r";
@@ -115,6 +132,51 @@ pub const RES_DX: &str = "dx";
 pub const RES_HAS_BIAS: &str = "has_bias";
 pub const RES_HAS_DARK: &str = "has_dark";
 pub const RES_HAS_FLAT: &str = "has_flat";
+pub const RES_DARK_SCALE: &str = "dark_scale";
+pub const RES_DARK_SCALE_MODE: &str = "dark_scale_mode";
+pub const RES_HOT_PIXELS: &str = "hot_pixels";
+pub const RES_COLD_PIXELS: &str = "cold_pixels";
+pub const RES_BAD_LINE_PIXELS: &str = "bad_line_pixels";
+pub const RES_COSMETIC_CORRECTED: &str = "cosmetic_corrected";
+pub const RES_DEFECT_MAP_PATH: &str = "defect_map_path";
+
+pub const RES_BAYER_PATTERN: &str = "bayer_pattern";
+pub const RES_DEBAYER_METHOD: &str = "debayer_method";
+pub const RES_CHANNEL_PATHS: &str = "channel_paths";
+pub const RES_CALIBRATED: &str = "calibrated";
+pub const RES_WEIGHT_PATHS: &str = "weight_paths";
+pub const RES_WEIGHT_PATH: &str = "weight_path";
+pub const RES_CONTEXT_PATH: &str = "context_path";
+pub const RES_CONTEXT_PLANES: &str = "context_planes";
+pub const RES_REGISTRATION_PATH: &str = "registration_path";
+pub const RES_REFERENCE: &str = "reference";
+pub const RES_REFERENCE_INDEX: &str = "reference_index";
+pub const RES_REFERENCE_PATH: &str = "reference_path";
+pub const RES_METHOD: &str = "method";
+pub const RES_MAPPING: &str = "mapping";
+pub const RES_INLIERS: &str = "inliers";
+pub const RES_RESIDUAL_PX: &str = "residual_px";
+pub const RES_IS_FLIPPED: &str = "is_flipped";
+pub const RES_STAR_STACK: &str = "star_stack";
+pub const RES_COMET_STACK: &str = "comet_stack";
+pub const RES_COMBINED: &str = "combined";
+pub const RES_OBJECT_OFFSETS: &str = "object_offsets";
+pub const RES_COMET_START: &str = "comet_start";
+pub const RES_COMET_END: &str = "comet_end";
+pub const RES_MOTION_PX_PER_HOUR: &str = "motion_px_per_hour";
+pub const RES_DETECTED: &str = "detected";
+pub const RES_TIMESTAMPS_USED: &str = "timestamps_used";
+pub const RES_MASKED_STARS: &str = "masked_stars";
+pub const RES_TRAILS: &str = "trails";
+pub const RES_TRAIL_COUNT: &str = "trail_count";
+pub const RES_TRAIL_MASK_PATH: &str = "trail_mask_path";
+pub const RES_MASKED_PIXELS: &str = "masked_pixels";
+pub const RES_SELECTED_COUNT: &str = "selected_count";
+pub const RES_BEST_INDEX: &str = "best_index";
+pub const RES_QUALITY: &str = "quality";
+pub const RES_PATCH_COUNT: &str = "patch_count";
+pub const RES_MEAN_VALID_PATCHES: &str = "mean_valid_patches";
+pub const RES_MAX_LOCAL_SHIFT_PX: &str = "max_local_shift_px";
 
 pub const RES_SCNR_APPLIED: &str = "scnr_applied";
 pub const RES_OFFSET_G: &str = "offset_g";
@@ -124,6 +186,7 @@ pub const RES_DIMENSION_INFO: &str = "dimension_info";
 pub const RES_FRAMES: &str = "frames";
 pub const RES_BITPIX: &str = "bitpix";
 pub const RES_FRAME_INDEX: &str = "frame_index";
+pub const RES_TIMESTAMP: &str = "timestamp";
 pub const RES_SPECTRUM: &str = "spectrum";
 pub const RES_SPECTRAL_CLASSIFICATION: &str = "spectral_classification";
 pub const RES_IS_SPECTRAL: &str = "is_spectral";
@@ -168,6 +231,9 @@ pub const WB_MODE_NONE: &str = "none";
 pub const SCNR_METHOD_MAXIMUM: &str = "maximum";
 
 pub const SUFFIX_DECONV: &str = "deconv";
+pub const RES_PSF_TILES: &str = "psf_tiles";
+pub const RES_TILES_MEASURED: &str = "tiles_measured";
+pub const RES_MEASURED: &str = "measured";
 
 pub const DEFAULT_DRIZZLE_SCALE: f64 = 2.0;
 pub const DEFAULT_DRIZZLE_PIXFRAC: f64 = 0.7;
@@ -176,12 +242,37 @@ pub const DEFAULT_DRIZZLE_SIGMA_ITERS: usize = 5;
 pub const KERNEL_GAUSSIAN: &str = "gaussian";
 pub const KERNEL_LANCZOS3: &str = "lanczos3";
 pub const KERNEL_LANCZOS: &str = "lanczos";
+pub const KERNEL_TURBO: &str = "turbo";
+pub const KERNEL_POINT: &str = "point";
+
+pub const GEOMETRY_AFFINE: &str = "affine";
+pub const GEOMETRY_WCS: &str = "wcs";
+
+pub const DARK_SCALE_EXPOSURE: &str = "exposure";
+pub const DARK_SCALE_OPTIMIZE: &str = "optimize";
+pub const DEBAYER_VNG: &str = "vng";
+pub const DEBAYER_AHD: &str = "ahd";
+pub const DEBAYER_AHD4: &str = "ahd4";
 
 pub const STAGE_RENDER: &str = "render";
 pub const STAGE_SAVE: &str = "save";
 
 pub const FILE_DRIZZLE_RGB_PNG: &str = "drizzle_rgb.png";
 pub const FILE_DRIZZLE_RGB_FITS: &str = "drizzle_rgb.fits";
+pub const FILE_DRIZZLE_CFA_PNG: &str = "drizzle_cfa.png";
+pub const FILE_DRIZZLE_CFA_STEM: &str = "drizzle_cfa";
+pub const FILE_DRIZZLE_STEM: &str = "drizzle";
+pub const FILE_DRIZZLE_WEIGHT_FITS: &str = "drizzle_weight.fits";
+pub const FILE_DRIZZLE_CONTEXT_FITS: &str = "drizzle_context.fits";
+pub const FILE_DRIZZLE_CFA_CONTEXT_FITS: &str = "drizzle_cfa_context.fits";
+pub const FILE_REGISTRATION_STEM: &str = "registration";
+pub const FILE_COMET_STEM: &str = "comet";
+pub const FILE_LUCKY_STEM: &str = "lucky";
+pub const FILE_PIXELMATH_STEM: &str = "pixelmath";
+
+pub const EXT_ERR: &str = "ERR";
+pub const EXT_DQ: &str = "DQ";
+pub const DQ_DO_NOT_USE: u32 = 1;
 
 pub const RESAMPLED: &str = "resampled";
 pub const LRGB_APPLIED: &str = "lrgb_applied";
@@ -225,6 +316,12 @@ pub const RES_SPREAD_PIXELS: &str = "spread_pixels";
 pub const RES_STARS_USED: &str = "stars_used";
 pub const RES_STARS_REJECTED: &str = "stars_rejected";
 pub const RES_KERNEL: &str = "kernel";
+pub const RES_OVERSAMPLING: &str = "oversampling";
+pub const RES_PSF_PIXEL_SCALE: &str = "psf_pixel_scale";
+pub const RES_SCALE_FACTOR: &str = "scale_factor";
+pub const RES_SOURCE_FWHM: &str = "source_fwhm";
+pub const RES_TARGET_FWHM: &str = "target_fwhm";
+pub const SUFFIX_PSF_MATCHED: &str = "psfmatched";
 
 pub const RES_STARS_MASKED: &str = "stars_masked";
 pub const RES_MASK_COVERAGE: &str = "mask_coverage";
@@ -240,6 +337,28 @@ pub const RES_WHITE_REF: &str = "white_reference";
 pub const RES_CATALOG_NAME: &str = "catalog_name";
 
 pub const SUFFIX_MASKED_STRETCH: &str = "masked_stretch";
+pub const SUFFIX_STARLESS: &str = "starless";
+pub const SUFFIX_STARS: &str = "stars";
+pub const SUFFIX_SCREEN: &str = "screen";
+pub const RES_STARLESS: &str = "starless";
+pub const RES_STARS_ONLY: &str = "stars_only";
+pub const RES_FILL_SCALES: &str = "fill_scales";
+
+pub const SUFFIX_GHS: &str = "ghs";
+pub const RES_GHS_CURVE: &str = "curve";
+pub const RES_LINKED: &str = "linked";
+pub const GHS_CURVE_SAMPLES: usize = 256;
+
+pub const RES_COLOR_SPACE: &str = "color_space";
+pub const RES_COMPONENTS: &str = "components";
+pub const RES_PROFILE: &str = "profile";
+pub const RES_CONVERTED: &str = "converted";
+
+pub const RES_SAMPLE_FORMAT: &str = "sample_format";
+pub const RES_COMPRESSION: &str = "compression";
+pub const RES_ICC_EMBEDDED: &str = "icc_embedded";
+pub const RES_KEYWORD_COUNT: &str = "keyword_count";
+pub const RES_PROPERTY_COUNT: &str = "property_count";
 
 pub const RES_BLEND_PRESET: &str = "blend_preset";
 
@@ -48,6 +48,313 @@ impl Default for StfParams {
     }
 }
 
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GhsParams {
+    pub stretch: f64,
+    pub local_intensity: f64,
+    pub symmetry_point: f64,
+    pub shadow_protection: f64,
+    pub highlight_protection: f64,
+    pub inverse: bool,
+}
+
+impl Default for GhsParams {
+    fn default() -> Self {
+        Self {
+            stretch: 0.0,
+            local_intensity: 0.0,
+            symmetry_point: 0.0,
+            shadow_protection: 0.0,
+            highlight_protection: 1.0,
+            inverse: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorSpace {
+    Lab,
+    #[default]
+    Lch,
+    Hsv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RgbColorSpace {
+    LinearSrgb,
+    #[default]
+    Srgb,
+    DisplayP3,
+    AdobeRgb,
+    Rec2020,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleFormat {
+    UInt8,
+    UInt16,
+    #[default]
+    Float32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TiffCompression {
+    #[default]
+    None,
+    Lzw,
+    Deflate,
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HueShiftRange {
+    pub center: f64,
+    pub width: f64,
+    pub shift: f64,
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LuminanceMask {
+    pub low: f64,
+    pub high: f64,
+    pub invert: bool,
+}
+
+impl Default for LuminanceMask {
+    fn default() -> Self {
+        Self {
+            low: 0.0,
+            high: 1.0,
+            invert: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ColorAdjustParams {
+    pub space: ColorSpace,
+    pub saturation: f64,
+    pub hue_saturation: Vec<[f64; 2]>,
+    pub lightness_saturation: Vec<[f64; 2]>,
+    pub hue_shifts: Vec<HueShiftRange>,
+    pub luminance_mask: Option<LuminanceMask>,
+}
+
+impl Default for ColorAdjustParams {
+    fn default() -> Self {
+        Self {
+            space: ColorSpace::Lch,
+            saturation: 1.0,
+            hue_saturation: Vec::new(),
+            lightness_saturation: Vec::new(),
+            hue_shifts: Vec::new(),
+            luminance_mask: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WaveletLayerParams {
+    pub enabled: bool,
+    pub bias: f64,
+    pub noise_threshold: f64,
+    pub noise_reduction: f64,
+}
+
+impl Default for WaveletLayerParams {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            bias: 0.0,
+            noise_threshold: 0.0,
+            noise_reduction: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MultiscaleParams {
+    pub num_scales: usize,
+    pub layers: Vec<WaveletLayerParams>,
+    pub sharpen: f64,
+    pub sharpen_layers: usize,
+    pub local_contrast: f64,
+    pub contrast_layers: [usize; 2],
+    pub residual: bool,
+    pub residual_gain: f64,
+    pub luminance_mask: Option<LuminanceMask>,
+}
+
+impl Default for MultiscaleParams {
+    fn default() -> Self {
+        Self {
+            num_scales: 5,
+            layers: Vec::new(),
+            sharpen: 0.0,
+            sharpen_layers: 2,
+            local_contrast: 0.0,
+            contrast_layers: [2, 4],
+            residual: true,
+            residual_gain: 1.0,
+            luminance_mask: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DenoiseMethod {
+    Nlm,
+    Tgv,
+    #[default]
+    Mmt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoiseModelKind {
+    #[default]
+    Auto,
+    Global,
+    Detector,
+    ErrPlane,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DenoiseParams {
+    pub method: DenoiseMethod,
+    pub strength: f64,
+    pub amount: f64,
+    pub patch_radius: usize,
+    pub search_radius: usize,
+    pub iterations: usize,
+    pub tgv_alpha_ratio: f64,
+    pub num_scales: usize,
+    pub scale_thresholds: Vec<f64>,
+    pub noise_model: NoiseModelKind,
+    pub gain: Option<f64>,
+    pub read_noise: Option<f64>,
+    pub luminance_mask: Option<LuminanceMask>,
+    pub chrominance_only: bool,
+}
+
+impl Default for DenoiseParams {
+    fn default() -> Self {
+        Self {
+            method: DenoiseMethod::Mmt,
+            strength: 1.0,
+            amount: 1.0,
+            patch_radius: 2,
+            search_radius: 7,
+            iterations: 150,
+            tgv_alpha_ratio: 2.0,
+            num_scales: 4,
+            scale_thresholds: vec![3.0, 2.5, 2.0, 1.5],
+            noise_model: NoiseModelKind::Auto,
+            gain: None,
+            read_noise: None,
+            luminance_mask: None,
+            chrominance_only: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PsfSource {
+    Gaussian,
+    #[default]
+    Measured,
+    External,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeconvRegularization {
+    #[default]
+    None,
+    Tv,
+    Wavelet,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SpatialDeconvParams {
+    pub iterations: usize,
+    pub psf_source: PsfSource,
+    pub psf_sigma: f64,
+    pub psf_size: usize,
+    pub psf_num_stars: usize,
+    pub psf_cutout_radius: usize,
+    pub psf_path: Option<String>,
+    pub pixel_scale: Option<f64>,
+    pub tile_grid: [usize; 2],
+    pub regularization: DeconvRegularization,
+    pub tv_weight: f64,
+    pub wavelet_threshold: f64,
+    pub star_mask: bool,
+    pub deringing: bool,
+    pub deringing_threshold: f64,
+}
+
+impl Default for SpatialDeconvParams {
+    fn default() -> Self {
+        Self {
+            iterations: 30,
+            psf_source: PsfSource::Measured,
+            psf_sigma: 2.0,
+            psf_size: 15,
+            psf_num_stars: 20,
+            psf_cutout_radius: 15,
+            psf_path: None,
+            pixel_scale: None,
+            tile_grid: [1, 1],
+            regularization: DeconvRegularization::None,
+            tv_weight: 0.002,
+            wavelet_threshold: 1.0,
+            star_mask: true,
+            deringing: false,
+            deringing_threshold: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PsfMatchParams {
+    pub source_psf: Option<String>,
+    pub target_psf: Option<String>,
+    pub target_image: Option<String>,
+    pub pixel_scale: Option<f64>,
+    pub regularization: f64,
+    pub psf_num_stars: usize,
+    pub psf_cutout_radius: usize,
+}
+
+impl Default for PsfMatchParams {
+    fn default() -> Self {
+        Self {
+            source_psf: None,
+            target_psf: None,
+            target_image: None,
+            pixel_scale: None,
+            regularization: 1e-4,
+            psf_num_stars: 20,
+            psf_cutout_radius: 15,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub struct AutoStfConfig {
     pub target_bg: f64,
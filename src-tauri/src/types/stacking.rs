@@ -1,11 +1,56 @@
 use ndarray::Array2;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReferenceSelection {
+    #[default]
+    Auto,
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ReferenceChoice {
+    pub index: usize,
+    pub automatic: bool,
+    pub score: f64,
+    pub star_count: usize,
+    pub fwhm: f64,
+    pub snr: f64,
+    pub noise_ratio: f64,
+    pub reason: String,
+}
+
+impl ReferenceChoice {
+    pub fn fixed(index: usize, reason: &str) -> Self {
+        Self { index, reason: reason.to_string(), ..Default::default() }
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct DetectedTrail {
+    pub x0: f64,
+    pub y0: f64,
+    pub x1: f64,
+    pub y1: f64,
+    pub angle_deg: f64,
+    pub length: f64,
+    pub width: f64,
+    pub votes: u32,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct FrameTrailReport {
+    pub index: usize,
+    pub trails: Vec<DetectedTrail>,
+    pub masked_pixels: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct StackConfig {
     pub sigma_low: f32,
     pub sigma_high: f32,
     pub max_iterations: usize,
     pub align: bool,
+    pub reference: ReferenceSelection,
 }
 
 impl Default for StackConfig {
@@ -15,16 +60,108 @@ impl Default for StackConfig {
             sigma_high: 3.0,
             max_iterations: 5,
             align: true,
+            reference: ReferenceSelection::default(),
         }
     }
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct CalibrationReport {
+    pub dark_scales: Vec<Option<f32>>,
+    pub cosmetic_corrected: Vec<usize>,
+}
+
 #[derive(Debug, Clone)]
 pub struct StackResult {
     pub image: Array2<f32>,
     pub frame_count: usize,
     pub rejected_pixels: u64,
     pub offsets: Vec<(i32, i32)>,
+    pub reference: ReferenceChoice,
+    pub trails: Vec<FrameTrailReport>,
+    pub calibration: CalibrationReport,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CometConfig {
+    pub stack: StackConfig,
+    pub first_position: Option<(f64, f64)>,
+    pub last_position: Option<(f64, f64)>,
+    pub reject_stars: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct CometStackResult {
+    pub star_stack: Array2<f32>,
+    pub comet_stack: Array2<f32>,
+    pub combined: Option<Array2<f32>>,
+    pub frame_count: usize,
+    pub star_offsets: Vec<(f64, f64)>,
+    pub comet_offsets: Vec<(f64, f64)>,
+    pub comet_start: (f64, f64),
+    pub comet_end: (f64, f64),
+    pub motion_px_per_hour: Option<f64>,
+    pub detected: bool,
+    pub timestamps_used: bool,
+    pub masked_stars: usize,
+    pub rejected_pixels: u64,
+    pub reference: ReferenceChoice,
+    pub calibration: CalibrationReport,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityMetric {
+    #[default]
+    Laplacian,
+    Gradient,
+}
+
+#[derive(Debug, Clone)]
+pub struct LuckyConfig {
+    pub metric: QualityMetric,
+    pub keep_percent: f32,
+    pub reference_frames: usize,
+    pub local_align: bool,
+    pub patch_size: usize,
+    pub grid_step: usize,
+    pub min_patch_confidence: f64,
+}
+
+impl Default for LuckyConfig {
+    fn default() -> Self {
+        Self {
+            metric: QualityMetric::Laplacian,
+            keep_percent: 25.0,
+            reference_frames: 5,
+            local_align: true,
+            patch_size: 64,
+            grid_step: 48,
+            min_patch_confidence: 4.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct FrameQuality {
+    pub index: usize,
+    pub score: f64,
+    pub rank: usize,
+    pub selected: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct LuckyStackResult {
+    pub image: Array2<f32>,
+    pub frame_count: usize,
+    pub selected_count: usize,
+    pub best_index: usize,
+    pub quality: Vec<FrameQuality>,
+    pub offsets: Vec<(f64, f64)>,
+    pub patch_count: usize,
+    pub mean_valid_patches: f64,
+    pub max_local_shift_px: f64,
+    pub calibration: CalibrationReport,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -49,6 +186,8 @@ pub struct DrizzleConfig {
     pub sigma_iterations: usize,
     pub align: bool,
     pub alignment_method: AlignmentMethod,
+    pub geometry: DrizzleGeometry,
+    pub reference: ReferenceSelection,
 }
 
 impl Default for DrizzleConfig {
@@ -62,6 +201,8 @@ impl Default for DrizzleConfig {
             sigma_iterations: 5,
             align: true,
             alignment_method: AlignmentMethod::default(),
+            geometry: DrizzleGeometry::default(),
+            reference: ReferenceSelection::default(),
         }
     }
 }
@@ -69,20 +210,83 @@ impl Default for DrizzleConfig {
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum DrizzleKernel {
     Square,
+    Turbo,
+    Point,
     Gaussian,
     Lanczos3,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DrizzleGeometry {
+    #[default]
+    Translation,
+    Affine,
+    Wcs,
+}
+
+#[derive(Debug, Clone)]
+pub struct DrizzleFrameWeights {
+    pub weight: Option<Array2<f32>>,
+    pub mask: Option<Array2<u8>>,
+    pub frame_weight: f32,
+}
+
+impl Default for DrizzleFrameWeights {
+    fn default() -> Self {
+        Self {
+            weight: None,
+            mask: None,
+            frame_weight: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DrizzleWeighting {
+    pub weight_paths: Option<Vec<String>>,
+    pub mask_paths: Option<Vec<String>>,
+    pub use_err: bool,
+    pub use_dq: bool,
+    pub dq_bad_bits: Option<u32>,
+    pub saturation: Option<f32>,
+    pub exposure_weight: bool,
+    pub trail_sigma: Option<f32>,
+}
+
 #[derive(Debug, Clone)]
 pub struct DrizzleResult {
     pub image: Array2<f32>,
     pub weight_map: Array2<f32>,
+    pub context: Vec<Array2<u32>>,
+    pub frame_count: usize,
+    pub output_scale: f64,
+    pub input_dims: (usize, usize),
+    pub output_dims: (usize, usize),
+    pub offsets: Vec<(f64, f64)>,
+    pub rejected_pixels: u64,
+    pub reference: ReferenceChoice,
+    pub trails: Vec<FrameTrailReport>,
+    pub calibration: CalibrationReport,
+}
+
+#[derive(Debug, Clone)]
+pub struct CfaDrizzleResult {
+    pub r: Array2<f32>,
+    pub g: Array2<f32>,
+    pub b: Array2<f32>,
+    pub weight_r: Array2<f32>,
+    pub weight_g: Array2<f32>,
+    pub weight_b: Array2<f32>,
+    pub context: Vec<Array2<u32>>,
     pub frame_count: usize,
     pub output_scale: f64,
     pub input_dims: (usize, usize),
     pub output_dims: (usize, usize),
     pub offsets: Vec<(f64, f64)>,
     pub rejected_pixels: u64,
+    pub reference: ReferenceChoice,
+    pub trails: Vec<FrameTrailReport>,
+    pub calibration: CalibrationReport,
 }
 
 #[derive(Debug, Clone)]